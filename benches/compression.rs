@@ -0,0 +1,173 @@
+//! compare the grpc throughput of the clients with and without gzip
+//! the server binary is started in a temp dir with the threshold 0 to compress every response
+
+use acts_channel::{acts_service_client::ActsServiceClient, Message, MessageOptions, Vars};
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use serde_json::Value;
+use std::{
+    process::{Child, Command, Stdio},
+    time::Duration,
+};
+use tokio::runtime::Runtime;
+use tokio_stream::StreamExt;
+use tonic::{codec::CompressionEncoding, transport::Channel, Request};
+
+const PORT: u16 = 10190;
+const SIZES: [usize; 3] = [1024, 64 * 1024, 512 * 1024];
+const MODEL: &str =
+    "id: m1\nsteps:\n  - id: step1\n    acts:\n      - act: irq\n        key: act1\n";
+
+/// the server process which is killed when the bench is done
+struct Server(Child);
+
+impl Server {
+    fn start() -> Self {
+        let dir = std::env::temp_dir().join("acts-server-benches");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(
+            dir.join("acts.conf"),
+            format!("port: {PORT}\ndata_dir: data\ncompression: {{\n    encoding: gzip,\n    threshold: 0\n}}\n"),
+        )
+        .unwrap();
+        let child = Command::new(env!("CARGO_BIN_EXE_acts-server"))
+            .current_dir(&dir)
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .unwrap();
+        Self(child)
+    }
+}
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        let _ = self.0.kill();
+        let _ = self.0.wait();
+    }
+}
+
+async fn connect(gzip: bool) -> ActsServiceClient<Channel> {
+    let url = format!("http://127.0.0.1:{PORT}");
+    // wait for the server to be ready
+    for _ in 0..100 {
+        if let Ok(client) = ActsServiceClient::connect(url.clone()).await {
+            return match gzip {
+                true => client
+                    .accept_compressed(CompressionEncoding::Gzip)
+                    .send_compressed(CompressionEncoding::Gzip),
+                false => client,
+            };
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    panic!("failed to connect server {url}");
+}
+
+fn request(name: &str, data: Vars) -> Request<Message> {
+    Request::new(Message {
+        name: name.to_string(),
+        seq: acts_channel::create_seq(),
+        ack: None,
+        data: Some(data.to_bytes()),
+    })
+}
+
+/// the json text like the real vars, it is neither random nor the same byte repeated
+fn payload(size: usize) -> String {
+    let mut text = String::with_capacity(size);
+    let mut i = 0;
+    while text.len() < size {
+        text.push_str(&format!(
+            "{{\"id\":{i},\"name\":\"item-{i}\",\"done\":{}}},",
+            i % 3 == 0
+        ));
+        i += 1;
+    }
+    text.truncate(size);
+    text
+}
+
+async fn start(client: &mut ActsServiceClient<Channel>, big: &str) -> Value {
+    let resp = client
+        .send(request(
+            "proc:start",
+            Vars::new().with("id", "m1").with("big", big),
+        ))
+        .await
+        .unwrap()
+        .into_inner();
+    serde_json::from_slice(resp.data()).unwrap()
+}
+
+fn compression(c: &mut Criterion) {
+    let rt = Runtime::new().unwrap();
+    let _server = Server::start();
+    rt.block_on(async {
+        connect(false)
+            .await
+            .send(request("model:deploy", Vars::new().with("model", MODEL)))
+            .await
+            .unwrap();
+    });
+
+    let mut group = c.benchmark_group("send");
+    for size in SIZES {
+        group.throughput(Throughput::Bytes(size as u64));
+        for gzip in [false, true] {
+            let mut client = rt.block_on(connect(gzip));
+            let pid = rt.block_on(start(&mut client, &payload(size)));
+            let id = BenchmarkId::new(if gzip { "gzip" } else { "none" }, size);
+            group.bench_function(id, |b| {
+                b.to_async(&rt).iter(|| {
+                    let mut client = client.clone();
+                    let pid = pid.clone();
+                    async move {
+                        client
+                            .send(request("proc:vars:get", Vars::new().with("pid", pid)))
+                            .await
+                            .unwrap()
+                    }
+                })
+            });
+        }
+    }
+    group.finish();
+
+    // each iteration starts a proc and waits for its irq message in the stream
+    let mut group = c.benchmark_group("on_message");
+    group.sample_size(20);
+    for size in SIZES {
+        group.throughput(Throughput::Bytes(size as u64));
+        for gzip in [false, true] {
+            let mut client = rt.block_on(connect(gzip));
+            let name = if gzip { "gzip" } else { "none" };
+            let mut stream = rt.block_on(async {
+                client
+                    .on_message(MessageOptions {
+                        client_id: format!("bench-{name}-{size}"),
+                        r#type: "irq".to_string(),
+                        state: "created".to_string(),
+                        tag: "*".to_string(),
+                        key: "*".to_string(),
+                    })
+                    .await
+                    .unwrap()
+                    .into_inner()
+            });
+            let big = payload(size);
+            group.bench_function(BenchmarkId::new(name, size), |b| {
+                b.iter(|| {
+                    rt.block_on(async {
+                        start(&mut client, &big).await;
+                        stream.next().await.unwrap().unwrap()
+                    })
+                })
+            });
+        }
+    }
+    group.finish();
+}
+
+criterion_group!(benches, compression);
+criterion_main!(benches);
@@ -1,4 +1,4 @@
-use clap::{arg, command, Parser};
+use clap::Parser;
 
 #[derive(Parser, Debug)]
 #[command(name = "acts-cli")]
@@ -9,4 +9,16 @@ pub struct Cli {
 
     #[arg(short, long)]
     pub port: Option<u16>,
+
+    /// the namespace of the models, packages and procs, it should be allowed for the auth token
+    #[arg(short, long)]
+    pub namespace: Option<String>,
+
+    /// the auth token which is mapped to a namespace by the server
+    #[arg(long)]
+    pub token: Option<String>,
+
+    /// gzip the requests, the gzip responses are always accepted
+    #[arg(long)]
+    pub compress: bool,
 }
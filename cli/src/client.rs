@@ -1,6 +1,191 @@
-use acts_channel::ActsChannel;
+use acts_channel::{
+    acts_service_client::ActsServiceClient, ActionResult, ActsOptions, Message, MessageOptions,
+    Vars,
+};
+use serde::{de::DeserializeOwned, Serialize};
+use std::str::FromStr;
+use tokio_stream::StreamExt;
+use tonic::{
+    codec::CompressionEncoding,
+    metadata::{Ascii, MetadataValue},
+    service::{interceptor::InterceptedService, Interceptor},
+    transport::{Channel, Endpoint},
+    Request, Status,
+};
 
-pub async fn connect(url: &str) -> Result<ActsChannel, Box<dyn std::error::Error>> {
-    let client = ActsChannel::connect(url).await?;
-    Ok(client)
+/// the metadata which is sent with every request, such as the namespace and the auth token
+#[derive(Debug, Clone, Default)]
+pub struct Metadata {
+    namespace: Option<MetadataValue<Ascii>>,
+    token: Option<MetadataValue<Ascii>>,
+}
+
+impl Metadata {
+    pub fn new(
+        namespace: Option<&str>,
+        token: Option<&str>,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        Ok(Self {
+            namespace: namespace.map(|ns| ns.parse()).transpose()?,
+            token: token
+                .map(|token| format!("Bearer {token}").parse())
+                .transpose()?,
+        })
+    }
+}
+
+impl Interceptor for Metadata {
+    fn call(&mut self, mut request: Request<()>) -> Result<Request<()>, Status> {
+        if let Some(namespace) = &self.namespace {
+            request
+                .metadata_mut()
+                .insert("x-acts-namespace", namespace.clone());
+        }
+        if let Some(token) = &self.token {
+            request
+                .metadata_mut()
+                .insert("authorization", token.clone());
+        }
+        Ok(request)
+    }
+}
+
+/// the subscription options which are sent in the on_message metadata
+#[derive(Debug, Clone, Default)]
+pub struct SubscribeOptions {
+    /// one of 'acked', 'seq:{seq}' and 'time:{millis}' to receive the messages before the live ones
+    pub replay: Option<String>,
+    /// the consumer group, each message goes to exactly one member of the group
+    pub group: Option<String>,
+    /// the comma separated dotted field paths to receive, such as 'id,pid,state'
+    pub fields: Option<String>,
+}
+
+/// the server client which sends the actions with the metadata
+/// it works like the acts channel, which cannot send the request metadata
+#[derive(Debug, Clone)]
+pub struct Client {
+    client: ActsServiceClient<InterceptedService<Channel, Metadata>>,
+}
+
+impl Client {
+    pub async fn send<T>(&mut self, name: &str, data: Vars) -> Result<ActionResult<T>, Status>
+    where
+        T: Serialize + DeserializeOwned,
+    {
+        self.send_with_ack(name, data, None).await
+    }
+
+    pub async fn start(&mut self, id: &str, vars: Vars) -> Result<ActionResult<String>, Status> {
+        self.send("proc:start", Vars::new().with("id", id).extend(&vars))
+            .await
+    }
+
+    pub async fn ack(&mut self, id: &str) -> Result<ActionResult<()>, Status> {
+        self.send_with_ack("msg:ack", Vars::new().with("id", id), Some(id.to_string()))
+            .await
+    }
+
+    /// subscribe the server messages, the messages are acked before handling if the ack option is set
+    /// the message is the json value, which has only the selected fields if the fields option is set
+    pub async fn subscribe<F: Fn(&serde_json::Value) + Send + Sync + 'static>(
+        &mut self,
+        client_id: &str,
+        handle: F,
+        options: &ActsOptions,
+        sub: &SubscribeOptions,
+    ) {
+        let filter = |v: &Option<String>| v.clone().unwrap_or("*".to_string());
+        let mut request = Request::new(MessageOptions {
+            client_id: client_id.to_string(),
+            r#type: filter(&options.r#type),
+            state: filter(&options.state),
+            tag: filter(&options.tag),
+            key: filter(&options.key),
+        });
+        for (name, value) in [
+            ("x-acts-replay", &sub.replay),
+            ("x-acts-group", &sub.group),
+            ("x-acts-fields", &sub.fields),
+        ] {
+            let Some(value) = value else {
+                continue;
+            };
+            match value.parse() {
+                Ok(value) => {
+                    request.metadata_mut().insert(name, value);
+                }
+                Err(err) => {
+                    println!("on_message err:{:?}", err);
+                    return;
+                }
+            }
+        }
+        let mut stream = match self.client.on_message(request).await {
+            Ok(resp) => resp.into_inner(),
+            Err(err) => {
+                println!("on_message err:{:?}", err);
+                return;
+            }
+        };
+        let mut chan = self.clone();
+        let auto_ack = options.ack.unwrap_or(true);
+        tokio::spawn(async move {
+            while let Some(Ok(m)) = stream.next().await {
+                let Ok(message) = serde_json::from_slice::<serde_json::Value>(m.data()) else {
+                    continue;
+                };
+                if auto_ack {
+                    if let Err(err) = chan.ack(&m.seq).await {
+                        println!("on_message err:{:?}", err);
+                        continue;
+                    }
+                }
+                handle(&message);
+            }
+        });
+    }
+
+    async fn send_with_ack<T>(
+        &mut self,
+        name: &str,
+        data: Vars,
+        ack: Option<String>,
+    ) -> Result<ActionResult<T>, Status>
+    where
+        T: Serialize + DeserializeOwned,
+    {
+        let mut ret = ActionResult::begin();
+        let resp = self
+            .client
+            .send(Request::new(Message {
+                name: name.to_string(),
+                seq: acts_channel::create_seq(),
+                ack,
+                data: Some(data.to_bytes()),
+            }))
+            .await?;
+        ret.data = resp
+            .into_inner()
+            .data
+            .map(|v| serde_json::from_slice(&v))
+            .transpose()
+            .map_err(|err| Status::internal(err.to_string()))?;
+        ret.end()
+    }
+}
+
+/// the server compresses the large responses when the client accepts gzip
+pub async fn connect(
+    url: &str,
+    metadata: Metadata,
+    compress: bool,
+) -> Result<Client, Box<dyn std::error::Error>> {
+    let channel = Endpoint::from_str(url)?.connect().await?;
+    let mut client = ActsServiceClient::with_interceptor(channel, metadata)
+        .accept_compressed(CompressionEncoding::Gzip);
+    if compress {
+        client = client.send_compressed(CompressionEncoding::Gzip);
+    }
+    Ok(Client { client })
 }
@@ -1,22 +1,30 @@
 mod act;
+mod audit;
+mod dlq;
 mod model;
 mod msg;
+mod opts;
 mod pack;
 mod proc;
+mod sys;
 mod task;
-// mod vars;
+mod vars;
 
+use crate::client::Client;
 use act::ActArgs;
-use acts_channel::{self, ActsChannel, Vars};
-use clap::{command, Parser, Subcommand};
+use acts_channel::{self, Vars};
+use audit::AuditArgs;
+use clap::{Parser, Subcommand};
+use dlq::DlqArgs;
 use model::ModelArgs;
 use msg::MessageArgs;
+use opts::OptsArgs;
 use owo_colors::OwoColorize;
 use pack::PacakgeArgs;
 use proc::ProcArgs;
-use std::io::Write;
+use sys::SysArgs;
 use task::TaskArgs;
-// use vars::VarsArgs;
+use vars::VarsArgs;
 
 #[derive(Debug, Parser)]
 #[command(name = "act")]
@@ -28,8 +36,6 @@ pub struct ActsRootCommand {
 
 #[derive(Debug, Subcommand)]
 pub enum Commands {
-    // #[command(about = "set options for command arguments")]
-    // Vars(VarsArgs),
     #[command(about = "execute model commands")]
     Model(ModelArgs),
     #[command(about = "execute package commands")]
@@ -42,17 +48,27 @@ pub enum Commands {
     Message(MessageArgs),
     #[command(about = "execute act commands")]
     Act(ActArgs),
+    #[command(about = "get or patch the proc and task vars")]
+    Vars(VarsArgs),
+    #[command(about = "set options for command arguments")]
+    Opts(OptsArgs),
+    #[command(about = "execute system commands")]
+    Sys(SysArgs),
+    #[command(about = "execute audit commands")]
+    Audit(AuditArgs),
+    #[command(about = "execute dead-letter message commands")]
+    Dlq(DlqArgs),
     #[command(about = "exit the cli")]
     Exit,
 }
 
 pub struct CommandRunner<'a> {
     vars: Vars,
-    client: &'a mut ActsChannel,
+    client: &'a mut Client,
 }
 
 impl<'a> CommandRunner<'a> {
-    pub fn new(client: &'a mut ActsChannel) -> Self {
+    pub fn new(client: &'a mut Client) -> Self {
         Self {
             client,
             vars: Vars::new(),
@@ -70,9 +86,6 @@ impl<'a> CommandRunner<'a> {
             Commands::Exit => {
                 return Ok(true);
             }
-            // Commands::Vars(args) => {
-            //     vars::process(self, &args.command).await?;
-            // }
             Commands::Model(args) => {
                 model::process(self, &args.command).await?;
             }
@@ -91,6 +104,21 @@ impl<'a> CommandRunner<'a> {
             Commands::Act(args) => {
                 act::process(self, &args.command).await?;
             }
+            Commands::Vars(args) => {
+                vars::process(self, &args.command).await?;
+            }
+            Commands::Opts(args) => {
+                opts::process(self, &args.command).await?;
+            }
+            Commands::Sys(args) => {
+                sys::process(self, &args.command).await?;
+            }
+            Commands::Audit(args) => {
+                audit::process(self, &args.command).await?;
+            }
+            Commands::Dlq(args) => {
+                dlq::process(self, &args.command).await?;
+            }
         };
 
         Ok(false)
@@ -98,7 +126,7 @@ impl<'a> CommandRunner<'a> {
 
     pub fn output(&self, value: &str) {
         for line in value.lines() {
-            writeln!(std::io::stdout(), "{}", line.green()).unwrap();
+            println!("{}", line.green());
         }
     }
 }
@@ -13,6 +13,31 @@ pub struct ActArgs {
     pub command: ActCommands,
 }
 
+/// the expected task of the act, the server rejects the act if the task is updated by others
+#[derive(Debug, Clone, Args)]
+pub struct Expect {
+    #[arg(long = "expect-state", help = "the expected task state")]
+    state: Option<String>,
+    #[arg(
+        long = "expect-revision",
+        help = "the expected task revision which is shown by 'task get'"
+    )]
+    revision: Option<String>,
+}
+
+impl Expect {
+    fn with(&self, vars: &[(String, serde_json::Value)]) -> Vec<(String, serde_json::Value)> {
+        let mut vars = vars.to_vec();
+        if let Some(state) = &self.state {
+            vars.push(("expected_state".to_string(), json!(state)));
+        }
+        if let Some(revision) = &self.revision {
+            vars.push(("expected_revision".to_string(), json!(revision)));
+        }
+        vars
+    }
+}
+
 #[derive(Debug, Subcommand)]
 pub enum ActCommands {
     #[command(about = "submit a running act")]
@@ -23,6 +48,8 @@ pub enum ActCommands {
         tid: String,
         #[arg(short, long, help="vars in K=V format\nthe V can be number, string, or json, \nif the V contains whitesapce, please wrap it in `'` or `\"`\nexample: \n-v a=1 -v b=abc -v c='[2, 3, 4]' -v d='{ \"value\": 100 }' -v e=null", value_parser = util::parse_key_json::<String>)]
         vars: Vec<(String, serde_json::Value)>,
+        #[command(flatten)]
+        expect: Expect,
     },
 
     #[command(about = "complete a running act")]
@@ -33,6 +60,8 @@ pub enum ActCommands {
         tid: String,
         #[arg(short, long, help="vars in K=V format\nthe V can be number, string, or json, \nif the V contains whitesapce, please wrap it in `'` or `\"`\nexample: \n-v a=1 -v b=abc -v c='[2, 3, 4]' -v d='{ \"value\": 100 }' -v e=null", value_parser = util::parse_key_json::<String>)]
         vars: Vec<(String, serde_json::Value)>,
+        #[command(flatten)]
+        expect: Expect,
     },
 
     #[command(about = "skip a running act")]
@@ -87,6 +116,8 @@ pub enum ActCommands {
         to: String,
         #[arg(short, long, help="vars in K=V format\nthe V can be number, string, or json, \nif the V contains whitesapce, please wrap it in `'` or `\"`\nexample: \n-v a=1 -v b=abc -v c='[2, 3, 4]' -v d='{ \"value\": 100 }' -v e=null", value_parser = util::parse_key_json::<String>)]
         vars: Vec<(String, serde_json::Value)>,
+        #[command(flatten)]
+        expect: Expect,
     },
 
     #[command(about = "push a new act under a step")]
@@ -112,10 +143,18 @@ pub enum ActCommands {
 
 pub async fn process(parent: &mut Command<'_>, command: &ActCommands) -> Result<(), String> {
     let ret = match command {
-        ActCommands::Submit { pid, tid, vars } => send(parent, "act:submit", pid, tid, vars).await,
-        ActCommands::Complete { pid, tid, vars } => {
-            send(parent, "act:complete", pid, tid, vars).await
-        }
+        ActCommands::Submit {
+            pid,
+            tid,
+            vars,
+            expect,
+        } => send(parent, "act:submit", pid, tid, &expect.with(vars)).await,
+        ActCommands::Complete {
+            pid,
+            tid,
+            vars,
+            expect,
+        } => send(parent, "act:complete", pid, tid, &expect.with(vars)).await,
         ActCommands::Skip { pid, tid, vars } => send(parent, "act:skip", pid, tid, vars).await,
         ActCommands::Abort { pid, tid, vars } => send(parent, "act:abort", pid, tid, vars).await,
         ActCommands::Error {
@@ -130,8 +169,14 @@ pub async fn process(parent: &mut Command<'_>, command: &ActCommands) -> Result<
             Ok(ret)
         }
         ActCommands::Cancel { pid, tid, vars } => send(parent, "act:cancel", pid, tid, vars).await,
-        ActCommands::Back { pid, tid, to, vars } => {
-            let mut vars = vars.clone();
+        ActCommands::Back {
+            pid,
+            tid,
+            to,
+            vars,
+            expect,
+        } => {
+            let mut vars = expect.with(vars);
             vars.push(("to".to_string(), json!(to)));
             send(parent, "act:back", pid, tid, &vars).await
         }
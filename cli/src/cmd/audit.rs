@@ -0,0 +1,144 @@
+use super::CommandRunner as Command;
+use crate::util;
+use acts_channel::{model::PageData, Vars};
+use clap::{Args, Subcommand};
+use prettytable::{row, Table};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Audit {
+    pub id: String,
+    pub action: String,
+    pub pid: String,
+    pub tid: String,
+    pub targets: serde_json::Value,
+    pub user: Option<String>,
+    pub peer: String,
+    pub namespace: String,
+    pub params: serde_json::Value,
+    pub outcome: String,
+    pub error: String,
+    pub latency: u64,
+    pub create_time: i64,
+}
+
+#[derive(Debug, Args)]
+#[command(args_conflicts_with_subcommands = true)]
+#[command(flatten_help = true)]
+pub struct AuditArgs {
+    #[command(subcommand)]
+    pub command: AuditCommands,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum AuditCommands {
+    #[command(about = "list the audits of the mutating actions, the latest first")]
+    Ls {
+        #[arg(short, long, help = "skip the offset number to begin count")]
+        offset: Option<u32>,
+        #[arg(short, long, help = "expect to load the item count")]
+        count: Option<u32>,
+
+        #[arg(short='Q', long, help = "query by keys. \nexample: -Q action=model:rm -Q user=admin", value_parser = util::parse_key_value)]
+        query_by: Vec<(String, String)>,
+
+        #[arg(short='O', long, help = "order by keys. \nexample: -O action -O create_time,desc", value_parser = util::parse_sort)]
+        order_by: Vec<(String, bool)>,
+
+        #[arg(short, long, help = "show the params of the actions")]
+        params: bool,
+    },
+}
+
+pub async fn process(parent: &mut Command<'_>, command: &AuditCommands) -> Result<(), String> {
+    let ret = match command {
+        AuditCommands::Ls {
+            offset,
+            count,
+            query_by,
+            order_by,
+            params,
+        } => ls(parent, offset, count, query_by, order_by, *params).await,
+    }?;
+
+    parent.output(&ret);
+    Ok(())
+}
+
+pub async fn ls(
+    parent: &mut Command<'_>,
+    offset: &Option<u32>,
+    count: &Option<u32>,
+    query_by: &Vec<(String, String)>,
+    order_by: &Vec<(String, bool)>,
+    params: bool,
+) -> Result<String, String> {
+    let mut ret = String::new();
+    let mut options = Vars::new();
+    if let Some(offset) = offset {
+        options.set("offset", offset);
+    };
+    if let Some(count) = count {
+        options.set("count", count);
+    };
+    options.set("query_by", query_by);
+    options.set("order_by", order_by);
+
+    let resp = parent
+        .client
+        .send::<PageData<Audit>>("audit:ls", options)
+        .await
+        .map_err(|err| err.message().to_string())?;
+
+    let data = resp.data.as_ref().unwrap();
+    let mut table = Table::new();
+    let mut header = row![
+        "time",
+        "action",
+        "user",
+        "peer",
+        "namespace",
+        "targets",
+        "outcome",
+        "latency"
+    ];
+    if params {
+        header.add_cell(prettytable::Cell::new("params"));
+    }
+    table.add_row(header);
+    for a in &data.rows {
+        let targets = match &a.targets {
+            serde_json::Value::Object(map) => map
+                .iter()
+                .map(|(k, v)| format!("{k}={}", v.as_str().map_or(v.to_string(), String::from)))
+                .collect::<Vec<_>>()
+                .join("\n"),
+            _ => String::new(),
+        };
+        let outcome = if a.error.is_empty() {
+            a.outcome.clone()
+        } else {
+            format!("{}: {}", a.outcome, a.error)
+        };
+        let mut row = row![
+            util::local_time(a.create_time),
+            a.action,
+            a.user.as_deref().unwrap_or_default(),
+            a.peer,
+            a.namespace,
+            targets,
+            outcome,
+            format!("{}ms", a.latency)
+        ];
+        if params {
+            row.add_cell(prettytable::Cell::new(&a.params.to_string()));
+        }
+        table.add_row(row);
+    }
+    table.printstd();
+    util::print_pager(&mut ret, data);
+    util::print_cost(&mut ret, &resp);
+
+    Ok(ret)
+}
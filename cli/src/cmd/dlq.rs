@@ -0,0 +1,221 @@
+use super::CommandRunner as Command;
+use crate::util;
+use acts_channel::{
+    model::{MessageInfo, PageData},
+    Vars,
+};
+use clap::{Args, Subcommand};
+use prettytable::{row, Table};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeadLetter {
+    #[serde(flatten)]
+    pub message: MessageInfo,
+    pub reason: String,
+    pub attempts: usize,
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Attempt {
+    pub id: String,
+    pub message_id: String,
+    pub attempt: i32,
+    pub kind: String,
+    pub client: String,
+    pub reason: String,
+    pub create_time: i64,
+}
+
+#[derive(Debug, Args)]
+#[command(args_conflicts_with_subcommands = true)]
+#[command(flatten_help = true)]
+pub struct DlqArgs {
+    #[command(subcommand)]
+    pub command: DlqCommands,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum DlqCommands {
+    #[command(
+        about = "list the messages which are dead after all of the retries, the latest first"
+    )]
+    Ls {
+        #[arg(short, long, help = "skip the offset number to begin count")]
+        offset: Option<u32>,
+        #[arg(short, long, help = "expect to load the item count")]
+        count: Option<u32>,
+
+        #[arg(short='Q', long, help = "query by keys. \nexample: -Q pid=xxx -Q key=act1", value_parser = util::parse_key_value)]
+        query_by: Vec<(String, String)>,
+
+        #[arg(short='O', long, help = "order by keys. \nexample: -O update_time,desc", value_parser = util::parse_sort)]
+        order_by: Vec<(String, bool)>,
+    },
+    #[command(
+        about = "send the dead messages to the subscribers again by id or by query, all of them if neither is set",
+        long_about = "send the dead messages to the subscribers again by id or by query, all of them if neither is set\nthe message keeps dead until a subscriber acks it"
+    )]
+    Retry {
+        #[arg(help = "message id")]
+        id: Option<String>,
+        #[arg(short='Q', long, help = "query by keys. \nexample: -Q pid=xxx -Q key=act1", value_parser = util::parse_key_value)]
+        query_by: Vec<(String, String)>,
+    },
+    #[command(about = "remove the dead messages and their retry history by id or by filters")]
+    Purge {
+        #[arg(help = "message id")]
+        id: Option<String>,
+        #[arg(short='Q', long, help = "query by keys. \nexample: -Q pid=xxx -Q key=act1", value_parser = util::parse_key_value)]
+        query_by: Vec<(String, String)>,
+        #[arg(
+            short,
+            long,
+            help = "only the messages which are dead before the time in millis"
+        )]
+        before: Option<i64>,
+    },
+    #[command(about = "show why each delivery of the message failed")]
+    History {
+        #[arg(help = "message id")]
+        id: String,
+    },
+}
+
+pub async fn process(parent: &mut Command<'_>, command: &DlqCommands) -> Result<(), String> {
+    let ret = match command {
+        DlqCommands::Ls {
+            offset,
+            count,
+            query_by,
+            order_by,
+        } => ls(parent, offset, count, query_by, order_by).await,
+        DlqCommands::Retry { id, query_by } => {
+            change(parent, "msg:dlq:retry", id, query_by, &None).await
+        }
+        DlqCommands::Purge {
+            id,
+            query_by,
+            before,
+        } => change(parent, "msg:dlq:purge", id, query_by, before).await,
+        DlqCommands::History { id } => history(parent, id).await,
+    }?;
+
+    parent.output(&ret);
+    Ok(())
+}
+
+pub async fn ls(
+    parent: &mut Command<'_>,
+    offset: &Option<u32>,
+    count: &Option<u32>,
+    query_by: &Vec<(String, String)>,
+    order_by: &Vec<(String, bool)>,
+) -> Result<String, String> {
+    let mut ret = String::new();
+    let mut options = Vars::new();
+    if let Some(offset) = offset {
+        options.set("offset", offset);
+    };
+    if let Some(count) = count {
+        options.set("count", count);
+    };
+    options.set("query_by", query_by);
+    options.set("order_by", order_by);
+
+    let resp = parent
+        .client
+        .send::<PageData<DeadLetter>>("msg:dlq:ls", options)
+        .await
+        .map_err(|err| err.message().to_string())?;
+
+    let data = resp.data.as_ref().unwrap();
+    let mut table = Table::new();
+    table.add_row(row![
+        "type",
+        "id",
+        "pid",
+        "state",
+        "key",
+        "retries",
+        "reason",
+        "dead time"
+    ]);
+    for d in &data.rows {
+        let m = &d.message;
+        table.add_row(row![
+            m.r#type,
+            m.id,
+            m.pid,
+            m.state,
+            m.key,
+            m.retry_times,
+            d.reason,
+            util::local_time(m.update_time)
+        ]);
+    }
+    table.printstd();
+    util::print_pager(&mut ret, data);
+    util::print_cost(&mut ret, &resp);
+
+    Ok(ret)
+}
+
+async fn change(
+    parent: &mut Command<'_>,
+    name: &str,
+    id: &Option<String>,
+    query_by: &Vec<(String, String)>,
+    before: &Option<i64>,
+) -> Result<String, String> {
+    let mut ret = String::new();
+    let mut options = Vars::new();
+    if let Some(id) = id {
+        options.set("id", id);
+    }
+    if let Some(before) = before {
+        options.set("before", before);
+    }
+    options.set("query_by", query_by);
+    let resp = parent
+        .client
+        .send::<usize>(name, options)
+        .await
+        .map_err(|err| err.message().to_string())?;
+    ret.push_str(&format!("{} messages\n", resp.data.unwrap_or_default()));
+
+    // print the elapsed
+    let cost = resp.end_time - resp.start_time;
+    ret.push_str(&format!("(elapsed {cost}ms)"));
+
+    Ok(ret)
+}
+
+async fn history(parent: &mut Command<'_>, id: &str) -> Result<String, String> {
+    let mut ret = String::new();
+    let resp = parent
+        .client
+        .send::<Vec<Attempt>>("msg:dlq:history", Vars::new().with("id", id))
+        .await
+        .map_err(|err| err.message().to_string())?;
+
+    let mut table = Table::new();
+    table.add_row(row!["time", "attempt", "kind", "client", "reason"]);
+    for a in resp.data.as_deref().unwrap_or_default() {
+        table.add_row(row![
+            util::local_time(a.create_time),
+            a.attempt,
+            a.kind,
+            a.client,
+            a.reason
+        ]);
+    }
+    table.printstd();
+
+    // print the elapsed
+    let cost = resp.end_time - resp.start_time;
+    ret.push_str(&format!("(elapsed {cost}ms)"));
+
+    Ok(ret)
+}
@@ -5,8 +5,30 @@ use acts_channel::{
     Vars,
 };
 use clap::{Args, Subcommand};
+use globset::GlobBuilder;
 use prettytable::{row, Table};
-use std::path::PathBuf;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ModelVersion {
+    pub id: String,
+    pub mid: String,
+    pub ver: u32,
+    pub name: String,
+    pub size: u32,
+    pub data: String,
+    pub create_time: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Diagnostic {
+    pub level: String,
+    pub message: String,
+    pub line: usize,
+    pub column: usize,
+}
 
 #[derive(Debug, Args)]
 #[command(args_conflicts_with_subcommands = true)]
@@ -23,8 +45,54 @@ pub enum ModelCommands {
         #[arg(help = "model id")]
         id: String,
 
-        #[arg(short, long, help = "format to print, the value should be one of json and tree", value_parser(["json", "tree"]))]
+        #[arg(short, long, help = "format to print, the value should be one of json, tree, mermaid, dot and graph", value_parser(["json", "tree", "mermaid", "dot", "graph"]))]
         fmt: Option<String>,
+
+        #[arg(
+            short,
+            long,
+            help = "model version, get the latest version if not specified"
+        )]
+        ver: Option<u32>,
+
+        #[arg(short, long, help = "save the output to file")]
+        out: Option<PathBuf>,
+    },
+
+    #[command(about = "list the deployed versions of a model")]
+    History {
+        #[arg(help = "model id")]
+        id: String,
+        #[arg(short, long, help = "skip the offset number to begin count")]
+        offset: Option<u32>,
+        #[arg(short, long, help = "expect to load the max count")]
+        count: Option<u32>,
+    },
+
+    #[command(about = "diff two versions of a model")]
+    Diff {
+        #[arg(help = "model id")]
+        id: String,
+        #[arg(
+            short,
+            long,
+            help = "the version to diff from, default is the previous version of 'to'"
+        )]
+        from: Option<u32>,
+        #[arg(
+            short,
+            long,
+            help = "the version to diff to, default is the latest version"
+        )]
+        to: Option<u32>,
+    },
+
+    #[command(about = "rollback a model to an old version by deploying it as the latest version")]
+    Rollback {
+        #[arg(help = "model id")]
+        id: String,
+        #[arg(help = "model version")]
+        ver: u32,
     },
 
     #[command(about = "list all models")]
@@ -45,16 +113,38 @@ pub enum ModelCommands {
         id: String,
     },
 
-    #[command(about = "deploy a workflow model")]
+    #[command(
+        about = "deploy a workflow model",
+        long_about = r#"deploy a workflow model
+the path can be a model file or a directory, all of the models in the directory are deployed as a bundle
+the bundle is deployed atomically, none of the models is deployed if one of them is invalid
+Example: deploy ./models -g 'approve/*.yml'"#
+    )]
     Deploy {
-        #[arg(required = true, help = "model file path")]
+        #[arg(required = true, help = "model file or directory path")]
         path: PathBuf,
+        #[arg(
+            short,
+            long,
+            help = "glob to match the model files in the directory, default is **/*.{yml,yaml,json}"
+        )]
+        glob: Option<String>,
+        #[arg(short, long, help = "model file format, default is by the file extension", value_parser(["yaml", "json"]))]
+        format: Option<String>,
+        #[arg(
+            long,
+            help = "validate the model and print the diagnostics without deploying"
+        )]
+        dry_run: bool,
     },
 }
 
 pub async fn process(parent: &mut Command<'_>, command: &ModelCommands) -> Result<(), String> {
     let ret = match command {
-        ModelCommands::Get { id, fmt } => get(parent, id, fmt).await,
+        ModelCommands::Get { id, fmt, ver, out } => get(parent, id, fmt, ver, out).await,
+        ModelCommands::History { id, offset, count } => history(parent, id, offset, count).await,
+        ModelCommands::Diff { id, from, to } => diff(parent, id, from, to).await,
+        ModelCommands::Rollback { id, ver } => rollback(parent, id, *ver).await,
         ModelCommands::Ls {
             offset,
             count,
@@ -62,21 +152,170 @@ pub async fn process(parent: &mut Command<'_>, command: &ModelCommands) -> Resul
             order_by,
         } => ls(parent, offset, count, query_by, order_by).await,
         ModelCommands::Rm { id } => rm(parent, id).await,
-        ModelCommands::Deploy { path } => deploy(parent, path).await,
+        ModelCommands::Deploy {
+            path,
+            glob,
+            format,
+            dry_run,
+        } => {
+            let source = Source::load(path, glob, format)?;
+            if *dry_run {
+                validate(parent, &source).await
+            } else {
+                deploy(parent, &source).await
+            }
+        }
     }?;
 
     parent.output(&ret);
     Ok(())
 }
 
-async fn deploy(parent: &mut Command<'_>, path: &PathBuf) -> Result<String, String> {
+/// the model text to deploy, several files are joined into a yaml bundle
+struct Source {
+    text: String,
+    format: String,
+    /// the file path and its start line in the text, the line is none if the file is converted
+    files: Vec<(String, Option<usize>)>,
+}
+
+impl Source {
+    fn load(
+        path: &PathBuf,
+        glob: &Option<String>,
+        format: &Option<String>,
+    ) -> Result<Self, String> {
+        let mut paths = Vec::new();
+        if path.is_dir() {
+            let pattern = glob.as_deref().unwrap_or("**/*.{yml,yaml,json}");
+            let matcher = GlobBuilder::new(pattern)
+                .literal_separator(true)
+                .build()
+                .map_err(|err| err.to_string())?
+                .compile_matcher();
+            util::walk(path, &mut |file| {
+                if file
+                    .strip_prefix(path)
+                    .is_ok_and(|name| matcher.is_match(name))
+                {
+                    paths.push(file.to_path_buf());
+                }
+            })
+            .map_err(|err| err.to_string())?;
+            paths.sort();
+        } else {
+            paths.push(path.clone());
+        }
+
+        match paths.len() {
+            0 => Err(format!("cannot find any model in '{}'", path.display())),
+            1 => {
+                let text = std::fs::read_to_string(&paths[0]).map_err(|err| err.to_string())?;
+                let format = format.clone().unwrap_or(file_format(&paths[0]).to_string());
+                Ok(Self {
+                    text,
+                    format,
+                    files: vec![(paths[0].display().to_string(), Some(1))],
+                })
+            }
+            _ => {
+                let mut text = String::new();
+                let mut files = Vec::new();
+                for path in &paths {
+                    let content = std::fs::read_to_string(path).map_err(|err| err.to_string())?;
+                    if !text.is_empty() {
+                        text.push_str("---\n");
+                    }
+                    if file_format(path) == "json" {
+                        files.push((path.display().to_string(), None));
+                        let value: serde_json::Value = serde_json::from_str(&content)
+                            .map_err(|err| format!("{}: {err}", path.display()))?;
+                        let values = match value {
+                            serde_json::Value::Array(values) => values,
+                            value => vec![value],
+                        };
+                        for (index, value) in values.iter().enumerate() {
+                            if index > 0 {
+                                text.push_str("---\n");
+                            }
+                            text.push_str(
+                                &serde_yaml::to_string(value).map_err(|err| err.to_string())?,
+                            );
+                        }
+                    } else {
+                        files.push((path.display().to_string(), Some(text.lines().count() + 1)));
+                        text.push_str(&content);
+                        if !content.ends_with('\n') {
+                            text.push('\n');
+                        }
+                    }
+                }
+                Ok(Self {
+                    text,
+                    format: "yaml".to_string(),
+                    files,
+                })
+            }
+        }
+    }
+
+    /// map the line in text to the file position
+    fn position(&self, line: usize, column: usize) -> String {
+        let file = self
+            .files
+            .iter()
+            .filter(|f| f.1.is_some_and(|start| start <= line))
+            .max_by_key(|f| f.1);
+        match file {
+            Some((path, Some(start))) if line > 0 => {
+                if self.files.len() == 1 {
+                    format!("{}:{column}", line - start + 1)
+                } else {
+                    format!("{path}:{}:{column}", line - start + 1)
+                }
+            }
+            _ => "-".to_string(),
+        }
+    }
+}
+
+fn file_format(path: &Path) -> &'static str {
+    match path.extension().and_then(|ext| ext.to_str()) {
+        Some("json") => "json",
+        _ => "yaml",
+    }
+}
+
+async fn deploy(parent: &mut Command<'_>, source: &Source) -> Result<String, String> {
     let mut ret = String::new();
-    let text = std::fs::read_to_string(path).map_err(|err| err.to_string())?;
     let resp = parent
         .client
-        .deploy(&text, None)
+        .send::<bool>(
+            "model:deploy",
+            Vars::new()
+                .with("model", &source.text)
+                .with("format", &source.format),
+        )
         .await
-        .map_err(|err| err.message().to_string())?;
+        .map_err(|err| {
+            // map the diagnostic positions to the files
+            let re = Regex::new(r"^(\d+):(\d+) (.*)$").unwrap();
+            err.message()
+                .lines()
+                .map(|line| match re.captures(line) {
+                    Some(caps) => format!(
+                        "{} {}",
+                        source.position(caps[1].parse().unwrap(), caps[2].parse().unwrap()),
+                        &caps[3]
+                    ),
+                    None => line.to_string(),
+                })
+                .collect::<Vec<_>>()
+                .join("\n")
+        })?;
+    if source.files.len() > 1 {
+        ret.push_str(&format!("deployed {} files\n", source.files.len()));
+    }
     // print the elapsed
     let cost = resp.end_time - resp.start_time;
     ret.push_str(&format!("(elapsed {cost}ms)"));
@@ -84,6 +323,45 @@ async fn deploy(parent: &mut Command<'_>, path: &PathBuf) -> Result<String, Stri
     Ok(ret)
 }
 
+async fn validate(parent: &mut Command<'_>, source: &Source) -> Result<String, String> {
+    let mut ret = String::new();
+    let resp = parent
+        .client
+        .send::<Vec<Diagnostic>>(
+            "model:validate",
+            Vars::new()
+                .with("model", &source.text)
+                .with("format", &source.format),
+        )
+        .await
+        .map_err(|err| err.message().to_string())?;
+    let diagnostics = resp.data.as_ref().unwrap();
+    if diagnostics.is_empty() {
+        ret.push_str("the model is valid\n");
+    } else {
+        let mut table = Table::new();
+        table.add_row(row!["level", "position", "message"]);
+        for d in diagnostics {
+            table.add_row(row![
+                util::state(&d.level),
+                source.position(d.line, d.column),
+                d.message
+            ]);
+        }
+        table.printstd();
+
+        let errors = diagnostics.iter().filter(|d| d.level == "error").count();
+        ret.push_str(&format!(
+            "errors: {errors}, warnings: {}\n",
+            diagnostics.len() - errors
+        ));
+    }
+    let cost = resp.end_time - resp.start_time;
+    ret.push_str(&format!("(elapsed {cost}ms)"));
+
+    Ok(ret)
+}
+
 async fn ls(
     parent: &mut Command<'_>,
     offset: &Option<u32>,
@@ -129,19 +407,28 @@ async fn ls(
     }
 
     table.printstd();
-    util::print_pager(&mut ret, &data);
+    util::print_pager(&mut ret, data);
     util::print_cost(&mut ret, &resp);
 
     Ok(ret)
 }
 
-async fn get(parent: &mut Command<'_>, id: &str, fmt: &Option<String>) -> Result<String, String> {
+async fn get(
+    parent: &mut Command<'_>,
+    id: &str,
+    fmt: &Option<String>,
+    ver: &Option<u32>,
+    out: &Option<PathBuf>,
+) -> Result<String, String> {
     let mut ret = String::new();
     let mut options = Vars::new();
     options.set("id", id);
     if let Some(fmt) = fmt {
         options.set("fmt", fmt);
     };
+    if let Some(ver) = ver {
+        options.set("ver", ver);
+    };
 
     let resp = parent
         .client
@@ -149,7 +436,7 @@ async fn get(parent: &mut Command<'_>, id: &str, fmt: &Option<String>) -> Result
         .await
         .map_err(|err| err.message().to_string())?;
     let model = resp.data.unwrap();
-    ret.push_str(&model.data);
+    util::write_or_print(&mut ret, &model.data, out)?;
     let cost = resp.end_time - resp.start_time;
     ret.push_str(&format!("(elapsed {cost}ms)"));
 
@@ -170,3 +457,87 @@ async fn rm(parent: &mut Command<'_>, id: &str) -> Result<String, String> {
 
     Ok(ret)
 }
+
+async fn history(
+    parent: &mut Command<'_>,
+    id: &str,
+    offset: &Option<u32>,
+    count: &Option<u32>,
+) -> Result<String, String> {
+    let mut ret = String::new();
+    let mut options = Vars::new().with("id", id);
+    if let Some(offset) = offset {
+        options.set("offset", offset);
+    };
+    if let Some(count) = count {
+        options.set("count", count);
+    };
+
+    let resp = parent
+        .client
+        .send::<PageData<ModelVersion>>("model:versions", options)
+        .await
+        .map_err(|err| err.message().to_string())?;
+    let data = resp.data.as_ref().unwrap();
+    let mut table = Table::new();
+    table.add_row(row!["version", "name", "size", "deploy time"]);
+    for v in &data.rows {
+        table.add_row(row![
+            format!("{}", v.ver),
+            v.name,
+            util::size(v.size),
+            util::local_time(v.create_time)
+        ]);
+    }
+
+    table.printstd();
+    util::print_pager(&mut ret, data);
+    util::print_cost(&mut ret, &resp);
+
+    Ok(ret)
+}
+
+async fn diff(
+    parent: &mut Command<'_>,
+    id: &str,
+    from: &Option<u32>,
+    to: &Option<u32>,
+) -> Result<String, String> {
+    let mut ret = String::new();
+    let mut options = Vars::new().with("id", id);
+    if let Some(from) = from {
+        options.set("from", from);
+    };
+    if let Some(to) = to {
+        options.set("to", to);
+    };
+
+    let resp = parent
+        .client
+        .send::<String>("model:diff", options)
+        .await
+        .map_err(|err| err.message().to_string())?;
+    ret.push_str(&resp.data.unwrap());
+    let cost = resp.end_time - resp.start_time;
+    ret.push_str(&format!("(elapsed {cost}ms)"));
+
+    Ok(ret)
+}
+
+async fn rollback(parent: &mut Command<'_>, id: &str, ver: u32) -> Result<String, String> {
+    let mut ret = String::new();
+    let resp = parent
+        .client
+        .send::<ModelVersion>(
+            "model:rollback",
+            Vars::new().with("id", id).with("ver", ver),
+        )
+        .await
+        .map_err(|err| err.message().to_string())?;
+    let v = resp.data.unwrap();
+    ret.push_str(&format!("ver={}\n", v.ver));
+    let cost = resp.end_time - resp.start_time;
+    ret.push_str(&format!("(elapsed {cost}ms)"));
+
+    Ok(ret)
+}
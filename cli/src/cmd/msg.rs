@@ -1,5 +1,5 @@
 use super::CommandRunner as Command;
-use crate::util;
+use crate::{client::SubscribeOptions, util};
 use acts_channel::{
     model::{MessageInfo, PageData},
     ActsOptions, Vars,
@@ -84,6 +84,23 @@ pub enum MessageCommands {
             help = "auto ack message by client, if false you should ack message from you app"
         )]
         ack: bool,
+        #[arg(
+            long,
+            help = "replay the messages before the live ones, one of acked, seq:{seq} and time:{millis}"
+        )]
+        replay: Option<String>,
+        #[arg(
+            short,
+            long,
+            help = "join the consumer group, each message goes to one member and the unacked ones go to the others when it leaves"
+        )]
+        group: Option<String>,
+        #[arg(
+            short,
+            long,
+            help = "only receive the fields, the comma separated dotted paths without the array index or wildcard. \nexample: -f 'id,pid,state,$.outputs.total'"
+        )]
+        fields: Option<String>,
     },
     #[command(about = "unsubscribe server messages by client id")]
     Unsub {
@@ -112,8 +129,18 @@ pub async fn process(parent: &mut Command<'_>, command: &MessageCommands) -> Res
             key,
             tag,
             ack,
-        } => sub(parent, &client_id, r#type, state, key, tag, ack).await,
-        MessageCommands::Unsub { client_id } => ubsub(parent, &client_id).await,
+            replay,
+            group,
+            fields,
+        } => {
+            let options = SubscribeOptions {
+                replay: replay.clone(),
+                group: group.clone(),
+                fields: fields.clone(),
+            };
+            sub(parent, client_id, r#type, state, key, tag, ack, &options).await
+        }
+        MessageCommands::Unsub { client_id } => ubsub(parent, client_id).await,
     }?;
 
     parent.output(&ret);
@@ -197,7 +224,7 @@ pub async fn ls(
         ]);
     }
     table.printstd();
-    util::print_pager(&mut ret, &data);
+    util::print_pager(&mut ret, data);
     util::print_cost(&mut ret, &resp);
 
     Ok(ret)
@@ -280,6 +307,7 @@ pub async fn clear(parent: &mut Command<'_>, pid: &Option<String>) -> Result<Str
     Ok(ret)
 }
 
+#[allow(clippy::too_many_arguments)]
 async fn sub(
     parent: &mut Command<'_>,
     client_id: &str,
@@ -288,6 +316,7 @@ async fn sub(
     tag: &Option<String>,
     key: &Option<String>,
     ack: &bool,
+    options: &SubscribeOptions,
 ) -> Result<String, String> {
     let ret = String::new();
 
@@ -309,8 +338,9 @@ async fn sub(
                 state: Some(state.to_string()),
                 tag: Some(tag.to_string()),
                 key: Some(key.to_string()),
-                ack: Some(ack.clone()),
+                ack: Some(*ack),
             },
+            options,
         )
         .await;
 
@@ -1,12 +1,64 @@
 use super::CommandRunner as Command;
 use crate::util;
 use acts_channel::{
-    model::{Package, PackageInfo, PageData},
+    model::{PackageInfo, PageData},
     Vars,
 };
+use base64::{engine::general_purpose::STANDARD, Engine};
 use clap::{Args, Subcommand};
+use flate2::read::GzDecoder;
+use globset::{GlobBuilder, GlobSetBuilder};
 use prettytable::{row, Table};
-use std::path::PathBuf;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    fs::File,
+    io::Read,
+    path::{Component, Path, PathBuf},
+};
+
+const MANIFEST: &str = "package.yml";
+
+/// the package manifest, the version is optional and defaults to the next patch version
+#[derive(Debug, Default, Deserialize, Serialize)]
+struct Manifest {
+    id: String,
+    #[serde(default)]
+    name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    version: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    schema: Option<serde_json::Value>,
+    /// the inline script of the single file package
+    #[serde(skip_serializing_if = "Option::is_none")]
+    body: Option<String>,
+    /// the entry js file of the package with several files
+    #[serde(skip_serializing_if = "Option::is_none")]
+    entry: Option<String>,
+    /// the globs of the package files, all of the files are included if it is empty
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    files: Vec<String>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+struct PackageVersion {
+    id: String,
+    pack: String,
+    version: String,
+    name: String,
+    size: u32,
+    body: String,
+    #[serde(default)]
+    entry: String,
+    /// the base64 contents of the files
+    #[serde(default)]
+    files: BTreeMap<String, String>,
+    #[serde(default)]
+    schema: serde_json::Value,
+    publisher: String,
+    create_time: i64,
+    current: bool,
+}
 
 #[derive(Debug, Args)]
 #[command(args_conflicts_with_subcommands = true)]
@@ -20,24 +72,65 @@ pub struct PacakgeArgs {
 pub enum PacakgeCommands {
     #[command(
         about = "publish a package",
-        long_about = r#"publish a package with yml file
+        long_about = r#"publish a package with yml file, or a directory and tar archive with package.yml
+the version is semver and defaults to the next patch version of the latest one
+the package with several files sets the 'entry' instead of 'body', and lists the file globs in 'files'
+the js files are loaded by 'require', the other utf-8 files are loaded as text or json and the binary files are only kept in the package
 Example: publish ./package.yml
+         publish ./my-package/
+         publish ./my-package.tar.gz
 // package.yml
 id: test
 name: test name
+version: 1.0.0
+schema:
+  inputs:
+    type: object
+    required: [a]
+  outputs:
+    type: object
 body: |
     act.set("a", 100);
     act.complete();
 "#
     )]
     Publish {
-        #[arg(required = true, help = "package file path")]
+        #[arg(required = true, help = "package file, directory or tar archive path")]
         path: PathBuf,
+        #[arg(short, long, help = "package version, overrides the version in file")]
+        ver: Option<String>,
     },
-    #[command(about = "get package by id")]
+    #[command(about = "pull a package and write the package.yml and files to the directory")]
+    Pull {
+        #[arg(help = "package id")]
+        id: String,
+        #[arg(help = "the output directory")]
+        dir: PathBuf,
+        #[arg(short, long, help = "package version, defaults to the current one")]
+        ver: Option<String>,
+    },
+    #[command(about = "list the published versions of a package")]
+    Versions {
+        #[arg(help = "package id")]
+        id: String,
+        #[arg(short, long, help = "skip the offset number to begin count")]
+        offset: Option<u32>,
+        #[arg(short, long, help = "expect to load the max count")]
+        count: Option<u32>,
+    },
+    #[command(about = "make an old version as the current one of a package")]
+    Rollback {
+        #[arg(help = "package id")]
+        id: String,
+        #[arg(help = "package version")]
+        ver: String,
+    },
+    #[command(about = "get package by id with the inputs and outputs schema")]
     Get {
         #[arg(help = "package id")]
         id: String,
+        #[arg(short, long, help = "package version, defaults to the current one")]
+        ver: Option<String>,
     },
     #[command(about = "list all packages")]
     Ls {
@@ -50,38 +143,70 @@ body: |
         #[arg(short='O', long, help = "order by keys. \nexample: -O start_time -O update_time,desc", value_parser = util::parse_sort)]
         order_by: Vec<(String, bool)>,
     },
-    #[command(about = "remove a package by id")]
+    #[command(about = "remove a package by id with all of its versions")]
     Rm {
         #[arg(help = "package id")]
         id: String,
+        #[arg(short, long, help = "remove even if the deployed models reference it")]
+        force: bool,
     },
 }
 
 pub async fn process(parent: &mut Command<'_>, command: &PacakgeCommands) -> Result<(), String> {
     let ret = match command {
-        PacakgeCommands::Get { id } => get(parent, id).await,
+        PacakgeCommands::Get { id, ver } => get(parent, id, ver).await,
         PacakgeCommands::Ls {
             offset,
             count,
             query_by,
             order_by,
         } => ls(parent, offset, count, query_by, order_by).await,
-        PacakgeCommands::Rm { id } => rm(parent, id).await,
-        PacakgeCommands::Publish { path } => publish(parent, path).await,
+        PacakgeCommands::Rm { id, force } => rm(parent, id, *force).await,
+        PacakgeCommands::Publish { path, ver } => publish(parent, path, ver).await,
+        PacakgeCommands::Versions { id, offset, count } => {
+            versions(parent, id, offset, count).await
+        }
+        PacakgeCommands::Rollback { id, ver } => rollback(parent, id, ver).await,
+        PacakgeCommands::Pull { id, dir, ver } => pull(parent, id, dir, ver).await,
     }?;
 
     parent.output(&ret);
     Ok(())
 }
 
-pub async fn publish(parent: &mut Command<'_>, path: &PathBuf) -> Result<String, String> {
+pub async fn publish(
+    parent: &mut Command<'_>,
+    path: &Path,
+    ver: &Option<String>,
+) -> Result<String, String> {
     let mut ret = String::new();
-    let text = std::fs::read_to_string(path).map_err(|err| err.to_string())?;
-
-    let package = serde_yaml::from_str::<Package>(&text).map_err(|err| err.to_string())?;
+    let (package, files) = load(path)?;
+    let mut options = Vars::new()
+        .with("id", package.id)
+        .with("name", package.name);
+    match (package.body, package.entry) {
+        (_, Some(entry)) => {
+            let files: BTreeMap<String, String> = files
+                .into_iter()
+                .map(|(name, content)| (name, STANDARD.encode(content)))
+                .collect();
+            options.set(
+                "files",
+                serde_json::json!({ "entry": entry, "files": files }),
+            );
+        }
+        (Some(body), None) => options.set("body", body),
+        (None, None) => return Err("package 'body' or 'entry' is required".to_string()),
+    }
+    if let Some(schema) = package.schema {
+        options.set("schema", schema);
+    }
+    if let Some(version) = ver.as_ref().or(package.version.as_ref()) {
+        options.set("version", version);
+    }
     let resp = parent
         .client
-        .publish(&package)
+        .send::<bool>("pack:publish", options)
         .await
         .map_err(|err| err.message().to_string())?;
     ret.push_str(&format!("{}", resp.data.unwrap()));
@@ -93,13 +218,88 @@ pub async fn publish(parent: &mut Command<'_>, path: &PathBuf) -> Result<String,
     Ok(ret)
 }
 
-pub async fn get(parent: &mut Command<'_>, id: &str) -> Result<String, String> {
+pub async fn pull(
+    parent: &mut Command<'_>,
+    id: &str,
+    dir: &Path,
+    ver: &Option<String>,
+) -> Result<String, String> {
+    let mut ret = String::new();
+    let mut options = Vars::new().with("id", id);
+    if let Some(ver) = ver {
+        options.set("version", ver);
+    }
+    let resp = parent
+        .client
+        .send::<PackageVersion>("pack:get", options)
+        .await
+        .map_err(|err| err.message().to_string())?;
+
+    let package = resp.data.unwrap();
+    let has_schema = package.schema.as_object().is_some_and(|s| !s.is_empty());
+    let manifest = Manifest {
+        id: package.pack,
+        name: package.name,
+        version: (!package.version.is_empty()).then_some(package.version.clone()),
+        schema: has_schema.then_some(package.schema),
+        body: package.files.is_empty().then_some(package.body),
+        entry: (!package.entry.is_empty()).then_some(package.entry),
+        files: Vec::new(),
+    };
+
+    let mut writes = vec![(
+        dir.join(MANIFEST),
+        serde_yaml::to_string(&manifest)
+            .map_err(|err| err.to_string())?
+            .into_bytes(),
+    )];
+    for (name, content) in package.files {
+        let relative = Path::new(&name);
+        if !relative
+            .components()
+            .all(|c| matches!(c, Component::Normal(_)))
+        {
+            return Err(format!("invalid file path '{name}' in package"));
+        }
+        let content = STANDARD
+            .decode(content)
+            .map_err(|err| format!("{name}: {err}"))?;
+        writes.push((dir.join(relative), content));
+    }
+    for (path, content) in &writes {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).map_err(|err| err.to_string())?;
+        }
+        std::fs::write(path, content).map_err(|err| err.to_string())?;
+    }
+    ret.push_str(&format!(
+        "pulled {}@{} with {} files to {}\n",
+        manifest.id,
+        manifest.version.as_deref().unwrap_or("-"),
+        writes.len(),
+        dir.display()
+    ));
+
+    let cost = resp.end_time - resp.start_time;
+    ret.push_str(&format!("(elapsed {cost}ms)"));
+
+    Ok(ret)
+}
+
+pub async fn get(
+    parent: &mut Command<'_>,
+    id: &str,
+    ver: &Option<String>,
+) -> Result<String, String> {
     let mut ret = String::new();
     let mut options = Vars::new();
     options.set("id", id);
+    if let Some(ver) = ver {
+        options.set("version", ver);
+    }
     let resp = parent
         .client
-        .send::<PackageInfo>("pack:get", options)
+        .send::<PackageVersion>("pack:get", options)
         .await
         .map_err(|err| err.message().to_string())?;
 
@@ -151,17 +351,86 @@ pub async fn ls(
         ]);
     }
     table.printstd();
-    util::print_pager(&mut ret, &data);
+    util::print_pager(&mut ret, data);
+    util::print_cost(&mut ret, &resp);
+
+    Ok(ret)
+}
+
+pub async fn versions(
+    parent: &mut Command<'_>,
+    id: &str,
+    offset: &Option<u32>,
+    count: &Option<u32>,
+) -> Result<String, String> {
+    let mut ret = String::new();
+    let mut options = Vars::new().with("id", id);
+    if let Some(offset) = offset {
+        options.set("offset", offset);
+    };
+    if let Some(count) = count {
+        options.set("count", count);
+    };
+    let resp = parent
+        .client
+        .send::<PageData<PackageVersion>>("pack:versions", options)
+        .await
+        .map_err(|err| err.message().to_string())?;
+
+    let data = resp.data.as_ref().unwrap();
+    let mut table = Table::new();
+    table.add_row(row![
+        "version",
+        "name",
+        "size",
+        "publisher",
+        "publish time",
+        "current"
+    ]);
+    for v in &data.rows {
+        table.add_row(row![
+            v.version,
+            v.name,
+            util::size(v.size),
+            v.publisher,
+            util::local_time(v.create_time),
+            if v.current { "*" } else { "" }
+        ]);
+    }
+    table.printstd();
+    util::print_pager(&mut ret, data);
     util::print_cost(&mut ret, &resp);
 
     Ok(ret)
 }
 
-pub async fn rm(parent: &mut Command<'_>, id: &str) -> Result<String, String> {
+pub async fn rollback(parent: &mut Command<'_>, id: &str, ver: &str) -> Result<String, String> {
     let mut ret = String::new();
     let resp = parent
         .client
-        .send::<bool>("pack:rm", Vars::new().with("id", id))
+        .send::<PackageVersion>(
+            "pack:rollback",
+            Vars::new().with("id", id).with("version", ver),
+        )
+        .await
+        .map_err(|err| err.message().to_string())?;
+
+    let version = resp.data.as_ref().unwrap();
+    ret.push_str(&format!(
+        "{}@{} is current\n",
+        version.pack, version.version
+    ));
+    let cost = resp.end_time - resp.start_time;
+    ret.push_str(&format!("(elapsed {cost}ms)"));
+
+    Ok(ret)
+}
+
+pub async fn rm(parent: &mut Command<'_>, id: &str, force: bool) -> Result<String, String> {
+    let mut ret = String::new();
+    let resp = parent
+        .client
+        .send::<bool>("pack:rm", Vars::new().with("id", id).with("force", force))
         .await
         .map_err(|err| err.message().to_string())?;
 
@@ -171,3 +440,159 @@ pub async fn rm(parent: &mut Command<'_>, id: &str) -> Result<String, String> {
 
     Ok(ret)
 }
+
+/// load the manifest and the package files from a manifest file, a directory or a tar archive
+/// the files are filtered by the manifest globs before reading
+fn load(path: &Path) -> Result<(Manifest, BTreeMap<String, Vec<u8>>), String> {
+    let name = path.to_string_lossy().to_string();
+    let is_tar = name.ends_with(".tar") || name.ends_with(".tar.gz") || name.ends_with(".tgz");
+    let (text, prefix) = if path.is_dir() {
+        let text = std::fs::read_to_string(path.join(MANIFEST))
+            .map_err(|err| format!("{MANIFEST}: {err}"))?;
+        (text, String::new())
+    } else if is_tar {
+        read_tar_manifest(path)?
+    } else {
+        let text = std::fs::read_to_string(path).map_err(|err| err.to_string())?;
+        (text, String::new())
+    };
+
+    let manifest =
+        serde_yaml::from_str::<Manifest>(&text).map_err(|err| format!("{MANIFEST}: {err}"))?;
+    if manifest.entry.is_none() {
+        return Ok((manifest, BTreeMap::new()));
+    }
+
+    let mut globs = GlobSetBuilder::new();
+    for pattern in &manifest.files {
+        globs.add(
+            GlobBuilder::new(pattern)
+                .literal_separator(true)
+                .build()
+                .map_err(|err| err.to_string())?,
+        );
+    }
+    let globs = globs.build().map_err(|err| err.to_string())?;
+    let is_included =
+        |name: &str| name != MANIFEST && (manifest.files.is_empty() || globs.is_match(name));
+
+    // the files of the single manifest file are beside it
+    let files = if path.is_dir() {
+        read_dir(path, &is_included)?
+    } else if is_tar {
+        read_tar(path, &prefix, &is_included)?
+    } else {
+        match path.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => read_dir(dir, &is_included)?,
+            _ => read_dir(Path::new("."), &is_included)?,
+        }
+    };
+
+    Ok((manifest, files))
+}
+
+fn read_dir(
+    dir: &Path,
+    is_included: &dyn Fn(&str) -> bool,
+) -> Result<BTreeMap<String, Vec<u8>>, String> {
+    let mut paths = Vec::new();
+    util::walk(dir, &mut |file| paths.push(file.to_path_buf())).map_err(|err| err.to_string())?;
+
+    let mut files = BTreeMap::new();
+    for path in paths {
+        let name = path
+            .strip_prefix(dir)
+            .map_err(|err| err.to_string())?
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/");
+        if !is_included(&name) {
+            continue;
+        }
+        let content = std::fs::read(&path).map_err(|err| format!("{}: {err}", path.display()))?;
+        files.insert(name, content);
+    }
+    Ok(files)
+}
+
+fn open_tar(path: &Path) -> Result<tar::Archive<Box<dyn Read>>, String> {
+    let file = File::open(path).map_err(|err| err.to_string())?;
+    let reader: Box<dyn Read> = if path.to_string_lossy().ends_with(".tar") {
+        Box::new(file)
+    } else {
+        Box::new(GzDecoder::new(file))
+    };
+    Ok(tar::Archive::new(reader))
+}
+
+/// visit the files in the tar with the path without the leading './'
+fn walk_tar(
+    path: &Path,
+    f: &mut dyn FnMut(&str, &mut dyn Read) -> Result<(), String>,
+) -> Result<(), String> {
+    let mut archive = open_tar(path)?;
+    for entry in archive.entries().map_err(|err| err.to_string())? {
+        let mut entry = entry.map_err(|err| err.to_string())?;
+        if !entry.header().entry_type().is_file() {
+            continue;
+        }
+        let name = entry
+            .path()
+            .map_err(|err| err.to_string())?
+            .to_string_lossy()
+            .trim_start_matches("./")
+            .to_string();
+        f(&name, &mut entry)?;
+    }
+    Ok(())
+}
+
+/// read the manifest in the tar and return it with the directory prefix
+/// the manifest can be in a top level directory
+fn read_tar_manifest(path: &Path) -> Result<(String, String), String> {
+    let mut manifest: Option<(String, String)> = None;
+    walk_tar(path, &mut |name, reader| {
+        let is_manifest = name == MANIFEST || name.ends_with(&format!("/{MANIFEST}"));
+        if !is_manifest
+            || manifest
+                .as_ref()
+                .is_some_and(|(m, _)| m.len() <= name.len())
+        {
+            return Ok(());
+        }
+        let mut text = String::new();
+        reader
+            .read_to_string(&mut text)
+            .map_err(|err| format!("{name}: {err}"))?;
+        manifest = Some((name.to_string(), text));
+        Ok(())
+    })?;
+
+    let (name, text) = manifest.ok_or(format!("cannot find {MANIFEST} in '{}'", path.display()))?;
+    Ok((text, name.trim_end_matches(MANIFEST).to_string()))
+}
+
+/// read the files under the prefix directory in the tar
+fn read_tar(
+    path: &Path,
+    prefix: &str,
+    is_included: &dyn Fn(&str) -> bool,
+) -> Result<BTreeMap<String, Vec<u8>>, String> {
+    let mut files = BTreeMap::new();
+    walk_tar(path, &mut |name, reader| {
+        let Some(name) = name.strip_prefix(prefix) else {
+            return Ok(());
+        };
+        if !is_included(name) {
+            return Ok(());
+        }
+        let mut content = Vec::new();
+        reader
+            .read_to_end(&mut content)
+            .map_err(|err| format!("{name}: {err}"))?;
+        files.insert(name.to_string(), content);
+        Ok(())
+    })?;
+    Ok(files)
+}
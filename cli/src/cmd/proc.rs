@@ -1,11 +1,12 @@
 use super::CommandRunner as Command;
-use crate::util;
+use crate::{tree::TaskTree, util};
 use acts_channel::{
     model::{PageData, ProcInfo},
     Vars,
 };
 use clap::{Args, Subcommand};
 use prettytable::{row, Table};
+use std::path::PathBuf;
 
 #[derive(Debug, Args)]
 #[command(args_conflicts_with_subcommands = true)]
@@ -21,8 +22,17 @@ pub enum ProcCommands {
     Get {
         #[arg(help = "proc id")]
         id: String,
-        #[arg(short, long, help = "format to print, the value should be one of json and tree", value_parser(["json", "tree"]))]
+        #[arg(short, long, help = "format to print, the value should be one of json, tree, mermaid, dot and graph\nthe mermaid, dot and graph highlight the node states of the proc", value_parser(["json", "tree", "mermaid", "dot", "graph"]))]
         fmt: Option<String>,
+        #[arg(short, long, help = "save the output to file")]
+        out: Option<PathBuf>,
+    },
+    #[command(about = "list all tasks of a proc in tree")]
+    Tasks {
+        #[arg(help = "proc id")]
+        pid: String,
+        #[arg(short, long, help = "print the task variables")]
+        vars: bool,
     },
     #[command(about = "list all procs")]
     Ls {
@@ -41,19 +51,27 @@ pub enum ProcCommands {
         id: String,
         #[arg(short, long, help = "specify a pid for proc")]
         pid: Option<String>,
+        #[arg(
+            long,
+            help = "pin the model version, start with the latest version if not specified"
+        )]
+        ver: Option<u32>,
     },
 }
 
 pub async fn process(parent: &mut Command<'_>, command: &ProcCommands) -> Result<(), String> {
     let ret = match command {
-        ProcCommands::Get { id, fmt } => get(parent, id, fmt).await,
+        ProcCommands::Get { id, fmt, out } => get(parent, id, fmt, out).await,
+        ProcCommands::Tasks { pid, vars } => tasks(parent, pid, *vars).await,
         ProcCommands::Ls {
             offset,
             count,
             query_by,
             order_by,
         } => ls(parent, offset, count, query_by, order_by).await,
-        ProcCommands::Start { id, pid } => start(parent, id, pid, &parent.vars.clone()).await,
+        ProcCommands::Start { id, pid, ver } => {
+            start(parent, id, pid, ver, &parent.vars.clone()).await
+        }
     }?;
 
     parent.output(&ret);
@@ -64,6 +82,7 @@ async fn start(
     parent: &mut Command<'_>,
     mid: &str,
     pid: &Option<String>,
+    ver: &Option<u32>,
     vars: &Vars,
 ) -> Result<String, String> {
     let mut ret = String::new();
@@ -71,6 +90,9 @@ async fn start(
     if let Some(pid) = pid {
         options.set("pid", pid);
     }
+    if let Some(ver) = ver {
+        options.set("ver", ver);
+    }
     let resp = parent
         .client
         .start(mid, options)
@@ -90,7 +112,14 @@ pub async fn get(
     parent: &mut Command<'_>,
     pid: &str,
     fmt: &Option<String>,
+    out: &Option<PathBuf>,
 ) -> Result<String, String> {
+    match fmt.as_deref() {
+        Some("tree") => return tasks(parent, pid, false).await,
+        Some("mermaid" | "dot" | "graph") => return graph(parent, pid, fmt, out).await,
+        _ => {}
+    }
+
     let mut ret = String::new();
     let mut options = Vars::new();
     options.set("pid", pid);
@@ -106,7 +135,27 @@ pub async fn get(
         .map_err(|err| err.message().to_string())?;
 
     let proc = resp.data.unwrap();
-    ret.push_str(&serde_json::to_string_pretty(&proc).unwrap());
+    util::write_or_print(&mut ret, &serde_json::to_string_pretty(&proc).unwrap(), out)?;
+    let cost = resp.end_time - resp.start_time;
+    ret.push_str(&format!("(elapsed {cost}ms)"));
+
+    Ok(ret)
+}
+
+async fn graph(
+    parent: &mut Command<'_>,
+    pid: &str,
+    fmt: &Option<String>,
+    out: &Option<PathBuf>,
+) -> Result<String, String> {
+    let mut ret = String::new();
+    let resp = parent
+        .client
+        .send::<String>("proc:get", Vars::new().with("pid", pid).with("fmt", fmt))
+        .await
+        .map_err(|err| err.message().to_string())?;
+
+    util::write_or_print(&mut ret, &resp.data.unwrap(), out)?;
     let cost = resp.end_time - resp.start_time;
     ret.push_str(&format!("(elapsed {cost}ms)"));
 
@@ -148,8 +197,69 @@ pub async fn ls(
         ]);
     }
     table.printstd();
-    util::print_pager(&mut ret, &data);
+    util::print_pager(&mut ret, data);
     util::print_cost(&mut ret, &resp);
 
     Ok(ret)
 }
+
+pub async fn tasks(parent: &mut Command<'_>, pid: &str, vars: bool) -> Result<String, String> {
+    let mut ret = String::new();
+    let resp = parent
+        .client
+        .send::<Vec<TaskTree>>("proc:tasks", Vars::new().with("pid", pid))
+        .await
+        .map_err(|err| err.message().to_string())?;
+
+    let mut out = String::new();
+    for root in resp.data.as_ref().unwrap() {
+        print_task(&mut out, root, "", "", vars);
+    }
+    print!("{out}");
+
+    let cost = resp.end_time - resp.start_time;
+    ret.push_str(&format!("(elapsed {cost}ms)"));
+
+    Ok(ret)
+}
+
+fn print_task(out: &mut String, task: &TaskTree, prefix: &str, child_prefix: &str, vars: bool) {
+    let mut title = task.name.clone();
+    if title.is_empty() {
+        title = task.nid.clone();
+    }
+    out.push_str(&format!(
+        "{prefix}{} [{}] {} tid={} {} ~ {}\n",
+        title,
+        task.r#type,
+        util::state(&task.state),
+        task.id,
+        util::local_time(task.start_time),
+        util::local_time(task.end_time),
+    ));
+
+    let len = task.children.len();
+    if vars {
+        if let Some(data) = task.data.as_object().filter(|v| !v.is_empty()) {
+            let bar = if len > 0 { "│ " } else { "  " };
+            for (k, v) in data {
+                out.push_str(&format!("{child_prefix}{bar} {k}={v}\n"));
+            }
+        }
+    }
+
+    for (i, child) in task.children.iter().enumerate() {
+        let (head, next) = if i + 1 == len {
+            ("└─ ", "   ")
+        } else {
+            ("├─ ", "│  ")
+        };
+        print_task(
+            out,
+            child,
+            &format!("{child_prefix}{head}"),
+            &format!("{child_prefix}{next}"),
+            vars,
+        );
+    }
+}
@@ -0,0 +1,226 @@
+use super::CommandRunner as Command;
+use crate::util;
+use acts_channel::Vars;
+use clap::{Args, Subcommand};
+use prettytable::{row, Table};
+use serde::{Deserialize, Serialize};
+use std::{fs::File, io::Write, path::PathBuf};
+
+/// the size of the uploading chunks, which is the same as the exporting chunks of the server
+const CHUNK_SIZE: usize = 1024 * 1024;
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct ImportReport {
+    pub mode: String,
+    pub dry_run: bool,
+    pub collections: Vec<ImportCollection>,
+    pub restart_required: bool,
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct ImportCollection {
+    pub name: String,
+    pub total: usize,
+    pub created: usize,
+    pub updated: usize,
+    pub removed: usize,
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct ExportChunk {
+    pub id: String,
+    pub next: u64,
+    pub data: String,
+    pub done: bool,
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub name: String,
+    pub path: String,
+    pub create_time: i64,
+    pub files: Vec<SnapshotFile>,
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct SnapshotFile {
+    pub db: String,
+    pub file: String,
+    pub size: u64,
+}
+
+#[derive(Debug, Args)]
+#[command(args_conflicts_with_subcommands = true)]
+#[command(flatten_help = true)]
+pub struct SysArgs {
+    #[command(subcommand)]
+    pub command: SysCommands,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum SysCommands {
+    #[command(
+        about = "take a snapshot of the server data to the backup dir in acts.conf while serving\nrestore it by starting the server with '--restore <snapshot path>'"
+    )]
+    Backup,
+    #[command(
+        about = "export all of the models, packages, procs, tasks and messages to a jsonl archive"
+    )]
+    Export {
+        #[arg(short, long, help = "save the archive to file")]
+        out: PathBuf,
+    },
+    #[command(
+        about = "import the jsonl archive which is exported by 'sys export'\nthe replace mode only runs with --dry-run here, replace the data by starting the server with '--import <archive> --replace'"
+    )]
+    Import {
+        #[arg(help = "archive file path")]
+        path: PathBuf,
+        #[arg(short, long, help = "merge into the current data or replace all of the data", default_value = "merge", value_parser(["merge", "replace"]))]
+        mode: String,
+        #[arg(
+            long,
+            help = "validate the archive and report the changes without writing"
+        )]
+        dry_run: bool,
+    },
+}
+
+pub async fn process(parent: &mut Command<'_>, command: &SysCommands) -> Result<(), String> {
+    let ret = match command {
+        SysCommands::Backup => backup(parent).await,
+        SysCommands::Export { out } => export(parent, out).await,
+        SysCommands::Import {
+            path,
+            mode,
+            dry_run,
+        } => import(parent, path, mode, *dry_run).await,
+    }?;
+
+    parent.output(&ret);
+    Ok(())
+}
+
+async fn backup(parent: &mut Command<'_>) -> Result<String, String> {
+    let mut ret = String::new();
+    let resp = parent
+        .client
+        .send::<Snapshot>("sys:backup", Vars::new())
+        .await
+        .map_err(|err| err.message().to_string())?;
+
+    let snapshot = resp.data.unwrap();
+    let mut table = Table::new();
+    table.add_row(row!["db", "file", "size"]);
+    for f in &snapshot.files {
+        table.add_row(row![f.db, f.file, util::size(f.size as u32)]);
+    }
+    table.printstd();
+
+    ret.push_str(&format!(
+        "saved snapshot {} to {} at {}\n",
+        snapshot.name,
+        snapshot.path,
+        util::local_time(snapshot.create_time)
+    ));
+    let cost = resp.end_time - resp.start_time;
+    ret.push_str(&format!("(elapsed {cost}ms)"));
+
+    Ok(ret)
+}
+
+async fn export(parent: &mut Command<'_>, out: &PathBuf) -> Result<String, String> {
+    let mut ret = String::new();
+    let mut file = File::create(out).map_err(|err| err.to_string())?;
+    let mut options = Vars::new();
+    let mut lines = 0;
+    let mut cost = 0;
+    loop {
+        let resp = parent
+            .client
+            .send::<ExportChunk>("sys:export", options)
+            .await
+            .map_err(|err| err.message().to_string())?;
+        cost += resp.end_time - resp.start_time;
+
+        let chunk = resp.data.unwrap();
+        file.write_all(chunk.data.as_bytes())
+            .map_err(|err| err.to_string())?;
+        lines += chunk.data.lines().count();
+        if chunk.done {
+            break;
+        }
+        options = Vars::new().with("id", chunk.id).with("offset", chunk.next);
+    }
+    ret.push_str(&format!(
+        "exported {} rows to {}\n",
+        lines.saturating_sub(1),
+        out.display()
+    ));
+    ret.push_str(&format!("(elapsed {cost}ms)"));
+
+    Ok(ret)
+}
+
+async fn import(
+    parent: &mut Command<'_>,
+    path: &PathBuf,
+    mode: &str,
+    dry_run: bool,
+) -> Result<String, String> {
+    let mut ret = String::new();
+    let archive = std::fs::read_to_string(path).map_err(|err| err.to_string())?;
+
+    // upload the archive in chunks to keep each message under the grpc limit
+    let mut upload: Option<String> = None;
+    let mut rest = archive.as_str();
+    while !rest.is_empty() {
+        let mut end = rest.len().min(CHUNK_SIZE);
+        while !rest.is_char_boundary(end) {
+            end -= 1;
+        }
+        let mut options = Vars::new().with("data", &rest[..end]);
+        if let Some(id) = &upload {
+            options.set("id", id);
+        }
+        let resp = parent
+            .client
+            .send::<String>("sys:upload", options)
+            .await
+            .map_err(|err| err.message().to_string())?;
+        upload = resp.data;
+        rest = &rest[end..];
+    }
+
+    let resp = parent
+        .client
+        .send::<ImportReport>(
+            "sys:import",
+            Vars::new()
+                .with("upload", upload.unwrap_or_default())
+                .with("mode", mode)
+                .with("dry_run", dry_run),
+        )
+        .await
+        .map_err(|err| err.message().to_string())?;
+
+    let report = resp.data.unwrap();
+    let mut table = Table::new();
+    table.add_row(row!["collection", "total", "created", "updated", "removed"]);
+    for c in &report.collections {
+        table.add_row(row![c.name, c.total, c.created, c.updated, c.removed]);
+    }
+    table.printstd();
+
+    if report.dry_run {
+        ret.push_str("dry run, nothing is written\n");
+    } else if report.restart_required {
+        ret.push_str(
+            "the procs, tasks or messages are imported, restart the server to load them\n",
+        );
+    }
+    let cost = resp.end_time - resp.start_time;
+    ret.push_str(&format!("(elapsed {cost}ms)"));
+
+    Ok(ret)
+}
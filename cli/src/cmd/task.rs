@@ -106,7 +106,7 @@ pub async fn ls(
         ]);
     }
     table.printstd();
-    util::print_pager(&mut ret, &data);
+    util::print_pager(&mut ret, data);
     util::print_cost(&mut ret, &resp);
 
     Ok(ret)
@@ -119,7 +119,7 @@ pub async fn get(parent: &mut Command<'_>, pid: &str, tid: &str) -> Result<Strin
     options.set("tid", tid);
     let resp = parent
         .client
-        .send::<TaskInfo>("task:get", options)
+        .send::<serde_json::Value>("task:get", options)
         .await
         .map_err(|err| err.message().to_string())?;
     let task = resp.data.unwrap();
@@ -1,37 +1,7 @@
 use super::CommandRunner as Command;
-use acts_channel::model::ActValue;
-use clap::{Args, Subcommand, ValueEnum};
-use serde_json::json;
-
-#[derive(Debug, Subcommand)]
-pub enum VarsCommands {
-    #[command(about = "set a record with key and value")]
-    Set {
-        #[arg(help = "vars key in string")]
-        key: String,
-        #[arg(
-            help = "vars value to set, the default format is string, to change format by using -c argument"
-        )]
-        value: String,
-
-        #[arg(
-            short,
-            long,
-            default_value_t = VarFmt::String,
-            value_enum,
-            help="vars value format"
-        )]
-        fmt: VarFmt,
-    },
-    #[command(about = "get value by key")]
-    Get { key: String },
-    #[command(about = "list all items")]
-    Ls,
-    #[command(about = "remove a item by key")]
-    Rm { key: String },
-    #[command(about = "clear all options")]
-    Clear,
-}
+use crate::util;
+use acts_channel::Vars;
+use clap::{Args, Subcommand};
 
 #[derive(Debug, Args)]
 #[command(args_conflicts_with_subcommands = true)]
@@ -41,80 +11,86 @@ pub struct VarsArgs {
     pub command: VarsCommands,
 }
 
-#[derive(ValueEnum, Copy, Clone, Debug, PartialEq, Eq)]
-pub enum VarFmt {
-    Int,
-    Float,
-    String,
-    Json,
+#[derive(Debug, Subcommand)]
+pub enum VarsCommands {
+    #[command(about = "get the vars of a proc or a task")]
+    Get {
+        #[arg(help = "proc id")]
+        pid: String,
+        #[arg(short, long, help = "task id, get the task vars if specified")]
+        tid: Option<String>,
+    },
+    #[command(
+        about = "patch the proc vars",
+        long_about = r#"patch the proc vars with json merge-patch semantics
+a null value removes the key in an object, the vars should exist in the running proc
+Example: set <pid> -v a=1 -v b='{ "x": 1, "y": null }'"#
+    )]
+    Set {
+        #[arg(help = "proc id")]
+        pid: String,
+        #[arg(short, long, required = true, help="vars in K=V format\nthe V can be number, string, or json, \nif the V contains whitesapce, please wrap it in `'` or `\"`\nexample: \n-v a=1 -v b=abc -v c='[2, 3, 4]' -v d='{ \"value\": 100 }' -v e=null", value_parser = util::parse_key_json::<String>)]
+        vars: Vec<(String, serde_json::Value)>,
+    },
 }
 
 pub async fn process(parent: &mut Command<'_>, command: &VarsCommands) -> Result<(), String> {
     let ret = match command {
-        VarsCommands::Set { key, value, fmt } => set(parent, &key, &value, fmt),
-        VarsCommands::Get { key } => get(parent, key),
-        VarsCommands::Ls => ls(parent),
-        VarsCommands::Rm { key } => rm(parent, key),
-        VarsCommands::Clear => clear(parent),
+        VarsCommands::Get { pid, tid } => get(parent, pid, tid).await,
+        VarsCommands::Set { pid, vars } => set(parent, pid, vars).await,
     }?;
 
     parent.output(&ret);
     Ok(())
 }
 
-fn set(parent: &mut Command<'_>, key: &str, value: &str, fmt: &VarFmt) -> Result<String, String> {
-    let value = to_json(&value, &fmt)?;
-    parent.vars.set(key, &value);
-
-    Ok(format!("{key}:{value}"))
-}
-
-fn get(parent: &mut Command<'_>, key: &str) -> Result<String, String> {
-    let ret = match parent.vars.get::<ActValue>(key) {
-        Some(v) => v.to_string(),
-        None => "(nil)".to_string(),
+async fn get(parent: &mut Command<'_>, pid: &str, tid: &Option<String>) -> Result<String, String> {
+    let mut ret = String::new();
+    let mut options = Vars::new().with("pid", pid);
+    let name = match tid {
+        Some(tid) => {
+            options.set("tid", tid);
+            "task:vars:get"
+        }
+        None => "proc:vars:get",
     };
+    let resp = parent
+        .client
+        .send::<serde_json::Value>(name, options)
+        .await
+        .map_err(|err| err.message().to_string())?;
 
-    Ok(ret)
-}
+    let vars = resp.data.unwrap();
+    ret.push_str(&serde_json::to_string_pretty(&vars).unwrap());
+    let cost = resp.end_time - resp.start_time;
+    ret.push_str(&format!("(elapsed {cost}ms)"));
 
-fn rm(parent: &mut Command<'_>, key: &str) -> Result<String, String> {
-    parent.vars.remove(key);
-    Ok("".to_string())
+    Ok(ret)
 }
 
-fn ls(parent: &mut Command<'_>) -> Result<String, String> {
+async fn set(
+    parent: &mut Command<'_>,
+    pid: &str,
+    vars: &Vec<(String, serde_json::Value)>,
+) -> Result<String, String> {
     let mut ret = String::new();
-    if parent.vars.is_empty() {
-        return Ok("(nil)".to_string());
-    }
-    for (k, v) in parent.vars.iter() {
-        ret.push_str(&format!("{k}: {}\n", v.to_string()));
+    let mut patch = Vars::new();
+    for (k, v) in vars {
+        patch.set(k, v);
     }
+    let resp = parent
+        .client
+        .send::<serde_json::Value>(
+            "proc:vars:set",
+            Vars::new().with("pid", pid).with("vars", patch),
+        )
+        .await
+        .map_err(|err| err.message().to_string())?;
 
-    Ok(ret)
-}
+    let vars = resp.data.unwrap();
+    ret.push_str(&serde_json::to_string_pretty(&vars).unwrap());
+    let cost = resp.end_time - resp.start_time;
+    ret.push_str(&format!("(elapsed {cost}ms)"));
 
-fn clear(parent: &mut Command<'_>) -> Result<String, String> {
-    let ret = String::new();
-    parent.vars.clear();
     Ok(ret)
 }
-
-fn to_json(value: &str, fmt: &VarFmt) -> Result<serde_json::Value, String> {
-    match fmt {
-        VarFmt::Int => {
-            let v = value.parse::<i64>().map_err(|err| err.to_string())?;
-            Ok(json!(v))
-        }
-        VarFmt::Float => {
-            let v = value.parse::<f64>().map_err(|err| err.to_string())?;
-            Ok(json!(v))
-        }
-        VarFmt::String => Ok(json!(value)),
-        VarFmt::Json => {
-            Ok(serde_json::de::from_str::<serde_json::Value>(value)
-                .map_err(|err| err.to_string())?)
-        }
-    }
-}
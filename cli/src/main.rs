@@ -1,6 +1,8 @@
 mod cli;
 mod client;
 mod cmd;
+#[path = "../../src/tree/node.rs"]
+mod tree;
 mod util;
 
 use clap::Parser;
@@ -25,8 +27,12 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     }
 
     let uri = format!("http://{hostname}:{port}");
-    let tip = format!("{}:{} $ ", hostname, port);
-    let mut client = client::connect(&uri).await?;
+    let tip = match &cli.namespace {
+        Some(ns) => format!("{}:{} [{ns}] $ ", hostname, port),
+        None => format!("{}:{} $ ", hostname, port),
+    };
+    let metadata = client::Metadata::new(cli.namespace.as_deref(), cli.token.as_deref())?;
+    let mut client = client::connect(&uri, metadata, cli.compress).await?;
     let mut cmd = CommandRunner::new(&mut client);
     show_help_tip();
     loop {
@@ -63,5 +69,5 @@ fn readline(tip: &str) -> Result<String, String> {
 
 fn show_help_tip() {
     let text = "tap 'help' to list available subcommands and some concept guides";
-    writeln!(std::io::stdout(), "{text}").unwrap();
+    println!("{text}");
 }
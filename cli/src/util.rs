@@ -1,6 +1,10 @@
 use acts_channel::{model::PageData, ActionResult};
 use chrono::prelude::*;
-use std::error::Error;
+use owo_colors::OwoColorize;
+use std::{
+    error::Error,
+    path::{Path, PathBuf},
+};
 
 pub const CLAP_STYLING: clap::builder::styling::Styles = clap::builder::styling::Styles::styled()
     .header(clap_cargo::style::HEADER)
@@ -21,6 +25,40 @@ pub fn local_time(millis: i64) -> String {
     }
 }
 
+pub fn state(state: &str) -> String {
+    match state {
+        "completed" | "submitted" => state.green().to_string(),
+        "running" | "pending" | "ready" | "interrupted" | "warning" => state.yellow().to_string(),
+        "error" | "aborted" => state.red().to_string(),
+        _ => state.dimmed().to_string(),
+    }
+}
+
+/// save the text to the file if the path is specified, otherwise print it to the output
+pub fn write_or_print(out: &mut String, text: &str, path: &Option<PathBuf>) -> Result<(), String> {
+    match path {
+        Some(path) => {
+            std::fs::write(path, text).map_err(|err| err.to_string())?;
+            out.push_str(&format!("saved to {}\n", path.display()));
+        }
+        None => out.push_str(text),
+    }
+    Ok(())
+}
+
+/// visit all of the files in the dir recursively
+pub fn walk(dir: &Path, f: &mut dyn FnMut(&Path)) -> std::io::Result<()> {
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            walk(&path, f)?;
+        } else {
+            f(&path);
+        }
+    }
+    Ok(())
+}
+
 pub fn size(bits: u32) -> String {
     let mut ret = String::new();
     if bits < 1024 {
@@ -0,0 +1,560 @@
+use crate::{
+    backend,
+    store::{self, map_db_err, Store},
+    utils,
+};
+use acts::{ActError, Builder, Config, Result};
+use rusqlite::{params_from_iter, types::Value as DbValue, Connection};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::{
+    collections::HashMap,
+    fs::{File, OpenOptions},
+    io::{BufRead, BufReader, BufWriter, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+/// the kind and version in the archive header, the version is increased for the incompatible changes
+pub const ARCHIVE_KIND: &str = "acts-server-archive";
+pub const ARCHIVE_VERSION: u32 = 1;
+
+/// the engine tables, the engine creates them when starting
+const ENGINE_TABLES: [&str; 5] = ["models", "packages", "procs", "tasks", "messages"];
+/// the engine tables which are loaded into the engine cache, the changes take effect after restarting
+const CACHED_TABLES: [&str; 3] = ["procs", "tasks", "messages"];
+
+/// the max size of the chunk when exporting, which keeps the message under the grpc limit
+pub const CHUNK_SIZE: usize = 1024 * 1024;
+/// the exporting and uploading archives in the data dir, they are removed after an hour if not finished
+const EXPORT_DIR: &str = "exports";
+const UPLOAD_DIR: &str = "uploads";
+const TEMP_EXPIRE: Duration = Duration::from_secs(3600);
+
+/// the rows of one collection in the archive
+type Rows = Vec<Map<String, Value>>;
+
+/// the first line of the archive, each collection is followed by its rows in the declared order
+/// the row line is { "collection": "engine.models", "row": { ... } }
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct Header {
+    pub kind: String,
+    pub version: u32,
+    pub create_time: i64,
+    pub collections: Vec<CollectionInfo>,
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct CollectionInfo {
+    /// '{db}.{table}', the db is one of 'engine' and 'server'
+    pub name: String,
+    pub count: usize,
+}
+
+/// a part of the exported archive, the next chunk starts from the 'next' offset until it is done
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct ExportChunk {
+    pub id: String,
+    pub offset: u64,
+    pub next: u64,
+    pub size: u64,
+    pub data: String,
+    pub done: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Line {
+    collection: String,
+    row: Map<String, Value>,
+}
+
+/// the import result of each collection
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct ImportReport {
+    pub mode: String,
+    pub dry_run: bool,
+    pub collections: Vec<ImportCollection>,
+    /// the procs, tasks or messages are changed, which are loaded by the engine when starting
+    pub restart_required: bool,
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct ImportCollection {
+    pub name: String,
+    pub total: usize,
+    pub created: usize,
+    pub updated: usize,
+    /// the removed rows in replace mode
+    pub removed: usize,
+}
+
+/// export the engine and server data to a jsonl archive file
+/// both dbs are read in one transaction, so that the archive is a consistent snapshot
+pub fn export_file(config: &Config, path: &Path) -> Result<Header> {
+    let mut conn = engine_conn(config)?;
+    let server = Path::new(&config.data_dir).join(store::DB_NAME);
+    conn.execute(
+        "attach database ?1 as server",
+        [server.to_string_lossy().as_ref()],
+    )
+    .map_err(map_db_err)?;
+    let tx = conn.transaction().map_err(map_db_err)?;
+
+    let mut collections = Vec::new();
+    for table in ENGINE_TABLES {
+        collections.push((
+            format!("engine.{table}"),
+            read_rows(&tx, &format!("main.{table}"))?,
+        ));
+    }
+    for table in tables(&tx, "server")? {
+        collections.push((
+            format!("server.{table}"),
+            read_rows(&tx, &format!("server.{table}"))?,
+        ));
+    }
+    tx.commit().map_err(map_db_err)?;
+
+    let header = Header {
+        kind: ARCHIVE_KIND.to_string(),
+        version: ARCHIVE_VERSION,
+        create_time: utils::time_millis(),
+        collections: collections
+            .iter()
+            .map(|(name, rows)| CollectionInfo {
+                name: name.clone(),
+                count: rows.len(),
+            })
+            .collect(),
+    };
+    let mut writer = BufWriter::new(File::create(path)?);
+    serde_json::to_writer(&mut writer, &header)?;
+    writer.write_all(b"\n")?;
+    for (name, rows) in collections {
+        for row in rows {
+            serde_json::to_writer(
+                &mut writer,
+                &Line {
+                    collection: name.clone(),
+                    row,
+                },
+            )?;
+            writer.write_all(b"\n")?;
+        }
+    }
+    writer.flush()?;
+
+    Ok(header)
+}
+
+/// read the exported archive in chunks, the first call without id exports the archive to the data dir
+/// the archive file is removed after the last chunk is read
+pub fn export(config: &Config, id: Option<&str>, offset: u64) -> Result<ExportChunk> {
+    let dir = temp_dir(config, EXPORT_DIR)?;
+    let id = match id {
+        Some(id) => check_id(id)?.to_string(),
+        None => {
+            let id = utils::longid();
+            export_file(config, &dir.join(format!("{id}.jsonl")))?;
+            id
+        }
+    };
+
+    let path = dir.join(format!("{id}.jsonl"));
+    let file = File::open(&path)
+        .map_err(|_| ActError::Action(format!("cannot find the exported archive '{id}'")))?;
+    let size = file.metadata()?.len();
+    let mut reader = BufReader::new(file);
+    reader.seek(SeekFrom::Start(offset))?;
+
+    // the chunk ends at the line end to keep the utf-8 chars
+    let mut data = String::new();
+    while data.len() < CHUNK_SIZE {
+        if reader.read_line(&mut data)? == 0 {
+            break;
+        }
+    }
+    let next = offset + data.len() as u64;
+    let done = next >= size;
+    if done {
+        std::fs::remove_file(&path)?;
+    }
+
+    Ok(ExportChunk {
+        id,
+        offset,
+        next,
+        size,
+        data,
+        done,
+    })
+}
+
+/// append the chunk to the uploading archive, which is imported by the returned id later
+pub fn upload(config: &Config, id: Option<&str>, data: &str) -> Result<String> {
+    let dir = temp_dir(config, UPLOAD_DIR)?;
+    let id = match id {
+        Some(id) => check_id(id)?.to_string(),
+        None => utils::longid(),
+    };
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(dir.join(format!("{id}.jsonl")))?;
+    file.write_all(data.as_bytes())?;
+    Ok(id)
+}
+
+/// take the uploaded archive, the file is removed after reading
+pub fn take_upload(config: &Config, id: &str) -> Result<String> {
+    let path = temp_dir(config, UPLOAD_DIR)?.join(format!("{}.jsonl", check_id(id)?));
+    let text = std::fs::read_to_string(&path)
+        .map_err(|_| ActError::Action(format!("cannot find the uploaded archive '{id}'")))?;
+    std::fs::remove_file(&path)?;
+    Ok(text)
+}
+
+/// import the archive file before the engine starts, which is the only way to run the replace mode
+/// the engine is built once to create its tables in the new data dir
+pub fn import_offline(config: &Config, path: &str, mode: &str) -> Result<ImportReport> {
+    let text = std::fs::read_to_string(path)?;
+    let mut builder = Builder::new();
+    builder.set_config(config);
+    let engine = builder.build();
+    engine.close();
+    drop(engine);
+
+    let store = Store::new(&config.data_dir)?;
+    import(config, &store, &text, mode, false)
+}
+
+/// import the archive with 'merge' or 'replace' mode
+/// merge overwrites the rows with the same id, and replace removes all of the rows before importing
+/// the archive is validated at first, and both of the dbs are rolled back if any of them fails
+pub fn import(
+    config: &Config,
+    store: &Store,
+    archive: &str,
+    mode: &str,
+    dry_run: bool,
+) -> Result<ImportReport> {
+    if mode != "merge" && mode != "replace" {
+        return Err(ActError::Action(format!(
+            "import mode should be 'merge' or 'replace', but got '{mode}'"
+        )));
+    }
+    let collections = parse(archive)?;
+
+    let mut engine = engine_conn(config)?;
+    let mut server = store.connection();
+    let engine_tx = engine.transaction().map_err(map_db_err)?;
+    let server_tx = server.transaction().map_err(map_db_err)?;
+
+    let mut report = ImportReport {
+        mode: mode.to_string(),
+        dry_run,
+        ..Default::default()
+    };
+
+    // replace clears all of the collections including those are not in the archive
+    let mut names: Vec<String> = ENGINE_TABLES
+        .iter()
+        .map(|t| format!("engine.{t}"))
+        .collect();
+    if mode == "replace" {
+        names.extend(
+            tables(&server_tx, "main")?
+                .iter()
+                .map(|t| format!("server.{t}")),
+        );
+    }
+    for (name, _) in &collections {
+        if !names.contains(name) {
+            names.push(name.clone());
+        }
+    }
+
+    for name in names {
+        let (db, table) = name.split_once('.').unwrap();
+        let conn = if db == "engine" {
+            &engine_tx
+        } else {
+            &server_tx
+        };
+        if db == "server" {
+            conn.execute(
+                &format!(
+                    "create table if not exists {table} (id VARCHAR PRIMARY KEY NOT NULL, data VARCHAR, create_time BIGINT)"
+                ),
+                [],
+            )
+            .map_err(map_db_err)?;
+        }
+
+        let rows = collections
+            .iter()
+            .find(|(n, _)| *n == name)
+            .map(|(_, rows)| rows.as_slice())
+            .unwrap_or_default();
+        let mut item = ImportCollection {
+            name: name.clone(),
+            total: rows.len(),
+            ..Default::default()
+        };
+        if mode == "replace" {
+            item.removed = conn
+                .execute(&format!("delete from {table}"), [])
+                .map_err(map_db_err)?;
+        }
+        write_rows(conn, table, rows, &mut item)?;
+
+        let changed = item.created + item.updated + item.removed > 0;
+        if changed && db == "engine" && CACHED_TABLES.contains(&table) {
+            report.restart_required = true;
+        }
+        report.collections.push(item);
+    }
+
+    if !dry_run {
+        engine_tx.commit().map_err(map_db_err)?;
+        server_tx.commit().map_err(map_db_err)?;
+    }
+    Ok(report)
+}
+
+/// parse and validate the archive, returns the rows by collection in the declared order
+fn parse(archive: &str) -> Result<Vec<(String, Rows)>> {
+    let mut lines = archive
+        .lines()
+        .enumerate()
+        .filter(|(_, l)| !l.trim().is_empty());
+    let (_, first) = lines
+        .next()
+        .ok_or(ActError::Action("the archive is empty".to_string()))?;
+    let header: Header = serde_json::from_str(first)
+        .map_err(|err| ActError::Action(format!("invalid archive header: {err}")))?;
+    if header.kind != ARCHIVE_KIND {
+        return Err(ActError::Action(format!(
+            "invalid archive kind '{}'",
+            header.kind
+        )));
+    }
+    if header.version > ARCHIVE_VERSION {
+        return Err(ActError::Action(format!(
+            "unsupported archive version {}, the latest supported version is {ARCHIVE_VERSION}",
+            header.version
+        )));
+    }
+
+    let mut collections: Vec<(String, Rows)> = Vec::new();
+    let mut index = HashMap::new();
+    for info in &header.collections {
+        let valid = match info.name.split_once('.') {
+            Some(("engine", table)) => ENGINE_TABLES.contains(&table),
+            Some(("server", table)) => is_name(table),
+            _ => false,
+        };
+        if !valid || index.contains_key(&info.name) {
+            return Err(ActError::Action(format!(
+                "invalid collection '{}' in archive",
+                info.name
+            )));
+        }
+        index.insert(info.name.clone(), collections.len());
+        collections.push((info.name.clone(), Vec::new()));
+    }
+
+    for (num, line) in lines {
+        let line: Line = serde_json::from_str(line)
+            .map_err(|err| ActError::Action(format!("invalid archive line {}: {err}", num + 1)))?;
+        let Some(pos) = index.get(&line.collection) else {
+            return Err(ActError::Action(format!(
+                "undeclared collection '{}' at line {}",
+                line.collection,
+                num + 1
+            )));
+        };
+        if !line.row.get("id").is_some_and(|id| id.is_string())
+            || !line.row.keys().all(|k| is_name(k))
+        {
+            return Err(ActError::Action(format!("invalid row at line {}", num + 1)));
+        }
+        collections[*pos].1.push(line.row);
+    }
+
+    for info in &header.collections {
+        let rows = &collections[index[&info.name]].1;
+        if rows.len() != info.count {
+            return Err(ActError::Action(format!(
+                "collection '{}' declares {} rows, but has {}",
+                info.name,
+                info.count,
+                rows.len()
+            )));
+        }
+    }
+
+    Ok(collections)
+}
+
+pub fn engine_conn(config: &Config) -> Result<Connection> {
+    let conn =
+        Connection::open(Path::new(&config.data_dir).join(&config.db_name)).map_err(map_db_err)?;
+    conn.busy_timeout(Duration::from_secs(5))
+        .map_err(map_db_err)?;
+    Ok(conn)
+}
+
+/// the temp dir in the data dir, the expired files are removed when using it
+fn temp_dir(config: &Config, name: &str) -> Result<PathBuf> {
+    let dir = Path::new(&config.data_dir).join(name);
+    std::fs::create_dir_all(&dir)?;
+    for entry in std::fs::read_dir(&dir)?.flatten() {
+        let expired = entry
+            .metadata()
+            .and_then(|m| m.modified())
+            .ok()
+            .and_then(|t| t.elapsed().ok())
+            .is_some_and(|elapsed| elapsed > TEMP_EXPIRE);
+        if expired {
+            let _ = std::fs::remove_file(entry.path());
+        }
+    }
+    Ok(dir)
+}
+
+/// the id is used as the file name, it is generated by the server
+fn check_id(id: &str) -> Result<&str> {
+    if id.is_empty()
+        || !id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+    {
+        return Err(ActError::Action(format!("invalid archive id '{id}'")));
+    }
+    Ok(id)
+}
+
+fn tables(conn: &Connection, schema: &str) -> Result<Vec<String>> {
+    let mut stmt = conn
+        .prepare(&format!("select name from {schema}.sqlite_master where type = 'table' and name not like 'sqlite_%' and name != ?1 order by name"))
+        .map_err(map_db_err)?;
+    let ret = stmt
+        .query_map([backend::MIGRATION_TABLE], |row| {
+            row.get::<usize, String>(0)
+        })
+        .map_err(map_db_err)?
+        .collect::<std::result::Result<Vec<_>, _>>()
+        .map_err(map_db_err)?;
+    Ok(ret)
+}
+
+fn columns(conn: &Connection, table: &str) -> Result<Vec<String>> {
+    let stmt = conn
+        .prepare(&format!("select * from {table} limit 0"))
+        .map_err(map_db_err)?;
+    Ok(stmt.column_names().iter().map(|c| c.to_string()).collect())
+}
+
+fn read_rows(conn: &Connection, table: &str) -> Result<Vec<Map<String, Value>>> {
+    let columns = columns(conn, table)?;
+    let mut stmt = conn
+        .prepare(&format!("select * from {table} order by rowid"))
+        .map_err(map_db_err)?;
+    let rows = stmt
+        .query_map([], |row| {
+            let mut ret = Map::new();
+            for (index, column) in columns.iter().enumerate() {
+                ret.insert(column.clone(), to_json(row.get::<usize, DbValue>(index)?));
+            }
+            Ok(ret)
+        })
+        .map_err(map_db_err)?
+        .collect::<std::result::Result<Vec<_>, _>>()
+        .map_err(map_db_err)?;
+    Ok(rows)
+}
+
+fn write_rows(
+    conn: &Connection,
+    table: &str,
+    rows: &[Map<String, Value>],
+    item: &mut ImportCollection,
+) -> Result<()> {
+    let columns = columns(conn, table)?;
+    for row in rows {
+        if let Some(key) = row.keys().find(|k| !columns.contains(k)) {
+            return Err(ActError::Action(format!(
+                "cannot find column '{key}' in collection '{}'",
+                item.name
+            )));
+        }
+        let id = row["id"].as_str().unwrap_or_default();
+        let exists = conn
+            .query_row(
+                &format!("select count(id) from {table} where id = ?1"),
+                [id],
+                |r| r.get::<usize, usize>(0),
+            )
+            .map_err(map_db_err)?
+            > 0;
+
+        let keys: Vec<&str> = row.keys().map(|k| k.as_str()).collect();
+        let values = row.values().map(to_db);
+        conn.execute(
+            &format!(
+                "insert or replace into {table} ({}) values ({})",
+                keys.join(","),
+                vec!["?"; keys.len()].join(",")
+            ),
+            params_from_iter(values),
+        )
+        .map_err(map_db_err)?;
+
+        if exists {
+            item.updated += 1;
+        } else {
+            item.created += 1;
+        }
+    }
+    Ok(())
+}
+
+/// the blob is saved as { "$blob": "<hex>" }
+fn to_json(value: DbValue) -> Value {
+    match value {
+        DbValue::Null => Value::Null,
+        DbValue::Integer(v) => Value::from(v),
+        DbValue::Real(v) => Value::from(v),
+        DbValue::Text(v) => Value::String(v),
+        DbValue::Blob(v) => {
+            let hex: String = v.iter().map(|b| format!("{b:02x}")).collect();
+            serde_json::json!({ "$blob": hex })
+        }
+    }
+}
+
+fn to_db(value: &Value) -> DbValue {
+    match value {
+        Value::Null => DbValue::Null,
+        Value::Bool(v) => DbValue::Integer(*v as i64),
+        Value::Number(v) => match v.as_i64() {
+            Some(v) => DbValue::Integer(v),
+            None => DbValue::Real(v.as_f64().unwrap_or_default()),
+        },
+        Value::String(v) => DbValue::Text(v.clone()),
+        Value::Object(obj) if obj.len() == 1 && obj.contains_key("$blob") => {
+            let hex = obj["$blob"].as_str().unwrap_or_default();
+            let bytes = (0..hex.len() / 2)
+                .filter_map(|i| u8::from_str_radix(&hex[i * 2..i * 2 + 2], 16).ok())
+                .collect();
+            DbValue::Blob(bytes)
+        }
+        v => DbValue::Text(v.to_string()),
+    }
+}
+
+/// only allow the simple names to avoid injecting into the sql
+fn is_name(name: &str) -> bool {
+    !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
@@ -0,0 +1,240 @@
+use crate::{
+    grpc::Identity,
+    namespace::Namespace,
+    store::{DbItem, PageData, Store},
+    utils,
+};
+use acts::{ActError, ExecutorQuery, Result, Vars};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::time::Duration;
+
+/// the actions which only read the data, all of the other actions are audited
+/// the pattern '*:get' matches the action with the ':get' suffix, such as 'proc:vars:get'
+const READ_ONLY_ACTIONS: [&str; 7] = [
+    "*:ls",
+    "*:get",
+    "*:diff",
+    "*:validate",
+    "*:versions",
+    "*:tasks",
+    "*:history",
+];
+
+/// 'msg:ack' is left out since the subscribers ack every message they receive
+const UNAUDITED_ACTIONS: [&str; 1] = ["msg:ack"];
+
+/// the user of the audits for the actions which are started by the server itself
+const SYSTEM_USER: &str = "system";
+
+/// the request options which are saved as the audit targets
+const TARGET_KEYS: [&str; 5] = ["pid", "tid", "id", "mid", "client_id"];
+
+/// the request options which are never saved in the audit params
+const SECRET_KEYS: [&str; 6] = [
+    "password",
+    "secret",
+    "token",
+    "authorization",
+    "api_key",
+    "credential",
+];
+
+/// the bulky params such as the model text are saved with the size only
+const MAX_PARAM_LEN: usize = 1024;
+
+/// audit entry for the changes made by the server actions
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Audit {
+    pub id: String,
+    pub action: String,
+    pub pid: String,
+    pub tid: String,
+    /// the ids in the request and the created ids, such as the started pid
+    pub targets: Value,
+    /// the 'x-acts-user' metadata, or 'system' for the actions started by the server itself
+    pub user: Option<String>,
+    /// the remote address, or the source of the system action such as 'schedule:{id}'
+    pub peer: String,
+    pub namespace: String,
+    /// the request options with the secrets redacted
+    pub params: Value,
+    /// 'ok' or the grpc status code of the failure
+    pub outcome: String,
+    pub error: String,
+    /// the milliseconds taken by the action
+    pub latency: u64,
+    pub before: Value,
+    pub after: Value,
+    pub create_time: i64,
+}
+
+impl DbItem for Audit {
+    fn name() -> &'static str {
+        "audit"
+    }
+
+    fn id(&self) -> &str {
+        &self.id
+    }
+}
+
+/// the values before and after the change, the action attaches it to the response extensions
+#[derive(Debug, Default, Clone)]
+pub struct Change {
+    pub before: Value,
+    pub after: Value,
+}
+
+pub fn record(store: &Store, audit: Audit) -> Result<()> {
+    let audit = Audit {
+        id: utils::longid(),
+        create_time: utils::time_millis(),
+        ..audit
+    };
+    tracing::info!("audit: {audit:?}");
+    store.collection::<Audit>()?.create(&audit)?;
+    Ok(())
+}
+
+pub fn is_mutating(action: &str) -> bool {
+    let read_only = READ_ONLY_ACTIONS
+        .iter()
+        .any(|pattern| match pattern.strip_prefix('*') {
+            Some(suffix) => action.ends_with(suffix),
+            None => action == *pattern,
+        });
+    !read_only && !UNAUDITED_ACTIONS.contains(&action)
+}
+
+/// record the action which is sent by the identity, the failure of the audit never fails the action
+pub fn log(
+    store: &Store,
+    identity: &Identity,
+    action: &str,
+    options: &Vars,
+    ret: std::result::Result<Option<&Value>, &tonic::Status>,
+    change: Option<&Change>,
+    latency: Duration,
+) {
+    let mut targets = Map::new();
+    for key in TARGET_KEYS {
+        if let Some(value) = options.get_value(key) {
+            targets.insert(key.to_string(), value.clone());
+        }
+    }
+    let (outcome, error) = match ret {
+        Ok(data) => {
+            // the created ids are returned as the response
+            if let (Some(Value::String(pid)), "proc:start") = (data, action) {
+                targets.insert("pid".to_string(), Value::String(pid.clone()));
+            }
+            ("ok".to_string(), String::new())
+        }
+        Err(status) => (format!("{:?}", status.code()), status.message().to_string()),
+    };
+    let text = |key: &str| {
+        targets
+            .get(key)
+            .and_then(|v| v.as_str())
+            .unwrap_or_default()
+            .to_string()
+    };
+
+    let audit = Audit {
+        action: action.to_string(),
+        pid: text("pid"),
+        tid: text("tid"),
+        user: identity.user.clone(),
+        peer: identity.peer.clone(),
+        namespace: identity.namespace.to_string(),
+        params: redact(&options.clone().into()),
+        outcome,
+        error,
+        latency: latency.as_millis() as u64,
+        targets: Value::Object(targets),
+        before: change.map(|c| c.before.clone()).unwrap_or_default(),
+        after: change.map(|c| c.after.clone()).unwrap_or_default(),
+        ..Default::default()
+    };
+    if let Err(err) = record(store, audit) {
+        tracing::error!("audit: {err}");
+    }
+}
+
+/// record the proc which is started by the server itself, the source is such as 'schedule:{id}'
+/// the failure of the audit never fails the start
+pub fn log_start(
+    store: &Store,
+    source: &str,
+    mid: &str,
+    vars: &Vars,
+    ret: std::result::Result<&str, &ActError>,
+    latency: Duration,
+) {
+    let mut targets = Map::new();
+    targets.insert("id".to_string(), Value::String(mid.to_string()));
+    let (pid, outcome, error) = match ret {
+        Ok(pid) => {
+            targets.insert("pid".to_string(), Value::String(pid.to_string()));
+            (pid.to_string(), "ok".to_string(), String::new())
+        }
+        Err(err) => (String::new(), "Error".to_string(), err.to_string()),
+    };
+
+    let audit = Audit {
+        action: "proc:start".to_string(),
+        pid,
+        user: Some(SYSTEM_USER.to_string()),
+        peer: source.to_string(),
+        namespace: Namespace::of(mid).to_string(),
+        params: redact(&vars.clone().into()),
+        outcome,
+        error,
+        latency: latency.as_millis() as u64,
+        targets: Value::Object(targets),
+        ..Default::default()
+    };
+    if let Err(err) = record(store, audit) {
+        tracing::error!("audit: {err}");
+    }
+}
+
+/// list the audits, the latest one comes first by default
+pub fn list(store: &Store, query: &ExecutorQuery) -> Result<PageData<Audit>> {
+    let query = ExecutorQuery {
+        offset: query.offset,
+        count: query.count,
+        query_by: query.query_by.clone(),
+        order_by: if query.order_by.is_empty() {
+            vec![("create_time".to_string(), true)]
+        } else {
+            query.order_by.clone()
+        },
+    };
+    store.collection::<Audit>()?.query(&query)
+}
+
+fn redact(value: &Value) -> Value {
+    match value {
+        Value::Object(map) => Value::Object(
+            map.iter()
+                .map(|(key, value)| {
+                    let lower = key.to_lowercase();
+                    let value = if SECRET_KEYS.iter().any(|k| lower.contains(k)) {
+                        Value::String("***".to_string())
+                    } else {
+                        redact(value)
+                    };
+                    (key.clone(), value)
+                })
+                .collect(),
+        ),
+        Value::Array(items) => Value::Array(items.iter().map(redact).collect()),
+        Value::String(text) if text.len() > MAX_PARAM_LEN => {
+            Value::String(format!("({} bytes)", text.len()))
+        }
+        _ => value.clone(),
+    }
+}
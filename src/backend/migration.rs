@@ -0,0 +1,127 @@
+use crate::{
+    store::{map_db_err, DB_NAME},
+    utils,
+};
+use acts::{Config, Result};
+use rusqlite::{params, Connection, TransactionBehavior};
+use std::{path::Path, time::Duration};
+
+/// the migration table in the server db, it is skipped by the archive
+pub const MIGRATION_TABLE: &str = "acts_migrations";
+
+/// the schema migration of the server db, the applied versions are saved in the migration table
+/// the engine db is owned by the acts engine and never changed here
+/// never change an applied migration, append a new one instead
+pub struct Migration {
+    pub version: i64,
+    pub name: &'static str,
+    /// the collection and the json field to index, the index matches the `find_by` and `delete_by` queries
+    pub indexes: &'static [(&'static str, &'static str)],
+    /// the collection and the table column to index, such as the create_time of the rows
+    pub columns: &'static [(&'static str, &'static str)],
+}
+
+pub const MIGRATIONS: [Migration; 2] = [
+    Migration {
+        version: 1,
+        name: "create query indexes",
+        indexes: &[
+            ("model_version", "mid"),
+            ("package_version", "pack"),
+            ("message_attempt", "message_id"),
+            ("schedule", "state"),
+            ("trigger", "path"),
+            ("webhook_delivery", "hook"),
+            ("webhook_delivery", "status"),
+        ],
+        columns: &[],
+    },
+    Migration {
+        version: 2,
+        name: "create journal indexes",
+        indexes: &[
+            ("message_journal", "mid"),
+            ("message_inflight", "mid"),
+            ("message_inflight", "client"),
+        ],
+        columns: &[("message_journal", "create_time")],
+    },
+];
+
+/// apply the pending migrations to the server db before the store opens and return the applied versions
+pub fn migrate(config: &Config) -> Result<Vec<i64>> {
+    std::fs::create_dir_all(&config.data_dir)?;
+    let mut conn =
+        Connection::open(Path::new(&config.data_dir).join(DB_NAME)).map_err(map_db_err)?;
+    conn.busy_timeout(Duration::from_secs(30))
+        .map_err(map_db_err)?;
+
+    let tx = conn
+        .transaction_with_behavior(TransactionBehavior::Immediate)
+        .map_err(map_db_err)?;
+    tx.execute(
+        &format!(
+            "create table if not exists {MIGRATION_TABLE} (version integer primary key not null, name text, apply_time integer)"
+        ),
+        [],
+    )
+    .map_err(map_db_err)?;
+
+    let mut applied = Vec::new();
+    for migration in MIGRATIONS.iter() {
+        let exists = tx
+            .query_row(
+                &format!("select count(version) from {MIGRATION_TABLE} where version = ?1"),
+                params![migration.version],
+                |row| row.get::<usize, i64>(0),
+            )
+            .map_err(map_db_err)?
+            > 0;
+        if exists {
+            continue;
+        }
+        let indexes = migration
+            .indexes
+            .iter()
+            .map(|(table, key)| {
+                (
+                    table,
+                    key,
+                    format!("cast(json_extract(data, '$.{key}') as text)"),
+                )
+            })
+            .chain(
+                migration
+                    .columns
+                    .iter()
+                    .map(|(table, column)| (table, column, column.to_string())),
+            );
+        for (table, key, expr) in indexes {
+            // the collection is created lazily by the store, create it here to index it
+            tx.execute(
+                &format!(
+                    "create table if not exists {table} (id VARCHAR PRIMARY KEY NOT NULL, data VARCHAR, create_time BIGINT)"
+                ),
+                [],
+            )
+            .map_err(map_db_err)?;
+            tx.execute(
+                &format!("create index if not exists idx_{table}_{key} on {table} ({expr})"),
+                [],
+            )
+            .map_err(map_db_err)?;
+        }
+        tx.execute(
+            &format!(
+                "insert into {MIGRATION_TABLE} (version, name, apply_time) values (?1, ?2, ?3)"
+            ),
+            params![migration.version, migration.name, utils::time_millis()],
+        )
+        .map_err(map_db_err)?;
+        tracing::info!("migration: {} {}", migration.version, migration.name);
+        applied.push(migration.version);
+    }
+    tx.commit().map_err(map_db_err)?;
+
+    Ok(applied)
+}
@@ -0,0 +1,6 @@
+mod migration;
+
+#[cfg(test)]
+mod tests;
+
+pub use migration::{migrate, MIGRATION_TABLE};
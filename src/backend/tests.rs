@@ -0,0 +1,90 @@
+use super::{migrate, migration::MIGRATIONS};
+use crate::store::DB_NAME;
+use acts::Config;
+use rusqlite::Connection;
+use std::path::Path;
+
+fn config(name: &str) -> Config {
+    let dir = std::env::temp_dir()
+        .join("acts-server-tests")
+        .join("backend")
+        .join(name);
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    Config {
+        data_dir: dir.to_string_lossy().to_string(),
+        ..Default::default()
+    }
+}
+
+#[test]
+fn backend_migrate() {
+    let config = config("backend_migrate");
+    let applied = migrate(&config).unwrap();
+    assert_eq!(
+        applied,
+        MIGRATIONS.iter().map(|m| m.version).collect::<Vec<_>>()
+    );
+
+    let conn = Connection::open(Path::new(&config.data_dir).join(DB_NAME)).unwrap();
+    let count = conn
+        .query_row(
+            "select count(name) from sqlite_master where type = 'index' and name like 'idx_%'",
+            [],
+            |row| row.get::<usize, usize>(0),
+        )
+        .unwrap();
+    assert_eq!(
+        count,
+        MIGRATIONS
+            .iter()
+            .map(|m| m.indexes.len() + m.columns.len())
+            .sum::<usize>()
+    );
+
+    // the index is used by the find_by query
+    let plan = conn
+        .query_row(
+            "explain query plan select data from model_version where cast(json_extract(data, '$.mid') as text) = ?1",
+            ["m1"],
+            |row| row.get::<usize, String>(3),
+        )
+        .unwrap();
+    assert!(plan.contains("idx_model_version_mid"), "{plan}");
+
+    // the journal is purged by the create_time column
+    let plan = conn
+        .query_row(
+            "explain query plan select id from message_journal where create_time < ?1",
+            [0],
+            |row| row.get::<usize, String>(3),
+        )
+        .unwrap();
+    assert!(plan.contains("idx_message_journal_create_time"), "{plan}");
+
+    // the engine db is never changed
+    assert!(!Path::new(&config.data_dir).join(&config.db_name).exists());
+
+    // the applied migrations are skipped
+    assert!(migrate(&config).unwrap().is_empty());
+}
+
+#[test]
+fn backend_migrate_rollback() {
+    let config = config("backend_migrate_rollback");
+    let conn = Connection::open(Path::new(&config.data_dir).join(DB_NAME)).unwrap();
+    // the view cannot be indexed
+    conn.execute("create view schedule as select 1 as data", [])
+        .unwrap();
+    assert!(migrate(&config).is_err());
+
+    // the failed migration is rolled back
+    let count = conn
+        .query_row(
+            "select count(name) from sqlite_master where name = 'acts_migrations' or name like 'idx_%'",
+            [],
+            |row| row.get::<usize, usize>(0),
+        )
+        .unwrap();
+    assert_eq!(count, 0);
+}
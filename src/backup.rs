@@ -0,0 +1,272 @@
+use crate::{
+    archive,
+    store::{self, map_db_err},
+    utils,
+};
+use acts::{ActError, Config, Result};
+use rusqlite::{backup::Backup, Connection, DatabaseName, OpenFlags};
+use serde::{Deserialize, Serialize};
+use std::{path::Path, time::Duration};
+
+/// the kind and version in the snapshot manifest
+pub const SNAPSHOT_KIND: &str = "acts-server-snapshot";
+pub const SNAPSHOT_VERSION: u32 = 1;
+
+const MANIFEST: &str = "snapshot.json";
+const PREFIX: &str = "snapshot-";
+
+/// the manifest saved in the snapshot directory
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub kind: String,
+    pub version: u32,
+    /// the snapshot directory name
+    pub name: String,
+    pub path: String,
+    pub create_time: i64,
+    pub files: Vec<SnapshotFile>,
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct SnapshotFile {
+    /// one of 'engine' and 'server'
+    pub db: String,
+    pub file: String,
+    pub size: u64,
+}
+
+/// the scheduled backup options in acts.conf
+#[derive(Debug, Clone)]
+pub struct BackupOptions {
+    pub dir: String,
+    /// the seconds between two backups
+    pub interval: u64,
+    /// the max snapshots to keep, the older ones are removed after each backup
+    pub keep: usize,
+}
+
+/// take a point-in-time snapshot of the engine and server db into a new directory of the target dir
+/// both dbs are copied in one read transaction, so the server keeps serving and the snapshot is consistent
+pub fn backup(config: &Config, dir: &str) -> Result<Snapshot> {
+    let create_time = utils::time_millis();
+    let name = format!("{PREFIX}{create_time}");
+    let target = Path::new(dir).join(&name);
+    if target.exists() {
+        return Err(ActError::Action(format!(
+            "snapshot '{}' already exists",
+            target.display()
+        )));
+    }
+
+    // write to a temporary directory first, so an interrupted backup never looks like a snapshot
+    let temp = Path::new(dir).join(format!(".{name}"));
+    std::fs::create_dir_all(&temp)?;
+    let ret = snapshot(config, &temp, &target, create_time).and_then(|snapshot| {
+        std::fs::rename(&temp, &target)?;
+        Ok(snapshot)
+    });
+    if ret.is_err() {
+        let _ = std::fs::remove_dir_all(&temp);
+    }
+    ret
+}
+
+/// list the snapshots in the dir, the newest first
+pub fn list(dir: &str) -> Result<Vec<Snapshot>> {
+    let mut snapshots = Vec::new();
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return Ok(snapshots);
+    };
+    for entry in entries {
+        let path = entry?.path();
+        let is_snapshot = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .is_some_and(|name| name.starts_with(PREFIX));
+        if is_snapshot && path.join(MANIFEST).is_file() {
+            snapshots.push(manifest(&path)?);
+        }
+    }
+    snapshots.sort_by_key(|s| std::cmp::Reverse(s.create_time));
+    Ok(snapshots)
+}
+
+/// remove the older snapshots and keep the newest ones
+pub fn prune(dir: &str, keep: usize) -> Result<Vec<Snapshot>> {
+    let removed = list(dir)?.into_iter().skip(keep).collect::<Vec<_>>();
+    for snapshot in &removed {
+        std::fs::remove_dir_all(&snapshot.path)?;
+    }
+    Ok(removed)
+}
+
+/// restore the snapshot into the data dir, it should be called before the engine starts
+pub fn restore(config: &Config, path: &str) -> Result<Snapshot> {
+    let snapshot = manifest(Path::new(path))?;
+    for file in &snapshot.files {
+        check(&Path::new(path).join(&file.file))?;
+    }
+
+    std::fs::create_dir_all(&config.data_dir)?;
+    for file in &snapshot.files {
+        let target = Path::new(&config.data_dir).join(db_name(config, &file.db)?);
+        let temp = target.with_extension("restore");
+        std::fs::copy(Path::new(path).join(&file.file), &temp)?;
+        // the stale journal files belong to the replaced db
+        for ext in ["-wal", "-shm", "-journal"] {
+            let mut journal = target.clone().into_os_string();
+            journal.push(ext);
+            let _ = std::fs::remove_file(journal);
+        }
+        std::fs::rename(&temp, &target)?;
+    }
+    Ok(snapshot)
+}
+
+/// run the scheduled backups in background
+pub fn schedule(config: Config, options: BackupOptions) {
+    tokio::spawn(async move {
+        let interval = Duration::from_secs(options.interval.max(1));
+        loop {
+            tokio::time::sleep(interval).await;
+            let config = config.clone();
+            let options = options.clone();
+            let ret = tokio::task::spawn_blocking(move || {
+                let snapshot = backup(&config, &options.dir)?;
+                let removed = prune(&options.dir, options.keep.max(1))?;
+                Ok::<_, ActError>((snapshot, removed))
+            })
+            .await;
+            match ret {
+                Ok(Ok((snapshot, removed))) => {
+                    tracing::info!(
+                        "backup: snapshot={} removed={}",
+                        snapshot.path,
+                        removed.len()
+                    );
+                }
+                Ok(Err(err)) => tracing::error!("backup: {err}"),
+                Err(err) => tracing::error!("backup: {err}"),
+            }
+        }
+    });
+}
+
+fn snapshot(config: &Config, temp: &Path, target: &Path, create_time: i64) -> Result<Snapshot> {
+    // both dbs are attached to one connection and copied in one read transaction
+    let mut conn = archive::engine_conn(config)?;
+    let mut dbs = vec![("main", config.db_name.clone())];
+    let server = Path::new(&config.data_dir).join(store::DB_NAME);
+    if server.exists() {
+        conn.execute(
+            "attach database ?1 as server",
+            [server.to_string_lossy().as_ref()],
+        )
+        .map_err(map_db_err)?;
+        dbs.push(("server", store::DB_NAME.to_string()));
+    }
+    let tx = conn.transaction().map_err(map_db_err)?;
+    for (schema, _) in &dbs {
+        // start reading to hold the shared lock until the transaction ends
+        tx.query_row(
+            &format!("select count(*) from {schema}.sqlite_master"),
+            [],
+            |row| row.get::<usize, i64>(0),
+        )
+        .map_err(map_db_err)?;
+    }
+
+    let mut files = Vec::new();
+    for (schema, file) in dbs {
+        let dest = temp.join(&file);
+        let mut dest_conn = Connection::open(&dest).map_err(map_db_err)?;
+        let source = match schema {
+            "main" => DatabaseName::Main,
+            _ => DatabaseName::Attached(schema),
+        };
+        Backup::new_with_names(&tx, source, &mut dest_conn, DatabaseName::Main)
+            .and_then(|backup| backup.step(-1))
+            .map_err(map_db_err)?;
+        drop(dest_conn);
+        files.push(SnapshotFile {
+            db: if schema == "main" { "engine" } else { "server" }.to_string(),
+            size: std::fs::metadata(&dest)?.len(),
+            file,
+        });
+    }
+    tx.commit().map_err(map_db_err)?;
+
+    let snapshot = Snapshot {
+        kind: SNAPSHOT_KIND.to_string(),
+        version: SNAPSHOT_VERSION,
+        name: target
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default(),
+        path: target.to_string_lossy().to_string(),
+        create_time,
+        files,
+    };
+    std::fs::write(
+        temp.join(MANIFEST),
+        serde_json::to_string_pretty(&snapshot)?,
+    )?;
+    Ok(snapshot)
+}
+
+fn manifest(path: &Path) -> Result<Snapshot> {
+    let text = std::fs::read_to_string(path.join(MANIFEST)).map_err(|err| {
+        ActError::Action(format!(
+            "cannot read the snapshot manifest in '{}': {err}",
+            path.display()
+        ))
+    })?;
+    let mut snapshot: Snapshot = serde_json::from_str(&text)?;
+    if snapshot.kind != SNAPSHOT_KIND || snapshot.version != SNAPSHOT_VERSION {
+        return Err(ActError::Action(format!(
+            "unsupported snapshot '{}' version {}",
+            snapshot.kind, snapshot.version
+        )));
+    }
+    for file in &snapshot.files {
+        if Path::new(&file.file)
+            .file_name()
+            .and_then(|name| name.to_str())
+            != Some(&file.file)
+        {
+            return Err(ActError::Action(format!(
+                "invalid file '{}' in snapshot",
+                file.file
+            )));
+        }
+    }
+    // the snapshot directory may be moved after backup
+    snapshot.path = path.to_string_lossy().to_string();
+    Ok(snapshot)
+}
+
+/// check the integrity of the snapshot db before restoring
+fn check(path: &Path) -> Result<()> {
+    let conn =
+        Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY).map_err(map_db_err)?;
+    let ret = conn
+        .query_row("pragma integrity_check", [], |row| {
+            row.get::<usize, String>(0)
+        })
+        .map_err(map_db_err)?;
+    if ret != "ok" {
+        return Err(ActError::Action(format!(
+            "the snapshot db '{}' is corrupted: {ret}",
+            path.display()
+        )));
+    }
+    Ok(())
+}
+
+fn db_name(config: &Config, db: &str) -> Result<String> {
+    match db {
+        "engine" => Ok(config.db_name.clone()),
+        "server" => Ok(store::DB_NAME.to_string()),
+        _ => Err(ActError::Action(format!("unknown db '{db}' in snapshot"))),
+    }
+}
@@ -0,0 +1,54 @@
+use super::{Broker, BrokerMessage, Handler};
+use acts::Result;
+use std::sync::Mutex;
+
+/// the broker in process, the handlers are called in the publishing thread
+#[derive(Default)]
+pub struct MemoryBroker {
+    subscribers: Mutex<Vec<(String, Handler)>>,
+}
+
+impl MemoryBroker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl Broker for MemoryBroker {
+    fn publish(&self, message: BrokerMessage) -> Result<()> {
+        let handlers = self
+            .subscribers
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(pattern, _)| is_match(pattern, &message.subject))
+            .map(|(_, handler)| handler.clone())
+            .collect::<Vec<_>>();
+        for handler in handlers {
+            handler(message.clone());
+        }
+        Ok(())
+    }
+
+    fn subscribe(&self, subject: &str, handler: Handler) -> Result<()> {
+        self.subscribers
+            .lock()
+            .unwrap()
+            .push((subject.to_string(), handler));
+        Ok(())
+    }
+}
+
+/// check the subject matches the pattern with the '*' and '>' wildcards
+fn is_match(pattern: &str, subject: &str) -> bool {
+    let mut tokens = subject.split('.');
+    for part in pattern.split('.') {
+        match (part, tokens.next()) {
+            (">", Some(_)) => return true,
+            ("*", Some(_)) => {}
+            (part, Some(token)) if part == token => {}
+            _ => return false,
+        }
+    }
+    tokens.next().is_none()
+}
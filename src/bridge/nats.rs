@@ -0,0 +1,106 @@
+use super::{BridgeOptions, Broker, BrokerMessage, Handler};
+use acts::{ActError, Result};
+use async_nats::{Client, ConnectOptions};
+use tokio::sync::mpsc::{self, UnboundedSender};
+use tokio_stream::StreamExt;
+
+/// the nats client, it reconnects and resubscribes the subjects when the connection is lost
+/// the published messages are sent in order by the background writer
+pub struct NatsBroker {
+    client: Client,
+    writer: UnboundedSender<BrokerMessage>,
+}
+
+impl NatsBroker {
+    pub async fn connect(options: &BridgeOptions) -> Result<Self> {
+        let (url, user, pass) = credentials(&options.url)?;
+        let err = |err: String| ActError::Runtime(format!("cannot connect nats '{url}': {err}"));
+        let mut connect = ConnectOptions::new()
+            .name("acts-server")
+            .require_tls(options.tls)
+            .event_callback(|event| async move { tracing::info!("nats: {event}") });
+        match (user, pass) {
+            (Some(user), Some(pass)) => connect = connect.user_and_password(user, pass),
+            (Some(token), None) => connect = connect.token(token),
+            _ => {}
+        }
+        if let Some(path) = &options.credentials {
+            connect = connect
+                .credentials_file(path)
+                .await
+                .map_err(|e| err(e.to_string()))?;
+        }
+        if let Some(path) = &options.root_ca {
+            connect = connect.add_root_certificates(path.into());
+        }
+        let client = connect
+            .connect(url.as_str())
+            .await
+            .map_err(|e| err(e.to_string()))?;
+
+        let (tx, mut rx) = mpsc::unbounded_channel::<BrokerMessage>();
+        let writer = client.clone();
+        tokio::spawn(async move {
+            while let Some(message) = rx.recv().await {
+                let payload = message.payload.into();
+                let ret = match message.reply {
+                    Some(reply) => {
+                        writer
+                            .publish_with_reply(message.subject, reply, payload)
+                            .await
+                    }
+                    None => writer.publish(message.subject, payload).await,
+                };
+                if let Err(err) = ret {
+                    tracing::error!("nats: {err}");
+                }
+            }
+        });
+        Ok(Self { client, writer: tx })
+    }
+}
+
+impl Broker for NatsBroker {
+    fn publish(&self, message: BrokerMessage) -> Result<()> {
+        self.writer
+            .send(message)
+            .map_err(|_| ActError::Runtime("nats client is closed".to_string()))
+    }
+
+    fn subscribe(&self, subject: &str, handler: Handler) -> Result<()> {
+        let client = self.client.clone();
+        let subject = subject.to_string();
+        tokio::spawn(async move {
+            let mut subscriber = match client.subscribe(subject.clone()).await {
+                Ok(subscriber) => subscriber,
+                Err(err) => {
+                    tracing::error!("nats: subject={subject} error={err}");
+                    return;
+                }
+            };
+            while let Some(message) = subscriber.next().await {
+                handler(BrokerMessage {
+                    subject: message.subject.to_string(),
+                    reply: message.reply.map(|reply| reply.to_string()),
+                    payload: message.payload.to_vec(),
+                });
+            }
+        });
+        Ok(())
+    }
+}
+
+/// take the user and password, or the token out of the url
+/// the url without the credentials is returned, so it can be logged
+pub fn credentials(url: &str) -> Result<(String, Option<String>, Option<String>)> {
+    let mut parsed = reqwest::Url::parse(url)
+        .map_err(|err| ActError::Runtime(format!("invalid nats url: {err}")))?;
+    let user = Some(parsed.username().to_string()).filter(|user| !user.is_empty());
+    let pass = parsed.password().map(|pass| pass.to_string());
+    if user.is_none() && pass.is_none() {
+        return Ok((url.to_string(), None, None));
+    }
+    let _ = parsed.set_username("");
+    let _ = parsed.set_password(None);
+    Ok((parsed.to_string(), user, pass))
+}
@@ -0,0 +1,102 @@
+use acts::{ActError, Result};
+use std::collections::BTreeMap;
+
+/// the loader of the bundled files, the js files are loaded as commonjs modules
+/// and the other files are loaded as text, or parsed if it is json
+const LOADER_SCRIPT: &str = r#"
+    const cache = {};
+    function resolve(base, path) {
+        let parts = path.startsWith(".") ? base.split("/").slice(0, -1) : [];
+        for (let part of path.split("/")) {
+            if (part === "..") parts.pop();
+            else if (part !== "." && part !== "") parts.push(part);
+        }
+        let name = parts.join("/");
+        if (sources[name] === undefined && texts[name] === undefined && sources[name + ".js"] !== undefined) {
+            name += ".js";
+        }
+        return name;
+    }
+    function load(name) {
+        if (cache[name] !== undefined) return cache[name].exports;
+        if (texts[name] !== undefined) {
+            return name.endsWith(".json") ? JSON.parse(texts[name]) : texts[name];
+        }
+        if (sources[name] === undefined) {
+            throw new Error("cannot find file '" + name + "' in package");
+        }
+        let module = { exports: {} };
+        cache[name] = module;
+        sources[name](module, module.exports, (path) => load(resolve(name, path)));
+        return module.exports;
+    }
+"#;
+
+/// bundle the package files into one script which runs the entry file
+/// the files which are not utf-8 are kept in the package, but they cannot be loaded by 'require'
+pub fn bundle(entry: &str, files: &BTreeMap<String, Vec<u8>>) -> Result<String> {
+    for path in files.keys() {
+        check_path(path)?;
+    }
+    if !files.contains_key(entry) {
+        return Err(ActError::Action(format!(
+            "cannot find the entry '{entry}' in package files"
+        )));
+    }
+    if !is_script(entry) {
+        return Err(ActError::Action(format!(
+            "the entry '{entry}' should be a js file"
+        )));
+    }
+
+    let mut sources = String::new();
+    let mut texts = String::new();
+    for (path, content) in files {
+        let key = serde_json::to_string(path)?;
+        let content = match std::str::from_utf8(content) {
+            Ok(content) => content,
+            Err(_) if is_script(path) => {
+                return Err(ActError::Action(format!(
+                    "the js file '{path}' should be utf-8"
+                )))
+            }
+            Err(_) => continue,
+        };
+        if is_script(path) {
+            sources.push_str(&format!(
+                "        {key}: function (module, exports, require) {{\n{content}\n        }},\n"
+            ));
+        } else {
+            texts.push_str(&format!(
+                "        {key}: {},\n",
+                serde_json::to_string(content)?
+            ));
+        }
+    }
+
+    Ok(format!(
+        "(function () {{\n    const sources = {{\n{sources}    }};\n    const texts = {{\n{texts}    }};\n{LOADER_SCRIPT}    load({});\n}})();\n",
+        serde_json::to_string(entry)?
+    ))
+}
+
+/// the file path should be relative and stay in the package
+pub fn check_path(path: &str) -> Result<()> {
+    let invalid = path.is_empty()
+        || path.starts_with('/')
+        || path.contains('\\')
+        || path.contains(':')
+        || path
+            .split('/')
+            .any(|p| p.is_empty() || p == "." || p == "..");
+    if invalid {
+        return Err(ActError::Action(format!(
+            "invalid file path '{path}' in package"
+        )));
+    }
+    Ok(())
+}
+
+fn is_script(path: &str) -> bool {
+    path.ends_with(".js")
+}
@@ -0,0 +1,296 @@
+use crate::grpc::GrpcServer;
+use acts::{ActError, Result};
+use acts_channel::acts_service_server::ActsServiceServer;
+use hyper::{
+    body::{Bytes, HttpBody, SizeHint},
+    header::HeaderValue,
+    http, Body, HeaderMap,
+};
+use std::{
+    convert::Infallible,
+    io::{Read, Write},
+    pin::Pin,
+    task::{ready, Context, Poll},
+};
+use tonic::{
+    body::BoxBody,
+    codec::CompressionEncoding,
+    codegen::{BoxFuture, Service},
+    transport::NamedService,
+    Status,
+};
+
+const ENCODING_HEADER: &str = "grpc-encoding";
+const ACCEPT_ENCODING_HEADER: &str = "grpc-accept-encoding";
+
+/// the flag byte and the length of the grpc message frame
+const FRAME_HEADER: usize = 5;
+
+/// the grpc compression options in acts.conf
+/// the messages are only compressed for the clients which send the 'grpc-accept-encoding' header
+#[derive(Debug, Clone)]
+pub struct CompressionOptions {
+    /// the preferred encoding of the messages, gzip is used for the clients which do not accept it
+    /// none to never compress them
+    pub encoding: Option<Encoding>,
+    /// the min bytes of the messages to compress, both of the unary responses and the on_message stream
+    pub threshold: usize,
+}
+
+impl Default for CompressionOptions {
+    fn default() -> Self {
+        Self {
+            encoding: Some(Encoding::Gzip),
+            threshold: 1024,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Encoding {
+    Gzip,
+    Zstd,
+}
+
+impl Encoding {
+    fn name(&self) -> &'static str {
+        match self {
+            Encoding::Gzip => "gzip",
+            Encoding::Zstd => "zstd",
+        }
+    }
+
+    fn compress(&self, data: &[u8]) -> std::io::Result<Vec<u8>> {
+        match self {
+            Encoding::Gzip => {
+                let mut encoder =
+                    flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+                encoder.write_all(data)?;
+                encoder.finish()
+            }
+            Encoding::Zstd => zstd::encode_all(data, zstd::DEFAULT_COMPRESSION_LEVEL),
+        }
+    }
+
+    fn decompress(&self, data: &[u8]) -> std::io::Result<Vec<u8>> {
+        match self {
+            Encoding::Gzip => {
+                let mut ret = Vec::new();
+                flate2::read::GzDecoder::new(data).read_to_end(&mut ret)?;
+                Ok(ret)
+            }
+            Encoding::Zstd => zstd::decode_all(data),
+        }
+    }
+}
+
+/// parse the encoding name in acts.conf
+pub fn encoding(name: &str) -> Result<Option<Encoding>> {
+    match name {
+        "gzip" => Ok(Some(Encoding::Gzip)),
+        "zstd" => Ok(Some(Encoding::Zstd)),
+        "none" => Ok(None),
+        _ => Err(ActError::Runtime(format!(
+            "compression encoding should be 'gzip', 'zstd' or 'none', but got '{name}'"
+        ))),
+    }
+}
+
+/// the grpc service which accepts the compressed requests and compresses the large messages of the responses
+/// tonic 0.8 compresses every message once negotiated and has no zstd, so the frames are compressed here
+/// and tonic only decodes the gzip requests
+pub fn service(
+    server: GrpcServer,
+    options: &CompressionOptions,
+) -> Compressed<ActsServiceServer<GrpcServer>> {
+    Compressed {
+        inner: ActsServiceServer::new(server).accept_compressed(CompressionEncoding::Gzip),
+        options: options.clone(),
+    }
+}
+
+/// the encoding of the responses for the 'grpc-accept-encoding' header of the request
+pub fn negotiate(headers: &HeaderMap, options: &CompressionOptions) -> Option<Encoding> {
+    let preferred = options.encoding?;
+    let accepted = headers.get(ACCEPT_ENCODING_HEADER)?.to_str().ok()?;
+    let accepted = accepted.split(',').map(str::trim).collect::<Vec<_>>();
+    [preferred, Encoding::Gzip]
+        .into_iter()
+        .find(|encoding| accepted.contains(&encoding.name()))
+}
+
+#[derive(Debug, Clone)]
+pub struct Compressed<S> {
+    inner: S,
+    options: CompressionOptions,
+}
+
+impl<S: NamedService> NamedService for Compressed<S> {
+    const NAME: &'static str = S::NAME;
+}
+
+impl<S> Service<http::Request<Body>> for Compressed<S>
+where
+    S: Service<http::Request<Frames<Body>>, Response = http::Response<BoxBody>, Error = Infallible>,
+    S::Future: Send + 'static,
+{
+    type Response = http::Response<BoxBody>;
+    type Error = Infallible;
+    type Future = BoxFuture<Self::Response, Self::Error>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<std::result::Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: http::Request<Body>) -> Self::Future {
+        let encoding = negotiate(req.headers(), &self.options);
+        let threshold = self.options.threshold;
+
+        // the zstd requests are decoded here, tonic decodes the gzip ones
+        let (mut parts, body) = req.into_parts();
+        let transform = match parts.headers.get(ENCODING_HEADER) {
+            Some(value) if value == Encoding::Zstd.name() => {
+                parts.headers.remove(ENCODING_HEADER);
+                Some(Transform::Decompress(Encoding::Zstd))
+            }
+            _ => None,
+        };
+        let resp = self.inner.call(http::Request::from_parts(
+            parts,
+            Frames::new(body, transform),
+        ));
+
+        Box::pin(async move {
+            let (mut parts, body) = resp.await?.into_parts();
+            if parts.headers.contains_key(ACCEPT_ENCODING_HEADER) {
+                parts.headers.insert(
+                    ACCEPT_ENCODING_HEADER,
+                    HeaderValue::from_static("gzip,zstd"),
+                );
+            }
+            let Some(encoding) = encoding else {
+                return Ok(http::Response::from_parts(parts, body));
+            };
+            parts
+                .headers
+                .insert(ENCODING_HEADER, HeaderValue::from_static(encoding.name()));
+            let body = Frames::new(body, Some(Transform::Compress(encoding, threshold)));
+            Ok(http::Response::from_parts(parts, body.boxed_unsync()))
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum Transform {
+    /// compress the messages which are not smaller than the threshold
+    Compress(Encoding, usize),
+    Decompress(Encoding),
+}
+
+/// the body which transforms each of the grpc message frames, the data is passed through without the transform
+pub struct Frames<B> {
+    inner: B,
+    transform: Option<Transform>,
+    buf: Vec<u8>,
+    done: bool,
+}
+
+impl<B> Frames<B> {
+    pub fn new(inner: B, transform: Option<Transform>) -> Self {
+        Self {
+            inner,
+            transform,
+            buf: Vec::new(),
+            done: false,
+        }
+    }
+
+    /// take the next complete frame from the buffer
+    fn next_frame(&mut self, transform: Transform) -> std::io::Result<Option<Bytes>> {
+        if self.buf.len() < FRAME_HEADER {
+            return Ok(None);
+        }
+        let len = u32::from_be_bytes([self.buf[1], self.buf[2], self.buf[3], self.buf[4]]) as usize;
+        if self.buf.len() < FRAME_HEADER + len {
+            return Ok(None);
+        }
+        let frame = self.buf.drain(..FRAME_HEADER + len).collect::<Vec<_>>();
+        let (compressed, data) = (frame[0] == 1, &frame[FRAME_HEADER..]);
+        let (compressed, data) = match transform {
+            Transform::Compress(encoding, threshold) if !compressed && len >= threshold => {
+                (true, encoding.compress(data)?)
+            }
+            Transform::Decompress(encoding) if compressed => (false, encoding.decompress(data)?),
+            _ => return Ok(Some(Bytes::from(frame))),
+        };
+
+        let mut ret = Vec::with_capacity(FRAME_HEADER + data.len());
+        ret.push(compressed as u8);
+        ret.extend_from_slice(&(data.len() as u32).to_be_bytes());
+        ret.extend_from_slice(&data);
+        Ok(Some(Bytes::from(ret)))
+    }
+}
+
+impl<B> HttpBody for Frames<B>
+where
+    B: HttpBody<Data = Bytes> + Unpin,
+    B::Error: Into<Box<dyn std::error::Error + Send + Sync>>,
+{
+    type Data = Bytes;
+    type Error = Status;
+
+    fn poll_data(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<std::result::Result<Self::Data, Self::Error>>> {
+        let this = self.get_mut();
+        let Some(transform) = this.transform else {
+            return Pin::new(&mut this.inner)
+                .poll_data(cx)
+                .map_err(|err| Status::from_error(err.into()));
+        };
+        loop {
+            match this.next_frame(transform) {
+                Ok(Some(frame)) => return Poll::Ready(Some(Ok(frame))),
+                Ok(None) => {}
+                Err(err) => return Poll::Ready(Some(Err(Status::internal(err.to_string())))),
+            }
+            if this.done {
+                if !this.buf.is_empty() {
+                    this.buf.clear();
+                    return Poll::Ready(Some(Err(Status::internal("incomplete grpc frame"))));
+                }
+                return Poll::Ready(None);
+            }
+            match ready!(Pin::new(&mut this.inner).poll_data(cx)) {
+                Some(Ok(data)) => this.buf.extend_from_slice(&data),
+                Some(Err(err)) => return Poll::Ready(Some(Err(Status::from_error(err.into())))),
+                None => this.done = true,
+            }
+        }
+    }
+
+    fn poll_trailers(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<std::result::Result<Option<HeaderMap>, Self::Error>> {
+        Pin::new(&mut self.get_mut().inner)
+            .poll_trailers(cx)
+            .map_err(|err| Status::from_error(err.into()))
+    }
+
+    fn is_end_stream(&self) -> bool {
+        match self.transform {
+            Some(_) => self.done && self.buf.is_empty(),
+            None => self.inner.is_end_stream(),
+        }
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        match self.transform {
+            Some(_) => SizeHint::default(),
+            None => self.inner.size_hint(),
+        }
+    }
+}
@@ -1,10 +1,41 @@
+use crate::{
+    backup::BackupOptions, bridge::BridgeOptions, compression::CompressionOptions,
+    cursor::JournalOptions, dlq::DlqOptions, group::GroupOptions, idempotency::IdempotencyOptions,
+    namespace::NamespaceOptions, trigger::TriggerOptions, webhook::WebhookOptions,
+};
 use serde::Deserialize;
+use std::collections::HashMap;
+
+/// the server options besides the engine config
+#[derive(Debug, Default, Clone)]
+pub struct ServerOptions {
+    pub namespace: NamespaceOptions,
+    pub idempotency: IdempotencyOptions,
+    pub webhook: WebhookOptions,
+    pub trigger: TriggerOptions,
+    pub bridge: Option<BridgeOptions>,
+    pub journal: JournalOptions,
+    pub group: GroupOptions,
+    pub dlq: DlqOptions,
+    pub compression: CompressionOptions,
+    pub backup: Option<BackupOptions>,
+}
 
 #[derive(Deserialize)]
 pub struct Config {
     pub data_dir: Option<String>,
     pub log: Option<ConfigLog>,
     pub port: Option<u32>,
+    pub backup: Option<ConfigBackup>,
+    pub namespace: Option<ConfigNamespace>,
+    pub idempotency: Option<ConfigIdempotency>,
+    pub webhook: Option<ConfigWebhook>,
+    pub trigger: Option<ConfigTrigger>,
+    pub bridge: Option<ConfigBridge>,
+    pub journal: Option<ConfigJournal>,
+    pub group: Option<ConfigGroup>,
+    pub dlq: Option<ConfigDlq>,
+    pub compression: Option<ConfigCompression>,
 }
 
 #[derive(Deserialize)]
@@ -12,3 +43,95 @@ pub struct ConfigLog {
     pub dir: Option<String>,
     pub level: Option<String>,
 }
+
+#[derive(Deserialize)]
+pub struct ConfigBackup {
+    pub dir: Option<String>,
+    /// the seconds between two backups
+    pub interval: Option<u64>,
+    /// the max snapshots to keep
+    pub keep: Option<usize>,
+}
+
+#[derive(Deserialize)]
+pub struct ConfigNamespace {
+    /// map the auth token to the namespace
+    pub tokens: Option<HashMap<String, String>>,
+    /// the namespace of the requests without the auth token
+    pub default: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct ConfigIdempotency {
+    /// the seconds to remember the response of the idempotency key
+    pub window: Option<u64>,
+}
+
+#[derive(Deserialize)]
+pub struct ConfigWebhook {
+    /// the max retries after the first failed delivery
+    pub retries: Option<u32>,
+    /// the millis before the first retry, it doubles for each retry
+    pub backoff: Option<u64>,
+    /// the hosts which can be private or loopback addresses
+    pub allow_hosts: Option<Vec<String>>,
+}
+
+#[derive(Deserialize)]
+pub struct ConfigTrigger {
+    /// the port of the http trigger endpoints
+    pub port: Option<u16>,
+}
+
+#[derive(Deserialize)]
+pub struct ConfigBridge {
+    /// the kind of the message broker, such as 'nats'
+    pub kind: Option<String>,
+    /// the broker url, the credentials can be in it
+    pub url: Option<String>,
+    /// the nats credentials file
+    pub credentials: Option<String>,
+    /// require the tls connection
+    pub tls: Option<bool>,
+    /// the pem file of the root certificates
+    pub root_ca: Option<String>,
+    /// the auth token of the commands which is mapped to the namespace
+    pub token: Option<String>,
+    /// the first token of the subjects
+    pub prefix: Option<String>,
+    /// the globs of the published messages
+    pub r#type: Option<String>,
+    pub state: Option<String>,
+    pub tag: Option<String>,
+    pub key: Option<String>,
+    /// map the command to the action
+    pub commands: Option<HashMap<String, String>>,
+}
+
+#[derive(Deserialize)]
+pub struct ConfigJournal {
+    /// the seconds to keep the messages for replaying
+    pub retention: Option<u64>,
+}
+
+#[derive(Deserialize)]
+pub struct ConfigGroup {
+    /// the seconds to wait for the ack before sending the message to the next member
+    pub ack_timeout: Option<u64>,
+}
+
+#[derive(Deserialize)]
+pub struct ConfigDlq {
+    /// the engine retries before the message is dead, 0 to retry forever
+    pub retries: Option<i32>,
+    /// the seconds to keep the retry history
+    pub retention: Option<u64>,
+}
+
+#[derive(Deserialize)]
+pub struct ConfigCompression {
+    /// one of 'gzip', 'zstd' and 'none'
+    pub encoding: Option<String>,
+    /// the min bytes of the messages to compress
+    pub threshold: Option<usize>,
+}
@@ -0,0 +1,449 @@
+use crate::{
+    model,
+    namespace::Namespace,
+    store::{map_db_err, DbItem, Store},
+    utils,
+};
+use acts::{ActError, Engine, Message, Result};
+use rusqlite::params;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::VecDeque,
+    str::FromStr,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use tokio::sync::Notify;
+
+/// the metadata of on_message to replay the messages before the live ones
+/// it is one of 'acked', 'seq:{seq}' and 'time:{millis}'
+pub const REPLAY_METADATA: &str = "x-acts-replay";
+
+/// the rows to read in one batch when replaying
+pub const BATCH: usize = 500;
+
+/// the recent message seqs which are looked up by the live messages
+const RECENT: usize = 1024;
+
+/// the interval to write the appended journal entries
+const FLUSH_INTERVAL: Duration = Duration::from_millis(100);
+
+/// the interval to remove the expired journal entries
+const PURGE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// the journal options in acts.conf
+#[derive(Debug, Clone)]
+pub struct JournalOptions {
+    /// the seconds to keep the messages for replaying
+    pub retention: u64,
+}
+
+impl Default for JournalOptions {
+    fn default() -> Self {
+        Self { retention: 604800 }
+    }
+}
+
+/// the position to replay the messages from
+#[derive(Debug, Clone, PartialEq)]
+pub enum Replay {
+    /// the next one of the last acked message of the client
+    Acked,
+    Seq(i64),
+    Time(i64),
+}
+
+impl FromStr for Replay {
+    type Err = ActError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let invalid = || {
+            ActError::Action(format!(
+                "invalid replay '{s}', it should be one of 'acked', 'seq:{{seq}}' and 'time:{{millis}}'"
+            ))
+        };
+        match s.split_once(':') {
+            None if s == "acked" => Ok(Replay::Acked),
+            Some(("seq", seq)) => seq.parse().map(Replay::Seq).map_err(|_| invalid()),
+            Some(("time", time)) => time.parse().map(Replay::Time).map_err(|_| invalid()),
+            _ => Err(invalid()),
+        }
+    }
+}
+
+/// the engine message in the journal, the seq is increased in the emitting order
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Entry {
+    /// the zero padded seq to order by id
+    pub id: String,
+    pub seq: i64,
+    /// the message id
+    pub mid: String,
+    pub message: Message,
+    pub create_time: i64,
+}
+
+impl DbItem for Entry {
+    fn name() -> &'static str {
+        "message_journal"
+    }
+
+    fn id(&self) -> &str {
+        &self.id
+    }
+}
+
+/// the durable position of the client id
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Cursor {
+    /// the client id in the engine
+    pub id: String,
+    /// the seq of the last acked message
+    pub acked: i64,
+    /// the seq of the last sent message
+    pub delivered: i64,
+    pub update_time: i64,
+}
+
+impl DbItem for Cursor {
+    fn name() -> &'static str {
+        "message_cursor"
+    }
+
+    fn id(&self) -> &str {
+        &self.id
+    }
+}
+
+/// the message which is sent to the client and not acked yet
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Inflight {
+    /// '{client}/{mid}'
+    pub id: String,
+    pub client: String,
+    pub mid: String,
+    pub seq: i64,
+}
+
+impl DbItem for Inflight {
+    fn name() -> &'static str {
+        "message_inflight"
+    }
+
+    fn id(&self) -> &str {
+        &self.id
+    }
+}
+
+/// the seqs of the journal in memory
+#[derive(Default)]
+struct JournalState {
+    last: i64,
+    recent: VecDeque<(String, i64)>,
+    /// the appended entries which are not written to the store yet
+    pending: Vec<Entry>,
+}
+
+/// append the engine messages to the journal, the entries are written in batches by `flush`
+pub struct Journal {
+    store: Arc<Store>,
+    state: Mutex<JournalState>,
+    /// one flush at a time, so the entries are written when another flush returns
+    writer: Mutex<()>,
+    notify: Notify,
+}
+
+impl Journal {
+    pub fn new(store: &Arc<Store>) -> Result<Self> {
+        store.collection::<Entry>()?;
+        let last = store
+            .connection()
+            .query_row(
+                &format!("select max(id) from {}", Entry::name()),
+                [],
+                |row| row.get::<usize, Option<String>>(0),
+            )
+            .map_err(map_db_err)?
+            .and_then(|id| id.parse::<i64>().ok())
+            .unwrap_or_default();
+        Ok(Self {
+            store: store.clone(),
+            state: Mutex::new(JournalState {
+                last,
+                ..Default::default()
+            }),
+            writer: Mutex::new(()),
+            notify: Notify::new(),
+        })
+    }
+
+    /// assign the seq to the message, it is called in the engine callback and never touches the store
+    pub fn append(&self, message: &Message) -> i64 {
+        let mut state = self.state.lock().unwrap();
+        let seq = state.last + 1;
+        state.last = seq;
+        state.pending.push(Entry {
+            id: format!("{seq:020}"),
+            seq,
+            mid: message.id.clone(),
+            message: message.clone(),
+            create_time: utils::time_millis(),
+        });
+        state.recent.push_back((message.id.clone(), seq));
+        if state.recent.len() > RECENT {
+            state.recent.pop_front();
+        }
+        if state.pending.len() >= BATCH {
+            self.notify.notify_one();
+        }
+        seq
+    }
+
+    /// write the pending entries in one transaction, it should be called before reading the journal
+    pub fn flush(&self) -> Result<()> {
+        let _writer = self.writer.lock().unwrap();
+        let pending = std::mem::take(&mut self.state.lock().unwrap().pending);
+        if pending.is_empty() {
+            return Ok(());
+        }
+        let mut conn = self.store.connection();
+        let tx = conn.transaction().map_err(map_db_err)?;
+        {
+            let mut stmt = tx
+                .prepare(&format!(
+                    "insert into {} (id, data, create_time) values (?1, ?2, ?3)",
+                    Entry::name()
+                ))
+                .map_err(map_db_err)?;
+            for entry in &pending {
+                stmt.execute(params![
+                    entry.id,
+                    serde_json::to_string(entry)?,
+                    entry.create_time
+                ])
+                .map_err(map_db_err)?;
+            }
+        }
+        tx.commit().map_err(map_db_err)?;
+        Ok(())
+    }
+
+    /// the seq of the message id, the recent ones are found without the store
+    pub fn seq(&self, mid: &str) -> Result<Option<i64>> {
+        let recent = self
+            .state
+            .lock()
+            .unwrap()
+            .recent
+            .iter()
+            .rev()
+            .find(|(id, _)| id == mid)
+            .map(|(_, seq)| *seq);
+        if recent.is_some() {
+            return Ok(recent);
+        }
+        self.flush()?;
+        Ok(find(&self.store, mid)?.map(|entry| entry.seq))
+    }
+}
+
+/// journal the engine messages and remove the expired ones in background
+/// it should be called before the clients subscribe, so the messages are journaled before sending
+pub fn run(
+    engine: &Arc<Engine>,
+    store: &Arc<Store>,
+    options: JournalOptions,
+) -> Result<Arc<Journal>> {
+    let journal = Arc::new(Journal::new(store)?);
+    {
+        let journal = journal.clone();
+        engine.channel().on_message(move |e| {
+            // the retried messages are journaled at the first time
+            if e.retry_times > 0 {
+                return;
+            }
+            journal.append(&model::unpin_message(e.inner()));
+        });
+    }
+    {
+        let journal = journal.clone();
+        tokio::spawn(async move {
+            loop {
+                tokio::select! {
+                    _ = tokio::time::sleep(FLUSH_INTERVAL) => {}
+                    _ = journal.notify.notified() => {}
+                }
+                let journal = journal.clone();
+                let ret = tokio::task::spawn_blocking(move || journal.flush()).await;
+                match ret {
+                    Ok(Err(err)) => tracing::error!("journal: {err}"),
+                    Err(err) => tracing::error!("journal: {err}"),
+                    _ => {}
+                }
+            }
+        });
+    }
+
+    let store = store.clone();
+    tokio::spawn(async move {
+        loop {
+            tokio::time::sleep(PURGE_INTERVAL).await;
+            let store = store.clone();
+            let expire_time = utils::time_millis() - options.retention as i64 * 1000;
+            let ret = tokio::task::spawn_blocking(move || purge(&store, expire_time)).await;
+            match ret {
+                Ok(Err(err)) => tracing::error!("journal: {err}"),
+                Err(err) => tracing::error!("journal: {err}"),
+                _ => {}
+            }
+        }
+    });
+    Ok(journal)
+}
+
+/// the first seq to replay for the client
+pub fn position(store: &Store, client: &str, replay: &Replay) -> Result<i64> {
+    match replay {
+        Replay::Acked => {
+            let cursors = store.collection::<Cursor>()?;
+            let acked = if cursors.exists(client)? {
+                cursors.find(client)?.acked
+            } else {
+                0
+            };
+            Ok(acked + 1)
+        }
+        Replay::Seq(seq) => Ok(*seq),
+        Replay::Time(time) => {
+            let seq = store
+                .connection()
+                .query_row(
+                    &format!(
+                        "select min(id) from {} where create_time >= ?1",
+                        Entry::name()
+                    ),
+                    [time],
+                    |row| row.get::<usize, Option<String>>(0),
+                )
+                .map_err(map_db_err)?;
+            // all of the messages are before the time if there is no one after it
+            Ok(seq
+                .and_then(|id| id.parse::<i64>().ok())
+                .unwrap_or(i64::MAX))
+        }
+    }
+}
+
+/// the journal entries from the seq in order
+pub fn read(store: &Store, from: i64, count: usize) -> Result<Vec<Entry>> {
+    if from == i64::MAX {
+        return Ok(Vec::new());
+    }
+    // make sure the table exists
+    store.collection::<Entry>()?;
+    let conn = store.connection();
+    let mut stmt = conn
+        .prepare(&format!(
+            "select data from {} where id >= ?1 order by id limit {count}",
+            Entry::name()
+        ))
+        .map_err(map_db_err)?;
+    let mut rows = Vec::new();
+    for data in stmt
+        .query_map([format!("{:020}", from.max(0))], |row| {
+            row.get::<usize, String>(0)
+        })
+        .map_err(map_db_err)?
+    {
+        rows.push(serde_json::from_str(&data.map_err(map_db_err)?)?);
+    }
+    Ok(rows)
+}
+
+pub fn find(store: &Store, mid: &str) -> Result<Option<Entry>> {
+    Ok(store
+        .collection::<Entry>()?
+        .find_by("mid", mid)?
+        .into_iter()
+        .next())
+}
+
+/// check the journaled message belongs to the namespace, the acked message may be removed from the engine
+pub fn owns(store: &Store, mid: &str, ns: &Namespace) -> bool {
+    find(store, mid).is_ok_and(|entry| entry.is_some_and(|entry| ns.owns(&entry.message.mid)))
+}
+
+/// record the message which is sent to the client
+pub fn deliver(store: &Store, client: &str, mid: &str, seq: i64) -> Result<()> {
+    let inflights = store.collection::<Inflight>()?;
+    let id = format!("{client}/{mid}");
+    // the message may be sent again by the engine retrying
+    if !inflights.exists(&id)? {
+        inflights.create(&Inflight {
+            id,
+            client: client.to_string(),
+            mid: mid.to_string(),
+            seq,
+        })?;
+    }
+    let cursors = store.collection::<Cursor>()?;
+    let mut cursor = if cursors.exists(client)? {
+        cursors.find(client)?
+    } else {
+        let cursor = Cursor {
+            id: client.to_string(),
+            ..Default::default()
+        };
+        cursors.create(&cursor)?;
+        cursor
+    };
+    cursor.delivered = cursor.delivered.max(seq);
+    cursor.update_time = utils::time_millis();
+    cursors.update(&cursor)?;
+    Ok(())
+}
+
+/// move the cursors of the clients which received the message, it returns false if no one is waiting for it
+/// the cursor stops before the first message which is still in flight, so replaying from 'acked' never skips one
+pub fn ack(store: &Store, mid: &str) -> Result<bool> {
+    let inflights = store.collection::<Inflight>()?;
+    let cursors = store.collection::<Cursor>()?;
+    let rows = inflights.find_by("mid", mid)?;
+    for inflight in &rows {
+        inflights.delete(&inflight.id)?;
+        if let Ok(mut cursor) = cursors.find(&inflight.client) {
+            cursor.acked = inflights
+                .find_by("client", &inflight.client)?
+                .iter()
+                .map(|inflight| inflight.seq - 1)
+                .min()
+                .unwrap_or(cursor.delivered);
+            cursor.update_time = utils::time_millis();
+            cursors.update(&cursor)?;
+        }
+    }
+    Ok(!rows.is_empty())
+}
+
+/// remove the cursor when the client unsubscribes
+pub fn rm(store: &Store, client: &str) -> Result<bool> {
+    store
+        .collection::<Inflight>()?
+        .delete_by("client", client)?;
+    store.collection::<Cursor>()?.delete(client)
+}
+
+fn purge(store: &Store, expire_time: i64) -> Result<()> {
+    store.collection::<Entry>()?;
+    store
+        .connection()
+        .execute(
+            &format!("delete from {} where create_time < ?1", Entry::name()),
+            [expire_time],
+        )
+        .map_err(map_db_err)?;
+    Ok(())
+}
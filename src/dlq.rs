@@ -0,0 +1,341 @@
+use crate::{
+    cursor, model, namespace,
+    store::{map_db_err, DbItem, PageData, Store},
+    utils,
+};
+use acts::{
+    data::MessageStatus, ActError, Engine, Executor, ExecutorQuery, Message, MessageInfo, Result,
+    Vars,
+};
+use serde::{Deserialize, Serialize};
+use std::{sync::Arc, time::Duration};
+use tokio::sync::broadcast;
+
+/// the engine message status after all of the retries
+pub const STATUS_DEAD: &str = "error";
+
+/// the kinds of the attempts in the retry history
+pub const KIND_TIMEOUT: &str = "timeout";
+pub const KIND_DISCONNECTED: &str = "disconnected";
+pub const KIND_RETRIED: &str = "retried";
+
+/// the interval to remove the expired attempts
+const PURGE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// the retried messages which wait for the slow subscribers
+const RETRIED_CAPACITY: usize = 1024;
+
+/// the dead-letter options in acts.conf, the retries before the dead-letter state are set to the engine config
+#[derive(Debug, Clone)]
+pub struct DlqOptions {
+    /// the seconds to keep the retry history
+    pub retention: u64,
+}
+
+impl Default for DlqOptions {
+    fn default() -> Self {
+        Self { retention: 604800 }
+    }
+}
+
+/// one failed or manual delivery of the engine message
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Attempt {
+    pub id: String,
+    pub message_id: String,
+    /// the retry times of the message when it happens
+    pub attempt: i32,
+    /// one of 'timeout', 'disconnected' and 'retried'
+    pub kind: String,
+    /// the client id which failed to receive the message
+    pub client: String,
+    pub reason: String,
+    pub create_time: i64,
+}
+
+impl DbItem for Attempt {
+    fn name() -> &'static str {
+        "message_attempt"
+    }
+
+    fn id(&self) -> &str {
+        &self.id
+    }
+}
+
+/// the dead message with the reason of its last failed delivery
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeadLetter {
+    #[serde(flatten)]
+    pub message: MessageInfo,
+    pub reason: String,
+    /// the attempts in the retry history
+    pub attempts: usize,
+}
+
+/// the dead messages which are retried, the server sends them to the subscribers
+/// because the engine never sends the message again after all of its retries
+#[derive(Clone)]
+pub struct Retried(broadcast::Sender<Message>);
+
+impl Default for Retried {
+    fn default() -> Self {
+        Self(broadcast::channel(RETRIED_CAPACITY).0)
+    }
+}
+
+impl Retried {
+    pub fn subscribe(&self) -> broadcast::Receiver<Message> {
+        self.0.subscribe()
+    }
+
+    /// it returns false if there is no subscriber
+    fn send(&self, message: Message) -> bool {
+        self.0.send(message).is_ok()
+    }
+}
+
+/// record the engine retries and remove the expired history in background
+pub fn run(engine: &Arc<Engine>, store: &Arc<Store>, options: DlqOptions) {
+    {
+        let store = store.clone();
+        let secs = engine.config().tick_interval_secs;
+        engine.channel().on_message(move |e| {
+            // the engine resends the message which is neither acked nor completed in one tick
+            if e.retry_times == 0 {
+                return;
+            }
+            let reason = format!("not acked in {secs}s");
+            if let Err(err) = record(&store, &e.id, e.retry_times, KIND_TIMEOUT, "", &reason) {
+                tracing::error!("dlq: {err}");
+            }
+        });
+    }
+
+    let store = store.clone();
+    tokio::spawn(async move {
+        loop {
+            tokio::time::sleep(PURGE_INTERVAL).await;
+            let store = store.clone();
+            let expire_time = utils::time_millis() - options.retention as i64 * 1000;
+            let ret = tokio::task::spawn_blocking(move || expire(&store, expire_time)).await;
+            match ret {
+                Ok(Err(err)) => tracing::error!("dlq: {err}"),
+                Err(err) => tracing::error!("dlq: {err}"),
+                _ => {}
+            }
+        }
+    });
+}
+
+pub fn record(
+    store: &Store,
+    message_id: &str,
+    attempt: i32,
+    kind: &str,
+    client: &str,
+    reason: &str,
+) -> Result<()> {
+    store.collection::<Attempt>()?.create(&Attempt {
+        id: utils::longid(),
+        message_id: message_id.to_string(),
+        attempt,
+        kind: kind.to_string(),
+        client: client.to_string(),
+        reason: reason.to_string(),
+        create_time: utils::time_millis(),
+    })?;
+    Ok(())
+}
+
+/// the retry history of the message in order
+pub fn history(store: &Store, message_id: &str) -> Result<Vec<Attempt>> {
+    let mut rows = store
+        .collection::<Attempt>()?
+        .find_by("message_id", message_id)?;
+    rows.sort_by_key(|attempt| attempt.create_time);
+    Ok(rows)
+}
+
+/// the engine query of the dead messages, the latest dead first by default
+pub fn query(query: &ExecutorQuery) -> ExecutorQuery {
+    let mut query_by = query.query_by.clone();
+    query_by.retain(|(key, _)| key != "status");
+    // the engine stores the status as the number
+    query_by.push((
+        "status".to_string(),
+        (MessageStatus::Error as i8).to_string(),
+    ));
+    let order_by = if query.order_by.is_empty() {
+        vec![("update_time".to_string(), true)]
+    } else {
+        query.order_by.clone()
+    };
+    ExecutorQuery {
+        offset: query.offset,
+        count: query.count,
+        query_by,
+        order_by,
+    }
+}
+
+/// add the reason of the last failed delivery to the dead messages
+pub fn letters(store: &Store, page: PageData<MessageInfo>) -> Result<PageData<DeadLetter>> {
+    let mut rows = Vec::new();
+    for message in page.rows {
+        let history = history(store, &message.id)?;
+        // the attempts since the last manual retry, the timeout is caused by the disconnected client
+        let failed = history
+            .iter()
+            .rev()
+            .take_while(|attempt| attempt.kind != KIND_RETRIED)
+            .collect::<Vec<_>>();
+        let reason = failed
+            .iter()
+            .find(|attempt| attempt.kind == KIND_DISCONNECTED)
+            .or(failed.first())
+            .map(|attempt| attempt.reason.clone())
+            .unwrap_or_else(|| format!("not acked after {} retries", message.retry_times));
+        rows.push(DeadLetter {
+            message,
+            reason,
+            attempts: history.len(),
+        });
+    }
+    Ok(PageData {
+        count: page.count,
+        page_num: page.page_num,
+        page_count: page.page_count,
+        page_size: page.page_size,
+        rows,
+    })
+}
+
+/// the dead messages to retry or purge, by the id or by the query and the update time before
+pub fn select(
+    executor: &Executor,
+    id: Option<&str>,
+    query_by: Vec<(String, String)>,
+    before: Option<i64>,
+    owns: impl FnMut(&MessageInfo) -> bool,
+) -> Result<Vec<MessageInfo>> {
+    if let Some(id) = id {
+        let message = executor.msg().get(id)?;
+        if message.status != STATUS_DEAD {
+            return Err(ActError::Action(format!(
+                "message '{id}' is not a dead letter, the status is '{}'",
+                message.status
+            )));
+        }
+        return Ok(vec![message]);
+    }
+    let query = self::query(&ExecutorQuery {
+        offset: 0,
+        count: usize::MAX,
+        query_by,
+        order_by: Vec::new(),
+    });
+    let page = namespace::list(
+        &query,
+        |q| executor.msg().list(q).map(|page| (page.rows, page.count)),
+        owns,
+    )?;
+    Ok(page
+        .rows
+        .into_iter()
+        .filter(|message| before.is_none_or(|before| message.update_time < before))
+        .collect())
+}
+
+/// send the dead messages to the subscribers again, each message keeps dead until a subscriber acks it
+/// it returns the count of the messages which are sent to at least one subscriber
+pub fn retry(
+    executor: &Executor,
+    store: &Store,
+    retried: &Retried,
+    messages: &[MessageInfo],
+    by: &str,
+) -> Result<usize> {
+    let mut count = 0;
+    for message in messages {
+        // the message may be acked or removed by the others
+        let Ok(message) = executor.msg().get(&message.id) else {
+            continue;
+        };
+        if message.status != STATUS_DEAD || !retried.send(self::message(executor, store, &message)?)
+        {
+            continue;
+        }
+        record(
+            store,
+            &message.id,
+            message.retry_times,
+            KIND_RETRIED,
+            "",
+            &format!("retried by {by}"),
+        )?;
+        count += 1;
+    }
+    Ok(count)
+}
+
+/// remove the dead messages with their retry history
+pub fn purge(executor: &Executor, store: &Store, messages: &[MessageInfo]) -> Result<usize> {
+    let attempts = store.collection::<Attempt>()?;
+    let mut count = 0;
+    for message in messages {
+        if executor.msg().rm(&message.id)? {
+            count += 1;
+        }
+        attempts.delete_by("message_id", &message.id)?;
+    }
+    Ok(count)
+}
+
+/// the emitted message in the journal, or the one built from the engine message after it is expired
+fn message(executor: &Executor, store: &Store, info: &MessageInfo) -> Result<Message> {
+    if let Some(entry) = cursor::find(store, &info.id)? {
+        return Ok(Message {
+            retry_times: info.retry_times,
+            ..entry.message
+        });
+    }
+    let proc = executor.proc().get(&info.pid)?;
+    let vars = |text: &str| serde_json::from_str::<Vars>(text).unwrap_or_default();
+    let mut message = Message {
+        id: info.id.clone(),
+        tid: info.tid.clone(),
+        name: info.name.clone(),
+        state: info.state.clone(),
+        r#type: info.r#type.clone(),
+        pid: info.pid.clone(),
+        nid: info.nid.clone(),
+        mid: model::unpin(&proc.mid).to_string(),
+        key: info.key.clone(),
+        inputs: vars(&info.inputs),
+        outputs: vars(&info.outputs),
+        tag: info.tag.clone(),
+        start_time: info.create_time,
+        retry_times: info.retry_times,
+        ..Default::default()
+    };
+    message.model.id = message.mid.clone();
+    message.model.name = proc.name;
+    Ok(message)
+}
+
+fn expire(store: &Store, expire_time: i64) -> Result<()> {
+    store.collection::<Attempt>()?;
+    store
+        .connection()
+        .execute(
+            &format!(
+                "delete from {} where json_extract(data, '$.create_time') < ?1",
+                Attempt::name()
+            ),
+            [expire_time],
+        )
+        .map_err(map_db_err)?;
+    Ok(())
+}
@@ -0,0 +1,344 @@
+use crate::{model, store::Store, tree};
+use acts::{ActError, Executor, Result, Step, TaskInfo, Workflow};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// the formats rendered from the model graph
+pub const FORMATS: [&str; 3] = ["mermaid", "dot", "graph"];
+
+/// flowchart of the model with the steps, branches and acts
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct Graph {
+    pub id: String,
+    pub name: String,
+    pub nodes: Vec<Node>,
+    pub edges: Vec<Edge>,
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct Node {
+    /// node id in graph
+    pub id: String,
+    /// node id in model
+    pub nid: String,
+    /// one of start, end, step, branch and act
+    pub r#type: String,
+    pub name: String,
+    /// the overlay state, one of completed, running and error
+    pub state: Option<String>,
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct Edge {
+    pub from: String,
+    pub to: String,
+    pub label: String,
+    /// 'flow' for the step flow and 'act' for the acts in step
+    pub r#type: String,
+}
+
+/// the edge target, the step is resolved by the model id after all nodes are created
+#[derive(Clone)]
+enum Target {
+    Node(String),
+    Step(String),
+}
+
+impl From<&Workflow> for Graph {
+    fn from(workflow: &Workflow) -> Self {
+        let mut builder = GraphBuilder::default();
+        let start = builder.node(&workflow.id, "start", "start");
+        let end = builder.node("", "end", "end");
+        let entry = builder.steps(&workflow.steps, Target::Node(end));
+        builder.edge(&start, entry, "", "flow");
+        // put the start edge at first for reading
+        builder.edges.rotate_right(1);
+        builder.build(workflow)
+    }
+}
+
+impl Graph {
+    /// overlay the task states by the node id in model
+    pub fn overlay(&mut self, tasks: &[TaskInfo]) {
+        let mut states = HashMap::new();
+        let mut tasks: Vec<&TaskInfo> = tasks.iter().collect();
+        tasks.sort_by_key(|t| t.timestamp);
+        for task in tasks {
+            if task.r#type == "workflow" {
+                // the root task marks the start and end nodes
+                for node in self.nodes.iter_mut() {
+                    match (node.r#type.as_str(), task.state.as_str()) {
+                        ("start", _) | ("end", "completed") => {
+                            node.state = Some("completed".to_string())
+                        }
+                        ("end", "error" | "aborted") => node.state = Some("error".to_string()),
+                        _ => {}
+                    }
+                }
+                continue;
+            }
+            if let Some(state) = overlay_state(&task.state) {
+                states.insert(task.nid.as_str(), state);
+            }
+        }
+
+        for node in self.nodes.iter_mut() {
+            if node.nid.is_empty() || node.r#type == "start" {
+                continue;
+            }
+            if let Some(state) = states.get(node.nid.as_str()) {
+                node.state = Some(state.to_string());
+            }
+        }
+    }
+
+    pub fn to_mermaid(&self) -> String {
+        let mut ret = String::from("flowchart TD\n");
+        for node in &self.nodes {
+            let label = node.name.replace('"', "#quot;");
+            let shape = match node.r#type.as_str() {
+                "start" | "end" => format!("([\"{label}\"])"),
+                "branch" => format!("{{{{\"{label}\"}}}}"),
+                "act" => format!("[/\"{label}\"/]"),
+                _ => format!("[\"{label}\"]"),
+            };
+            ret.push_str(&format!("    {}{shape}\n", node.id));
+        }
+        for edge in &self.edges {
+            let arrow = if edge.r#type == "act" { "-.-" } else { "-->" };
+            if edge.label.is_empty() {
+                ret.push_str(&format!("    {} {arrow} {}\n", edge.from, edge.to));
+            } else {
+                let label = edge.label.replace('"', "#quot;");
+                ret.push_str(&format!(
+                    "    {} {arrow}|\"{label}\"| {}\n",
+                    edge.from, edge.to
+                ));
+            }
+        }
+
+        if self.nodes.iter().any(|n| n.state.is_some()) {
+            for (state, (fill, stroke)) in STYLES {
+                ret.push_str(&format!(
+                    "    classDef {state} fill:{fill},stroke:{stroke}\n"
+                ));
+            }
+            for node in &self.nodes {
+                if let Some(state) = &node.state {
+                    ret.push_str(&format!("    class {} {state}\n", node.id));
+                }
+            }
+        }
+        ret
+    }
+
+    pub fn to_dot(&self) -> String {
+        let mut ret = format!("digraph \"{}\" {{\n", dot_escape(&self.id));
+        ret.push_str("    rankdir=TB;\n");
+        for node in &self.nodes {
+            let shape = match node.r#type.as_str() {
+                "start" | "end" => "ellipse",
+                "branch" => "diamond",
+                "act" => "note",
+                _ => "box",
+            };
+            let mut attrs = format!("label=\"{}\", shape={shape}", dot_escape(&node.name));
+            if let Some((fill, stroke)) = node
+                .state
+                .as_deref()
+                .and_then(|s| STYLES.iter().find(|(state, _)| *state == s))
+                .map(|(_, style)| style)
+            {
+                attrs.push_str(&format!(
+                    ", style=filled, fillcolor=\"{fill}\", color=\"{stroke}\""
+                ));
+            }
+            ret.push_str(&format!("    {} [{attrs}];\n", node.id));
+        }
+        for edge in &self.edges {
+            let mut attrs = Vec::new();
+            if !edge.label.is_empty() {
+                attrs.push(format!("label=\"{}\"", dot_escape(&edge.label)));
+            }
+            if edge.r#type == "act" {
+                attrs.push("style=dashed, arrowhead=none".to_string());
+            }
+            if attrs.is_empty() {
+                ret.push_str(&format!("    {} -> {};\n", edge.from, edge.to));
+            } else {
+                ret.push_str(&format!(
+                    "    {} -> {} [{}];\n",
+                    edge.from,
+                    edge.to,
+                    attrs.join(", ")
+                ));
+            }
+        }
+        ret.push_str("}\n");
+        ret
+    }
+}
+
+/// render the model to one of the graph formats, the tasks are overlaid if specified
+pub fn render(workflow: &Workflow, fmt: &str, tasks: Option<&[TaskInfo]>) -> Result<String> {
+    let mut graph = Graph::from(workflow);
+    if let Some(tasks) = tasks {
+        graph.overlay(tasks);
+    }
+    match fmt {
+        "mermaid" => Ok(graph.to_mermaid()),
+        "dot" => Ok(graph.to_dot()),
+        "graph" => Ok(serde_json::to_string(&graph)?),
+        _ => Err(ActError::Action(format!(
+            "unsupported graph format '{fmt}'"
+        ))),
+    }
+}
+
+/// render the model of the proc with the task states overlay
+pub fn proc_graph(executor: &Executor, store: &Store, pid: &str, fmt: &str) -> Result<String> {
+    let proc = executor.proc().get(pid)?;
+    let workflow = model::proc_model(executor, store, &proc)?;
+    let tasks = tree::tasks(executor, &proc)?;
+    render(&workflow, fmt, Some(&tasks))
+}
+
+const STYLES: [(&str, (&str, &str)); 3] = [
+    ("completed", ("#d4edda", "#28a745")),
+    ("running", ("#fff3cd", "#ffc107")),
+    ("error", ("#f8d7da", "#dc3545")),
+];
+
+fn overlay_state(state: &str) -> Option<&'static str> {
+    match state {
+        "completed" | "submitted" | "backed" | "skipped" => Some("completed"),
+        "running" | "pending" | "ready" | "interrupted" => Some("running"),
+        "error" | "aborted" | "cancelled" => Some("error"),
+        _ => None,
+    }
+}
+
+fn dot_escape(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+#[derive(Default)]
+struct GraphBuilder {
+    nodes: Vec<Node>,
+    edges: Vec<(String, Target, String, String)>,
+    steps: HashMap<String, String>,
+}
+
+impl GraphBuilder {
+    fn node(&mut self, nid: &str, r#type: &str, name: &str) -> String {
+        let id = format!("n{}", self.nodes.len());
+        self.nodes.push(Node {
+            id: id.clone(),
+            nid: nid.to_string(),
+            r#type: r#type.to_string(),
+            name: name.to_string(),
+            state: None,
+        });
+        id
+    }
+
+    fn edge(&mut self, from: &str, to: Target, label: &str, r#type: &str) {
+        self.edges
+            .push((from.to_string(), to, label.to_string(), r#type.to_string()));
+    }
+
+    /// add the steps which go to the exit at last, and return the entry of the steps
+    fn steps(&mut self, steps: &[Step], exit: Target) -> Target {
+        let ids: Vec<String> = steps
+            .iter()
+            .map(|step| {
+                let name = if step.name.is_empty() {
+                    &step.id
+                } else {
+                    &step.name
+                };
+                let id = self.node(&step.id, "step", name);
+                if !step.id.is_empty() {
+                    self.steps.insert(step.id.clone(), id.clone());
+                }
+                id
+            })
+            .collect();
+
+        let mut exit = Some(exit);
+        for (index, step) in steps.iter().enumerate() {
+            let next = match (&step.next, ids.get(index + 1)) {
+                (Some(next), _) => Target::Step(next.clone()),
+                (None, Some(id)) => Target::Node(id.clone()),
+                (None, None) => exit.take().unwrap(),
+            };
+            let from = &ids[index];
+
+            for act in &step.acts {
+                let mut name = act.act.clone();
+                if !act.key.is_empty() {
+                    name.push_str(&format!(": {}", act.key));
+                }
+                if !act.name.is_empty() {
+                    name = format!("{} ({name})", act.name);
+                }
+                let id = self.node(&act.id, "act", &name);
+                self.edge(from, Target::Node(id), "", "act");
+            }
+
+            if step.branches.is_empty() {
+                self.edge(from, next, "", "flow");
+                continue;
+            }
+            for branch in &step.branches {
+                let name = if branch.name.is_empty() {
+                    &branch.id
+                } else {
+                    &branch.name
+                };
+                let id = self.node(&branch.id, "branch", name);
+                let label = match (&branch.r#if, branch.r#else) {
+                    (Some(cond), _) => cond.clone(),
+                    (None, true) => "else".to_string(),
+                    _ => String::new(),
+                };
+                self.edge(from, Target::Node(id.clone()), &label, "flow");
+                let entry = self.steps(&branch.steps, next.clone());
+                self.edge(&id, entry, "", "flow");
+            }
+        }
+
+        match ids.first() {
+            Some(id) => Target::Node(id.clone()),
+            None => exit.unwrap(),
+        }
+    }
+
+    fn build(self, workflow: &Workflow) -> Graph {
+        let steps = self.steps;
+        let edges = self
+            .edges
+            .into_iter()
+            .filter_map(|(from, to, label, r#type)| {
+                let to = match to {
+                    Target::Node(id) => id,
+                    // the dangling next is ignored, which is reported by the model validation
+                    Target::Step(nid) => steps.get(&nid)?.clone(),
+                };
+                Some(Edge {
+                    from,
+                    to,
+                    label,
+                    r#type,
+                })
+            })
+            .collect();
+
+        Graph {
+            id: workflow.id.clone(),
+            name: workflow.name.clone(),
+            nodes: self.nodes,
+            edges,
+        }
+    }
+}
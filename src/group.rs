@@ -0,0 +1,220 @@
+use acts::{ActError, Channel, ChannelOptions, Message, Result};
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+use tokio::{sync::mpsc::UnboundedSender, task::AbortHandle};
+
+/// the metadata of on_message to join the consumer group
+pub const GROUP_METADATA: &str = "x-acts-group";
+
+/// the prefix of the engine channel id of the group
+pub const CHANNEL_PREFIX: &str = "group:";
+
+/// the interval to check the ack deadlines
+const REDELIVER_INTERVAL: Duration = Duration::from_secs(1);
+
+/// the consumer group options in acts.conf
+#[derive(Debug, Clone)]
+pub struct GroupOptions {
+    /// the seconds to wait for the ack before sending the message to the next member
+    pub ack_timeout: u64,
+}
+
+impl Default for GroupOptions {
+    fn default() -> Self {
+        Self { ack_timeout: 30 }
+    }
+}
+
+/// the consumer groups, each message of the group goes to exactly one live member
+/// the unacked messages of the left member are sent to the other members again
+#[derive(Clone, Default)]
+pub struct Groups {
+    groups: Arc<Mutex<HashMap<String, Group>>>,
+    /// open and close the group channels in order, the channel callbacks take the groups lock
+    /// so the channels are never changed with it
+    channels: Arc<Mutex<()>>,
+    options: GroupOptions,
+}
+
+struct Group {
+    /// the channel options of the first member, the others should join with the same filters
+    options: ChannelOptions,
+    channel: Option<Arc<Channel>>,
+    /// the task which sends the retried dead messages to the group
+    retried: Option<AbortHandle>,
+    ack_timeout: Duration,
+    members: Vec<(u64, UnboundedSender<Message>)>,
+    next: usize,
+    last_member: u64,
+    /// the unacked messages with the member id and the ack deadline
+    pending: HashMap<String, (u64, Message, Instant)>,
+    /// the messages which arrive when there is no live member
+    backlog: VecDeque<Message>,
+}
+
+impl Group {
+    fn dispatch(&mut self, message: Message) {
+        while !self.members.is_empty() {
+            let index = self.next % self.members.len();
+            self.next = index + 1;
+            let (id, sender) = &self.members[index];
+            if sender.send(message.clone()).is_ok() {
+                let deadline = Instant::now() + self.ack_timeout;
+                self.pending
+                    .insert(message.id.clone(), (*id, message, deadline));
+                return;
+            }
+            // the member is closed before leaving
+            let id = *id;
+            self.remove(id);
+        }
+        self.backlog.push_back(message);
+    }
+
+    /// remove the member and send its unacked messages to the others
+    fn remove(&mut self, member: u64) {
+        self.members.retain(|(id, _)| *id != member);
+        self.redeliver(|id, _| id == member);
+    }
+
+    /// send the messages which are not acked before the deadline to the next members
+    fn expire(&mut self, now: Instant) {
+        self.redeliver(|_, deadline| deadline <= now);
+    }
+
+    fn redeliver(&mut self, f: impl Fn(u64, Instant) -> bool) {
+        let keys = self
+            .pending
+            .iter()
+            .filter(|(_, (id, _, deadline))| f(*id, *deadline))
+            .map(|(key, _)| key.clone())
+            .collect::<Vec<_>>();
+        let mut messages = keys
+            .iter()
+            .filter_map(|key| self.pending.remove(key))
+            .map(|(_, message, _)| message)
+            .collect::<Vec<_>>();
+        messages.sort_by_key(|message| message.start_time);
+        for message in messages {
+            self.dispatch(message);
+        }
+    }
+}
+
+impl Groups {
+    pub fn new(options: &GroupOptions) -> Self {
+        Self {
+            options: options.clone(),
+            ..Default::default()
+        }
+    }
+
+    /// add the member to the group and return the member id, the group channel is opened for the first member
+    /// with the task of the retried messages, the member with the filters which differ from the group is rejected
+    pub fn join(
+        &self,
+        group: &str,
+        options: &ChannelOptions,
+        sender: UnboundedSender<Message>,
+        open: impl FnOnce() -> (Arc<Channel>, AbortHandle),
+    ) -> Result<u64> {
+        let _channels = self.channels.lock().unwrap();
+        let (id, is_new) = {
+            let mut groups = self.groups.lock().unwrap();
+            let is_new = !groups.contains_key(group);
+            let group = groups.entry(group.to_string()).or_insert_with(|| Group {
+                options: options.clone(),
+                channel: None,
+                retried: None,
+                ack_timeout: Duration::from_secs(self.options.ack_timeout),
+                members: Vec::new(),
+                next: 0,
+                last_member: 0,
+                pending: HashMap::new(),
+                backlog: VecDeque::new(),
+            });
+            if group.options.pattern() != options.pattern() {
+                return Err(ActError::Action(format!(
+                    "the filters '{}' differ from '{}' of the other members",
+                    options.pattern(),
+                    group.options.pattern()
+                )));
+            }
+            group.last_member += 1;
+            let id = group.last_member;
+            group.members.push((id, sender));
+            while let Some(message) = group.backlog.pop_front() {
+                group.dispatch(message);
+            }
+            (id, is_new)
+        };
+        if is_new {
+            let (channel, retried) = open();
+            if let Some(group) = self.groups.lock().unwrap().get_mut(group) {
+                group.channel = Some(channel);
+                group.retried = Some(retried);
+            }
+        }
+        Ok(id)
+    }
+
+    /// remove the member, the group is removed with its channel when the last member leaves
+    /// and the engine sends the unacked messages again when the group is back
+    pub fn leave(&self, group: &str, member: u64) {
+        let _channels = self.channels.lock().unwrap();
+        let removed = {
+            let mut groups = self.groups.lock().unwrap();
+            match groups.get_mut(group) {
+                Some(entry) => {
+                    entry.remove(member);
+                    if entry.members.is_empty() {
+                        groups.remove(group)
+                    } else {
+                        None
+                    }
+                }
+                None => None,
+            }
+        };
+        if let Some(group) = removed {
+            if let Some(channel) = group.channel {
+                channel.close();
+            }
+            if let Some(retried) = group.retried {
+                retried.abort();
+            }
+        }
+    }
+
+    pub fn dispatch(&self, group: &str, message: Message) {
+        if let Some(group) = self.groups.lock().unwrap().get_mut(group) {
+            group.dispatch(message);
+        }
+    }
+
+    /// remove the message from the unacked ones, it returns false if no group is waiting for it
+    pub fn ack(&self, id: &str) -> bool {
+        let mut acked = false;
+        for group in self.groups.lock().unwrap().values_mut() {
+            acked |= group.pending.remove(id).is_some();
+        }
+        acked
+    }
+
+    /// check the ack deadlines of the unacked messages in background
+    pub fn run(&self) {
+        let groups = self.groups.clone();
+        tokio::spawn(async move {
+            loop {
+                tokio::time::sleep(REDELIVER_INTERVAL).await;
+                let now = Instant::now();
+                for group in groups.lock().unwrap().values_mut() {
+                    group.expire(now);
+                }
+            }
+        });
+    }
+}
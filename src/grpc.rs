@@ -1,10 +1,35 @@
-use crate::utils;
+use crate::{
+    archive, audit, backend, backup, bridge, compression,
+    config::ServerOptions,
+    cursor::{self, Journal, Replay},
+    dlq, graph,
+    group::{self, Groups},
+    idempotency::{self, InFlight},
+    model,
+    namespace::{self, Namespace, NamespaceOptions, Owners},
+    package, revision,
+    schedule::{self, Schedule},
+    schema,
+    store::{PageData, Store},
+    transform::{self, Transform},
+    tree,
+    trigger::{self, Trigger},
+    utils::{self, MessageFilter},
+    validate, vars,
+    webhook::{self, Webhook},
+};
 use acts::ExecutorQuery;
-use acts::{data::Package, Builder, ChannelOptions, Engine, Workflow};
+use acts::{data::Package, Builder, ChannelOptions, Engine};
 use acts_channel::MessageOptions;
 use acts_channel::{acts_service_server::*, Message};
-use std::{net::SocketAddr, pin::Pin, sync::Arc};
-use tokio::sync::mpsc::{self, Sender};
+use std::{net::SocketAddr, pin::Pin, sync::Arc, time::Instant};
+use tokio::{
+    sync::{
+        broadcast::error::RecvError,
+        mpsc::{self, Sender},
+    },
+    task::JoinHandle,
+};
 use tokio_stream::{wrappers::ReceiverStream, Stream};
 use tonic::{transport::Server, Code, Response, Status};
 
@@ -31,51 +56,432 @@ pub struct MessageClient {
     addr: String,
     sender: Sender<Result<Message, Status>>,
     options: ChannelOptions,
+    transform: Arc<Transform>,
 }
 
 impl MessageClient {
-    fn send(&self, message: Message) {
-        let msg = Ok(message);
-        let client = self.clone();
-        if client.sender.is_closed() {
+    /// send the message to the client and record it in the cursor
+    /// it returns false if the client is closed
+    async fn deliver(
+        &self,
+        store: &Store,
+        ns: &Namespace,
+        message: &acts::Message,
+        seq: Option<i64>,
+    ) -> bool {
+        if let Some(seq) = seq {
+            if let Err(err) = cursor::deliver(store, &self.options.id, &message.id, seq) {
+                tracing::error!("cursor: {err}");
+            }
+        }
+        let (id, retry_times) = (message.id.clone(), message.retry_times);
+        let mut data = serde_json::to_value(message).unwrap();
+        ns.unscope_value(&mut data);
+        let message = Message {
+            name: message.name.clone(),
+            seq: message.id.clone(),
+            ack: None,
+            data: Some(self.transform.encode(&data)),
+        };
+        if let Err(err) = self.sender.send(Ok(message)).await {
             println!(
-                "[ERROR] client {}({}) is closed",
-                client.addr, client.options.id
+                "[ERROR] send to {}({}), error={:?}",
+                self.addr, self.options.id, err
             );
-            return;
+            // the engine retries the message which waits for the ack
+            if self.options.ack {
+                let reason = format!("client '{}' is disconnected", self.options.id);
+                if let Err(err) = dlq::record(
+                    store,
+                    &id,
+                    retry_times,
+                    dlq::KIND_DISCONNECTED,
+                    &self.options.id,
+                    &reason,
+                ) {
+                    tracing::error!("dlq: {err}");
+                }
+            }
+            return false;
         }
-        tokio::spawn(async move {
-            match client.sender.send(msg).await {
-                Ok(_) => {
-                    println!("[OK] send to {}({})", client.addr, client.options.id);
+        true
+    }
+
+    /// send the replayed messages in order and then switch to the live ones
+    async fn forward(
+        self,
+        store: Arc<Store>,
+        journal: Option<Arc<Journal>>,
+        ns: Namespace,
+        filter: MessageFilter,
+        from: Option<i64>,
+        mut live: mpsc::UnboundedReceiver<acts::Message>,
+    ) {
+        let mut replayed = 0;
+        if let Some(mut from) = from {
+            // the messages before the live ones are appended already, write them to replay
+            if let Some(Err(err)) = journal.as_ref().map(|journal| journal.flush()) {
+                tracing::error!("journal: {err}");
+            }
+            loop {
+                let entries = match cursor::read(&store, from, cursor::BATCH) {
+                    Ok(entries) => entries,
+                    Err(err) => {
+                        tracing::error!("cursor: {err}");
+                        break;
+                    }
+                };
+                if entries.is_empty() {
+                    break;
                 }
-                Err(err) => {
-                    println!(
-                        "[ERROR] send to {}({}), error={:?}",
-                        client.addr, client.options.id, err
-                    );
-                    // clients.remove(index);
+                for entry in entries {
+                    from = entry.seq + 1;
+                    replayed = entry.seq;
+                    if !ns.owns(&entry.message.mid) || !filter.is_match(&entry.message) {
+                        continue;
+                    }
+                    if !self
+                        .deliver(&store, &ns, &entry.message, Some(entry.seq))
+                        .await
+                    {
+                        return;
+                    }
                 }
             }
+        }
+
+        while let Some(message) = live.recv().await {
+            let seq = journal
+                .as_ref()
+                .and_then(|journal| journal.seq(&message.id).ok().flatten());
+            // the live messages during the replaying are sent already
+            if message.retry_times == 0 && seq.is_some_and(|seq| seq <= replayed) {
+                continue;
+            }
+            if !self.deliver(&store, &ns, &message, seq).await {
+                break;
+            }
+        }
+    }
+}
+
+/// the caller of the action, the user is set by the 'x-acts-user' metadata
+/// and the namespace is set by the auth token, the 'x-acts-namespace' metadata only selects one of
+/// the namespaces which are allowed for the token
+#[derive(Debug, Default, Clone)]
+pub struct Identity {
+    pub user: Option<String>,
+    pub peer: String,
+    pub namespace: Namespace,
+}
+
+impl Identity {
+    #[allow(clippy::result_large_err)]
+    pub fn from_request<T>(
+        request: &tonic::Request<T>,
+        options: &NamespaceOptions,
+    ) -> Result<Self, Status> {
+        let metadata = |key: &str| {
+            request
+                .metadata()
+                .get(key)
+                .and_then(|v| v.to_str().ok())
+                .map(|v| v.to_string())
+        };
+        let token = metadata("authorization").map(|v| match v.strip_prefix("Bearer ") {
+            Some(token) => token.to_string(),
+            None => v,
         });
+        let namespace = Self::namespace(
+            options,
+            token.as_deref(),
+            metadata("x-acts-namespace").as_deref(),
+        )?;
+
+        Ok(Self {
+            user: metadata("x-acts-user"),
+            peer: request
+                .remote_addr()
+                .map(|addr| addr.to_string())
+                .unwrap_or_default(),
+            namespace,
+        })
+    }
+
+    /// the namespace of the auth token, the name selects one of the namespaces which are allowed for the token
+    /// the root is only for the '*' token or the server without the configured namespaces
+    #[allow(clippy::result_large_err)]
+    pub fn namespace(
+        options: &NamespaceOptions,
+        token: Option<&str>,
+        name: Option<&str>,
+    ) -> Result<Namespace, Status> {
+        let allowed = match token {
+            Some(token) => options
+                .tokens
+                .get(token)
+                .cloned()
+                .ok_or(Status::unauthenticated("invalid auth token"))?,
+            None if !options.enabled() => namespace::ROOT.to_string(),
+            None => options
+                .default
+                .clone()
+                .ok_or(Status::unauthenticated("auth token is required"))?,
+        };
+        let name = match name {
+            Some(name) if allowed != namespace::ROOT && name != allowed => {
+                return Err(Status::permission_denied(format!(
+                    "namespace '{name}' is not allowed for the auth token"
+                )));
+            }
+            Some(name) => name,
+            None => &allowed,
+        };
+        Namespace::new(name).map_err(|err| Status::invalid_argument(err.to_string()))
+    }
+}
+
+impl std::fmt::Display for Identity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.user {
+            Some(user) => write!(f, "{user}@{}", self.peer),
+            None => f.write_str(&self.peer),
+        }
     }
 }
 
 #[derive(Clone)]
 pub struct GrpcServer {
     engine: Arc<Engine>,
+    store: Arc<Store>,
+    options: Arc<ServerOptions>,
+    in_flight: InFlight,
+    journal: Option<Arc<Journal>>,
+    groups: Groups,
+    retried: dlq::Retried,
+    webhooks: Arc<webhook::Hooks>,
 }
 
 impl GrpcServer {
-    pub fn new(engine: &Arc<Engine>) -> Self {
-        let inst = Self {
+    pub fn new(engine: &Arc<Engine>, store: &Arc<Store>, options: &ServerOptions) -> Self {
+        Self {
             engine: engine.clone(),
+            store: store.clone(),
+            options: Arc::new(options.clone()),
+            in_flight: InFlight::default(),
+            journal: None,
+            groups: Groups::new(&options.group),
+            retried: dlq::Retried::default(),
+            webhooks: Arc::default(),
+        }
+    }
+
+    pub fn options(&self) -> &ServerOptions {
+        &self.options
+    }
+
+    /// track the client cursors with the message journal, so the subscribers can replay the messages
+    pub fn with_journal(mut self, journal: Arc<Journal>) -> Self {
+        self.journal = Some(journal);
+        self
+    }
+
+    /// add the client to the consumer group, the group receives the engine messages
+    /// with the filters of the first member and sends each of them to one member
+    #[allow(clippy::result_large_err)]
+    fn join(&self, client: MessageClient, ns: Namespace, group: &str) -> Result<(), Status> {
+        let key = ns.scope(group);
+        let (member_tx, member_rx) = mpsc::unbounded_channel::<acts::Message>();
+        let member = self
+            .groups
+            .join(&key, &client.options, member_tx, || {
+                let chan = self.engine.channel_with_options(&ChannelOptions {
+                    id: format!("{}{key}", group::CHANNEL_PREFIX),
+                    ..client.options.clone()
+                });
+                let retried = {
+                    let groups = self.groups.clone();
+                    let key = key.clone();
+                    self.forward_retried(&ns, &client.options, move |message| {
+                        groups.dispatch(&key, message);
+                        true
+                    })
+                };
+                let groups = self.groups.clone();
+                let ns = ns.clone();
+                let key = key.clone();
+                chan.on_message(move |e| {
+                    if ns.owns(&e.mid) {
+                        groups.dispatch(&key, model::unpin_message(e.inner()));
+                    }
+                });
+                (chan, retried.abort_handle())
+            })
+            .map_err(|err| Status::failed_precondition(err.to_string()))?;
+
+        // leave the group when the client is closed, even if there is no message to send
+        let sender = client.sender.clone();
+        let groups = self.groups.clone();
+        let group = key.clone();
+        tokio::spawn(async move {
+            sender.closed().await;
+            groups.leave(&group, member);
+        });
+
+        let store = self.store.clone();
+        let journal = self.journal.clone();
+        let groups = self.groups.clone();
+        tokio::spawn(async move {
+            let filter = MessageFilter::new("*", "*", "*", "*").unwrap();
+            client
+                .forward(store, journal, ns, filter, None, member_rx)
+                .await;
+            groups.leave(&key, member);
+        });
+        Ok(())
+    }
+
+    /// send the retried dead messages which belong to the namespace and match the filters
+    /// the task stops when the send returns false
+    fn forward_retried(
+        &self,
+        ns: &Namespace,
+        options: &ChannelOptions,
+        send: impl Fn(acts::Message) -> bool + Send + 'static,
+    ) -> JoinHandle<()> {
+        let mut retried = self.retried.subscribe();
+        let ns = ns.clone();
+        let filter =
+            MessageFilter::new(&options.r#type, &options.state, &options.tag, &options.key);
+        tokio::spawn(async move {
+            let Ok(filter) = filter else {
+                return;
+            };
+            loop {
+                match retried.recv().await {
+                    Ok(message) => {
+                        if ns.owns(&message.mid) && filter.is_match(&message) && !send(message) {
+                            break;
+                        }
+                    }
+                    Err(RecvError::Lagged(_)) => continue,
+                    Err(RecvError::Closed) => break,
+                }
+            }
+        })
+    }
+
+    /// rewrite the request options to the engine ids in the namespace
+    /// and check the procs and messages in the request belong to the namespace
+    #[allow(clippy::result_large_err)]
+    fn scope(&self, message: &mut Message, ns: &Namespace) -> Result<(), Status> {
+        let name = message.name.as_str();
+        let mut options = match &message.data {
+            Some(data) => serde_json::from_slice::<acts::Vars>(data)
+                .map_err(|err| Status::invalid_argument(err.to_string()))?,
+            None => acts::Vars::new(),
         };
+        let group = name.split(':').next().unwrap_or_default();
+        if group == "sys" || name == "msg:redo" {
+            return Err(Status::permission_denied(format!(
+                "action '{name}' is not allowed in namespace '{ns}'"
+            )));
+        }
+
+        // the generated schedule and webhook ids are scoped too
+        if matches!(name, "schedule:create" | "webhook:create")
+            && options.get::<String>("id").is_none()
+        {
+            options.set("id", utils::longid());
+        }
+        let mut scope = |key: &str| {
+            if let Some(id) = options.get::<String>(key) {
+                options.set(key, ns.scope(&id));
+            }
+        };
+        match name {
+            "msg:unsub" => scope("client_id"),
+            "schedule:create" | "trigger:create" => {
+                scope("mid");
+                scope("id");
+            }
+            "model:deploy" | "model:validate" => {}
+            _ if matches!(group, "model" | "pack" | "schedule" | "webhook" | "trigger")
+                || name == "proc:start" =>
+            {
+                scope("id")
+            }
+            _ => {}
+        }
+        let scoped_query = match name {
+            "model:ls" | "pack:ls" => Some("id"),
+            "proc:ls" => Some("mid"),
+            _ => None,
+        };
+        if let (Some(key), Some(mut query_by)) = (
+            scoped_query,
+            options.get::<Vec<(String, String)>>("query_by"),
+        ) {
+            for (k, v) in query_by.iter_mut() {
+                if k == key {
+                    *v = ns.scope(v);
+                }
+            }
+            options.set("query_by", query_by);
+        }
+        // the audits of the other namespaces are never listed
+        if name == "audit:ls" {
+            let mut query_by = options
+                .get::<Vec<(String, String)>>("query_by")
+                .unwrap_or_default();
+            query_by.retain(|(key, _)| key != "namespace");
+            query_by.push(("namespace".to_string(), ns.to_string()));
+            options.set("query_by", query_by);
+        }
 
-        inst
+        let executor = self.engine.executor();
+        let mut owners = Owners::new(&executor, ns);
+        match (options.get::<String>("pid"), name) {
+            (_, "proc:start") => {}
+            (Some(pid), _) if !owners.owns_proc(&pid) => {
+                return Err(Status::not_found(format!("cannot find proc '{pid}'")));
+            }
+            (None, "msg:clear") => {
+                return Err(Status::invalid_argument(format!(
+                    "pid is required in namespace '{ns}'"
+                )));
+            }
+            _ => {}
+        }
+        if let (
+            "msg:get" | "msg:ack" | "msg:rm" | "msg:dlq:retry" | "msg:dlq:purge"
+            | "msg:dlq:history",
+            Some(id),
+        ) = (name, options.get::<String>("id"))
+        {
+            // the replayed message may be removed from the engine
+            let journaled = name == "msg:ack"
+                && self
+                    .journal
+                    .as_ref()
+                    .is_some_and(|journal| journal.flush().is_ok())
+                && cursor::owns(&self.store, &id, ns);
+            if !owners.owns_message(&id) && !journaled {
+                return Err(Status::not_found(format!("cannot find message '{id}'")));
+            }
+        }
+
+        message.data =
+            Some(serde_json::to_vec(&options).map_err(|err| Status::internal(err.to_string()))?);
+        Ok(())
     }
 
-    fn do_action(&self, message: Message) -> Result<Response<Message>, Status> {
+    #[allow(clippy::result_large_err)]
+    async fn do_action(
+        &self,
+        message: Message,
+        identity: &Identity,
+    ) -> Result<Response<Message>, Status> {
         let options = match message.data {
             Some(data) => &serde_json::from_slice::<acts::Vars>(&data).unwrap(),
             None => &acts::Vars::new(),
@@ -99,6 +505,8 @@ impl GrpcServer {
                 let tid = options
                     .get::<String>("tid")
                     .ok_or(Status::invalid_argument("tid is required"))?;
+
+                let _lock = revision::lock(&pid).await;
                 wrap_result!(ack, name, executor.act().push(&pid, &tid, options))
             }
             "act:remove" => {
@@ -109,6 +517,7 @@ impl GrpcServer {
                     .get::<String>("tid")
                     .ok_or(Status::invalid_argument("tid is required"))?;
 
+                let _lock = revision::lock(&pid).await;
                 wrap_result!(ack, name, executor.act().remove(&pid, &tid, options))
             }
             "act:submit" => {
@@ -119,7 +528,10 @@ impl GrpcServer {
                     .get::<String>("tid")
                     .ok_or(Status::invalid_argument("tid is required"))?;
 
-                wrap_result!(ack, name, executor.act().submit(&pid, &tid, options))
+                let mut options = options.clone();
+                let _lock = revision::lock(&pid).await;
+                check_revision(&executor, &pid, &tid, &mut options)?;
+                wrap_result!(ack, name, executor.act().submit(&pid, &tid, &options))
             }
             "act:complete" => {
                 let pid = options
@@ -129,7 +541,19 @@ impl GrpcServer {
                     .get::<String>("tid")
                     .ok_or(Status::invalid_argument("tid is required"))?;
 
-                wrap_result!(ack, name, executor.act().complete(&pid, &tid, options))
+                let mut options = options.clone();
+                let _lock = revision::lock(&pid).await;
+                check_revision(&executor, &pid, &tid, &mut options)?;
+                let task = executor
+                    .task()
+                    .get(&pid, &tid)
+                    .map_err(|err| Status::not_found(err.to_string()))?;
+                if let Some(violation) = package::check_outputs(&self.store, &task, &options)
+                    .map_err(|err| Status::internal(err.to_string()))?
+                {
+                    return Err(Status::invalid_argument(violation.to_string()));
+                }
+                wrap_result!(ack, name, executor.act().complete(&pid, &tid, &options))
             }
             "act:abort" => {
                 let pid = options
@@ -139,6 +563,7 @@ impl GrpcServer {
                     .get::<String>("tid")
                     .ok_or(Status::invalid_argument("tid is required"))?;
 
+                let _lock = revision::lock(&pid).await;
                 wrap_result!(ack, name, executor.act().abort(&pid, &tid, options))
             }
             "act:cancel" => {
@@ -149,6 +574,7 @@ impl GrpcServer {
                     .get::<String>("tid")
                     .ok_or(Status::invalid_argument("tid is required"))?;
 
+                let _lock = revision::lock(&pid).await;
                 wrap_result!(ack, name, executor.act().cancel(&pid, &tid, options))
             }
             "act:back" => {
@@ -159,7 +585,10 @@ impl GrpcServer {
                     .get::<String>("tid")
                     .ok_or(Status::invalid_argument("tid is required"))?;
 
-                wrap_result!(ack, name, executor.act().back(&pid, &tid, options))
+                let mut options = options.clone();
+                let _lock = revision::lock(&pid).await;
+                check_revision(&executor, &pid, &tid, &mut options)?;
+                wrap_result!(ack, name, executor.act().back(&pid, &tid, &options))
             }
             "act:skip" => {
                 let pid = options
@@ -169,6 +598,7 @@ impl GrpcServer {
                     .get::<String>("tid")
                     .ok_or(Status::invalid_argument("tid is required"))?;
 
+                let _lock = revision::lock(&pid).await;
                 wrap_result!(ack, name, executor.act().skip(&pid, &tid, options))
             }
             "act:error" => {
@@ -179,6 +609,7 @@ impl GrpcServer {
                     .get::<String>("tid")
                     .ok_or(Status::invalid_argument("tid is required"))?;
 
+                let _lock = revision::lock(&pid).await;
                 wrap_result!(ack, name, executor.act().error(&pid, &tid, options))
             }
             // model
@@ -196,16 +627,20 @@ impl GrpcServer {
                     count,
                     query_by,
                     order_by,
-                    ..Default::default()
                 };
-                let ret = executor.model().list(&query);
+                // the pinned model is in the engine while the pinned proc is starting
+                let ret = namespace::list(
+                    &query,
+                    |q| executor.model().list(q).map(|page| (page.rows, page.count)),
+                    |m| identity.namespace.owns(&m.id) && !m.id.contains(model::PIN_SEPARATOR),
+                );
                 wrap_result!(ack, name, ret)
             }
             "model:rm" => {
                 let id = options
                     .get::<String>("id")
                     .ok_or(Status::invalid_argument("id is required"))?;
-                let ret = executor.model().rm(&id);
+                let ret = model::rm(&executor, &self.store, &id);
                 wrap_result!(ack, name, ret)
             }
             "model:get" => {
@@ -213,23 +648,207 @@ impl GrpcServer {
                     .get::<String>("id")
                     .ok_or(Status::invalid_argument("id is required"))?;
                 let fmt = options.get::<String>("fmt").unwrap_or("text".to_string());
-                let ret = executor.model().get(&mid, &fmt);
+                let ver = options.get::<u32>("ver");
+                let ret = model::get(&executor, &self.store, &mid, ver, &fmt);
+                wrap_result!(ack, name, ret)
+            }
+            "model:versions" => {
+                let mid = options
+                    .get::<String>("id")
+                    .ok_or(Status::invalid_argument("id is required"))?;
+                let offset = options.get::<i64>("offset").map_or(0, |v| v as usize);
+                let count = options.get::<i64>("count").map_or(100, |v| v as usize);
+                let ret = model::versions(&executor, &self.store, &mid, offset, count);
+                wrap_result!(ack, name, ret)
+            }
+            "model:diff" => {
+                let mid = options
+                    .get::<String>("id")
+                    .ok_or(Status::invalid_argument("id is required"))?;
+                let from = options.get::<u32>("from");
+                let to = options.get::<u32>("to");
+                let ret = model::diff(&executor, &self.store, &mid, from, to);
+                wrap_result!(ack, name, ret)
+            }
+            "model:rollback" => {
+                let mid = options
+                    .get::<String>("id")
+                    .ok_or(Status::invalid_argument("id is required"))?;
+                let ver = options
+                    .get::<u32>("ver")
+                    .ok_or(Status::invalid_argument("ver is required"))?;
+                let ret = model::rollback(&executor, &self.store, &mid, ver);
                 wrap_result!(ack, name, ret)
             }
             "model:deploy" => {
                 let model_text = options
                     .get::<String>("model")
                     .ok_or(Status::invalid_argument("model is required"))?;
-
-                let mut model =
-                    Workflow::from_yml(&model_text).map_err(|err| Status::invalid_argument(err))?;
-                if let Some(mid) = options.get::<String>("mid") {
-                    model.set_id(&mid);
+                let format = options
+                    .get::<String>("format")
+                    .unwrap_or("yaml".to_string());
+                let mid = options.get::<String>("mid");
+                let mut models = validate::load(
+                    &executor,
+                    &self.store,
+                    &identity.namespace,
+                    &model_text,
+                    &format,
+                    mid.as_deref(),
+                )
+                .map_err(|errors| {
+                    let messages: Vec<String> = errors
+                        .iter()
+                        .map(|d| format!("{}:{} {}", d.line, d.column, d.message))
+                        .collect();
+                    Status::invalid_argument(messages.join("\n"))
+                })?;
+                for model in models.iter_mut() {
+                    identity.namespace.scope_workflow(model);
+                }
+                let ret = model::deploy_all(&executor, &self.store, &models).map(|_| true);
+                wrap_result!(ack, name, ret)
+            }
+            "model:validate" => {
+                let model_text = options
+                    .get::<String>("model")
+                    .ok_or(Status::invalid_argument("model is required"))?;
+                let format = options
+                    .get::<String>("format")
+                    .unwrap_or("yaml".to_string());
+                let mid = options.get::<String>("mid");
+                let ret: acts::Result<_> = Ok(validate::validate(
+                    &executor,
+                    &self.store,
+                    &identity.namespace,
+                    &model_text,
+                    &format,
+                    mid.as_deref(),
+                ));
+                wrap_result!(ack, name, ret)
+            }
+            // audit
+            "audit:ls" => {
+                let offset = options.get::<i64>("offset").map_or(0, |v| v as usize);
+                let count = options.get::<i64>("count").map_or(100, |v| v as usize);
+                let query_by = options
+                    .get::<Vec<(String, String)>>("query_by")
+                    .unwrap_or_default();
+                let order_by = options
+                    .get::<Vec<(String, bool)>>("order_by")
+                    .unwrap_or_default();
+                let query = ExecutorQuery {
+                    offset,
+                    count,
+                    query_by,
+                    order_by,
                 };
-                wrap_result!(ack, name, executor.model().deploy(&model))
+                let ret = audit::list(&self.store, &query);
+                wrap_result!(ack, name, ret)
             }
-            // package
-            "pack:ls" => {
+            // schedule
+            "schedule:create" => {
+                let schedule = Schedule {
+                    id: options.get::<String>("id").unwrap_or_default(),
+                    mid: options
+                        .get::<String>("mid")
+                        .ok_or(Status::invalid_argument("mid is required"))?,
+                    cron: options.get::<String>("cron").unwrap_or_default(),
+                    at: options.get::<i64>("at").unwrap_or_default(),
+                    vars: options.get::<acts::Vars>("vars").unwrap_or_default(),
+                    catch_up: options.get::<String>("catch_up").unwrap_or_default(),
+                    ..Default::default()
+                };
+                let ret = schedule::create(&self.store, &schedule);
+                wrap_result!(ack, name, ret)
+            }
+            "schedule:ls" => {
+                let offset = options.get::<i64>("offset").map_or(0, |v| v as usize);
+                let count = options.get::<i64>("count").map_or(100, |v| v as usize);
+                let query_by = options
+                    .get::<Vec<(String, String)>>("query_by")
+                    .unwrap_or_default();
+                let order_by = options
+                    .get::<Vec<(String, bool)>>("order_by")
+                    .unwrap_or_default();
+                let query = ExecutorQuery {
+                    offset,
+                    count,
+                    query_by,
+                    order_by,
+                };
+                if identity.namespace.is_root() {
+                    wrap_result!(ack, name, schedule::list(&self.store, &query))
+                } else {
+                    let ret = namespace::list(
+                        &query,
+                        |q| schedule::list(&self.store, q).map(|page| (page.rows, page.count)),
+                        |s| identity.namespace.owns(&s.id),
+                    );
+                    wrap_result!(ack, name, ret)
+                }
+            }
+            "schedule:rm" => {
+                let id = options
+                    .get::<String>("id")
+                    .ok_or(Status::invalid_argument("id is required"))?;
+                let ret = schedule::rm(&self.store, &id);
+                wrap_result!(ack, name, ret)
+            }
+            "schedule:pause" | "schedule:resume" => {
+                let id = options
+                    .get::<String>("id")
+                    .ok_or(Status::invalid_argument("id is required"))?;
+                let ret = schedule::pause(&self.store, &id, name == "schedule:pause");
+                wrap_result!(ack, name, ret)
+            }
+            // webhook
+            "webhook:create" => {
+                let hook = Webhook {
+                    id: options.get::<String>("id").unwrap_or_default(),
+                    url: options
+                        .get::<String>("url")
+                        .ok_or(Status::invalid_argument("url is required"))?,
+                    secret: options.get::<String>("secret").unwrap_or_default(),
+                    r#type: options.get::<String>("type").unwrap_or_default(),
+                    state: options.get::<String>("state").unwrap_or_default(),
+                    tag: options.get::<String>("tag").unwrap_or_default(),
+                    key: options.get::<String>("key").unwrap_or_default(),
+                    namespace: identity.namespace.to_string(),
+                    ..Default::default()
+                };
+                let ret =
+                    webhook::create(&self.store, &self.webhooks, &hook, &self.options.webhook);
+                wrap_result!(ack, name, ret)
+            }
+            "webhook:ls" => {
+                let offset = options.get::<i64>("offset").map_or(0, |v| v as usize);
+                let count = options.get::<i64>("count").map_or(100, |v| v as usize);
+                let query_by = options
+                    .get::<Vec<(String, String)>>("query_by")
+                    .unwrap_or_default();
+                let order_by = options
+                    .get::<Vec<(String, bool)>>("order_by")
+                    .unwrap_or_default();
+                let query = ExecutorQuery {
+                    offset,
+                    count,
+                    query_by,
+                    order_by,
+                };
+                if identity.namespace.is_root() {
+                    wrap_result!(ack, name, webhook::list(&self.store, &query))
+                } else {
+                    let ret = namespace::list(
+                        &query,
+                        |q| webhook::list(&self.store, q).map(|page| (page.rows, page.count)),
+                        |hook| identity.namespace.owns(&hook.id),
+                    );
+                    wrap_result!(ack, name, ret)
+                }
+            }
+            // the deliveries which are failed after all of the retries, not the dead engine messages of msg:dlq:ls
+            "webhook:failed:ls" => {
                 let offset = options.get::<i64>("offset").map_or(0, |v| v as usize);
                 let count = options.get::<i64>("count").map_or(100, |v| v as usize);
                 let query_by = options
@@ -243,32 +862,227 @@ impl GrpcServer {
                     count,
                     query_by,
                     order_by,
+                };
+                let ret = webhook::failed_in(&self.store, &identity.namespace, &query);
+                wrap_result!(ack, name, ret)
+            }
+            "webhook:rm" => {
+                let id = options
+                    .get::<String>("id")
+                    .ok_or(Status::invalid_argument("id is required"))?;
+                let ret = webhook::rm(&self.store, &self.webhooks, &id);
+                wrap_result!(ack, name, ret)
+            }
+            // trigger
+            "trigger:create" => {
+                let trigger = Trigger {
+                    id: options.get::<String>("id").unwrap_or_default(),
+                    path: options
+                        .get::<String>("path")
+                        .ok_or(Status::invalid_argument("path is required"))?,
+                    secret: options.get::<String>("secret").unwrap_or_default(),
+                    mid: options
+                        .get::<String>("mid")
+                        .ok_or(Status::invalid_argument("mid is required"))?,
+                    mapping: options
+                        .get::<std::collections::HashMap<String, String>>("mapping")
+                        .unwrap_or_default(),
+                    vars: options.get::<acts::Vars>("vars").unwrap_or_default(),
                     ..Default::default()
                 };
-                let ret = executor.pack().list(&query);
+                let ret = trigger::create(&self.store, &identity.namespace, &trigger);
+                wrap_result!(ack, name, ret)
+            }
+            "trigger:ls" => {
+                let offset = options.get::<i64>("offset").map_or(0, |v| v as usize);
+                let count = options.get::<i64>("count").map_or(100, |v| v as usize);
+                let query_by = options
+                    .get::<Vec<(String, String)>>("query_by")
+                    .unwrap_or_default();
+                let order_by = options
+                    .get::<Vec<(String, bool)>>("order_by")
+                    .unwrap_or_default();
+                let query = ExecutorQuery {
+                    offset,
+                    count,
+                    query_by,
+                    order_by,
+                };
+                if identity.namespace.is_root() {
+                    wrap_result!(ack, name, trigger::list(&self.store, &query))
+                } else {
+                    let ret = namespace::list(
+                        &query,
+                        |q| trigger::list(&self.store, q).map(|page| (page.rows, page.count)),
+                        |t| identity.namespace.owns(&t.id),
+                    );
+                    wrap_result!(ack, name, ret)
+                }
+            }
+            "trigger:rm" => {
+                let id = options
+                    .get::<String>("id")
+                    .ok_or(Status::invalid_argument("id is required"))?;
+                let ret = trigger::rm(&self.store, &id);
+                wrap_result!(ack, name, ret)
+            }
+            // system
+            "sys:export" => {
+                let id = options.get::<String>("id");
+                let offset = options.get::<i64>("offset").map_or(0, |v| v as u64);
+                let ret = archive::export(&self.engine.config(), id.as_deref(), offset);
                 wrap_result!(ack, name, ret)
             }
+            "sys:upload" => {
+                let data = options
+                    .get::<String>("data")
+                    .ok_or(Status::invalid_argument("data is required"))?;
+                let id = options.get::<String>("id");
+                let ret = archive::upload(&self.engine.config(), id.as_deref(), &data);
+                wrap_result!(ack, name, ret)
+            }
+            "sys:backup" => {
+                let backup = self
+                    .options
+                    .backup
+                    .as_ref()
+                    .ok_or(Status::failed_precondition(
+                        "backup is not configured in acts.conf",
+                    ))?;
+                let ret = backup::backup(&self.engine.config(), &backup.dir);
+                wrap_result!(ack, name, ret)
+            }
+            "sys:import" => {
+                let mode = options.get::<String>("mode").unwrap_or("merge".to_string());
+                let dry_run = options.get::<bool>("dry_run").unwrap_or_default();
+                if mode == "replace" && !dry_run {
+                    return Err(Status::failed_precondition(
+                        "the replace mode cannot run while the engine is serving, stop the server and start it with '--import <archive> --replace'",
+                    ));
+                }
+                let text = match (
+                    options.get::<String>("archive"),
+                    options.get::<String>("upload"),
+                ) {
+                    (Some(text), _) => text,
+                    (None, Some(upload)) => archive::take_upload(&self.engine.config(), &upload)
+                        .map_err(|err| Status::invalid_argument(err.to_string()))?,
+                    (None, None) => {
+                        return Err(Status::invalid_argument("archive or upload is required"))
+                    }
+                };
+                let ret =
+                    archive::import(&self.engine.config(), &self.store, &text, &mode, dry_run);
+                wrap_result!(ack, name, ret)
+            }
+            // package
+            "pack:ls" => {
+                let offset = options.get::<i64>("offset").map_or(0, |v| v as usize);
+                let count = options.get::<i64>("count").map_or(100, |v| v as usize);
+                let query_by = options
+                    .get::<Vec<(String, String)>>("query_by")
+                    .unwrap_or_default();
+                let order_by = options
+                    .get::<Vec<(String, bool)>>("order_by")
+                    .unwrap_or_default();
+                let query = ExecutorQuery {
+                    offset,
+                    count,
+                    query_by,
+                    order_by,
+                };
+                if identity.namespace.is_root() {
+                    wrap_result!(ack, name, executor.pack().list(&query))
+                } else {
+                    let ret = namespace::list(
+                        &query,
+                        |q| executor.pack().list(q).map(|page| (page.rows, page.count)),
+                        |p| identity.namespace.owns(&p.id),
+                    );
+                    wrap_result!(ack, name, ret)
+                }
+            }
             "pack:publish" => {
                 let package_id = options
                     .get::<String>("id")
                     .ok_or(Status::invalid_argument("package 'id' is required"))?;
                 let package_name = options.get::<String>("name").unwrap_or_default();
-                let data = options
-                    .get::<String>("body")
-                    .ok_or(Status::invalid_argument("package 'body' is required"))?;
+                let files = options.get::<package::PackageFiles>("files");
+                let data = match (options.get::<String>("body"), &files) {
+                    (Some(body), _) => body,
+                    (None, Some(_)) => String::new(),
+                    (None, None) => {
+                        return Err(Status::invalid_argument(
+                            "package 'body' or 'files' is required",
+                        ))
+                    }
+                };
+                let version = options.get::<String>("version");
+                let schema = options
+                    .get::<package::PackageSchema>("schema")
+                    .unwrap_or_default();
                 let pack = Package {
                     id: package_id,
                     name: package_name,
                     data: data.into_bytes(),
                     ..Default::default()
                 };
-                wrap_result!(ack, name, executor.pack().publish(&pack))
+                let ret = package::publish(
+                    &executor,
+                    &self.store,
+                    &pack,
+                    version.as_deref(),
+                    schema,
+                    files,
+                    &identity.to_string(),
+                )
+                .map(|_| true);
+                wrap_result!(ack, name, ret)
+            }
+            "pack:get" => {
+                let id = options
+                    .get::<String>("id")
+                    .ok_or(Status::invalid_argument("id is required"))?;
+                let key = match options.get::<String>("version") {
+                    Some(version) => format!("{id}@{version}"),
+                    None => id,
+                };
+                let ret = package::get(&executor, &self.store, &key);
+                wrap_result!(ack, name, ret)
+            }
+            "pack:versions" => {
+                let id = options
+                    .get::<String>("id")
+                    .ok_or(Status::invalid_argument("id is required"))?;
+                let offset = options.get::<i64>("offset").map_or(0, |v| v as usize);
+                let count = options.get::<i64>("count").map_or(100, |v| v as usize);
+                let ret = package::versions(&self.store, &id, offset, count);
+                wrap_result!(ack, name, ret)
+            }
+            "pack:rollback" => {
+                let id = options
+                    .get::<String>("id")
+                    .ok_or(Status::invalid_argument("id is required"))?;
+                let version = options
+                    .get::<String>("version")
+                    .ok_or(Status::invalid_argument("version is required"))?;
+                let ret = package::rollback(&executor, &self.store, &id, &version);
+                wrap_result!(ack, name, ret)
             }
             "pack:rm" => {
                 let id = options
                     .get::<String>("id")
                     .ok_or(Status::invalid_argument("id is required"))?;
-                let ret = executor.pack().rm(&id);
+                let force = options.get::<bool>("force").unwrap_or_default();
+                let models = package::references(&executor, &id)
+                    .map_err(|err| Status::internal(err.to_string()))?;
+                if !models.is_empty() && !force {
+                    return Err(Status::failed_precondition(format!(
+                        "package '{id}' is referenced by models: {}",
+                        models.join(", ")
+                    )));
+                }
+                let ret = package::rm(&executor, &self.store, &id);
                 wrap_result!(ack, name, ret)
             }
             // proc
@@ -276,7 +1090,14 @@ impl GrpcServer {
                 let id = options
                     .get::<String>("id")
                     .ok_or(Status::invalid_argument("id is required"))?;
-                wrap_result!(ack, name, executor.proc().start(&id, options))
+                // the ver is used to pin the model version, not a proc var
+                let mut options = options.clone();
+                let ver = options
+                    .remove("ver")
+                    .and_then(|v| v.as_u64())
+                    .map(|v| v as u32);
+                let ret = model::start(&executor, &self.store, &id, ver, &options);
+                wrap_result!(ack, name, ret)
             }
             "proc:ls" => {
                 let offset = options.get::<i64>("offset").map_or(0, |v| v as usize);
@@ -292,21 +1113,79 @@ impl GrpcServer {
                     count,
                     query_by,
                     order_by,
-                    ..Default::default()
                 }
                 .with_offset(offset)
                 .with_count(count);
 
-                let ret = executor.proc().list(&query);
+                let ret = if identity.namespace.is_root() {
+                    executor.proc().list(&query).map(|page| PageData {
+                        count: page.count,
+                        page_num: page.page_num,
+                        page_count: page.page_count,
+                        page_size: page.page_size,
+                        rows: page.rows,
+                    })
+                } else {
+                    namespace::list(
+                        &query,
+                        |q| executor.proc().list(q).map(|page| (page.rows, page.count)),
+                        |p| identity.namespace.owns(&p.mid),
+                    )
+                };
+                let ret = ret.map(|page| PageData {
+                    rows: page.rows.into_iter().map(model::unpin_proc).collect(),
+                    ..page
+                });
                 wrap_result!(ack, name, ret)
             }
             "proc:get" => {
                 let pid = options
                     .get::<String>("pid")
                     .ok_or(Status::invalid_argument("pid is required"))?;
-                let ret = executor.proc().get(&pid);
+                match options.get::<String>("fmt") {
+                    Some(fmt) if graph::FORMATS.contains(&fmt.as_str()) => {
+                        let ret = graph::proc_graph(&executor, &self.store, &pid, &fmt);
+                        wrap_result!(ack, name, ret)
+                    }
+                    _ => {
+                        let ret = executor.proc().get(&pid).map(model::unpin_proc);
+                        wrap_result!(ack, name, ret)
+                    }
+                }
+            }
+            "proc:tasks" => {
+                let pid = options
+                    .get::<String>("pid")
+                    .ok_or(Status::invalid_argument("pid is required"))?;
+                let ret = tree::proc_tasks(&executor, &self.store, &pid);
                 wrap_result!(ack, name, ret)
             }
+            "proc:vars:get" => {
+                let pid = options
+                    .get::<String>("pid")
+                    .ok_or(Status::invalid_argument("pid is required"))?;
+                let ret = vars::proc_vars(&executor, &pid);
+                wrap_result!(ack, name, ret)
+            }
+            "proc:vars:set" => {
+                let pid = options
+                    .get::<String>("pid")
+                    .ok_or(Status::invalid_argument("pid is required"))?;
+                let patch = options
+                    .get::<acts::Vars>("vars")
+                    .ok_or(Status::invalid_argument("vars is required"))?;
+                let _lock = revision::lock(&pid).await;
+                let ret = vars::set_proc_vars(&executor, &pid, &patch).await;
+                let (vars, change) = match ret {
+                    Ok((vars, change)) => (Ok(vars), Some(change)),
+                    Err(err) => (Err(err), None),
+                };
+                let mut resp = wrap_result!(ack, name, vars)?;
+                if let Some(change) = change {
+                    resp.extensions_mut().insert(change);
+                }
+                Ok(resp)
+            }
             // task
             "task:ls" => {
                 let offset = options.get::<i64>("offset").map_or(0, |v| v as usize);
@@ -322,11 +1201,19 @@ impl GrpcServer {
                     count,
                     query_by,
                     order_by,
-                    ..Default::default()
                 };
 
-                let ret = executor.task().list(&query);
-                wrap_result!(ack, name, ret)
+                if identity.namespace.is_root() {
+                    wrap_result!(ack, name, executor.task().list(&query))
+                } else {
+                    let mut owners = Owners::new(&executor, &identity.namespace);
+                    let ret = namespace::list(
+                        &query,
+                        |q| executor.task().list(q).map(|page| (page.rows, page.count)),
+                        |t| owners.owns_proc(&t.pid),
+                    );
+                    wrap_result!(ack, name, ret)
+                }
             }
             "task:get" => {
                 let pid = options
@@ -335,7 +1222,17 @@ impl GrpcServer {
                 let tid = options
                     .get::<String>("tid")
                     .ok_or(Status::invalid_argument("tid is required"))?;
-                let ret = executor.task().get(&pid, &tid);
+                let ret = revision::get(&executor, &pid, &tid);
+                wrap_result!(ack, name, ret)
+            }
+            "task:vars:get" => {
+                let pid = options
+                    .get::<String>("pid")
+                    .ok_or(Status::invalid_argument("pid is required"))?;
+                let tid = options
+                    .get::<String>("tid")
+                    .ok_or(Status::invalid_argument("tid is required"))?;
+                let ret = vars::task_vars(&executor, &pid, &tid);
                 wrap_result!(ack, name, ret)
             }
             // msg
@@ -353,10 +1250,23 @@ impl GrpcServer {
                     count,
                     query_by,
                     order_by,
-                    ..Default::default()
                 };
-                let ret = executor.msg().list(&query);
-                wrap_result!(ack, name, ret)
+                // the dead-letter view of the webhook deliveries which are failed after all of the retries
+                if options.get::<bool>("dead_letter").unwrap_or_default() {
+                    let ret = webhook::failed_in(&self.store, &identity.namespace, &query);
+                    return wrap_result!(ack, name, ret);
+                }
+                if identity.namespace.is_root() {
+                    wrap_result!(ack, name, executor.msg().list(&query))
+                } else {
+                    let mut owners = Owners::new(&executor, &identity.namespace);
+                    let ret = namespace::list(
+                        &query,
+                        |q| executor.msg().list(q).map(|page| (page.rows, page.count)),
+                        |m| owners.owns_proc(&m.pid),
+                    );
+                    wrap_result!(ack, name, ret)
+                }
             }
             "msg:get" => {
                 let id = options
@@ -369,7 +1279,14 @@ impl GrpcServer {
                 let id = options
                     .get::<String>("id")
                     .ok_or(Status::invalid_argument("id is required"))?;
-                wrap_result!(ack, name, executor.msg().ack(&id))
+                let acked = cursor::ack(&self.store, &id)
+                    .map_err(|err| Status::internal(err.to_string()))?;
+                let acked = self.groups.ack(&id) || acked;
+                let ret = match executor.msg().ack(&id) {
+                    Err(_) if acked => Ok(()),
+                    ret => ret,
+                };
+                wrap_result!(ack, name, ret)
             }
             "msg:redo" => {
                 let ret = executor.msg().redo();
@@ -391,13 +1308,182 @@ impl GrpcServer {
                 let client_id = options
                     .get::<String>("client_id")
                     .ok_or(Status::invalid_argument("client id is required"))?;
-                let ret = executor.msg().unsub(&client_id);
+                let ret = executor
+                    .msg()
+                    .unsub(&client_id)
+                    .and_then(|_| cursor::rm(&self.store, &client_id).map(|_| ()));
+                wrap_result!(ack, name, ret)
+            }
+            // the messages which are dead after all of the engine retries
+            "msg:dlq:ls" => {
+                let offset = options.get::<i64>("offset").map_or(0, |v| v as usize);
+                let count = options.get::<i64>("count").map_or(100, |v| v as usize);
+                let query = dlq::query(&ExecutorQuery {
+                    offset,
+                    count,
+                    query_by: options
+                        .get::<Vec<(String, String)>>("query_by")
+                        .unwrap_or_default(),
+                    order_by: options
+                        .get::<Vec<(String, bool)>>("order_by")
+                        .unwrap_or_default(),
+                });
+                let page = if identity.namespace.is_root() {
+                    executor.msg().list(&query).map(|page| PageData {
+                        count: page.count,
+                        page_num: page.page_num,
+                        page_count: page.page_count,
+                        page_size: page.page_size,
+                        rows: page.rows,
+                    })
+                } else {
+                    let mut owners = Owners::new(&executor, &identity.namespace);
+                    namespace::list(
+                        &query,
+                        |q| executor.msg().list(q).map(|page| (page.rows, page.count)),
+                        |m| owners.owns_proc(&m.pid),
+                    )
+                };
+                let ret = page.and_then(|page| dlq::letters(&self.store, page));
+                wrap_result!(ack, name, ret)
+            }
+            "msg:dlq:retry" | "msg:dlq:purge" => {
+                let id = options.get::<String>("id");
+                let query_by = options
+                    .get::<Vec<(String, String)>>("query_by")
+                    .unwrap_or_default();
+                let before = options.get::<i64>("before");
+                let mut owners = Owners::new(&executor, &identity.namespace);
+                let is_root = identity.namespace.is_root();
+                let ret = dlq::select(&executor, id.as_deref(), query_by, before, |m| {
+                    is_root || owners.owns_proc(&m.pid)
+                })
+                .and_then(|messages| {
+                    if name == "msg:dlq:retry" {
+                        let by = identity.to_string();
+                        dlq::retry(&executor, &self.store, &self.retried, &messages, &by)
+                    } else {
+                        dlq::purge(&executor, &self.store, &messages)
+                    }
+                });
+                wrap_result!(ack, name, ret)
+            }
+            "msg:dlq:history" => {
+                let id = options
+                    .get::<String>("id")
+                    .ok_or(Status::invalid_argument("id is required"))?;
+                let ret = dlq::history(&self.store, &id);
                 wrap_result!(ack, name, ret)
             }
             _ => Err(Status::not_found(format!("not found action '{name}'"))),
         }
     }
 
+    /// run the action at most once for the idempotency key in the window
+    /// the retried request gets the stored response without running the action again
+    #[allow(clippy::result_large_err)]
+    async fn call_once(
+        &self,
+        message: Message,
+        identity: &Identity,
+        key: &str,
+    ) -> Result<Response<Message>, Status> {
+        let id = format!("{}/{key}", identity.namespace);
+        if !self.in_flight.enter(&id) {
+            return Err(Status::aborted(format!(
+                "the request with idempotency key '{key}' is in progress"
+            )));
+        }
+        let ret = self.replay_or_call(message, identity, &id, key).await;
+        self.in_flight.leave(&id);
+        ret
+    }
+
+    #[allow(clippy::result_large_err)]
+    async fn replay_or_call(
+        &self,
+        message: Message,
+        identity: &Identity,
+        id: &str,
+        key: &str,
+    ) -> Result<Response<Message>, Status> {
+        let hash = idempotency::hash(&message.name, message.data.as_deref());
+        let prev =
+            idempotency::find(&self.store, id).map_err(|err| Status::internal(err.to_string()))?;
+        if let Some(prev) = prev {
+            if prev.action != message.name {
+                return Err(Status::failed_precondition(format!(
+                    "idempotency key '{key}' is already used by action '{}'",
+                    prev.action
+                )));
+            }
+            if prev.hash != hash {
+                return Err(Status::failed_precondition(format!(
+                    "idempotency key '{key}' is already used with the different options"
+                )));
+            }
+            let mut resp = Response::new(Message {
+                name: message.name,
+                seq: acts_channel::create_seq(),
+                ack: Some(message.seq),
+                data: prev.data.map(|data| serde_json::to_vec(&data).unwrap()),
+            });
+            resp.metadata_mut()
+                .insert(idempotency::REPLAY_METADATA, "true".parse().unwrap());
+            return Ok(resp);
+        }
+
+        let name = message.name.clone();
+        let resp = self.call(message, identity).await?;
+        let data = resp
+            .get_ref()
+            .data
+            .as_ref()
+            .and_then(|data| serde_json::from_slice(data).ok());
+        if let Err(err) = idempotency::save(
+            &self.store,
+            id,
+            &name,
+            &hash,
+            data,
+            &self.options.idempotency,
+        ) {
+            tracing::error!("idempotency: {err}");
+        }
+        Ok(resp)
+    }
+
+    /// run the action in the namespace of the identity
+    #[allow(clippy::result_large_err)]
+    async fn call(
+        &self,
+        mut message: Message,
+        identity: &Identity,
+    ) -> Result<Response<Message>, Status> {
+        let ns = &identity.namespace;
+        if ns.is_root() {
+            return self.do_action(message, identity).await;
+        }
+
+        self.scope(&mut message, ns)?;
+        match self.do_action(message, identity).await {
+            Ok(mut resp) => {
+                let message = resp.get_mut();
+                if let Some(data) = &message.data {
+                    let mut value = serde_json::from_slice::<serde_json::Value>(data)
+                        .map_err(|err| Status::internal(err.to_string()))?;
+                    ns.unscope_value(&mut value);
+                    message.data = Some(serde_json::to_vec(&value).unwrap());
+                }
+                Ok(resp)
+            }
+            Err(status) => Err(Status::new(
+                status.code(),
+                ns.unscope_text(status.message()),
+            )),
+        }
+    }
+
     pub async fn init(&self) {}
 }
 
@@ -411,9 +1497,41 @@ impl ActsService for GrpcServer {
     ) -> Result<tonic::Response<Self::OnMessageStream>, tonic::Status> {
         let (tx, rx) = mpsc::channel::<Result<Message, Status>>(128);
         let addr = req.remote_addr().unwrap();
+        let ns = Identity::from_request(&req, &self.options.namespace)?.namespace;
+        #[allow(clippy::result_large_err)]
+        let metadata = |key: &str| -> Result<Option<String>, Status> {
+            req.metadata()
+                .get(key)
+                .map(|value| {
+                    value
+                        .to_str()
+                        .map(|value| value.to_string())
+                        .map_err(|err| Status::invalid_argument(err.to_string()))
+                })
+                .transpose()
+        };
+        let replay = metadata(cursor::REPLAY_METADATA)?
+            .map(|value| value.parse::<Replay>())
+            .transpose()
+            .map_err(|err| Status::invalid_argument(err.to_string()))?;
+        let group = metadata(group::GROUP_METADATA)?;
+        let transform = Transform::new(
+            metadata(transform::FIELDS_METADATA)?.as_deref(),
+            metadata(transform::ENCODING_METADATA)?.as_deref(),
+        )
+        .map_err(|err| Status::invalid_argument(err.to_string()))?;
         let options = req.into_inner();
+        let filter =
+            MessageFilter::new(&options.r#type, &options.state, &options.tag, &options.key)
+                .map_err(|err| Status::invalid_argument(err.to_string()))?;
 
-        tracing::info!("on_message: options={:?}", options);
+        tracing::info!(
+            "on_message: options={:?} replay={:?} group={:?} transform={:?}",
+            options,
+            replay,
+            group,
+            transform
+        );
         let client = MessageClient {
             addr: addr.to_string(),
             sender: tx,
@@ -423,21 +1541,61 @@ impl ActsService for GrpcServer {
                 tag: options.tag.clone(),
                 key: options.key.clone(),
                 ack: true,
-                id: options.client_id.clone(),
+                // the client ids of the namespaces never conflict
+                id: ns.scope(&options.client_id),
             },
+            transform: Arc::new(transform),
+        };
+        if let Some(group) = group {
+            if replay.is_some() {
+                return Err(Status::invalid_argument(
+                    "the replay is not supported in the consumer group",
+                ));
+            }
+            self.join(client, ns, &group)?;
+            return Ok(Response::new(Box::pin(ReceiverStream::new(rx))));
+        }
+
+        let from = match (&replay, &self.journal) {
+            (Some(replay), Some(journal)) => Some(
+                journal
+                    .flush()
+                    .and_then(|_| cursor::position(&self.store, &client.options.id, replay))
+                    .map_err(|err| Status::internal(err.to_string()))?,
+            ),
+            (Some(_), None) => {
+                return Err(Status::failed_precondition(
+                    "the message journal is disabled",
+                ))
+            }
+            _ => None,
         };
+
+        // the live messages wait in the queue until the replay is done
+        let (live_tx, live_rx) = mpsc::unbounded_channel::<acts::Message>();
         let chan = self.engine.channel_with_options(&client.options);
-        tokio::spawn(async move {
+        {
+            let live_tx = live_tx.clone();
+            self.forward_retried(&ns, &client.options, move |message| {
+                live_tx.send(message).is_ok()
+            });
+        }
+        {
+            let ns = ns.clone();
             chan.on_message(move |e| {
-                let message = Message {
-                    name: e.name.clone(),
-                    seq: e.id.clone(),
-                    ack: None,
-                    data: Some(serde_json::to_vec(e.inner()).unwrap()),
-                };
-                client.send(message);
+                if ns.owns(&e.mid) {
+                    let _ = live_tx.send(model::unpin_message(e.inner()));
+                }
             });
-        });
+        }
+        tokio::spawn(client.forward(
+            self.store.clone(),
+            self.journal.clone(),
+            ns,
+            filter,
+            from,
+            live_rx,
+        ));
 
         let chan_stream = Box::pin(ReceiverStream::new(rx));
         Ok(Response::new(chan_stream))
@@ -447,19 +1605,129 @@ impl ActsService for GrpcServer {
         &self,
         request: tonic::Request<Message>,
     ) -> Result<tonic::Response<Message>, tonic::Status> {
-        self.do_action(request.into_inner())
+        let identity = Identity::from_request(&request, &self.options.namespace)?;
+        let key = request
+            .metadata()
+            .get(idempotency::KEY_METADATA)
+            .and_then(|v| v.to_str().ok())
+            .map(|v| v.to_string());
+        self.dispatch(request.into_inner(), &identity, key).await
     }
 }
 
+impl GrpcServer {
+    /// run the action for the identity, the mutating actions are audited
+    /// and run at most once with the idempotency key
+    #[allow(clippy::result_large_err)]
+    pub async fn dispatch(
+        &self,
+        mut message: Message,
+        identity: &Identity,
+        key: Option<String>,
+    ) -> Result<Response<Message>, Status> {
+        if !audit::is_mutating(&message.name) {
+            return self.call(message, identity).await;
+        }
+
+        let name = message.name.clone();
+        let mut options = message
+            .data
+            .as_ref()
+            .and_then(|data| serde_json::from_slice::<acts::Vars>(data).ok())
+            .unwrap_or_default();
+        // the key is not an option of the action, such as the proc var
+        let key = match options.remove(idempotency::KEY_OPTION) {
+            Some(value) => {
+                message.data = Some(serde_json::to_vec(&options).unwrap());
+                value.as_str().map(|v| v.to_string()).or(key)
+            }
+            None => key,
+        };
+        let begin = Instant::now();
+        let ret = match key {
+            Some(key) => self.call_once(message, identity, &key).await,
+            None => self.call(message, identity).await,
+        };
+        let data = ret
+            .as_ref()
+            .ok()
+            .and_then(|resp| resp.get_ref().data.as_ref())
+            .and_then(|data| serde_json::from_slice::<serde_json::Value>(data).ok());
+        audit::log(
+            &self.store,
+            identity,
+            &name,
+            &options,
+            ret.as_ref().map(|_| data.as_ref()),
+            ret.as_ref()
+                .ok()
+                .and_then(|resp| resp.extensions().get::<audit::Change>()),
+            begin.elapsed(),
+        );
+        ret
+    }
+}
+
+/// fail the act action with the current task if the expected state or revision is stale
+#[allow(clippy::result_large_err)]
+fn check_revision(
+    executor: &acts::Executor,
+    pid: &str,
+    tid: &str,
+    options: &mut acts::Vars,
+) -> Result<(), Status> {
+    match revision::check(executor, pid, tid, options) {
+        Ok(None) => Ok(()),
+        Ok(Some(stale)) => Err(Status::failed_precondition(stale.to_string())),
+        Err(err) => Err(Status::not_found(err.to_string())),
+    }
+}
+
+#[allow(unused)]
 pub async fn start(addr: SocketAddr, opt: &acts::Config) -> Result<(), Box<dyn std::error::Error>> {
+    start_with(addr, opt, &ServerOptions::default()).await
+}
+
+pub async fn start_with(
+    addr: SocketAddr,
+    opt: &acts::Config,
+    options: &ServerOptions,
+) -> Result<(), Box<dyn std::error::Error>> {
     init_log(opt);
+    options.namespace.check()?;
 
     let mut builder = Builder::new();
-    builder.set_config(&opt);
+    builder.set_config(opt);
     let engine = Arc::new(builder.build());
-    let server = GrpcServer::new(&engine);
+    schema::register(&engine);
+    backend::migrate(opt)?;
+    let store = Arc::new(Store::new(&opt.data_dir)?);
+    let journal = cursor::run(&engine, &store, options.journal.clone())?;
+    let server = GrpcServer::new(&engine, &store, options).with_journal(journal);
     server.init().await;
-    let grpc = ActsServiceServer::new(server);
+    server.groups.run();
+    if let Some(bridge) = &options.bridge {
+        let broker = bridge::connect(bridge).await?;
+        bridge::run(&engine, &server, broker, bridge)?;
+    }
+    schedule::run(engine.clone(), store.clone());
+    model::run(&engine, &store);
+    dlq::run(&engine, &store, options.dlq.clone());
+    idempotency::run(&store);
+    webhook::run(
+        &engine,
+        store.clone(),
+        server.webhooks.clone(),
+        options.webhook.clone(),
+    );
+    if let Some(port) = options.trigger.port {
+        trigger::serve(
+            SocketAddr::new(addr.ip(), port),
+            engine.clone(),
+            store.clone(),
+        );
+    }
+    let grpc = compression::service(server, &options.compression);
 
     Server::builder().add_service(grpc).serve(addr).await?;
 
@@ -0,0 +1,159 @@
+use crate::{
+    store::{map_db_err, DbItem, Store},
+    utils,
+};
+use acts::Result;
+use ring::digest;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::{
+    collections::HashSet,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+/// the request option and metadata which carry the idempotency key
+pub const KEY_OPTION: &str = "idempotency_key";
+pub const KEY_METADATA: &str = "x-idempotency-key";
+
+/// the response metadata which is set when the stored response is returned
+pub const REPLAY_METADATA: &str = "x-idempotent-replay";
+
+/// the interval to remove the expired responses
+const PURGE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// the idempotency options in acts.conf
+#[derive(Debug, Clone)]
+pub struct IdempotencyOptions {
+    /// the seconds to remember the response of the key
+    pub window: u64,
+}
+
+impl Default for IdempotencyOptions {
+    fn default() -> Self {
+        Self { window: 86400 }
+    }
+}
+
+/// the remembered response of the mutating action which is sent with the idempotency key
+/// only the succeeded responses are remembered, so the failed requests can be retried
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct Idempotency {
+    /// '{namespace}/{key}'
+    pub id: String,
+    pub action: String,
+    /// the hex sha256 of the action and its options, the key cannot be reused by another request
+    pub hash: String,
+    pub data: Option<Value>,
+    pub create_time: i64,
+    pub expire_time: i64,
+}
+
+impl DbItem for Idempotency {
+    fn name() -> &'static str {
+        "idempotency"
+    }
+
+    fn id(&self) -> &str {
+        &self.id
+    }
+}
+
+/// the keys which are being processed, the retried request waits for nothing and fails fast
+#[derive(Debug, Default, Clone)]
+pub struct InFlight(Arc<Mutex<HashSet<String>>>);
+
+impl InFlight {
+    /// mark the key as processing, returns false if the key is already being processed
+    pub fn enter(&self, id: &str) -> bool {
+        self.0.lock().unwrap().insert(id.to_string())
+    }
+
+    pub fn leave(&self, id: &str) {
+        self.0.lock().unwrap().remove(id);
+    }
+}
+
+/// find the unexpired response of the key
+pub fn find(store: &Store, id: &str) -> Result<Option<Idempotency>> {
+    let items = store.collection::<Idempotency>()?;
+    if !items.exists(id)? {
+        return Ok(None);
+    }
+    let item = items.find(id)?;
+    if item.expire_time <= utils::time_millis() {
+        items.delete(id)?;
+        return Ok(None);
+    }
+    Ok(Some(item))
+}
+
+/// the hash of the request, the options are hashed in the sorted key order
+pub fn hash(action: &str, options: Option<&[u8]>) -> String {
+    let options = options
+        .and_then(|data| serde_json::from_slice::<Value>(data).ok())
+        .unwrap_or(Value::Null);
+    let text = format!("{action}\n{options}");
+    let hash = digest::digest(&digest::SHA256, text.as_bytes());
+    hash.as_ref().iter().map(|b| format!("{b:02x}")).collect()
+}
+
+/// remember the response of the key
+pub fn save(
+    store: &Store,
+    id: &str,
+    action: &str,
+    hash: &str,
+    data: Option<Value>,
+    options: &IdempotencyOptions,
+) -> Result<()> {
+    let now = utils::time_millis();
+    let item = Idempotency {
+        id: id.to_string(),
+        action: action.to_string(),
+        hash: hash.to_string(),
+        data,
+        create_time: now,
+        expire_time: now + options.window as i64 * 1000,
+    };
+    let items = store.collection::<Idempotency>()?;
+    if items.exists(id)? {
+        items.update(&item)?;
+    } else {
+        items.create(&item)?;
+    }
+    Ok(())
+}
+
+/// remove the responses which are expired at the time
+pub fn purge(store: &Store, now: i64) -> Result<usize> {
+    store.collection::<Idempotency>()?;
+    store
+        .connection()
+        .execute(
+            &format!(
+                "delete from {} where json_extract(data, '$.expire_time') <= ?1",
+                Idempotency::name()
+            ),
+            [now],
+        )
+        .map_err(map_db_err)
+}
+
+/// remove the expired responses in background
+pub fn run(store: &Arc<Store>) {
+    let store = store.clone();
+    tokio::spawn(async move {
+        loop {
+            tokio::time::sleep(PURGE_INTERVAL).await;
+            let store = store.clone();
+            let ret =
+                tokio::task::spawn_blocking(move || purge(&store, utils::time_millis())).await;
+            match ret {
+                Ok(Err(err)) => tracing::error!("idempotency: {err}"),
+                Err(err) => tracing::error!("idempotency: {err}"),
+                _ => {}
+            }
+        }
+    });
+}
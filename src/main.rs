@@ -1,17 +1,43 @@
 use acts::Config;
 use std::{fs, path::Path};
 
+mod archive;
+mod audit;
+mod backend;
+mod backup;
+mod bridge;
+mod bundle;
+mod compression;
 mod config;
+mod cursor;
+mod dlq;
+mod graph;
+mod group;
 mod grpc;
+mod idempotency;
+mod model;
+mod namespace;
+mod package;
+mod revision;
+mod schedule;
+mod schema;
+mod store;
 #[cfg(test)]
 mod tests;
+mod transform;
+mod tree;
+mod trigger;
 mod utils;
+mod validate;
+mod vars;
+mod webhook;
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let mut port = 10080;
     let mut options = Config::default();
-    if let Ok(conf_file) = fs::read_to_string(&Path::new("acts.conf")) {
+    let mut server = config::ServerOptions::default();
+    if let Ok(conf_file) = fs::read_to_string(Path::new("acts.conf")) {
         if let Ok(conf) = hocon::de::from_str::<config::Config>(&conf_file) {
             port = conf.port.unwrap_or(10080);
             options.data_dir = conf.data_dir.unwrap_or("data".to_string());
@@ -20,6 +46,127 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                 options.log_dir = log.dir.unwrap_or("log".to_string());
                 options.log_level = log.level.unwrap_or("INFO".to_string());
             }
+
+            if let Some(conf) = conf.namespace {
+                server.namespace = namespace::NamespaceOptions {
+                    tokens: conf.tokens.unwrap_or_default(),
+                    default: conf.default,
+                };
+            }
+
+            if let Some(conf) = conf.idempotency {
+                server.idempotency = idempotency::IdempotencyOptions {
+                    window: conf.window.unwrap_or(86400),
+                };
+            }
+
+            if let Some(conf) = conf.webhook {
+                let default = webhook::WebhookOptions::default();
+                server.webhook = webhook::WebhookOptions {
+                    retries: conf.retries.unwrap_or(default.retries),
+                    backoff: conf.backoff.unwrap_or(default.backoff),
+                    allow_hosts: conf.allow_hosts.unwrap_or(default.allow_hosts),
+                };
+            }
+
+            if let Some(conf) = conf.trigger {
+                server.trigger = trigger::TriggerOptions { port: conf.port };
+            }
+
+            if let Some(conf) = conf.bridge {
+                let default = bridge::BridgeOptions::default();
+                server.bridge = Some(bridge::BridgeOptions {
+                    kind: conf.kind.unwrap_or(default.kind),
+                    url: conf.url.unwrap_or(default.url),
+                    credentials: conf.credentials,
+                    tls: conf.tls.unwrap_or(default.tls),
+                    root_ca: conf.root_ca,
+                    token: conf.token,
+                    prefix: conf.prefix.unwrap_or(default.prefix),
+                    r#type: conf.r#type.unwrap_or(default.r#type),
+                    state: conf.state.unwrap_or(default.state),
+                    tag: conf.tag.unwrap_or(default.tag),
+                    key: conf.key.unwrap_or(default.key),
+                    commands: conf.commands.unwrap_or_default(),
+                });
+            }
+
+            if let Some(conf) = conf.journal {
+                server.journal = cursor::JournalOptions {
+                    retention: conf
+                        .retention
+                        .unwrap_or(cursor::JournalOptions::default().retention),
+                };
+            }
+
+            if let Some(conf) = conf.group {
+                server.group = group::GroupOptions {
+                    ack_timeout: conf
+                        .ack_timeout
+                        .unwrap_or(group::GroupOptions::default().ack_timeout),
+                };
+            }
+
+            if let Some(conf) = conf.dlq {
+                options.max_message_retry_times =
+                    conf.retries.unwrap_or(options.max_message_retry_times);
+                server.dlq = dlq::DlqOptions {
+                    retention: conf
+                        .retention
+                        .unwrap_or(dlq::DlqOptions::default().retention),
+                };
+            }
+
+            if let Some(conf) = conf.compression {
+                let default = compression::CompressionOptions::default();
+                server.compression = compression::CompressionOptions {
+                    encoding: match conf.encoding {
+                        Some(name) => compression::encoding(&name)?,
+                        None => default.encoding,
+                    },
+                    threshold: conf.threshold.unwrap_or(default.threshold),
+                };
+            }
+
+            if let Some(conf) = conf.backup {
+                server.backup = Some(backup::BackupOptions {
+                    dir: conf.dir.unwrap_or("backup".to_string()),
+                    interval: conf.interval.unwrap_or(86400),
+                    keep: conf.keep.unwrap_or(7),
+                });
+            }
+        }
+    }
+
+    // restore the snapshot before the engine loads the data
+    let args = std::env::args().collect::<Vec<_>>();
+    if let Some(pos) = args.iter().position(|arg| arg == "--restore") {
+        let path = args
+            .get(pos + 1)
+            .ok_or("--restore requires a snapshot path")?;
+        let snapshot = backup::restore(&options, path)?;
+        println!(
+            "restored the snapshot {} to {}",
+            snapshot.name, options.data_dir
+        );
+    }
+
+    // import the archive before the engine loads the data, the replace mode only runs here
+    if let Some(pos) = args.iter().position(|arg| arg == "--import") {
+        let path = args
+            .get(pos + 1)
+            .ok_or("--import requires an archive path")?;
+        let mode = if args.iter().any(|arg| arg == "--replace") {
+            "replace"
+        } else {
+            "merge"
+        };
+        let report = archive::import_offline(&options, path, mode)?;
+        for c in &report.collections {
+            println!(
+                "imported {}: total={} created={} updated={} removed={}",
+                c.name, c.total, c.created, c.updated, c.removed
+            );
         }
     }
 
@@ -29,8 +176,12 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         port
     );
 
+    if let Some(backup) = &server.backup {
+        backup::schedule(options.clone(), backup.clone());
+    }
+
     let addr = format!("0.0.0.0:{port}").parse().unwrap();
-    grpc::start(addr, &options).await?;
+    grpc::start_with(addr, &options, &server).await?;
 
     Ok(())
 }
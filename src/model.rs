@@ -0,0 +1,319 @@
+use crate::{
+    graph,
+    store::{DbItem, PageData, Store},
+    utils,
+};
+use acts::{Engine, Executor, ExecutorQuery, Message, ModelInfo, ProcInfo, Result, Vars, Workflow};
+use serde::{Deserialize, Serialize};
+use similar::TextDiff;
+use std::sync::{Arc, Mutex};
+
+/// the separator of the model id and the version in the engine model id of the pinned procs
+/// it is not allowed in the model ids, so the pinned model never conflicts with a deployed one
+pub const PIN_SEPARATOR: char = '@';
+
+/// one pinned start at a time, the pinned model is deployed to the engine until the proc is started
+static PIN_LOCK: Mutex<()> = Mutex::new(());
+
+/// a deployed version of the model
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct ModelVersion {
+    pub id: String,
+    pub mid: String,
+    pub ver: u32,
+    pub name: String,
+    pub size: u32,
+    pub data: String,
+    pub create_time: i64,
+}
+
+impl DbItem for ModelVersion {
+    fn name() -> &'static str {
+        "model_version"
+    }
+
+    fn id(&self) -> &str {
+        &self.id
+    }
+}
+
+/// the model version which the proc is pinned to when starting
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct ProcVersion {
+    /// the proc id
+    pub id: String,
+    pub mid: String,
+    pub ver: u32,
+    pub create_time: i64,
+}
+
+impl DbItem for ProcVersion {
+    fn name() -> &'static str {
+        "proc_version"
+    }
+
+    fn id(&self) -> &str {
+        &self.id
+    }
+}
+
+impl From<&ModelInfo> for ModelVersion {
+    fn from(m: &ModelInfo) -> Self {
+        Self {
+            id: version_id(&m.id, m.ver),
+            mid: m.id.clone(),
+            ver: m.ver,
+            name: m.name.clone(),
+            size: m.size,
+            data: m.data.clone(),
+            create_time: if m.update_time > 0 {
+                m.update_time
+            } else {
+                m.create_time
+            },
+        }
+    }
+}
+
+impl From<ModelVersion> for ModelInfo {
+    fn from(v: ModelVersion) -> Self {
+        Self {
+            id: v.mid,
+            name: v.name,
+            ver: v.ver,
+            size: v.size,
+            create_time: v.create_time,
+            update_time: 0,
+            data: v.data,
+        }
+    }
+}
+
+/// deploy the model and keep the deployed version in history
+pub fn deploy(executor: &Executor, store: &Store, model: &Workflow) -> Result<ModelVersion> {
+    executor.model().deploy(model)?;
+    let info = executor.model().get(&model.id, "text")?;
+    let version = ModelVersion::from(&info);
+    let versions = store.collection::<ModelVersion>()?;
+    if versions.exists(&version.id)? {
+        versions.update(&version)?;
+    } else {
+        versions.create(&version)?;
+    }
+    Ok(version)
+}
+
+/// deploy all of the models in the bundle after all of them are valid
+/// the validation runs before any deploying, so that an invalid model leaves the deployed ones unchanged
+/// if a model fails to deploy, the models of the bundle which are deployed are rolled back, the existing
+/// ones are redeployed with the previous version and the new ones are removed
+pub fn deploy_all(
+    executor: &Executor,
+    store: &Store,
+    models: &[Workflow],
+) -> Result<Vec<ModelVersion>> {
+    for model in models {
+        model.valid()?;
+    }
+    let mut versions = Vec::new();
+    let mut deployed = Vec::new();
+    for model in models {
+        let prev = executor.model().get(&model.id, "text").ok();
+        deployed.push((model.id.as_str(), prev));
+        match deploy(executor, store, model) {
+            Ok(version) => versions.push(version),
+            Err(err) => {
+                for (mid, prev) in deployed.into_iter().rev() {
+                    if let Err(err) = undeploy(executor, store, mid, prev) {
+                        tracing::error!("model: failed to roll back '{mid}': {err}");
+                    }
+                }
+                return Err(err);
+            }
+        }
+    }
+    Ok(versions)
+}
+
+/// list the versions of the model, the latest version comes first
+pub fn versions(
+    executor: &Executor,
+    store: &Store,
+    mid: &str,
+    offset: usize,
+    count: usize,
+) -> Result<PageData<ModelVersion>> {
+    backfill(executor, store, mid)?;
+    let query = ExecutorQuery::new()
+        .with_query("mid", mid)
+        .with_order("ver", true)
+        .with_offset(offset)
+        .with_count(count);
+    store.collection::<ModelVersion>()?.query(&query)
+}
+
+/// get the model by version, or the latest one if the version is not specified
+pub fn get(
+    executor: &Executor,
+    store: &Store,
+    mid: &str,
+    ver: Option<u32>,
+    fmt: &str,
+) -> Result<ModelInfo> {
+    let mut model = match ver {
+        Some(ver) => {
+            backfill(executor, store, mid)?;
+            store
+                .collection::<ModelVersion>()?
+                .find(&version_id(mid, ver))?
+                .into()
+        }
+        None => executor.model().get(mid, "text")?,
+    };
+    if fmt == "tree" {
+        model.data = Workflow::from_yml(&model.data)?.tree_output();
+    } else if graph::FORMATS.contains(&fmt) {
+        model.data = graph::render(&Workflow::from_yml(&model.data)?, fmt, None)?;
+    }
+    Ok(model)
+}
+
+/// get the model which the proc is started with, it is the pinned version or the latest one
+pub fn proc_model(executor: &Executor, store: &Store, proc: &ProcInfo) -> Result<Workflow> {
+    let ver = store
+        .collection::<ProcVersion>()?
+        .find(&proc.id)
+        .ok()
+        .map(|pinned| pinned.ver);
+    let model = get(executor, store, unpin(&proc.mid), ver, "text")?;
+    Workflow::from_yml(&model.data)
+}
+
+/// unified diff between two versions of the model
+/// the 'to' defaults to the latest version and the 'from' defaults to the previous one of 'to'
+pub fn diff(
+    executor: &Executor,
+    store: &Store,
+    mid: &str,
+    from: Option<u32>,
+    to: Option<u32>,
+) -> Result<String> {
+    let to = match to {
+        Some(ver) => ver,
+        None => executor.model().get(mid, "text")?.ver,
+    };
+    let from = from.unwrap_or(to.saturating_sub(1).max(1));
+    let old = get(executor, store, mid, Some(from), "text")?;
+    let new = get(executor, store, mid, Some(to), "text")?;
+
+    Ok(TextDiff::from_lines(&old.data, &new.data)
+        .unified_diff()
+        .header(&version_id(mid, from), &version_id(mid, to))
+        .to_string())
+}
+
+/// redeploy the old version as the latest version
+pub fn rollback(executor: &Executor, store: &Store, mid: &str, ver: u32) -> Result<ModelVersion> {
+    let model = get(executor, store, mid, Some(ver), "text")?;
+    let workflow = Workflow::from_yml(&model.data)?;
+    deploy(executor, store, &workflow)
+}
+
+/// start the proc with a specified version of the model
+/// the engine only starts the deployed models, so the version is deployed as '{mid}@{ver}' and removed
+/// after starting, the proc is shown with the model id and the pinned version is kept until the proc is done
+pub fn start(
+    executor: &Executor,
+    store: &Store,
+    mid: &str,
+    ver: Option<u32>,
+    options: &Vars,
+) -> Result<String> {
+    let Some(ver) = ver else {
+        return executor.proc().start(mid, options);
+    };
+
+    let model = get(executor, store, mid, Some(ver), "text")?;
+    let mut workflow = Workflow::from_yml(&model.data)?;
+    workflow.set_id(&format!("{mid}{PIN_SEPARATOR}{ver}"));
+    let pid = {
+        let _lock = PIN_LOCK.lock().unwrap();
+        executor.model().deploy(&workflow)?;
+        let ret = executor.proc().start(&workflow.id, options);
+        executor.model().rm(&workflow.id)?;
+        ret?
+    };
+    store.collection::<ProcVersion>()?.create(&ProcVersion {
+        id: pid.clone(),
+        mid: mid.to_string(),
+        ver,
+        create_time: utils::time_millis(),
+    })?;
+    Ok(pid)
+}
+
+/// the model id of the engine model id, which is '{mid}@{ver}' for the pinned procs
+pub fn unpin(mid: &str) -> &str {
+    mid.split_once(PIN_SEPARATOR).map_or(mid, |(mid, _)| mid)
+}
+
+/// the proc with the model id instead of the pinned one
+pub fn unpin_proc(mut proc: ProcInfo) -> ProcInfo {
+    proc.mid = unpin(&proc.mid).to_string();
+    proc
+}
+
+/// the engine message with the model id instead of the pinned one
+pub fn unpin_message(message: &Message) -> Message {
+    let mut message = message.clone();
+    message.mid = unpin(&message.mid).to_string();
+    message.model.id = unpin(&message.model.id).to_string();
+    message
+}
+
+/// remove the pinned versions of the procs which are done, the engine removes the procs too
+pub fn run(engine: &Arc<Engine>, store: &Arc<Store>) {
+    let unpin = |store: Arc<Store>| {
+        move |e: &acts::Event<acts::Message>| {
+            let ret = store
+                .collection::<ProcVersion>()
+                .and_then(|versions| versions.delete(&e.pid));
+            if let Err(err) = ret {
+                tracing::error!("model: {err}");
+            }
+        }
+    };
+    engine.channel().on_complete(unpin(store.clone()));
+    engine.channel().on_error(unpin(store.clone()));
+}
+
+/// remove the model and all of its versions
+pub fn rm(executor: &Executor, store: &Store, mid: &str) -> Result<bool> {
+    let ret = executor.model().rm(mid)?;
+    store.collection::<ModelVersion>()?.delete_by("mid", mid)?;
+    Ok(ret)
+}
+
+/// restore the model to the previous version, or remove it if it is not deployed before
+fn undeploy(executor: &Executor, store: &Store, mid: &str, prev: Option<ModelInfo>) -> Result<()> {
+    match prev {
+        Some(prev) => deploy(executor, store, &Workflow::from_yml(&prev.data)?).map(|_| ()),
+        None => rm(executor, store, mid).map(|_| ()),
+    }
+}
+
+/// keep the current version of the model which is deployed before the history is enabled
+fn backfill(executor: &Executor, store: &Store, mid: &str) -> Result<()> {
+    let Ok(info) = executor.model().get(mid, "text") else {
+        return Ok(());
+    };
+    let versions = store.collection::<ModelVersion>()?;
+    if !versions.exists(&version_id(mid, info.ver))? {
+        versions.create(&ModelVersion::from(&info))?;
+    }
+    Ok(())
+}
+
+fn version_id(mid: &str, ver: u32) -> String {
+    format!("{mid}:{ver}")
+}
@@ -0,0 +1,319 @@
+use crate::store::PageData;
+use acts::{Act, ActError, Executor, ExecutorQuery, Result, Step, Workflow};
+use serde_json::Value;
+use std::collections::HashMap;
+
+/// the separator between the namespace and the id in the engine
+pub const SEPARATOR: char = '/';
+
+/// the namespace which is mapped to all of the namespaces, such as the admin token
+pub const ROOT: &str = "*";
+
+/// the response keys which hold the scoped ids
+const SCOPED_KEYS: [&str; 5] = ["id", "mid", "pack", "key", "hook"];
+
+/// the namespace options in acts.conf
+#[derive(Debug, Default, Clone)]
+pub struct NamespaceOptions {
+    /// map the auth token to the namespace, the '*' namespace is the root
+    pub tokens: HashMap<String, String>,
+    /// the namespace of the requests without the auth token, they are rejected if it is not set
+    pub default: Option<String>,
+}
+
+impl NamespaceOptions {
+    /// the namespaces are isolated once the tokens or the default namespace are configured
+    pub fn enabled(&self) -> bool {
+        !self.tokens.is_empty() || self.default.is_some()
+    }
+
+    pub fn check(&self) -> Result<()> {
+        if let Some(name) = &self.default {
+            if Namespace::new(name)?.is_root() {
+                return Err(ActError::Action(
+                    "the default namespace cannot be the root namespace".to_string(),
+                ));
+            }
+        }
+        for name in self.tokens.values() {
+            Namespace::new(name)?;
+        }
+        Ok(())
+    }
+}
+
+/// the tenant of the request, the models and packages are saved with the '{namespace}/{id}' id
+/// and the procs, tasks and messages belong to the namespace of their models
+/// the root namespace has no prefix and can access all of the data
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Namespace(Option<String>);
+
+impl Namespace {
+    pub fn root() -> Self {
+        Self(None)
+    }
+
+    pub fn new(name: &str) -> Result<Self> {
+        if name == ROOT {
+            return Ok(Self::root());
+        }
+        let valid = !name.is_empty()
+            && name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+        if !valid {
+            return Err(ActError::Action(format!(
+                "invalid namespace '{name}', it should only contain letters, digits, '-' and '_'"
+            )));
+        }
+        Ok(Self(Some(name.to_string())))
+    }
+
+    /// the namespace of the engine id, such as the scoped model id
+    pub fn of(id: &str) -> Self {
+        match id.split_once(SEPARATOR) {
+            Some((ns, _)) => Self(Some(ns.to_string())),
+            None => Self::root(),
+        }
+    }
+
+    pub fn is_root(&self) -> bool {
+        self.0.is_none()
+    }
+
+    /// the engine id of the id in namespace
+    pub fn scope(&self, id: &str) -> String {
+        match &self.0 {
+            Some(ns) => format!("{ns}{SEPARATOR}{id}"),
+            None => id.to_string(),
+        }
+    }
+
+    /// the id in namespace of the engine id, or None if the id is not in the namespace
+    pub fn unscope<'a>(&self, id: &'a str) -> Option<&'a str> {
+        match &self.0 {
+            Some(ns) => id
+                .strip_prefix(ns.as_str())
+                .and_then(|id| id.strip_prefix(SEPARATOR)),
+            None => Some(id),
+        }
+    }
+
+    pub fn owns(&self, id: &str) -> bool {
+        self.unscope(id).is_some()
+    }
+
+    /// scope the model id and the model or package keys which are referenced by the acts
+    pub fn scope_workflow(&self, workflow: &mut Workflow) {
+        if self.is_root() {
+            return;
+        }
+        workflow.id = self.scope(&workflow.id);
+        visit_workflow(workflow, &mut |act| self.scope_act(act));
+    }
+
+    pub fn unscope_workflow(&self, workflow: &mut Workflow) {
+        let mut unscope = |act: &mut Act| {
+            if is_scoped_act(act) {
+                if let Some(key) = self.unscope(&act.key) {
+                    act.key = key.to_string();
+                }
+            }
+        };
+        if let Some(id) = self.unscope(&workflow.id) {
+            workflow.id = id.to_string();
+        }
+        visit_workflow(workflow, &mut unscope);
+    }
+
+    /// strip the namespace from the response data, including the model text
+    pub fn unscope_value(&self, value: &mut Value) {
+        if self.is_root() {
+            return;
+        }
+        match value {
+            Value::Object(map) => {
+                for (key, value) in map.iter_mut() {
+                    match value {
+                        Value::String(text) if SCOPED_KEYS.contains(&key.as_str()) => {
+                            if let Some(id) = self.unscope(text) {
+                                *text = id.to_string();
+                            }
+                        }
+                        Value::String(text) if key == "data" => {
+                            if let Some(model) = self.unscope_model(text) {
+                                *text = model;
+                            }
+                        }
+                        _ => self.unscope_value(value),
+                    }
+                }
+            }
+            Value::Array(items) => {
+                for item in items {
+                    self.unscope_value(item);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// strip the namespace from the message, such as the error message of the action
+    pub fn unscope_text(&self, text: &str) -> String {
+        match &self.0 {
+            Some(ns) => text.replace(&format!("{ns}{SEPARATOR}"), ""),
+            None => text.to_string(),
+        }
+    }
+
+    fn scope_act(&self, act: &mut Act) {
+        if is_scoped_act(act) && !act.key.is_empty() {
+            act.key = self.scope(&act.key);
+        }
+    }
+
+    fn unscope_model(&self, text: &str) -> Option<String> {
+        let mut workflow = Workflow::from_yml(text).ok()?;
+        if workflow.id.is_empty() || !self.owns(&workflow.id) {
+            return None;
+        }
+        self.unscope_workflow(&mut workflow);
+        workflow.to_yml().ok()
+    }
+}
+
+impl std::fmt::Display for Namespace {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.0.as_deref().unwrap_or(ROOT))
+    }
+}
+
+/// cache the namespace of the procs, the proc belongs to the namespace of its model
+pub struct Owners<'a> {
+    executor: &'a Executor,
+    ns: &'a Namespace,
+    procs: HashMap<String, bool>,
+}
+
+impl<'a> Owners<'a> {
+    pub fn new(executor: &'a Executor, ns: &'a Namespace) -> Self {
+        Self {
+            executor,
+            ns,
+            procs: HashMap::new(),
+        }
+    }
+
+    pub fn owns_proc(&mut self, pid: &str) -> bool {
+        if self.ns.is_root() {
+            return true;
+        }
+        if let Some(owned) = self.procs.get(pid) {
+            return *owned;
+        }
+        let owned = self
+            .executor
+            .proc()
+            .get(pid)
+            .is_ok_and(|proc| self.ns.owns(&proc.mid));
+        self.procs.insert(pid.to_string(), owned);
+        owned
+    }
+
+    pub fn owns_message(&mut self, id: &str) -> bool {
+        if self.ns.is_root() {
+            return true;
+        }
+        match self.executor.msg().get(id) {
+            Ok(message) => self.owns_proc(&message.pid),
+            Err(_) => false,
+        }
+    }
+}
+
+/// list the rows which are owned by the namespace
+/// the engine query only supports the equal conditions, so the rows are filtered and paged here
+pub fn list<T>(
+    query: &ExecutorQuery,
+    fetch: impl Fn(&ExecutorQuery) -> Result<(Vec<T>, usize)>,
+    mut owns: impl FnMut(&T) -> bool,
+) -> Result<PageData<T>> {
+    const BATCH: usize = 500;
+
+    let mut rows = Vec::new();
+    let mut offset = 0;
+    loop {
+        let batch = ExecutorQuery {
+            offset,
+            count: BATCH,
+            query_by: query.query_by.clone(),
+            order_by: query.order_by.clone(),
+        };
+        let (page, count) = fetch(&batch)?;
+        let len = page.len();
+        rows.extend(page.into_iter().filter(|row| owns(row)));
+        offset += len;
+        if len == 0 || offset >= count {
+            break;
+        }
+    }
+
+    let count = rows.len();
+    let page_size = query.count.max(1);
+    Ok(PageData {
+        count,
+        page_num: query.offset / page_size + 1,
+        page_count: count.div_ceil(page_size),
+        page_size,
+        rows: rows
+            .into_iter()
+            .skip(query.offset)
+            .take(page_size)
+            .collect(),
+    })
+}
+
+/// the pack act refers to the package and the call act refers to the model
+fn is_scoped_act(act: &Act) -> bool {
+    act.act == "pack" || act.act == "call"
+}
+
+/// visit all of the acts in the workflow, including the nested ones
+pub fn visit_workflow(workflow: &mut Workflow, f: &mut dyn FnMut(&mut Act)) {
+    visit_acts(&mut workflow.setup, f);
+    visit_steps(&mut workflow.steps, f);
+}
+
+fn visit_steps(steps: &mut [Step], f: &mut dyn FnMut(&mut Act)) {
+    for step in steps {
+        visit_acts(&mut step.setup, f);
+        visit_acts(&mut step.acts, f);
+        for catch in &mut step.catches {
+            visit_acts(&mut catch.then, f);
+        }
+        for timeout in &mut step.timeout {
+            visit_acts(&mut timeout.then, f);
+        }
+        for branch in &mut step.branches {
+            visit_steps(&mut branch.steps, f);
+        }
+    }
+}
+
+fn visit_acts(acts: &mut [Act], f: &mut dyn FnMut(&mut Act)) {
+    for act in acts {
+        f(act);
+        visit_acts(&mut act.setup, f);
+        visit_acts(&mut act.then, f);
+        visit_acts(&mut act.r#else, f);
+        if let Some(next) = &mut act.next {
+            visit_acts(std::slice::from_mut(next.as_mut()), f);
+        }
+        for catch in &mut act.catches {
+            visit_acts(&mut catch.then, f);
+        }
+        for timeout in &mut act.timeout {
+            visit_acts(&mut timeout.then, f);
+        }
+    }
+}
@@ -0,0 +1,403 @@
+use crate::{
+    bundle, namespace,
+    schema::{self, SchemaViolation},
+    store::{DbItem, PageData, Store},
+    utils,
+};
+use acts::{data::Package, ActError, Executor, ExecutorQuery, Result, TaskInfo, Vars, Workflow};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use semver::Version;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{BTreeMap, HashSet};
+
+/// a published version of the package
+/// each version is also published to the engine as '{pack}@{version}', so that the models can pin it
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct PackageVersion {
+    pub id: String,
+    pub pack: String,
+    pub version: String,
+    pub name: String,
+    pub size: u32,
+    /// the script which runs in the pack act, it is bundled from the files if there are several files
+    pub body: String,
+    /// the entry file of the package which is published from several files
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub entry: String,
+    /// the base64 contents of the package files by the relative path, which are served for pulling the package back
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub files: BTreeMap<String, String>,
+    /// the json schemas of the inputs and outputs
+    #[serde(default)]
+    pub schema: PackageSchema,
+    /// the identity of the client which publishes the version
+    pub publisher: String,
+    pub create_time: i64,
+    /// whether the version is the current one of the package, it is not saved
+    #[serde(default)]
+    pub current: bool,
+}
+
+impl DbItem for PackageVersion {
+    fn name() -> &'static str {
+        "package_version"
+    }
+
+    fn id(&self) -> &str {
+        &self.id
+    }
+}
+
+/// the inputs are checked when the pack act runs and the outputs are checked on act:complete
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct PackageSchema {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub inputs: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub outputs: Option<Value>,
+}
+
+/// the package files with the entry, which are published instead of the single body
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct PackageFiles {
+    pub entry: String,
+    /// the base64 contents by the relative path, so that the files are not limited to text
+    pub files: BTreeMap<String, String>,
+}
+
+/// the current version of the package which is published to the engine as the package id
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct PackageHead {
+    pub id: String,
+    pub version: String,
+    pub update_time: i64,
+}
+
+impl DbItem for PackageHead {
+    fn name() -> &'static str {
+        "package_head"
+    }
+
+    fn id(&self) -> &str {
+        &self.id
+    }
+}
+
+/// publish a new version of the package and make it the current one
+/// the version defaults to the next patch version of the latest one, or 0.1.0 for the first publish
+pub fn publish(
+    executor: &Executor,
+    store: &Store,
+    pack: &Package,
+    version: Option<&str>,
+    schema: PackageSchema,
+    files: Option<PackageFiles>,
+    publisher: &str,
+) -> Result<PackageVersion> {
+    if pack.id.contains('@') {
+        return Err(ActError::Action(format!(
+            "package id '{}' cannot contain '@'",
+            pack.id
+        )));
+    }
+
+    let version = match version {
+        Some(version) => parse_version(version)?,
+        None => match sorted(store, &pack.id)?.first() {
+            Some(latest) => {
+                let latest = parse_version(&latest.version)?;
+                Version::new(latest.major, latest.minor, latest.patch + 1)
+            }
+            None => Version::new(0, 1, 0),
+        },
+    };
+
+    let versions = store.collection::<PackageVersion>()?;
+    let id = version_id(&pack.id, &version.to_string());
+    if versions.exists(&id)? {
+        return Err(ActError::Action(format!(
+            "package version '{id}' is already published"
+        )));
+    }
+
+    for schema in [&schema.inputs, &schema.outputs].into_iter().flatten() {
+        schema::verify(schema)?;
+    }
+
+    let files = files.unwrap_or_default();
+    let body = if files.files.is_empty() {
+        String::from_utf8_lossy(&pack.data).to_string()
+    } else {
+        let mut contents = BTreeMap::new();
+        for (path, content) in &files.files {
+            let content = STANDARD.decode(content).map_err(|err| {
+                ActError::Action(format!("invalid base64 content of file '{path}': {err}"))
+            })?;
+            contents.insert(path.clone(), content);
+        }
+        bundle::bundle(&files.entry, &contents)?
+    };
+    let item = PackageVersion {
+        id: id.clone(),
+        pack: pack.id.clone(),
+        version: version.to_string(),
+        name: pack.name.clone(),
+        size: body.len() as u32,
+        body,
+        entry: files.entry,
+        files: files.files,
+        schema,
+        publisher: publisher.to_string(),
+        create_time: utils::time_millis(),
+        current: true,
+    };
+    executor.pack().publish(&engine_package(&id, &item))?;
+    executor.pack().publish(&engine_package(&pack.id, &item))?;
+    versions.create(&item)?;
+    set_head(store, &pack.id, &item.version)?;
+
+    Ok(item)
+}
+
+/// list the versions of the package, the highest version comes first
+pub fn versions(
+    store: &Store,
+    id: &str,
+    offset: usize,
+    count: usize,
+) -> Result<PageData<PackageVersion>> {
+    let head = head(store, id)?;
+    let mut rows = sorted(store, id)?;
+    for row in rows.iter_mut() {
+        row.current = head.as_deref() == Some(row.version.as_str());
+    }
+
+    let total = rows.len();
+    let limit = count.max(1);
+    Ok(PageData {
+        count: total,
+        page_size: limit,
+        page_num: offset / limit + 1,
+        page_count: total.div_ceil(limit),
+        rows: rows.into_iter().skip(offset).take(limit).collect(),
+    })
+}
+
+/// republish the old version as the current one of the package
+pub fn rollback(
+    executor: &Executor,
+    store: &Store,
+    id: &str,
+    version: &str,
+) -> Result<PackageVersion> {
+    let version = parse_version(version)?.to_string();
+    let mut item = store
+        .collection::<PackageVersion>()?
+        .find(&version_id(id, &version))?;
+    executor.pack().publish(&engine_package(id, &item))?;
+    set_head(store, id, &version)?;
+    item.current = true;
+
+    Ok(item)
+}
+
+/// get the package by the reference, the version defaults to the current one
+/// the package which is published before the history is enabled has no version
+pub fn get(executor: &Executor, store: &Store, key: &str) -> Result<PackageVersion> {
+    if let Some(item) = find(store, key)? {
+        return Ok(item);
+    }
+
+    let info = executor.pack().get(key)?;
+    Ok(PackageVersion {
+        id: info.id.clone(),
+        pack: info.id,
+        name: info.name,
+        size: info.size,
+        body: info.data,
+        create_time: info.create_time,
+        current: true,
+        ..Default::default()
+    })
+}
+
+/// check the outputs when completing the pack act, which key references a package with the outputs schema
+pub fn check_outputs(
+    store: &Store,
+    task: &TaskInfo,
+    outputs: &Vars,
+) -> Result<Option<SchemaViolation>> {
+    if task.r#type != "pack" {
+        return Ok(None);
+    }
+    let Some(item) = find(store, &task.key)? else {
+        return Ok(None);
+    };
+    let Some(outputs_schema) = &item.schema.outputs else {
+        return Ok(None);
+    };
+
+    let mut outputs = outputs.clone();
+    for key in ["pid", "tid"] {
+        outputs.remove(key);
+    }
+    let errors = schema::check(
+        &format!("{}#outputs", item.id),
+        outputs_schema,
+        &Value::from(outputs),
+    )?;
+    if errors.is_empty() {
+        return Ok(None);
+    }
+    Ok(Some(SchemaViolation {
+        ecode: schema::SCHEMA_ECODE.to_string(),
+        pack: task.key.clone(),
+        target: "outputs".to_string(),
+        errors,
+    }))
+}
+
+/// check if the package reference exists, the reference is either '{pack}' or '{pack}@{version}'
+pub fn exists(executor: &Executor, store: &Store, key: &str) -> Result<bool> {
+    match key.split_once('@') {
+        Some((id, version)) => {
+            let Ok(version) = Version::parse(version) else {
+                return Ok(false);
+            };
+            store
+                .collection::<PackageVersion>()?
+                .exists(&version_id(id, &version.to_string()))
+        }
+        None => Ok(executor.pack().get(key).is_ok()),
+    }
+}
+
+/// find the deployed models which reference the package by id or by any of its versions
+pub fn references(executor: &Executor, id: &str) -> Result<Vec<String>> {
+    let mut ret = Vec::new();
+    let mut offset = 0;
+    loop {
+        let page = executor
+            .model()
+            .list(&ExecutorQuery::new().with_offset(offset).with_count(100))?;
+        for model in &page.rows {
+            let Ok(mut workflow) = Workflow::from_yml(&model.data) else {
+                continue;
+            };
+            let mut keys = HashSet::new();
+            namespace::visit_workflow(&mut workflow, &mut |act| {
+                if act.act == "pack" {
+                    keys.insert(act.key.clone());
+                }
+            });
+            if keys
+                .iter()
+                .any(|key| key == id || key.split_once('@').is_some_and(|(pack, _)| pack == id))
+            {
+                ret.push(model.id.clone());
+            }
+        }
+
+        offset += page.rows.len();
+        if page.rows.is_empty() || offset >= page.count {
+            break;
+        }
+    }
+
+    Ok(ret)
+}
+
+/// remove the package with all of its versions
+pub fn rm(executor: &Executor, store: &Store, id: &str) -> Result<bool> {
+    let versions = store.collection::<PackageVersion>()?;
+    for item in versions.find_by("pack", id)? {
+        executor.pack().rm(&item.id)?;
+    }
+    let ret = executor.pack().rm(id)?;
+    versions.delete_by("pack", id)?;
+    store.collection::<PackageHead>()?.delete_by("id", id)?;
+    schema::forget(id);
+
+    Ok(ret)
+}
+
+fn sorted(store: &Store, id: &str) -> Result<Vec<PackageVersion>> {
+    let mut rows: Vec<(Version, PackageVersion)> = store
+        .collection::<PackageVersion>()?
+        .find_by("pack", id)?
+        .into_iter()
+        .filter_map(|item| Some((Version::parse(&item.version).ok()?, item)))
+        .collect();
+    rows.sort_by(|a, b| b.0.cmp(&a.0));
+    Ok(rows.into_iter().map(|(_, item)| item).collect())
+}
+
+/// find the version by '{pack}@{version}' or the current version by '{pack}'
+fn find(store: &Store, key: &str) -> Result<Option<PackageVersion>> {
+    let (id, version) = match key.split_once('@') {
+        Some((id, version)) => match Version::parse(version) {
+            Ok(version) => (id, version.to_string()),
+            Err(_) => return Ok(None),
+        },
+        None => match head(store, key)? {
+            Some(version) => (key, version),
+            None => return Ok(None),
+        },
+    };
+
+    let versions = store.collection::<PackageVersion>()?;
+    let id = version_id(id, &version);
+    if !versions.exists(&id)? {
+        return Ok(None);
+    }
+    let mut item = versions.find(&id)?;
+    item.current = head(store, &item.pack)?.as_deref() == Some(item.version.as_str());
+    Ok(Some(item))
+}
+
+/// the package in engine, the body is wrapped to check the inputs if the schema is declared
+fn engine_package(id: &str, item: &PackageVersion) -> Package {
+    let body = match &item.schema.inputs {
+        Some(inputs) => schema::wrap(&item.body, &item.id, inputs),
+        None => item.body.clone(),
+    };
+    Package {
+        id: id.to_string(),
+        name: item.name.clone(),
+        data: body.into_bytes(),
+        ..Default::default()
+    }
+}
+
+fn head(store: &Store, id: &str) -> Result<Option<String>> {
+    let heads = store.collection::<PackageHead>()?;
+    if !heads.exists(id)? {
+        return Ok(None);
+    }
+    Ok(Some(heads.find(id)?.version))
+}
+
+fn set_head(store: &Store, id: &str, version: &str) -> Result<()> {
+    let heads = store.collection::<PackageHead>()?;
+    let head = PackageHead {
+        id: id.to_string(),
+        version: version.to_string(),
+        update_time: utils::time_millis(),
+    };
+    if heads.exists(id)? {
+        heads.update(&head)?;
+    } else {
+        heads.create(&head)?;
+    }
+    Ok(())
+}
+
+fn parse_version(version: &str) -> Result<Version> {
+    Version::parse(version)
+        .map_err(|err| ActError::Action(format!("invalid package version '{version}': {err}")))
+}
+
+fn version_id(id: &str, version: &str) -> String {
+    format!("{id}@{version}")
+}
@@ -0,0 +1,133 @@
+use acts::{Executor, Result, TaskInfo, Vars};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    sync::{Arc, Mutex},
+};
+use tokio::sync::OwnedMutexGuard;
+
+/// the act options which carry the expected task, they are removed before doing the act
+pub const STATE_OPTION: &str = "expected_state";
+pub const REVISION_OPTION: &str = "expected_revision";
+
+/// the error code of the stale updates
+pub const STALE_ECODE: &str = "E_STALE";
+
+/// the locks of the procs which have the act actions in progress
+static LOCKS: Mutex<BTreeMap<String, Arc<tokio::sync::Mutex<()>>>> = Mutex::new(BTreeMap::new());
+
+/// the task with its revision, the revision changes whenever the task state or data changes
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TaskRevision {
+    #[serde(flatten)]
+    pub task: TaskInfo,
+    pub revision: String,
+}
+
+impl From<TaskInfo> for TaskRevision {
+    fn from(task: TaskInfo) -> Self {
+        Self {
+            revision: revision(&task),
+            task,
+        }
+    }
+}
+
+/// the current task which is returned when the expectation fails
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Stale {
+    pub ecode: String,
+    pub message: String,
+    pub state: String,
+    pub revision: String,
+}
+
+impl std::fmt::Display for Stale {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&serde_json::to_string(self).unwrap())
+    }
+}
+
+/// the lock of the proc which is released when dropped
+pub struct ProcLock {
+    pid: String,
+    guard: Option<OwnedMutexGuard<()>>,
+}
+
+impl Drop for ProcLock {
+    fn drop(&mut self) {
+        let mut locks = LOCKS.lock().unwrap_or_else(|err| err.into_inner());
+        self.guard.take();
+        // remove the lock when no other action holds or waits for it
+        if locks
+            .get(&self.pid)
+            .is_some_and(|lock| Arc::strong_count(lock) == 1)
+        {
+            locks.remove(&self.pid);
+        }
+    }
+}
+
+/// serialize the act actions by the proc, so the task cannot change between the expectation check and the
+/// action, the waiting action yields to the runtime instead of blocking its thread
+pub async fn lock(pid: &str) -> ProcLock {
+    let lock = LOCKS
+        .lock()
+        .unwrap_or_else(|err| err.into_inner())
+        .entry(pid.to_string())
+        .or_default()
+        .clone();
+    ProcLock {
+        pid: pid.to_string(),
+        guard: Some(lock.lock_owned().await),
+    }
+}
+
+pub fn get(executor: &Executor, pid: &str, tid: &str) -> Result<TaskRevision> {
+    executor.task().get(pid, tid).map(TaskRevision::from)
+}
+
+/// take the expectation out of the options and check it with the current task
+/// it should be called with the lock held
+pub fn check(
+    executor: &Executor,
+    pid: &str,
+    tid: &str,
+    options: &mut Vars,
+) -> Result<Option<Stale>> {
+    let state = options.remove(STATE_OPTION);
+    let rev = options.remove(REVISION_OPTION);
+    if state.is_none() && rev.is_none() {
+        return Ok(None);
+    }
+
+    let current = get(executor, pid, tid)?;
+    let stale_state = state
+        .as_ref()
+        .is_some_and(|state| state.as_str() != Some(current.task.state.as_str()));
+    let stale_rev = rev
+        .as_ref()
+        .is_some_and(|rev| rev.as_str() != Some(current.revision.as_str()));
+    if !stale_state && !stale_rev {
+        return Ok(None);
+    }
+    Ok(Some(Stale {
+        ecode: STALE_ECODE.to_string(),
+        message: format!("task '{tid}' has been updated, reload it and try again"),
+        state: current.task.state,
+        revision: current.revision,
+    }))
+}
+
+/// the fnv-1a hash of the mutable fields, it is stable across the server restarts
+fn revision(task: &TaskInfo) -> String {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    let end_time = task.end_time.to_string();
+    for part in [task.state.as_str(), task.data.as_str(), end_time.as_str()] {
+        for byte in part.bytes().chain(std::iter::once(0)) {
+            hash ^= byte as u64;
+            hash = hash.wrapping_mul(0x100000001b3);
+        }
+    }
+    format!("{hash:016x}")
+}
@@ -0,0 +1,138 @@
+use acts::{ActError, Result};
+use time::{Date, Duration, Month, OffsetDateTime, Time};
+
+const MACROS: [(&str, &str); 6] = [
+    ("@yearly", "0 0 1 1 *"),
+    ("@annually", "0 0 1 1 *"),
+    ("@monthly", "0 0 1 * *"),
+    ("@weekly", "0 0 * * 0"),
+    ("@daily", "0 0 * * *"),
+    ("@hourly", "0 * * * *"),
+];
+
+/// stop searching the next time if the expression never matches, such as '0 0 31 2 *'
+const MAX_STEPS: usize = 100_000;
+
+/// the 5 fields cron expression 'minute hour day-of-month month day-of-week' in UTC
+/// the fields support '*', lists, ranges and steps, such as '*/15 9-17 * * 1,3,5'
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cron {
+    minutes: u64,
+    hours: u64,
+    days: u64,
+    months: u64,
+    weekdays: u64,
+    /// the day matches either the day-of-month or the day-of-week if both are restricted
+    any_day: bool,
+    any_weekday: bool,
+}
+
+impl Cron {
+    pub fn parse(expr: &str) -> Result<Self> {
+        let expr = expr.trim();
+        let expr = MACROS
+            .iter()
+            .find(|(name, _)| *name == expr)
+            .map_or(expr, |(_, expr)| *expr);
+        let fields = expr.split_whitespace().collect::<Vec<_>>();
+        if fields.len() != 5 {
+            return Err(ActError::Action(format!(
+                "invalid cron '{expr}', it should have 5 fields: minute hour day-of-month month day-of-week"
+            )));
+        }
+
+        // the sunday is both 0 and 7 in the day-of-week
+        let mut weekdays = field(fields[4], 0, 7)?;
+        if weekdays & (1 << 7) != 0 {
+            weekdays |= 1;
+        }
+        Ok(Self {
+            minutes: field(fields[0], 0, 59)?,
+            hours: field(fields[1], 0, 23)?,
+            days: field(fields[2], 1, 31)?,
+            months: field(fields[3], 1, 12)?,
+            weekdays,
+            any_day: fields[2] == "*",
+            any_weekday: fields[4] == "*",
+        })
+    }
+
+    /// the first time in millis which is later than the time
+    pub fn next(&self, after: i64) -> Option<i64> {
+        let after = OffsetDateTime::from_unix_timestamp(after.div_euclid(1000)).ok()?;
+        let mut t = after.replace_second(0).ok()?.replace_nanosecond(0).ok()? + Duration::MINUTE;
+        for _ in 0..MAX_STEPS {
+            if !has(self.months, t.month() as u8) {
+                let (year, month) = match t.month() {
+                    Month::December => (t.year() + 1, Month::January),
+                    month => (t.year(), month.next()),
+                };
+                t = Date::from_calendar_date(year, month, 1)
+                    .ok()?
+                    .with_time(Time::MIDNIGHT)
+                    .assume_utc();
+                continue;
+            }
+            if !self.matches_day(t.date()) {
+                t = t.date().next_day()?.with_time(Time::MIDNIGHT).assume_utc();
+                continue;
+            }
+            if !has(self.hours, t.hour()) {
+                t = t.replace_minute(0).ok()? + Duration::HOUR;
+                continue;
+            }
+            if !has(self.minutes, t.minute()) {
+                t += Duration::MINUTE;
+                continue;
+            }
+            return Some(t.unix_timestamp() * 1000);
+        }
+        None
+    }
+
+    fn matches_day(&self, date: Date) -> bool {
+        let day = has(self.days, date.day());
+        let weekday = has(self.weekdays, date.weekday().number_days_from_sunday());
+        match (self.any_day, self.any_weekday) {
+            (false, false) => day || weekday,
+            _ => day && weekday,
+        }
+    }
+}
+
+fn has(bits: u64, value: u8) -> bool {
+    bits & (1 << value) != 0
+}
+
+/// parse the field to the bits of the matched values
+fn field(text: &str, min: u8, max: u8) -> Result<u64> {
+    let invalid = || ActError::Action(format!("invalid cron field '{text}'"));
+    let mut bits = 0u64;
+    for part in text.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => (range, step.parse::<u8>().map_err(|_| invalid())?),
+            None => (part, 1),
+        };
+        let (start, end) = match range {
+            "*" => (min, max),
+            _ => match range.split_once('-') {
+                Some((start, end)) => (
+                    start.parse::<u8>().map_err(|_| invalid())?,
+                    end.parse::<u8>().map_err(|_| invalid())?,
+                ),
+                None => {
+                    let value = range.parse::<u8>().map_err(|_| invalid())?;
+                    // 'n/step' means from n to the max
+                    (value, if step > 1 { max } else { value })
+                }
+            },
+        };
+        if step == 0 || start < min || end > max || start > end {
+            return Err(invalid());
+        }
+        for value in (start..=end).step_by(step as usize) {
+            bits |= 1 << value;
+        }
+    }
+    Ok(bits)
+}
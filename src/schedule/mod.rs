@@ -0,0 +1,280 @@
+mod cron;
+
+#[cfg(test)]
+mod tests;
+
+use crate::{
+    audit, model,
+    store::{DbItem, PageData, Store},
+    utils,
+};
+use acts::{ActError, Engine, Executor, ExecutorQuery, Result, Vars};
+use serde::{Deserialize, Serialize};
+use std::{
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+pub use cron::Cron;
+
+/// the policies for the runs which are missed, such as the server is down
+/// 'skip' skips the missed runs, 'once' runs once for all of them and 'all' runs each of them
+pub const CATCH_UP: [&str; 3] = ["skip", "once", "all"];
+
+pub const STATE_ACTIVE: &str = "active";
+pub const STATE_PAUSED: &str = "paused";
+pub const STATE_COMPLETED: &str = "completed";
+
+/// the interval to check the due schedules
+const TICK: Duration = Duration::from_secs(1);
+
+/// the run is missed if it is not fired in the grace millis
+const GRACE: i64 = 60_000;
+
+/// the max missed runs to catch up with the 'all' policy
+const MAX_CATCH_UP: usize = 100;
+
+/// serialize the updates of the schedules between the actions and the runner
+static LOCK: Mutex<()> = Mutex::new(());
+
+/// the schedule to start the proc with the cron expression or at the one-shot time
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Schedule {
+    pub id: String,
+    pub mid: String,
+    /// the cron expression, see Cron
+    pub cron: String,
+    /// the one-shot time in millis
+    pub at: i64,
+    /// the inputs of the started procs
+    pub vars: Vars,
+    pub catch_up: String,
+    /// one of 'active', 'paused' and 'completed'
+    pub state: String,
+    pub next_time: i64,
+    pub last_time: i64,
+    pub last_pid: String,
+    pub last_error: String,
+    pub runs: u64,
+    pub create_time: i64,
+}
+
+impl DbItem for Schedule {
+    fn name() -> &'static str {
+        "schedule"
+    }
+
+    fn id(&self) -> &str {
+        &self.id
+    }
+}
+
+/// the runs and the next time of the due schedule
+#[derive(Debug, Clone, PartialEq)]
+pub struct Due {
+    pub runs: usize,
+    pub next_time: i64,
+}
+
+pub fn create(store: &Store, schedule: &Schedule) -> Result<Schedule> {
+    if schedule.mid.is_empty() {
+        return Err(ActError::Action("schedule 'mid' is required".to_string()));
+    }
+    let now = utils::time_millis();
+    let next_time = match (schedule.cron.is_empty(), schedule.at) {
+        (false, 0) => Cron::parse(&schedule.cron)?
+            .next(now)
+            .ok_or(ActError::Action(format!(
+                "cron '{}' never runs",
+                schedule.cron
+            )))?,
+        (true, at) if at > 0 => at,
+        _ => {
+            return Err(ActError::Action(
+                "one of schedule 'cron' and 'at' is required".to_string(),
+            ))
+        }
+    };
+    let catch_up = match schedule.catch_up.as_str() {
+        "" => "once",
+        policy if CATCH_UP.contains(&policy) => policy,
+        policy => {
+            return Err(ActError::Action(format!(
+                "invalid catch up '{policy}', it should be one of {}",
+                CATCH_UP.join(", ")
+            )))
+        }
+    };
+
+    let schedule = Schedule {
+        id: if schedule.id.is_empty() {
+            utils::longid()
+        } else {
+            schedule.id.clone()
+        },
+        catch_up: catch_up.to_string(),
+        state: STATE_ACTIVE.to_string(),
+        next_time,
+        last_time: 0,
+        last_pid: String::new(),
+        last_error: String::new(),
+        runs: 0,
+        create_time: now,
+        ..schedule.clone()
+    };
+    let schedules = store.collection::<Schedule>()?;
+    let _lock = LOCK.lock().unwrap();
+    if schedules.exists(&schedule.id)? {
+        return Err(ActError::Action(format!(
+            "schedule '{}' already exists",
+            schedule.id
+        )));
+    }
+    schedules.create(&schedule)?;
+    Ok(schedule)
+}
+
+pub fn list(store: &Store, query: &ExecutorQuery) -> Result<PageData<Schedule>> {
+    store.collection::<Schedule>()?.query(query)
+}
+
+pub fn rm(store: &Store, id: &str) -> Result<bool> {
+    let _lock = LOCK.lock().unwrap();
+    store.collection::<Schedule>()?.delete(id)
+}
+
+/// pause or resume the schedule, the runs during the pause are not caught up
+pub fn pause(store: &Store, id: &str, paused: bool) -> Result<Schedule> {
+    let schedules = store.collection::<Schedule>()?;
+    let _lock = LOCK.lock().unwrap();
+    let mut schedule = schedules.find(id)?;
+    if schedule.state == STATE_COMPLETED {
+        return Err(ActError::Action(format!("schedule '{id}' is completed")));
+    }
+    if paused {
+        schedule.state = STATE_PAUSED.to_string();
+    } else if schedule.state == STATE_PAUSED {
+        schedule.state = STATE_ACTIVE.to_string();
+        if !schedule.cron.is_empty() {
+            schedule.next_time = Cron::parse(&schedule.cron)?
+                .next(utils::time_millis())
+                .unwrap_or_default();
+        }
+    }
+    schedules.update(&schedule)?;
+    Ok(schedule)
+}
+
+/// the runs of the schedule at the time by the catch up policy
+pub fn due(schedule: &Schedule, now: i64) -> Result<Due> {
+    if schedule.next_time == 0 || schedule.next_time > now {
+        return Ok(Due {
+            runs: 0,
+            next_time: schedule.next_time,
+        });
+    }
+
+    let (missed, latest, next_time) = if schedule.cron.is_empty() {
+        (1, schedule.next_time, 0)
+    } else {
+        let cron = Cron::parse(&schedule.cron)?;
+        let mut missed = 1;
+        let mut latest = schedule.next_time;
+        while let Some(next) = cron.next(latest) {
+            if next > now || missed > MAX_CATCH_UP {
+                break;
+            }
+            missed += 1;
+            latest = next;
+        }
+        (missed, latest, cron.next(now).unwrap_or_default())
+    };
+    let on_time = now - latest <= GRACE;
+    let runs = match schedule.catch_up.as_str() {
+        "skip" => usize::from(on_time),
+        "all" => missed.min(MAX_CATCH_UP),
+        _ => 1,
+    };
+    Ok(Due { runs, next_time })
+}
+
+/// start the procs of the due schedules
+pub fn tick(executor: &Executor, store: &Store, now: i64) -> Result<()> {
+    let schedules = store.collection::<Schedule>()?;
+    for schedule in schedules.find_by("state", STATE_ACTIVE)? {
+        let due = match due(&schedule, now) {
+            Ok(due) => due,
+            Err(err) => {
+                tracing::error!("schedule '{}': {err}", schedule.id);
+                continue;
+            }
+        };
+        if due.runs == 0 && due.next_time == schedule.next_time {
+            continue;
+        }
+
+        // move to the next time before starting, so the schedule never runs twice if the server crashes
+        let _lock = LOCK.lock().unwrap();
+        let Ok(mut schedule) = schedules.find(&schedule.id) else {
+            continue;
+        };
+        if schedule.state != STATE_ACTIVE {
+            continue;
+        }
+        schedule.next_time = due.next_time;
+        if due.next_time == 0 {
+            schedule.state = STATE_COMPLETED.to_string();
+        }
+        schedules.update(&schedule)?;
+
+        for _ in 0..due.runs {
+            let begin = Instant::now();
+            let ret = model::start(executor, store, &schedule.mid, None, &schedule.vars);
+            audit::log_start(
+                store,
+                &format!("schedule:{}", schedule.id),
+                &schedule.mid,
+                &schedule.vars,
+                ret.as_deref(),
+                begin.elapsed(),
+            );
+            match ret {
+                Ok(pid) => {
+                    schedule.last_pid = pid;
+                    schedule.last_error.clear();
+                }
+                Err(err) => schedule.last_error = err.to_string(),
+            }
+            schedule.runs += 1;
+            schedule.last_time = now;
+        }
+        schedules.update(&schedule)?;
+        tracing::info!(
+            "schedule: id={} runs={} next_time={}",
+            schedule.id,
+            due.runs,
+            schedule.next_time
+        );
+    }
+    Ok(())
+}
+
+/// run the schedules in background, the missed runs are caught up when the server starts
+pub fn run(engine: Arc<Engine>, store: Arc<Store>) {
+    tokio::spawn(async move {
+        loop {
+            let executor = engine.executor();
+            let store = store.clone();
+            let ret =
+                tokio::task::spawn_blocking(move || tick(&executor, &store, utils::time_millis()))
+                    .await;
+            match ret {
+                Ok(Err(err)) => tracing::error!("schedule: {err}"),
+                Err(err) => tracing::error!("schedule: {err}"),
+                _ => {}
+            }
+            tokio::time::sleep(TICK).await;
+        }
+    });
+}
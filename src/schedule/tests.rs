@@ -0,0 +1,165 @@
+use super::{create, due, pause, Cron, Due, Schedule, STATE_ACTIVE, STATE_PAUSED};
+use crate::store::Store;
+use time::macros::datetime;
+
+fn millis(t: time::OffsetDateTime) -> i64 {
+    t.unix_timestamp() * 1000
+}
+
+fn cron_schedule(cron: &str, catch_up: &str, next_time: i64) -> Schedule {
+    Schedule {
+        id: "s1".to_string(),
+        mid: "m1".to_string(),
+        cron: cron.to_string(),
+        catch_up: catch_up.to_string(),
+        state: STATE_ACTIVE.to_string(),
+        next_time,
+        ..Default::default()
+    }
+}
+
+#[test]
+fn schedule_cron_next() {
+    let now = millis(datetime!(2024-03-15 10:07:30 UTC));
+    let next = |expr: &str| Cron::parse(expr).unwrap().next(now).unwrap();
+
+    assert_eq!(next("* * * * *"), millis(datetime!(2024-03-15 10:08 UTC)));
+    assert_eq!(
+        next("*/15 * * * *"),
+        millis(datetime!(2024-03-15 10:15 UTC))
+    );
+    assert_eq!(next("30 2 * * *"), millis(datetime!(2024-03-16 02:30 UTC)));
+    assert_eq!(next("@monthly"), millis(datetime!(2024-04-01 00:00 UTC)));
+    assert_eq!(next("0 0 29 2 *"), millis(datetime!(2028-02-29 00:00 UTC)));
+    // 2024-03-17 is sunday, which is 0 or 7
+    assert_eq!(next("0 9 * * 7"), millis(datetime!(2024-03-17 09:00 UTC)));
+    assert_eq!(
+        next("0 9-17 * * 1-5"),
+        millis(datetime!(2024-03-15 11:00 UTC))
+    );
+    // the day matches either of the day-of-month and day-of-week
+    assert_eq!(next("0 0 20 * 6"), millis(datetime!(2024-03-16 00:00 UTC)));
+    assert_eq!(Cron::parse("0 0 31 2 *").unwrap().next(now), None);
+}
+
+#[test]
+fn schedule_cron_invalid() {
+    for expr in [
+        "",
+        "* * * *",
+        "60 * * * *",
+        "* 24 * * *",
+        "*/0 * * * *",
+        "a * * * *",
+        "5-1 * * * *",
+    ] {
+        assert!(Cron::parse(expr).is_err(), "{expr}");
+    }
+}
+
+#[test]
+fn schedule_due_on_time() {
+    let next_time = millis(datetime!(2024-03-15 10:00 UTC));
+    let schedule = cron_schedule("0 * * * *", "skip", next_time);
+    assert_eq!(
+        due(&schedule, next_time - 1000).unwrap(),
+        Due { runs: 0, next_time }
+    );
+    assert_eq!(
+        due(&schedule, next_time + 1000).unwrap(),
+        Due {
+            runs: 1,
+            next_time: millis(datetime!(2024-03-15 11:00 UTC))
+        }
+    );
+}
+
+#[test]
+fn schedule_due_catch_up() {
+    // the server is down from 10:00 to 13:30
+    let next_time = millis(datetime!(2024-03-15 10:00 UTC));
+    let now = millis(datetime!(2024-03-15 13:30 UTC));
+    let next = millis(datetime!(2024-03-15 14:00 UTC));
+    for (policy, runs) in [("skip", 0), ("once", 1), ("all", 4)] {
+        let schedule = cron_schedule("0 * * * *", policy, next_time);
+        assert_eq!(
+            due(&schedule, now).unwrap(),
+            Due {
+                runs,
+                next_time: next
+            },
+            "{policy}"
+        );
+    }
+
+    // the one-shot schedule is completed after the due time
+    let schedule = Schedule {
+        cron: String::new(),
+        at: next_time,
+        ..cron_schedule("", "skip", next_time)
+    };
+    assert_eq!(
+        due(&schedule, now).unwrap(),
+        Due {
+            runs: 0,
+            next_time: 0
+        }
+    );
+    let schedule = Schedule {
+        catch_up: "once".to_string(),
+        ..schedule
+    };
+    assert_eq!(
+        due(&schedule, now).unwrap(),
+        Due {
+            runs: 1,
+            next_time: 0
+        }
+    );
+}
+
+#[test]
+fn schedule_create_and_pause() {
+    let store = Store::memory();
+    let schedule = create(
+        &store,
+        &Schedule {
+            id: "s1".to_string(),
+            mid: "m1".to_string(),
+            cron: "@daily".to_string(),
+            ..Default::default()
+        },
+    )
+    .unwrap();
+    assert_eq!(schedule.state, STATE_ACTIVE);
+    assert_eq!(schedule.catch_up, "once");
+    assert!(schedule.next_time > 0);
+    assert!(create(&store, &schedule).is_err());
+
+    let schedule = pause(&store, "s1", true).unwrap();
+    assert_eq!(schedule.state, STATE_PAUSED);
+    let schedule = pause(&store, "s1", false).unwrap();
+    assert_eq!(schedule.state, STATE_ACTIVE);
+
+    let invalid = [
+        Schedule {
+            mid: "m1".to_string(),
+            ..Default::default()
+        },
+        Schedule {
+            mid: "m1".to_string(),
+            cron: "@daily".to_string(),
+            at: 1,
+            ..Default::default()
+        },
+        Schedule {
+            mid: "m1".to_string(),
+            cron: "@daily".to_string(),
+            catch_up: "twice".to_string(),
+            ..Default::default()
+        },
+    ];
+    for schedule in invalid {
+        assert!(create(&store, &schedule).is_err());
+    }
+}
@@ -0,0 +1,149 @@
+use acts::{ActError, ActModule, Engine, Result};
+use jsonschema::Validator;
+use rquickjs::Function;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::{
+    collections::BTreeMap,
+    sync::{Arc, Mutex},
+};
+
+/// the error code of the schema violations
+pub const SCHEMA_ECODE: &str = "E_SCHEMA";
+
+/// the compiled validators by the package version and the target, the published version never changes
+/// so each schema is compiled once until the package is removed
+static VALIDATORS: Mutex<BTreeMap<String, Arc<Validator>>> = Mutex::new(BTreeMap::new());
+
+/// the schema violation which is reported as the error message in json
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct SchemaViolation {
+    pub ecode: String,
+    /// the package key which declares the schema
+    pub pack: String,
+    /// one of 'inputs' and 'outputs'
+    pub target: String,
+    pub errors: Vec<SchemaError>,
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct SchemaError {
+    /// the json pointer of the value
+    pub path: String,
+    pub message: String,
+}
+
+impl std::fmt::Display for SchemaViolation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&serde_json::to_string(self).unwrap())
+    }
+}
+
+/// check the schema itself before publishing
+pub fn verify(schema: &Value) -> Result<()> {
+    jsonschema::draft7::new(schema).map(|_| ()).map_err(|err| {
+        ActError::Action(format!(
+            "invalid schema at '{}': {err}",
+            err.instance_path()
+        ))
+    })
+}
+
+/// check the value against the schema and return all of the errors
+/// the key is the package version with the target, which the compiled schema is cached by
+pub fn check(key: &str, schema: &Value, value: &Value) -> Result<Vec<SchemaError>> {
+    let validator = validator(key, schema)?;
+    Ok(validator
+        .iter_errors(value)
+        .map(|err| SchemaError {
+            path: err.instance_path().to_string(),
+            message: err.to_string(),
+        })
+        .collect())
+}
+
+/// drop the compiled schemas of the package, which is called when removing the package
+pub fn forget(pack: &str) {
+    let prefix = format!("{pack}@");
+    VALIDATORS
+        .lock()
+        .unwrap()
+        .retain(|key, _| !key.starts_with(&prefix));
+}
+
+/// register the checker to the engine, which the wrapped package body calls to check the inputs
+pub fn register(engine: &Engine) {
+    engine.extender().register_module(&SchemaModule);
+}
+
+/// wrap the package body to check the inputs before running
+pub fn wrap(body: &str, pack: &str, schema: &Value) -> String {
+    let violation = serde_json::to_string(&SchemaViolation {
+        ecode: SCHEMA_ECODE.to_string(),
+        pack: pack.to_string(),
+        target: "inputs".to_string(),
+        errors: Vec::new(),
+    })
+    .unwrap();
+    let key = serde_json::to_string(&format!("{pack}#inputs")).unwrap();
+    let schema = serde_json::to_string(&schema.to_string()).unwrap();
+    format!(
+        r#"(function () {{
+    let errors = JSON.parse({CHECKER}({key}, {schema}, JSON.stringify(act.inputs())));
+    if (errors.length > 0) {{
+        let violation = {violation};
+        violation.errors = errors;
+        act.fail("{SCHEMA_ECODE}", JSON.stringify(violation));
+        return;
+    }}
+    (function () {{
+{body}
+    }})();
+}})();
+"#
+    )
+}
+
+fn validator(key: &str, schema: &Value) -> Result<Arc<Validator>> {
+    if let Some(validator) = VALIDATORS.lock().unwrap().get(key) {
+        return Ok(validator.clone());
+    }
+
+    let validator = Arc::new(
+        jsonschema::draft7::new(schema)
+            .map_err(|err| ActError::Action(format!("invalid schema: {err}")))?,
+    );
+    VALIDATORS
+        .lock()
+        .unwrap()
+        .insert(key.to_string(), validator.clone());
+    Ok(validator)
+}
+
+/// the global function in the package script to check the value by the cached validator
+const CHECKER: &str = "__acts_schema_check";
+
+#[derive(Clone)]
+struct SchemaModule;
+
+impl ActModule for SchemaModule {
+    fn init(&self, ctx: &rquickjs::Ctx<'_>) -> Result<()> {
+        let check = Function::new(
+            ctx.clone(),
+            |key: String, schema: String, value: String| -> rquickjs::Result<String> {
+                let errors = serde_json::from_str(&schema)
+                    .map_err(ActError::from)
+                    .and_then(|schema| {
+                        let value = serde_json::from_str(&value)?;
+                        check(&key, &schema, &value)
+                    })
+                    .map_err(|err| {
+                        rquickjs::Error::new_from_js_message("schema", "errors", err.to_string())
+                    })?;
+                Ok(serde_json::to_string(&errors).unwrap())
+            },
+        )?;
+        ctx.globals().set(CHECKER, check)?;
+        Ok(())
+    }
+}
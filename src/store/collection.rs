@@ -0,0 +1,215 @@
+use super::{map_db_err, DbItem, PageData};
+use crate::utils;
+use acts::{ActError, ExecutorQuery, Result};
+use rusqlite::{params, params_from_iter, Connection, OptionalExtension};
+use std::{
+    marker::PhantomData,
+    sync::{Arc, Mutex},
+};
+
+pub struct Collection<T> {
+    conn: Arc<Mutex<Connection>>,
+    _item: PhantomData<T>,
+}
+
+impl<T: DbItem> Collection<T> {
+    pub(super) fn new(conn: &Arc<Mutex<Connection>>) -> Self {
+        Self {
+            conn: conn.clone(),
+            _item: PhantomData,
+        }
+    }
+
+    pub fn exists(&self, id: &str) -> Result<bool> {
+        let conn = self.conn.lock().unwrap();
+        let count = conn
+            .query_row(
+                &format!("select count(id) from {} where id = ?1", T::name()),
+                params![id],
+                |row| row.get::<usize, usize>(0),
+            )
+            .map_err(map_db_err)?;
+        Ok(count > 0)
+    }
+
+    pub fn find(&self, id: &str) -> Result<T> {
+        let conn = self.conn.lock().unwrap();
+        let data = conn
+            .query_row(
+                &format!("select data from {} where id = ?1", T::name()),
+                params![id],
+                |row| row.get::<usize, String>(0),
+            )
+            .optional()
+            .map_err(map_db_err)?
+            .ok_or(ActError::Store(format!(
+                "cannot find {} by id '{id}'",
+                T::name()
+            )))?;
+        Ok(serde_json::from_str(&data)?)
+    }
+
+    pub fn query(&self, q: &ExecutorQuery) -> Result<PageData<T>> {
+        let mut filter = String::new();
+        let mut values = Vec::new();
+        for (index, (key, value)) in q.query_by.iter().enumerate() {
+            filter.push_str(if index == 0 { " where " } else { " and " });
+            filter.push_str(&format!(
+                "cast(json_extract(data, '$.{}') as text) = ?",
+                field(key)?
+            ));
+            values.push(value.clone());
+        }
+
+        let mut order = String::new();
+        for (index, (key, rev)) in q.order_by.iter().enumerate() {
+            order.push_str(if index == 0 { " order by " } else { ", " });
+            order.push_str(&format!("json_extract(data, '$.{}')", field(key)?));
+            if *rev {
+                order.push_str(" desc");
+            }
+        }
+        if order.is_empty() {
+            order.push_str(" order by rowid");
+        }
+
+        let limit = q.count.max(1);
+        let conn = self.conn.lock().unwrap();
+        let count = conn
+            .query_row(
+                &format!("select count(id) from {}{filter}", T::name()),
+                params_from_iter(values.iter()),
+                |row| row.get::<usize, usize>(0),
+            )
+            .map_err(map_db_err)?;
+
+        let mut stmt = conn
+            .prepare(&format!(
+                "select data from {}{filter}{order} limit {limit} offset {}",
+                T::name(),
+                q.offset
+            ))
+            .map_err(map_db_err)?;
+        let mut rows = Vec::new();
+        for data in stmt
+            .query_map(params_from_iter(values.iter()), |row| {
+                row.get::<usize, String>(0)
+            })
+            .map_err(map_db_err)?
+        {
+            rows.push(serde_json::from_str(&data.map_err(map_db_err)?)?);
+        }
+
+        Ok(PageData {
+            count,
+            page_size: limit,
+            page_num: q.offset / limit + 1,
+            page_count: count.div_ceil(limit),
+            rows,
+        })
+    }
+
+    /// find all items in the collection
+    pub fn all(&self) -> Result<Vec<T>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn
+            .prepare(&format!("select data from {} order by rowid", T::name()))
+            .map_err(map_db_err)?;
+        let mut rows = Vec::new();
+        for data in stmt
+            .query_map([], |row| row.get::<usize, String>(0))
+            .map_err(map_db_err)?
+        {
+            rows.push(serde_json::from_str(&data.map_err(map_db_err)?)?);
+        }
+        Ok(rows)
+    }
+
+    /// find all items which the field value equals to the value
+    pub fn find_by(&self, key: &str, value: &str) -> Result<Vec<T>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn
+            .prepare(&format!(
+                "select data from {} where cast(json_extract(data, '$.{}') as text) = ?1 order by rowid",
+                T::name(),
+                field(key)?
+            ))
+            .map_err(map_db_err)?;
+        let mut rows = Vec::new();
+        for data in stmt
+            .query_map(params![value], |row| row.get::<usize, String>(0))
+            .map_err(map_db_err)?
+        {
+            rows.push(serde_json::from_str(&data.map_err(map_db_err)?)?);
+        }
+        Ok(rows)
+    }
+
+    pub fn create(&self, item: &T) -> Result<bool> {
+        let conn = self.conn.lock().unwrap();
+        let ret = conn
+            .execute(
+                &format!(
+                    "insert into {} (id, data, create_time) values (?1, ?2, ?3)",
+                    T::name()
+                ),
+                params![
+                    item.id(),
+                    serde_json::to_string(item)?,
+                    utils::time_millis()
+                ],
+            )
+            .map_err(map_db_err)?;
+        Ok(ret > 0)
+    }
+
+    pub fn update(&self, item: &T) -> Result<bool> {
+        let conn = self.conn.lock().unwrap();
+        let ret = conn
+            .execute(
+                &format!("update {} set data = ?2 where id = ?1", T::name()),
+                params![item.id(), serde_json::to_string(item)?],
+            )
+            .map_err(map_db_err)?;
+        Ok(ret > 0)
+    }
+
+    pub fn delete(&self, id: &str) -> Result<bool> {
+        let conn = self.conn.lock().unwrap();
+        let ret = conn
+            .execute(
+                &format!("delete from {} where id = ?1", T::name()),
+                params![id],
+            )
+            .map_err(map_db_err)?;
+        Ok(ret > 0)
+    }
+
+    /// delete all items which the field value equals to the value
+    pub fn delete_by(&self, key: &str, value: &str) -> Result<usize> {
+        let conn = self.conn.lock().unwrap();
+        let ret = conn
+            .execute(
+                &format!(
+                    "delete from {} where cast(json_extract(data, '$.{}') as text) = ?1",
+                    T::name(),
+                    field(key)?
+                ),
+                params![value],
+            )
+            .map_err(map_db_err)?;
+        Ok(ret)
+    }
+}
+
+/// only allow the simple field path to avoid injecting into the sql
+fn field(key: &str) -> Result<&str> {
+    if key.is_empty()
+        || !key
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.')
+    {
+        return Err(ActError::Store(format!("invalid query key '{key}'")));
+    }
+    Ok(key)
+}
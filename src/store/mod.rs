@@ -0,0 +1,92 @@
+mod collection;
+
+#[cfg(test)]
+mod tests;
+
+use acts::{ActError, Result};
+use rusqlite::Connection;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::{
+    collections::HashSet,
+    path::Path,
+    sync::{Arc, Mutex, MutexGuard},
+    time::Duration,
+};
+
+pub use collection::Collection;
+
+pub const DB_NAME: &str = "server.db";
+
+/// item saved in the server store
+pub trait DbItem: Serialize + DeserializeOwned {
+    /// collection (table) name
+    fn name() -> &'static str;
+    fn id(&self) -> &str;
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct PageData<T> {
+    pub count: usize,
+    pub page_num: usize,
+    pub page_count: usize,
+    pub page_size: usize,
+    pub rows: Vec<T>,
+}
+
+/// the store for the server owned data, such as audits
+/// it is a sqlite db in the data dir side by side with the engine db
+#[derive(Clone)]
+pub struct Store {
+    conn: Arc<Mutex<Connection>>,
+    tables: Arc<Mutex<HashSet<&'static str>>>,
+}
+
+impl Store {
+    pub fn new(data_dir: &str) -> Result<Self> {
+        std::fs::create_dir_all(data_dir)?;
+        let conn = Connection::open(Path::new(data_dir).join(DB_NAME)).map_err(map_db_err)?;
+        // wait for the archive import and the backup which lock the db
+        conn.busy_timeout(Duration::from_secs(5))
+            .map_err(map_db_err)?;
+        Ok(Self::create(conn))
+    }
+
+    #[allow(unused)]
+    pub fn memory() -> Self {
+        Self::create(Connection::open_in_memory().unwrap())
+    }
+
+    pub fn collection<T: DbItem>(&self) -> Result<Collection<T>> {
+        let mut tables = self.tables.lock().unwrap();
+        if !tables.contains(T::name()) {
+            let conn = self.conn.lock().unwrap();
+            conn.execute(
+                &format!(
+                    "create table if not exists {} (id VARCHAR PRIMARY KEY NOT NULL, data VARCHAR, create_time BIGINT)",
+                    T::name()
+                ),
+                [],
+            )
+            .map_err(map_db_err)?;
+            tables.insert(T::name());
+        }
+
+        Ok(Collection::new(&self.conn))
+    }
+
+    /// the raw connection for the operations across the collections, such as export and import
+    pub fn connection(&self) -> MutexGuard<'_, Connection> {
+        self.conn.lock().unwrap()
+    }
+
+    fn create(conn: Connection) -> Self {
+        Self {
+            conn: Arc::new(Mutex::new(conn)),
+            tables: Arc::new(Mutex::new(HashSet::new())),
+        }
+    }
+}
+
+pub fn map_db_err(err: impl std::error::Error) -> ActError {
+    ActError::Store(err.to_string())
+}
@@ -0,0 +1,96 @@
+use super::{DbItem, Store};
+use acts::ExecutorQuery;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+struct Item {
+    id: String,
+    name: String,
+    value: i64,
+}
+
+impl DbItem for Item {
+    fn name() -> &'static str {
+        "item"
+    }
+
+    fn id(&self) -> &str {
+        &self.id
+    }
+}
+
+fn item(id: &str, name: &str, value: i64) -> Item {
+    Item {
+        id: id.to_string(),
+        name: name.to_string(),
+        value,
+    }
+}
+
+#[test]
+fn store_collection_create() {
+    let store = Store::memory();
+    let items = store.collection::<Item>().unwrap();
+    items.create(&item("1", "a", 1)).unwrap();
+    assert!(items.exists("1").unwrap());
+    assert_eq!(items.find("1").unwrap(), item("1", "a", 1));
+}
+
+#[test]
+fn store_collection_update() {
+    let store = Store::memory();
+    let items = store.collection::<Item>().unwrap();
+    items.create(&item("1", "a", 1)).unwrap();
+    items.update(&item("1", "b", 2)).unwrap();
+    assert_eq!(items.find("1").unwrap(), item("1", "b", 2));
+}
+
+#[test]
+fn store_collection_delete() {
+    let store = Store::memory();
+    let items = store.collection::<Item>().unwrap();
+    items.create(&item("1", "a", 1)).unwrap();
+    assert!(items.delete("1").unwrap());
+    assert!(!items.exists("1").unwrap());
+    assert!(items.find("1").is_err());
+}
+
+#[test]
+fn store_collection_query() {
+    let store = Store::memory();
+    let items = store.collection::<Item>().unwrap();
+    for i in 0..5 {
+        items
+            .create(&item(
+                &i.to_string(),
+                if i % 2 == 0 { "even" } else { "odd" },
+                i,
+            ))
+            .unwrap();
+    }
+
+    let query = ExecutorQuery::new()
+        .with_query("name", "even")
+        .with_order("value", true)
+        .with_count(2);
+    let page = items.query(&query).unwrap();
+    assert_eq!(page.count, 3);
+    assert_eq!(page.page_count, 2);
+    assert_eq!(
+        page.rows.iter().map(|i| i.value).collect::<Vec<_>>(),
+        [4, 2]
+    );
+
+    let page = items
+        .query(&ExecutorQuery::new().with_query("value", "3").with_count(10))
+        .unwrap();
+    assert_eq!(page.rows, [item("3", "odd", 3)]);
+}
+
+#[test]
+fn store_collection_query_invalid_key() {
+    let store = Store::memory();
+    let items = store.collection::<Item>().unwrap();
+    let query = ExecutorQuery::new().with_query("name') or 1=1 --", "a");
+    assert!(items.query(&query).is_err());
+}
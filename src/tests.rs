@@ -1,11 +1,104 @@
-use crate::grpc;
+use crate::{
+    archive, backend, backup, config::ServerOptions, cursor, grpc, idempotency, namespace,
+    store::Store, tree::TaskTree, webhook,
+};
 use acts::Config;
-use acts_channel::{model::ModelInfo, ActsChannel, ActsOptions, Vars};
-use std::sync::{Arc, Mutex};
+use acts_channel::{
+    acts_service_client::ActsServiceClient,
+    model::{ModelInfo, PageData},
+    ActsChannel, ActsOptions, Message, MessageOptions, Vars,
+};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use serde_json::Value;
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use tokio_stream::StreamExt;
+use tonic::{Code, Request, Status};
+
+fn config(name: &str) -> Config {
+    let dir = std::env::temp_dir().join("acts-server-tests").join(name);
+    let _ = std::fs::remove_dir_all(&dir);
+    Config {
+        data_dir: dir.to_string_lossy().to_string(),
+        ..Default::default()
+    }
+}
+
+/// the tokens of the tenants, the 'admin' token is mapped to the root namespace
+fn tenants() -> namespace::NamespaceOptions {
+    namespace::NamespaceOptions {
+        tokens: [("team-a", "team-a"), ("team-b", "team-b"), ("admin", "*")]
+            .into_iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect(),
+        default: None,
+    }
+}
+
+const ADMIN: [(&str, &str); 1] = [("authorization", "Bearer admin")];
+
+async fn connect(port: u16) -> ActsChannel {
+    let url = format!("http://127.0.0.1:{port}");
+    // wait for the server to be ready
+    for _ in 0..100 {
+        if let Ok(client) = ActsChannel::connect(&url).await {
+            return client;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    panic!("failed to connect server {url}");
+}
+
+async fn serve(name: &str, port: u16) -> ActsChannel {
+    let options = config(name);
+    tokio::spawn(async move {
+        let addr = format!("127.0.0.1:{port}").parse().unwrap();
+        grpc::start(addr, &options).await.unwrap();
+    });
+
+    connect(port).await
+}
+
+async fn serve_with(name: &str, port: u16, server: ServerOptions) {
+    let options = config(name);
+    tokio::spawn(async move {
+        let addr = format!("127.0.0.1:{port}").parse().unwrap();
+        grpc::start_with(addr, &options, &server).await.unwrap();
+    });
+    connect(port).await;
+}
+
+/// send the action with the metadata, such as the namespace and the auth token
+async fn send_with(
+    port: u16,
+    metadata: &[(&'static str, &str)],
+    name: &str,
+    data: Vars,
+) -> Result<Value, Status> {
+    let mut client = ActsServiceClient::connect(format!("http://127.0.0.1:{port}"))
+        .await
+        .unwrap();
+    let mut request = Request::new(Message {
+        name: name.to_string(),
+        seq: acts_channel::create_seq(),
+        ack: None,
+        data: Some(data.to_bytes()),
+    });
+    for (key, value) in metadata {
+        request.metadata_mut().insert(*key, value.parse().unwrap());
+    }
+    let resp = client.send(request).await?.into_inner();
+    Ok(resp
+        .data
+        .map(|v| serde_json::from_slice(&v).unwrap())
+        .unwrap_or_default())
+}
 
 #[tokio::test]
 async fn grpc_start() {
-    let options = Config::default();
+    let options = config("grpc_start");
     let port = 10081;
 
     tokio::spawn(async move {
@@ -13,13 +106,12 @@ async fn grpc_start() {
         grpc::start(addr, &options).await.unwrap();
     });
 
-    let client = ActsChannel::connect(&format!("http://127.0.0.1:{port}")).await;
-    assert!(client.is_ok());
+    connect(port).await;
 }
 
 #[tokio::test]
 async fn grpc_action_ok() {
-    let options = Config::default();
+    let options = config("grpc_action_ok");
     let port = 10082;
 
     tokio::spawn(async move {
@@ -27,17 +119,17 @@ async fn grpc_action_ok() {
         grpc::start(addr, &options).await.unwrap();
     });
 
-    let mut client = ActsChannel::connect(&format!("http://127.0.0.1:{port}"))
-        .await
-        .unwrap();
+    let mut client = connect(port).await;
 
-    let ret = client.send::<ModelInfo>("models", Vars::new()).await;
+    let ret = client
+        .send::<PageData<ModelInfo>>("model:ls", Vars::new())
+        .await;
     assert!(ret.is_ok());
 }
 
 #[tokio::test]
 async fn grpc_action_err() {
-    let options = Config::default();
+    let options = config("grpc_action_err");
     let port = 10083;
 
     tokio::spawn(async move {
@@ -45,9 +137,7 @@ async fn grpc_action_err() {
         grpc::start(addr, &options).await.unwrap();
     });
 
-    let mut client = ActsChannel::connect(&format!("http://127.0.0.1:{port}"))
-        .await
-        .unwrap();
+    let mut client = connect(port).await;
 
     let ret = client.send::<()>("complete", Vars::new()).await;
     assert!(ret.is_err());
@@ -56,7 +146,7 @@ async fn grpc_action_err() {
 #[tokio::test]
 async fn grpc_message_all() {
     let messages = Arc::new(Mutex::new(Vec::new()));
-    let options = Config::default();
+    let options = config("grpc_message_all");
     let port = 10084;
 
     tokio::spawn(async move {
@@ -64,9 +154,7 @@ async fn grpc_message_all() {
         grpc::start(addr, &options).await.unwrap();
     });
 
-    let mut client = ActsChannel::connect(&format!("http://127.0.0.1:{port}"))
-        .await
-        .unwrap();
+    let mut client = connect(port).await;
 
     let m = messages.clone();
     client
@@ -85,14 +173,15 @@ async fn grpc_message_all() {
     "#;
     client.deploy(model, None).await.unwrap();
     client.start("m1", Vars::new()).await.unwrap();
-    std::thread::sleep(std::time::Duration::from_millis(10));
-    assert_eq!(messages.lock().unwrap().len(), 2);
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    // the engine emits the workflow completed message for both the root task and the proc
+    assert_eq!(messages.lock().unwrap().len(), 3);
 }
 
 #[tokio::test]
 async fn grpc_message_filter() {
     let messages = Arc::new(Mutex::new(Vec::new()));
-    let options = Config::default();
+    let options = config("grpc_message_filter");
     let port = 10085;
 
     tokio::spawn(async move {
@@ -100,9 +189,7 @@ async fn grpc_message_filter() {
         grpc::start(addr, &options).await.unwrap();
     });
 
-    let mut client = ActsChannel::connect(&format!("http://127.0.0.1:{port}"))
-        .await
-        .unwrap();
+    let mut client = connect(port).await;
 
     let m = messages.clone();
     client
@@ -124,6 +211,3796 @@ async fn grpc_message_filter() {
     "#;
     client.deploy(model, None).await.unwrap();
     client.start("m2", Vars::new()).await.unwrap();
-    std::thread::sleep(std::time::Duration::from_millis(10));
+    tokio::time::sleep(Duration::from_millis(100)).await;
     assert_eq!(messages.lock().unwrap().len(), 1);
 }
+
+#[tokio::test]
+async fn grpc_proc_tasks() {
+    let mut client = serve("grpc_proc_tasks", 10086).await;
+    let model = r#"
+    id: tasks
+    name: tasks
+    steps:
+      - id: step1
+        name: step 1
+        acts:
+          - act: irq
+            id: act1
+            key: act1
+    "#;
+    client.deploy(model, None).await.unwrap();
+    let pid = client
+        .start("tasks", Vars::new().with("a", 10))
+        .await
+        .unwrap()
+        .data
+        .unwrap();
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    let ret = client
+        .send::<Vec<TaskTree>>("proc:tasks", Vars::new().with("pid", &pid))
+        .await
+        .unwrap();
+    let roots = ret.data.unwrap();
+    assert_eq!(roots.len(), 1);
+
+    let root = &roots[0];
+    assert_eq!(root.r#type, "workflow");
+    assert_eq!(root.children.len(), 1);
+
+    let step = &root.children[0];
+    assert_eq!(step.nid, "step1");
+    assert_eq!(step.children.len(), 1);
+    assert_eq!(step.children[0].nid, "act1");
+    assert_eq!(step.children[0].state, "interrupted");
+}
+
+#[tokio::test]
+async fn grpc_proc_vars() {
+    let mut client = serve("grpc_proc_vars", 10087).await;
+    let model = r#"
+    id: vars
+    name: vars
+    steps:
+      - id: step1
+        acts:
+          - act: irq
+            id: act1
+            key: act1
+    "#;
+    client.deploy(model, None).await.unwrap();
+    let pid = client
+        .start(
+            "vars",
+            Vars::new()
+                .with("a", 1)
+                .with("obj", serde_json::json!({ "x": 1, "y": 2 })),
+        )
+        .await
+        .unwrap()
+        .data
+        .unwrap();
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    let patch = Vars::new()
+        .with("a", 2)
+        .with("obj", serde_json::json!({ "y": null, "z": 3 }));
+    let ret = client
+        .send::<serde_json::Value>(
+            "proc:vars:set",
+            Vars::new().with("pid", &pid).with("vars", patch),
+        )
+        .await;
+    assert!(ret.is_ok());
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    let vars = client
+        .send::<serde_json::Value>("proc:vars:get", Vars::new().with("pid", &pid))
+        .await
+        .unwrap()
+        .data
+        .unwrap();
+    assert_eq!(vars["a"], 2);
+    assert_eq!(vars["obj"], serde_json::json!({ "x": 1, "z": 3 }));
+
+    // one audit with the identity and the patched values
+    let audits = client
+        .send::<serde_json::Value>(
+            "audit:ls",
+            Vars::new().with("query_by", vec![("action", "proc:vars:set")]),
+        )
+        .await
+        .unwrap()
+        .data
+        .unwrap();
+    let rows = audits["rows"].as_array().unwrap();
+    assert_eq!(rows.len(), 1);
+    assert!(!rows[0]["peer"].as_str().unwrap().is_empty());
+    assert_eq!(
+        rows[0]["before"],
+        serde_json::json!({ "a": 1, "obj": { "x": 1, "y": 2 } })
+    );
+    assert_eq!(
+        rows[0]["after"],
+        serde_json::json!({ "a": 2, "obj": { "x": 1, "z": 3 } })
+    );
+
+    // the patch leaves no running act which keeps the step from completing
+    let proc = client
+        .send::<serde_json::Value>("proc:get", Vars::new().with("pid", &pid))
+        .await
+        .unwrap()
+        .data
+        .unwrap();
+    let tid = proc["tasks"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|t| t["key"] == "act1")
+        .map(|t| t["id"].as_str().unwrap().to_string())
+        .unwrap();
+    client
+        .send::<serde_json::Value>(
+            "act:complete",
+            Vars::new().with("pid", &pid).with("tid", &tid),
+        )
+        .await
+        .unwrap();
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    // the completed proc is removed from the engine
+    let ret = client
+        .send::<serde_json::Value>("proc:get", Vars::new().with("pid", &pid))
+        .await;
+    assert!(ret.is_err());
+}
+
+#[tokio::test]
+async fn grpc_proc_vars_reject_add_remove() {
+    let mut client = serve("grpc_proc_vars_reject_add_remove", 10088).await;
+    let model = r#"
+    id: vars
+    name: vars
+    steps:
+      - id: step1
+        acts:
+          - act: irq
+            key: act1
+    "#;
+    client.deploy(model, None).await.unwrap();
+    let pid = client
+        .start("vars", Vars::new().with("a", 1).with("b", 2))
+        .await
+        .unwrap()
+        .data
+        .unwrap();
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    let err = client
+        .send::<serde_json::Value>(
+            "proc:vars:set",
+            Vars::new()
+                .with("pid", &pid)
+                .with("vars", Vars::new().with("c", serde_json::json!({ "x": 1 }))),
+        )
+        .await
+        .unwrap_err();
+    assert!(err.message().contains("cannot add var 'c'"));
+
+    let err = client
+        .send::<serde_json::Value>(
+            "proc:vars:set",
+            Vars::new()
+                .with("pid", &pid)
+                .with("vars", Vars::new().with("a", serde_json::Value::Null)),
+        )
+        .await
+        .unwrap_err();
+    assert!(err.message().contains("cannot remove var 'a'"));
+
+    let vars = client
+        .send::<serde_json::Value>("proc:vars:get", Vars::new().with("pid", &pid))
+        .await
+        .unwrap()
+        .data
+        .unwrap();
+    assert_eq!(vars["a"], 1);
+    assert_eq!(vars["b"], 2);
+    assert!(vars.get("c").is_none());
+
+    // the built-in package is not published
+    let packages = client
+        .send::<serde_json::Value>("pack:ls", Vars::new())
+        .await
+        .unwrap()
+        .data
+        .unwrap();
+    assert!(!packages.to_string().contains("acts-server:vars"));
+}
+
+#[tokio::test]
+async fn grpc_task_vars() {
+    let mut client = serve("grpc_task_vars", 10089).await;
+    let model = r#"
+    id: vars
+    name: vars
+    steps:
+      - id: step1
+        acts:
+          - act: irq
+            id: act1
+            key: act1
+            inputs:
+              v: 10
+    "#;
+    client.deploy(model, None).await.unwrap();
+    let pid = client
+        .start("vars", Vars::new())
+        .await
+        .unwrap()
+        .data
+        .unwrap();
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    let tasks = client
+        .send::<Vec<TaskTree>>("proc:tasks", Vars::new().with("pid", &pid))
+        .await
+        .unwrap()
+        .data
+        .unwrap();
+    let act = &tasks[0].children[0].children[0];
+    let vars = client
+        .send::<serde_json::Value>(
+            "task:vars:get",
+            Vars::new().with("pid", &pid).with("tid", &act.id),
+        )
+        .await
+        .unwrap()
+        .data
+        .unwrap();
+    assert_eq!(vars["v"], 10);
+}
+
+#[tokio::test]
+async fn grpc_model_versions() {
+    let mut client = serve("grpc_model_versions", 10090).await;
+    let v1 = r#"
+    id: m1
+    name: model v1
+    steps:
+      - id: step1
+    "#;
+    let v2 = r#"
+    id: m1
+    name: model v2
+    steps:
+      - id: step1
+      - id: step2
+    "#;
+    client.deploy(v1, None).await.unwrap();
+    client.deploy(v2, None).await.unwrap();
+
+    let versions = client
+        .send::<serde_json::Value>("model:versions", Vars::new().with("id", "m1"))
+        .await
+        .unwrap()
+        .data
+        .unwrap();
+    assert_eq!(versions["count"], 2);
+    assert_eq!(versions["rows"][0]["ver"], 2);
+    assert_eq!(versions["rows"][1]["ver"], 1);
+
+    let model = client
+        .send::<ModelInfo>("model:get", Vars::new().with("id", "m1").with("ver", 1))
+        .await
+        .unwrap()
+        .data
+        .unwrap();
+    assert_eq!(model.ver, 1);
+    assert!(model.data.contains("model v1"));
+
+    let diff = client
+        .send::<String>("model:diff", Vars::new().with("id", "m1"))
+        .await
+        .unwrap()
+        .data
+        .unwrap();
+    assert!(diff.contains("-name: model v1"));
+    assert!(diff.contains("+name: model v2"));
+}
+
+#[tokio::test]
+async fn grpc_model_rollback() {
+    let mut client = serve("grpc_model_rollback", 10091).await;
+    client
+        .deploy("id: m1\nname: model v1\nsteps:\n  - id: step1", None)
+        .await
+        .unwrap();
+    client
+        .deploy("id: m1\nname: model v2\nsteps:\n  - id: step1", None)
+        .await
+        .unwrap();
+
+    let ret = client
+        .send::<serde_json::Value>(
+            "model:rollback",
+            Vars::new().with("id", "m1").with("ver", 1),
+        )
+        .await
+        .unwrap()
+        .data
+        .unwrap();
+    assert_eq!(ret["ver"], 3);
+
+    let model = client
+        .send::<ModelInfo>("model:get", Vars::new().with("id", "m1"))
+        .await
+        .unwrap()
+        .data
+        .unwrap();
+    assert_eq!(model.ver, 3);
+    assert_eq!(model.name, "model v1");
+}
+
+#[tokio::test]
+async fn grpc_model_rollback_not_exists() {
+    let mut client = serve("grpc_model_rollback_not_exists", 10092).await;
+    client
+        .deploy("id: m1\nname: model v1\nsteps:\n  - id: step1", None)
+        .await
+        .unwrap();
+
+    let ret = client
+        .send::<serde_json::Value>(
+            "model:rollback",
+            Vars::new().with("id", "m1").with("ver", 5),
+        )
+        .await;
+    assert!(ret.is_err());
+}
+
+#[tokio::test]
+async fn grpc_proc_start_pinned() {
+    let mut client = serve("grpc_proc_start_pinned", 10093).await;
+    client
+        .deploy("id: m1\nname: model v1\nsteps:\n  - id: step1\n    acts:\n      - act: irq\n        key: act1", None)
+        .await
+        .unwrap();
+    client
+        .deploy("id: m1\nname: model v2\nsteps:\n  - id: step2", None)
+        .await
+        .unwrap();
+
+    let pid = client
+        .start("m1", Vars::new().with("ver", 1))
+        .await
+        .unwrap()
+        .data
+        .unwrap();
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    let proc = client
+        .send::<serde_json::Value>("proc:get", Vars::new().with("pid", &pid))
+        .await
+        .unwrap()
+        .data
+        .unwrap();
+    assert_eq!(proc["mid"], "m1");
+    assert_eq!(proc["name"], "model v1");
+
+    // the pinned version is not deployed as a model
+    let models = client
+        .send::<PageData<ModelInfo>>("model:ls", Vars::new())
+        .await
+        .unwrap()
+        .data
+        .unwrap();
+    assert_eq!(models.count, 1);
+    assert_eq!(models.rows[0].ver, 2);
+    let procs = client
+        .send::<serde_json::Value>("proc:ls", Vars::new())
+        .await
+        .unwrap()
+        .data
+        .unwrap();
+    assert_eq!(procs["rows"][0]["mid"], "m1");
+
+    // the separator of the pinned model id is not allowed in the model ids
+    let ret = client
+        .deploy("id: m1@1\nname: model v1\nsteps:\n  - id: step1", None)
+        .await;
+    assert!(ret.is_err());
+
+    // the tasks and the graph use the pinned version
+    let tasks = client
+        .send::<Vec<TaskTree>>("proc:tasks", Vars::new().with("pid", &pid))
+        .await
+        .unwrap()
+        .data
+        .unwrap();
+    assert_eq!(tasks[0].children[0].nid, "step1");
+    let graph = client
+        .send::<String>(
+            "proc:get",
+            Vars::new().with("pid", &pid).with("fmt", "mermaid"),
+        )
+        .await
+        .unwrap()
+        .data
+        .unwrap();
+    assert!(graph.contains("step1"));
+    assert!(!graph.contains("step2"));
+}
+
+#[tokio::test]
+async fn grpc_model_validate() {
+    let mut client = serve("grpc_model_validate", 10094).await;
+    let model = r#"id: m1
+name: validate
+steps:
+  - id: step1
+    next: step3
+    acts:
+      - act: pack
+        key: not_exists
+  - id: step1
+    if: a >
+  - id: step2
+    acts:
+      - act: cmd
+        key: back
+        inputs:
+          to: step5
+"#;
+    let ret = client
+        .send::<Vec<serde_json::Value>>("model:validate", Vars::new().with("model", model))
+        .await
+        .unwrap()
+        .data
+        .unwrap();
+
+    let find = |message: &str| {
+        ret.iter()
+            .find(|d| d["message"].as_str().unwrap().contains(message))
+            .unwrap_or_else(|| panic!("cannot find diagnostic '{message}' in {ret:?}"))
+    };
+    let d = find("cannot find the target step 'step3'");
+    assert_eq!(
+        (d["level"].as_str(), d["line"].as_u64()),
+        (Some("error"), Some(5))
+    );
+    assert_eq!(d["column"], 5);
+    let d = find("cannot find package 'not_exists'");
+    assert_eq!(
+        (d["level"].as_str(), d["line"].as_u64()),
+        (Some("warning"), Some(8))
+    );
+    let d = find("duplicate id 'step1'");
+    assert_eq!(d["line"], 9);
+    let d = find("invalid expression 'a >'");
+    assert_eq!(d["line"], 10);
+    let d = find("cannot find the target step 'step5'");
+    assert_eq!(d["line"], 16);
+    assert_eq!(d["column"], 11);
+}
+
+#[tokio::test]
+async fn grpc_model_validate_yaml_error() {
+    let mut client = serve("grpc_model_validate_yaml_error", 10095).await;
+    let model = "id: m1\nsteps:\n  - id: step1\n   name: bad indent\n";
+    let ret = client
+        .send::<Vec<serde_json::Value>>("model:validate", Vars::new().with("model", model))
+        .await
+        .unwrap()
+        .data
+        .unwrap();
+    assert_eq!(ret.len(), 1);
+    assert_eq!(ret[0]["level"], "error");
+    assert_eq!(ret[0]["line"], 4);
+}
+
+#[tokio::test]
+async fn grpc_model_deploy_invalid() {
+    let mut client = serve("grpc_model_deploy_invalid", 10096).await;
+    let model = "id: m1\nsteps:\n  - id: step1\n    next: step2\n";
+    let ret = client.deploy(model, None).await;
+    assert!(ret.is_err());
+
+    let models = client
+        .send::<PageData<ModelInfo>>("model:ls", Vars::new())
+        .await
+        .unwrap()
+        .data
+        .unwrap();
+    assert_eq!(models.count, 0);
+}
+
+#[tokio::test]
+async fn grpc_model_deploy_json() {
+    let mut client = serve("grpc_model_deploy_json", 10097).await;
+    let model = r#"{ "id": "m1", "name": "json model", "steps": [{ "id": "step1" }] }"#;
+    let ret = client
+        .send::<bool>(
+            "model:deploy",
+            Vars::new().with("model", model).with("format", "json"),
+        )
+        .await;
+    assert!(ret.is_ok());
+
+    let model = client
+        .send::<ModelInfo>("model:get", Vars::new().with("id", "m1"))
+        .await
+        .unwrap()
+        .data
+        .unwrap();
+    assert_eq!(model.name, "json model");
+}
+
+#[tokio::test]
+async fn grpc_model_deploy_bundle() {
+    let mut client = serve("grpc_model_deploy_bundle", 10098).await;
+    let bundle = r#"id: m1
+steps:
+  - id: step1
+---
+id: m2
+steps:
+  - id: step1
+"#;
+    client.deploy(bundle, None).await.unwrap();
+
+    let models = client
+        .send::<PageData<ModelInfo>>("model:ls", Vars::new())
+        .await
+        .unwrap()
+        .data
+        .unwrap();
+    assert_eq!(models.count, 2);
+}
+
+#[tokio::test]
+async fn grpc_model_deploy_bundle_rollback() {
+    let mut client = serve("grpc_model_deploy_bundle_rollback", 10148).await;
+    client
+        .deploy("id: m1\nname: model v1\nsteps:\n  - id: step1\n", None)
+        .await
+        .unwrap();
+
+    // the history of the third model fails to save after the first two are deployed
+    let dir = std::env::temp_dir()
+        .join("acts-server-tests")
+        .join("grpc_model_deploy_bundle_rollback");
+    let store = Store::new(&dir.to_string_lossy()).unwrap();
+    store
+        .connection()
+        .execute_batch(
+            "create trigger fail_m3 before insert on model_version
+            when json_extract(new.data, '$.mid') = 'm3'
+            begin select raise(abort, 'cannot save m3'); end;",
+        )
+        .unwrap();
+
+    let bundle = r#"id: m1
+name: model v2
+steps:
+  - id: step1
+---
+id: m2
+steps:
+  - id: step1
+---
+id: m3
+steps:
+  - id: step1
+"#;
+    let err = client.deploy(bundle, None).await.unwrap_err();
+    assert!(err.message().contains("cannot save m3"));
+
+    // the deployed models of the bundle are rolled back
+    let models = client
+        .send::<PageData<ModelInfo>>("model:ls", Vars::new())
+        .await
+        .unwrap()
+        .data
+        .unwrap();
+    assert_eq!(
+        models
+            .rows
+            .iter()
+            .map(|m| m.id.as_str())
+            .collect::<Vec<_>>(),
+        vec!["m1"]
+    );
+    let model = client
+        .send::<ModelInfo>("model:get", Vars::new().with("id", "m1"))
+        .await
+        .unwrap()
+        .data
+        .unwrap();
+    assert_eq!(model.name, "model v1");
+}
+
+#[tokio::test]
+async fn grpc_model_deploy_bundle_markers() {
+    let mut client = serve("grpc_model_deploy_bundle_markers", 10140).await;
+    let bundle = r#"--- # the first model
+id: m1
+name: |
+  a
+  ---
+  b
+steps:
+  - id: step1
+...
+---
+id: m2
+steps:
+  - id: step1
+    next: step2
+"#;
+    let ret = client.deploy(bundle, None).await;
+    assert!(ret.unwrap_err().message().starts_with("14:5 "));
+
+    let bundle = bundle.replace("    next: step2\n", "");
+    client.deploy(&bundle, None).await.unwrap();
+    let model = client
+        .send::<ModelInfo>("model:get", Vars::new().with("id", "m1"))
+        .await
+        .unwrap()
+        .data
+        .unwrap();
+    assert!(model.data.contains("a\n  ---\n  b"));
+
+    let models = client
+        .send::<PageData<ModelInfo>>("model:ls", Vars::new())
+        .await
+        .unwrap()
+        .data
+        .unwrap();
+    assert_eq!(models.count, 2);
+}
+
+#[tokio::test]
+async fn grpc_model_deploy_bundle_atomic() {
+    let mut client = serve("grpc_model_deploy_bundle_atomic", 10099).await;
+    let bundle = r#"id: m1
+steps:
+  - id: step1
+---
+id: m2
+steps:
+  - id: step1
+    next: step2
+"#;
+    let ret = client.deploy(bundle, None).await;
+    assert!(ret.is_err());
+    assert!(ret.unwrap_err().message().starts_with("8:5 "));
+
+    let models = client
+        .send::<PageData<ModelInfo>>("model:ls", Vars::new())
+        .await
+        .unwrap()
+        .data
+        .unwrap();
+    assert_eq!(models.count, 0);
+}
+
+#[tokio::test]
+async fn grpc_model_validate_json() {
+    let mut client = serve("grpc_model_validate_json", 10100).await;
+    let model = r#"[
+  { "id": "m1", "steps": [{ "id": "step1" }] },
+  {
+    "id": "m1",
+    "steps": [
+      { "id": "step1", "next": "step3" }
+    ]
+  }
+]"#;
+    let ret = client
+        .send::<Vec<serde_json::Value>>(
+            "model:validate",
+            Vars::new().with("model", model).with("format", "json"),
+        )
+        .await
+        .unwrap()
+        .data
+        .unwrap();
+    let messages: Vec<&str> = ret.iter().map(|d| d["message"].as_str().unwrap()).collect();
+    assert!(messages.contains(&"duplicate model id 'm1' in bundle"));
+    assert!(messages.contains(&"cannot find the target step 'step3'"));
+    let d = ret
+        .iter()
+        .find(|d| d["message"] == "duplicate model id 'm1' in bundle")
+        .unwrap();
+    assert_eq!(d["line"], 4);
+}
+
+#[tokio::test]
+async fn grpc_model_get_mermaid() {
+    let mut client = serve("grpc_model_get_mermaid", 10101).await;
+    let model = r#"
+    id: m1
+    name: graph
+    steps:
+      - id: step1
+        name: "say \"hi\""
+        acts:
+          - act: irq
+            key: act1
+      - id: step2
+        branches:
+          - id: b1
+            if: $("a") > 1
+            steps:
+              - id: step3
+          - id: b2
+            else: true
+            next: step1
+    "#;
+    client.deploy(model, None).await.unwrap();
+    let model = client
+        .send::<ModelInfo>(
+            "model:get",
+            Vars::new().with("id", "m1").with("fmt", "mermaid"),
+        )
+        .await
+        .unwrap()
+        .data
+        .unwrap();
+    let text = model.data;
+    assert!(text.starts_with("flowchart TD\n"));
+    assert!(text.contains(r#"n0(["start"])"#));
+    assert!(text.contains(r#"n2["say #quot;hi#quot;"]"#));
+    assert!(text.contains(r#"n4[/"irq: act1"/]"#));
+    assert!(text.contains("n0 --> n2"));
+    assert!(text.contains("n2 -.- n4"));
+    assert!(text.contains(r#"n3 -->|"$(#quot;a#quot;) > 1"| n5"#));
+    assert!(text.contains("n6 --> n1"));
+    assert!(!text.contains("classDef"));
+}
+
+#[tokio::test]
+async fn grpc_model_get_dot() {
+    let mut client = serve("grpc_model_get_dot", 10102).await;
+    let model = r#"
+    id: m1
+    steps:
+      - id: step1
+      - id: step2
+    "#;
+    client.deploy(model, None).await.unwrap();
+    let model = client
+        .send::<ModelInfo>("model:get", Vars::new().with("id", "m1").with("fmt", "dot"))
+        .await
+        .unwrap()
+        .data
+        .unwrap();
+    let text = model.data;
+    assert!(text.starts_with("digraph \"m1\" {\n"));
+    assert!(text.contains("n2 [label=\"step1\", shape=box];"));
+    assert!(text.contains("n2 -> n3;"));
+    assert!(text.contains("n3 -> n1;"));
+}
+
+#[tokio::test]
+async fn grpc_proc_get_mermaid() {
+    let mut client = serve("grpc_proc_get_mermaid", 10103).await;
+    let model = r#"
+    id: m1
+    steps:
+      - id: step1
+      - id: step2
+        acts:
+          - id: act1
+            act: irq
+            key: act1
+      - id: step3
+    "#;
+    client.deploy(model, None).await.unwrap();
+    let pid = client.start("m1", Vars::new()).await.unwrap().data.unwrap();
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    let text = client
+        .send::<String>(
+            "proc:get",
+            Vars::new().with("pid", &pid).with("fmt", "mermaid"),
+        )
+        .await
+        .unwrap()
+        .data
+        .unwrap();
+    assert!(text.contains("classDef completed"));
+    assert!(text.contains("class n0 completed"));
+    assert!(text.contains("class n2 completed"));
+    assert!(text.contains("class n3 running"));
+    assert!(text.contains("class n5 running"));
+    assert!(!text.contains("class n4 "));
+    assert!(!text.contains("class n1 "));
+}
+
+#[tokio::test]
+async fn grpc_pack_versions() {
+    let mut client = serve("grpc_pack_versions", 10104).await;
+    for (version, body) in [
+        (None, "act.set('a', 1);"),
+        (Some("1.0.0"), "act.set('a', 2);"),
+    ] {
+        let mut options = Vars::new()
+            .with("id", "pack1")
+            .with("name", "pack 1")
+            .with("body", body);
+        if let Some(version) = version {
+            options.set("version", version);
+        }
+        client.send::<bool>("pack:publish", options).await.unwrap();
+    }
+
+    // the existing version cannot be published again
+    let ret = client
+        .send::<bool>(
+            "pack:publish",
+            Vars::new()
+                .with("id", "pack1")
+                .with("body", "")
+                .with("version", "1.0.0"),
+        )
+        .await;
+    assert!(ret.is_err());
+
+    let ret = client
+        .send::<serde_json::Value>("pack:versions", Vars::new().with("id", "pack1"))
+        .await
+        .unwrap()
+        .data
+        .unwrap();
+    assert_eq!(ret["count"], 2);
+    assert_eq!(ret["rows"][0]["version"], "1.0.0");
+    assert_eq!(ret["rows"][0]["current"], true);
+    assert_eq!(ret["rows"][1]["version"], "0.1.0");
+    assert!(!ret["rows"][1]["publisher"].as_str().unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn grpc_pack_rollback() {
+    let mut client = serve("grpc_pack_rollback", 10105).await;
+    for (version, body) in [("1.0.0", "act.set('a', 1);"), ("1.1.0", "act.set('a', 2);")] {
+        client
+            .send::<bool>(
+                "pack:publish",
+                Vars::new()
+                    .with("id", "pack1")
+                    .with("body", body)
+                    .with("version", version),
+            )
+            .await
+            .unwrap();
+    }
+
+    let ret = client
+        .send::<serde_json::Value>(
+            "pack:rollback",
+            Vars::new().with("id", "pack1").with("version", "1.0.0"),
+        )
+        .await
+        .unwrap()
+        .data
+        .unwrap();
+    assert_eq!(ret["version"], "1.0.0");
+
+    let ret = client
+        .send::<serde_json::Value>("pack:versions", Vars::new().with("id", "pack1"))
+        .await
+        .unwrap()
+        .data
+        .unwrap();
+    assert_eq!(ret["rows"][0]["current"], false);
+    assert_eq!(ret["rows"][1]["current"], true);
+
+    let ret = client
+        .send::<serde_json::Value>(
+            "pack:rollback",
+            Vars::new().with("id", "pack1").with("version", "2.0.0"),
+        )
+        .await;
+    assert!(ret.is_err());
+}
+
+#[tokio::test]
+async fn grpc_pack_pinned_ref() {
+    let mut client = serve("grpc_pack_pinned_ref", 10106).await;
+    client
+        .send::<bool>(
+            "pack:publish",
+            Vars::new()
+                .with("id", "pack1")
+                .with("body", "act.set('a', 1);")
+                .with("version", "1.0.0"),
+        )
+        .await
+        .unwrap();
+
+    let model = |key: &str| {
+        format!("id: m1\nsteps:\n  - id: step1\n    acts:\n      - act: pack\n        key: {key}\n")
+    };
+    let ret = client
+        .send::<bool>(
+            "model:deploy",
+            Vars::new().with("model", model("pack1@2.0.0")),
+        )
+        .await;
+    assert!(ret.is_err());
+
+    client
+        .send::<bool>(
+            "model:deploy",
+            Vars::new().with("model", model("pack1@1.0.0")),
+        )
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn grpc_pack_rm_referenced() {
+    let mut client = serve("grpc_pack_rm_referenced", 10107).await;
+    client
+        .send::<bool>(
+            "pack:publish",
+            Vars::new()
+                .with("id", "pack1")
+                .with("body", "act.set('a', 1);"),
+        )
+        .await
+        .unwrap();
+    client
+        .deploy(
+            "id: m1\nsteps:\n  - id: step1\n    acts:\n      - act: pack\n        key: pack1@0.1.0\n",
+            None,
+        )
+        .await
+        .unwrap();
+
+    let ret = client
+        .send::<bool>("pack:rm", Vars::new().with("id", "pack1"))
+        .await;
+    assert_eq!(ret.unwrap_err().code(), tonic::Code::FailedPrecondition);
+
+    client
+        .send::<bool>(
+            "pack:rm",
+            Vars::new().with("id", "pack1").with("force", true),
+        )
+        .await
+        .unwrap();
+    let ret = client
+        .send::<serde_json::Value>("pack:versions", Vars::new().with("id", "pack1"))
+        .await
+        .unwrap()
+        .data
+        .unwrap();
+    assert_eq!(ret["count"], 0);
+}
+
+#[tokio::test]
+async fn grpc_pack_schema_inputs() {
+    let mut client = serve("grpc_pack_schema_inputs", 10108).await;
+    client
+        .send::<bool>(
+            "pack:publish",
+            Vars::new()
+                .with("id", "pack1")
+                .with("body", "act.set('b', act.inputs().a + 1);")
+                .with(
+                    "schema",
+                    serde_json::json!({
+                        "inputs": {
+                            "type": "object",
+                            "required": ["a"],
+                            "properties": { "a": { "type": "number" } }
+                        }
+                    }),
+                ),
+        )
+        .await
+        .unwrap();
+
+    let model = r#"
+    id: m1
+    steps:
+      - id: step1
+        acts:
+          - act: pack
+            key: pack1
+            inputs:
+              a: abc
+        catches:
+          - on: E_SCHEMA
+            then:
+              - act: irq
+                key: caught
+    "#;
+    client.deploy(model, None).await.unwrap();
+    let pid = client.start("m1", Vars::new()).await.unwrap().data.unwrap();
+    tokio::time::sleep(Duration::from_millis(200)).await;
+
+    // the schema violation fails the act with the E_SCHEMA ecode, which is caught by the step
+    let proc = client
+        .send::<serde_json::Value>("proc:get", Vars::new().with("pid", &pid))
+        .await
+        .unwrap()
+        .data
+        .unwrap();
+    let tasks = proc["tasks"].as_array().unwrap();
+    let state = |key: &str| {
+        tasks
+            .iter()
+            .find(|t| t["key"] == key)
+            .map(|t| t["state"].as_str().unwrap().to_string())
+    };
+    assert_eq!(state("pack1").as_deref(), Some("error"));
+    assert_eq!(state("caught").as_deref(), Some("interrupted"));
+
+    // the valid inputs run the package body
+    let model = r#"
+    id: m2
+    steps:
+      - id: step1
+        acts:
+          - act: pack
+            key: pack1
+            inputs:
+              a: 1
+          - act: irq
+            key: act1
+    "#;
+    client.deploy(model, None).await.unwrap();
+    let pid = client.start("m2", Vars::new()).await.unwrap().data.unwrap();
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    let proc = client
+        .send::<serde_json::Value>("proc:get", Vars::new().with("pid", &pid))
+        .await
+        .unwrap()
+        .data
+        .unwrap();
+    let task = proc["tasks"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|t| t["key"] == "pack1")
+        .unwrap();
+    assert_eq!(task["state"], "completed");
+    let data: serde_json::Value = serde_json::from_str(task["data"].as_str().unwrap()).unwrap();
+    assert_eq!(data["b"].as_f64(), Some(2.0));
+}
+
+#[tokio::test]
+async fn grpc_pack_schema_outputs() {
+    let mut client = serve("grpc_pack_schema_outputs", 10109).await;
+    client
+        .send::<bool>(
+            "pack:publish",
+            Vars::new()
+                .with("id", "review")
+                .with("body", "// reviewed by users")
+                .with(
+                    "schema",
+                    serde_json::json!({
+                        "outputs": {
+                            "type": "object",
+                            "required": ["approved"],
+                            "properties": { "approved": { "type": "boolean" } }
+                        }
+                    }),
+                ),
+        )
+        .await
+        .unwrap();
+
+    let model = r#"
+    id: m1
+    steps:
+      - id: step1
+        acts:
+          - act: pack
+            key: review
+          - act: irq
+            key: review
+    "#;
+    client.deploy(model, None).await.unwrap();
+    let pid = client.start("m1", Vars::new()).await.unwrap().data.unwrap();
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    let proc = client
+        .send::<serde_json::Value>("proc:get", Vars::new().with("pid", &pid))
+        .await
+        .unwrap()
+        .data
+        .unwrap();
+    let tid = |r#type: &str| {
+        proc["tasks"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|t| t["key"] == "review" && t["type"] == r#type)
+            .map(|t| t["id"].as_str().unwrap().to_string())
+            .unwrap()
+    };
+
+    let err = client
+        .send::<serde_json::Value>(
+            "act:complete",
+            Vars::new()
+                .with("pid", &pid)
+                .with("tid", tid("pack"))
+                .with("approved", "yes"),
+        )
+        .await
+        .unwrap_err();
+    assert_eq!(err.code(), tonic::Code::InvalidArgument);
+    let violation: serde_json::Value = serde_json::from_str(err.message()).unwrap();
+    assert_eq!(violation["ecode"], "E_SCHEMA");
+    assert_eq!(violation["target"], "outputs");
+    assert_eq!(violation["errors"][0]["path"], "/approved");
+
+    // the irq act with the same key is not a package
+    client
+        .send::<serde_json::Value>(
+            "act:complete",
+            Vars::new()
+                .with("pid", &pid)
+                .with("tid", tid("irq"))
+                .with("approved", "yes"),
+        )
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn grpc_pack_get_schema() {
+    let mut client = serve("grpc_pack_get_schema", 10110).await;
+    let schema = serde_json::json!({ "inputs": { "type": "object" } });
+    client
+        .send::<bool>(
+            "pack:publish",
+            Vars::new()
+                .with("id", "pack1")
+                .with("body", "")
+                .with("schema", &schema),
+        )
+        .await
+        .unwrap();
+
+    let ret = client
+        .send::<serde_json::Value>("pack:get", Vars::new().with("id", "pack1"))
+        .await
+        .unwrap()
+        .data
+        .unwrap();
+    assert_eq!(ret["version"], "0.1.0");
+    assert_eq!(ret["schema"], schema);
+
+    // the invalid schema is rejected when publishing
+    let ret = client
+        .send::<bool>(
+            "pack:publish",
+            Vars::new().with("id", "pack1").with("body", "").with(
+                "schema",
+                serde_json::json!({ "inputs": { "type": "unknown" } }),
+            ),
+        )
+        .await;
+    assert!(ret.is_err());
+}
+
+#[tokio::test]
+async fn grpc_pack_publish_files() {
+    let mut client = serve("grpc_pack_publish_files", 10111).await;
+    let encode = |content: &[u8]| STANDARD.encode(content);
+    let files = serde_json::json!({
+        "entry": "main.js",
+        "files": {
+            "main.js": encode(b"const util = require('./lib/util');\nconst data = require('./fixtures/data.json');\nact.set('b', util.add(act.inputs().a, data.n));"),
+            "lib/util.js": encode(b"exports.add = (a, b) => a + b;"),
+            "fixtures/data.json": encode(b"{ \"n\": 10 }"),
+            // the binary files are kept for pulling the package back
+            "assets/logo.png": encode(&[0x89, 0x50, 0x4e, 0x47, 0xff, 0x00]),
+        }
+    });
+    client
+        .send::<bool>(
+            "pack:publish",
+            Vars::new().with("id", "pack1").with("files", &files),
+        )
+        .await
+        .unwrap();
+
+    let model = r#"
+    id: m1
+    steps:
+      - id: step1
+        acts:
+          - act: pack
+            key: pack1
+            inputs:
+              a: 1
+          - act: irq
+            key: act1
+    "#;
+    client.deploy(model, None).await.unwrap();
+    let pid = client.start("m1", Vars::new()).await.unwrap().data.unwrap();
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    let proc = client
+        .send::<serde_json::Value>("proc:get", Vars::new().with("pid", &pid))
+        .await
+        .unwrap()
+        .data
+        .unwrap();
+    let task = proc["tasks"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|t| t["key"] == "pack1")
+        .unwrap();
+    assert_eq!(task["state"], "completed");
+    let data: serde_json::Value = serde_json::from_str(task["data"].as_str().unwrap()).unwrap();
+    assert_eq!(data["b"].as_f64(), Some(11.0));
+
+    let ret = client
+        .send::<serde_json::Value>("pack:get", Vars::new().with("id", "pack1"))
+        .await
+        .unwrap()
+        .data
+        .unwrap();
+    assert_eq!(ret["entry"], "main.js");
+    assert_eq!(ret["files"], files["files"]);
+}
+
+#[tokio::test]
+async fn grpc_pack_publish_files_invalid() {
+    let mut client = serve("grpc_pack_publish_files_invalid", 10112).await;
+    for files in [
+        serde_json::json!({ "entry": "main.js", "files": { "../main.js": "" } }),
+        serde_json::json!({ "entry": "index.js", "files": { "main.js": "" } }),
+        serde_json::json!({ "entry": "data.json", "files": { "data.json": STANDARD.encode("{}") } }),
+        // the content is not base64
+        serde_json::json!({ "entry": "main.js", "files": { "main.js": "act.set('a', 1);" } }),
+        // the js file is not utf-8
+        serde_json::json!({ "entry": "main.js", "files": { "main.js": STANDARD.encode([0xff, 0xfe]) } }),
+    ] {
+        let ret = client
+            .send::<bool>(
+                "pack:publish",
+                Vars::new().with("id", "pack1").with("files", &files),
+            )
+            .await;
+        assert!(ret.is_err());
+    }
+}
+
+#[tokio::test]
+async fn grpc_sys_export_import() {
+    let mut source = serve("grpc_sys_export_import_source", 10113).await;
+    source
+        .deploy(
+            "id: m1\nsteps:\n  - id: step1\n    acts:\n      - act: irq\n        key: act1\n",
+            None,
+        )
+        .await
+        .unwrap();
+    source
+        .send::<bool>(
+            "pack:publish",
+            Vars::new()
+                .with("id", "pack1")
+                .with("body", "act.set('a', 1);"),
+        )
+        .await
+        .unwrap();
+    let pid = source.start("m1", Vars::new()).await.unwrap().data.unwrap();
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    // the archive is read in chunks until it is done
+    let mut archive = String::new();
+    let mut options = Vars::new();
+    loop {
+        let chunk = source
+            .send::<serde_json::Value>("sys:export", options.clone())
+            .await
+            .unwrap()
+            .data
+            .unwrap();
+        archive.push_str(chunk["data"].as_str().unwrap());
+        if chunk["done"] == true {
+            assert_eq!(chunk["next"], chunk["size"]);
+            break;
+        }
+        options = Vars::new()
+            .with("id", chunk["id"].as_str().unwrap())
+            .with("offset", chunk["next"].as_u64().unwrap());
+    }
+    let header: serde_json::Value = serde_json::from_str(archive.lines().next().unwrap()).unwrap();
+    assert_eq!(header["kind"], "acts-server-archive");
+    assert_eq!(header["version"], 1);
+
+    let mut target = serve("grpc_sys_export_import_target", 10114).await;
+    let report = target
+        .send::<serde_json::Value>(
+            "sys:import",
+            Vars::new().with("archive", &archive).with("dry_run", true),
+        )
+        .await
+        .unwrap()
+        .data
+        .unwrap();
+    let created = |report: &serde_json::Value, name: &str| {
+        report["collections"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|c| c["name"] == name)
+            .map(|c| c["created"].as_u64().unwrap())
+    };
+    assert_eq!(created(&report, "engine.models"), Some(1));
+    assert_eq!(report["restart_required"], true);
+    // the dry run does not write anything
+    let ret = target
+        .send::<ModelInfo>("model:get", Vars::new().with("id", "m1"))
+        .await;
+    assert!(ret.is_err());
+
+    // upload the archive in two chunks and import it by the upload id
+    let (head, tail) = archive.split_at(archive.len() / 2);
+    let upload = target
+        .send::<String>("sys:upload", Vars::new().with("data", head))
+        .await
+        .unwrap()
+        .data
+        .unwrap();
+    target
+        .send::<String>(
+            "sys:upload",
+            Vars::new().with("id", &upload).with("data", tail),
+        )
+        .await
+        .unwrap();
+    let report = target
+        .send::<serde_json::Value>("sys:import", Vars::new().with("upload", &upload))
+        .await
+        .unwrap()
+        .data
+        .unwrap();
+    assert_eq!(report["mode"], "merge");
+    assert_eq!(created(&report, "engine.procs"), Some(1));
+    assert_eq!(created(&report, "server.package_version"), Some(1));
+
+    let model = target
+        .send::<ModelInfo>("model:get", Vars::new().with("id", "m1"))
+        .await
+        .unwrap()
+        .data
+        .unwrap();
+    assert_eq!(model.id, "m1");
+    let pack = target
+        .send::<serde_json::Value>("pack:get", Vars::new().with("id", "pack1"))
+        .await
+        .unwrap()
+        .data
+        .unwrap();
+    assert_eq!(pack["version"], "0.1.0");
+    let proc = target
+        .send::<serde_json::Value>("proc:get", Vars::new().with("pid", &pid))
+        .await
+        .unwrap()
+        .data
+        .unwrap();
+    assert_eq!(proc["mid"], "m1");
+
+    // the replace mode is refused while serving, only the dry run is allowed
+    let err = target
+        .send::<serde_json::Value>(
+            "sys:import",
+            Vars::new()
+                .with("archive", &archive)
+                .with("mode", "replace"),
+        )
+        .await
+        .unwrap_err();
+    assert_eq!(err.code(), tonic::Code::FailedPrecondition);
+    let report = target
+        .send::<serde_json::Value>(
+            "sys:import",
+            Vars::new()
+                .with("archive", &archive)
+                .with("mode", "replace")
+                .with("dry_run", true),
+        )
+        .await
+        .unwrap()
+        .data
+        .unwrap();
+    assert_eq!(report["mode"], "replace");
+
+    // the replace mode removes the rows which are not in the archive before the engine starts
+    let options = config("grpc_sys_export_import_offline");
+    let _ = crate::store::Store::new(&options.data_dir).unwrap();
+    let path = std::path::Path::new(&options.data_dir).join("archive.jsonl");
+    std::fs::write(&path, &archive).unwrap();
+    archive::import_offline(&options, &path.to_string_lossy(), "merge").unwrap();
+    let report = archive::import_offline(&options, &path.to_string_lossy(), "replace").unwrap();
+    let models = report
+        .collections
+        .iter()
+        .find(|c| c.name == "engine.models")
+        .unwrap();
+    assert_eq!((models.removed, models.created), (1, 1));
+    tokio::spawn(async move {
+        let addr = "127.0.0.1:10141".parse().unwrap();
+        grpc::start(addr, &options).await.unwrap();
+    });
+    let mut offline = connect(10141).await;
+    let proc = offline
+        .send::<serde_json::Value>("proc:get", Vars::new().with("pid", &pid))
+        .await
+        .unwrap()
+        .data
+        .unwrap();
+    assert_eq!(proc["mid"], "m1");
+}
+
+#[tokio::test]
+async fn grpc_sys_import_invalid() {
+    let mut client = serve("grpc_sys_import_invalid", 10115).await;
+    let header = r#"{"kind":"acts-server-archive","version":1,"create_time":0,"collections":[{"name":"engine.models","count":1}]}"#;
+    for archive in [
+        "".to_string(),
+        r#"{"kind":"other","version":1,"create_time":0,"collections":[]}"#.to_string(),
+        r#"{"kind":"acts-server-archive","version":99,"create_time":0,"collections":[]}"#
+            .to_string(),
+        // the declared count does not match
+        header.to_string(),
+        // undeclared collection
+        format!(
+            "{header}\n{}",
+            r#"{"collection":"engine.procs","row":{"id":"p1"}}"#
+        ),
+        // unknown column
+        format!(
+            "{header}\n{}",
+            r#"{"collection":"engine.models","row":{"id":"m1","unknown":1}}"#
+        ),
+    ] {
+        let ret = client
+            .send::<serde_json::Value>("sys:import", Vars::new().with("archive", &archive))
+            .await;
+        assert!(ret.is_err(), "{archive}");
+    }
+}
+
+#[tokio::test]
+async fn grpc_sys_backup_restore() {
+    let dir = std::env::temp_dir()
+        .join("acts-server-tests")
+        .join("grpc_sys_backup_restore_snapshots");
+    let _ = std::fs::remove_dir_all(&dir);
+    let server = ServerOptions {
+        backup: Some(backup::BackupOptions {
+            dir: dir.to_string_lossy().to_string(),
+            interval: 86400,
+            keep: 7,
+        }),
+        ..Default::default()
+    };
+    serve_with("grpc_sys_backup_restore_source", 10116, server).await;
+    let mut client = connect(10116).await;
+    client
+        .deploy(
+            "id: m1\nsteps:\n  - id: step1\n    acts:\n      - act: irq\n        key: act1\n",
+            None,
+        )
+        .await
+        .unwrap();
+    let pid = client.start("m1", Vars::new()).await.unwrap().data.unwrap();
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    let snapshot = client
+        .send::<serde_json::Value>("sys:backup", Vars::new())
+        .await
+        .unwrap()
+        .data
+        .unwrap();
+    assert_eq!(snapshot["kind"], "acts-server-snapshot");
+    assert_eq!(snapshot["files"].as_array().unwrap().len(), 2);
+
+    // the server keeps serving after backup
+    client
+        .deploy("id: m2\nsteps:\n  - id: step1\n", None)
+        .await
+        .unwrap();
+
+    let options = config("grpc_sys_backup_restore_target");
+    backup::restore(&options, snapshot["path"].as_str().unwrap()).unwrap();
+    tokio::spawn(async move {
+        let addr = "127.0.0.1:10117".parse().unwrap();
+        grpc::start(addr, &options).await.unwrap();
+    });
+    let mut target = connect(10117).await;
+    let model = target
+        .send::<ModelInfo>("model:get", Vars::new().with("id", "m1"))
+        .await
+        .unwrap()
+        .data
+        .unwrap();
+    assert_eq!(model.id, "m1");
+    let ret = target
+        .send::<ModelInfo>("model:get", Vars::new().with("id", "m2"))
+        .await;
+    assert!(ret.is_err());
+    let proc = target
+        .send::<serde_json::Value>("proc:get", Vars::new().with("pid", &pid))
+        .await
+        .unwrap()
+        .data
+        .unwrap();
+    assert_eq!(proc["mid"], "m1");
+}
+
+#[tokio::test]
+async fn grpc_sys_backup_retention() {
+    let options = config("grpc_sys_backup_retention");
+    let dir = std::path::Path::new(&options.data_dir).join("snapshots");
+    let dir = dir.to_string_lossy();
+    let _ = crate::store::Store::new(&options.data_dir).unwrap();
+
+    let mut names = Vec::new();
+    for _ in 0..3 {
+        names.push(backup::backup(&options, &dir).unwrap().name);
+        tokio::time::sleep(Duration::from_millis(5)).await;
+    }
+    let removed = backup::prune(&dir, 2).unwrap();
+    assert_eq!(removed.len(), 1);
+    assert_eq!(removed[0].name, names[0]);
+
+    let snapshots = backup::list(&dir).unwrap();
+    assert_eq!(
+        snapshots.iter().map(|s| &s.name).collect::<Vec<_>>(),
+        vec![&names[2], &names[1]]
+    );
+
+    // the restore requires a valid snapshot
+    assert!(backup::restore(&options, &options.data_dir).is_err());
+}
+
+#[tokio::test]
+async fn grpc_migrate() {
+    let options = config("grpc_migrate");
+    let dir = std::path::PathBuf::from(&options.data_dir);
+
+    let db_name = options.db_name.clone();
+
+    let port = 10118;
+    tokio::spawn(async move {
+        let addr = format!("127.0.0.1:{port}").parse().unwrap();
+        grpc::start(addr, &options).await.unwrap();
+    });
+    let mut client = connect(port).await;
+
+    client
+        .deploy("id: m1\nsteps:\n  - id: step1\n", None)
+        .await
+        .unwrap();
+    client
+        .send::<bool>(
+            "pack:publish",
+            Vars::new()
+                .with("id", "pack1")
+                .with("body", "act.set('a', 1);"),
+        )
+        .await
+        .unwrap();
+    let pack = client
+        .send::<serde_json::Value>("pack:get", Vars::new().with("id", "pack1"))
+        .await
+        .unwrap()
+        .data
+        .unwrap();
+    assert_eq!(pack["version"], "0.1.0");
+    assert!(dir.join(&db_name).exists());
+    assert!(dir.join("server.db").exists());
+
+    // the migrations are applied to the server db only
+    let server = rusqlite::Connection::open(dir.join("server.db")).unwrap();
+    let count = server
+        .query_row(
+            "select count(name) from sqlite_master where name = ?1",
+            [backend::MIGRATION_TABLE],
+            |row| row.get::<usize, usize>(0),
+        )
+        .unwrap();
+    assert_eq!(count, 1);
+    let engine = rusqlite::Connection::open(dir.join(&db_name)).unwrap();
+    let count = engine
+        .query_row(
+            "select count(name) from sqlite_master where name = ?1 or name like 'idx_%'",
+            [backend::MIGRATION_TABLE],
+            |row| row.get::<usize, usize>(0),
+        )
+        .unwrap();
+    assert_eq!(count, 0);
+}
+
+#[tokio::test]
+async fn grpc_namespace_isolation() {
+    let port = 10120;
+    serve_with(
+        "grpc_namespace_isolation",
+        port,
+        ServerOptions {
+            namespace: tenants(),
+            ..Default::default()
+        },
+    )
+    .await;
+    let a = [("authorization", "Bearer team-a")];
+    let b = [("authorization", "Bearer team-b")];
+
+    // the namespaces deploy the models with the same id
+    for (ns, title) in [(&a, "model a"), (&b, "model b")] {
+        let model = format!(
+            "id: m1\nname: {title}\nsteps:\n  - id: step1\n    acts:\n      - act: irq\n        key: act1\n"
+        );
+        send_with(port, ns, "model:deploy", Vars::new().with("model", model))
+            .await
+            .unwrap();
+    }
+    let model = send_with(port, &a, "model:get", Vars::new().with("id", "m1"))
+        .await
+        .unwrap();
+    assert_eq!(model["id"], "m1");
+    assert_eq!(model["name"], "model a");
+    assert!(model["data"].as_str().unwrap().contains("id: m1"));
+    let models = send_with(port, &b, "model:ls", Vars::new()).await.unwrap();
+    assert_eq!(models["count"], 1);
+    assert_eq!(models["rows"][0]["name"], "model b");
+
+    // the root namespace sees all of the models
+    let models = send_with(port, &ADMIN, "model:ls", Vars::new())
+        .await
+        .unwrap();
+    let mut ids = models["rows"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|m| m["id"].as_str().unwrap().to_string())
+        .collect::<Vec<_>>();
+    ids.sort();
+    assert_eq!(ids, vec!["team-a/m1", "team-b/m1"]);
+
+    let pid = send_with(port, &a, "proc:start", Vars::new().with("id", "m1"))
+        .await
+        .unwrap();
+    let pid = pid.as_str().unwrap();
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    let proc = send_with(port, &a, "proc:get", Vars::new().with("pid", pid))
+        .await
+        .unwrap();
+    assert_eq!(proc["mid"], "m1");
+
+    // the proc and its tasks are invisible in the other namespace
+    let err = send_with(port, &b, "proc:get", Vars::new().with("pid", pid))
+        .await
+        .unwrap_err();
+    assert_eq!(err.code(), Code::NotFound);
+    let err = send_with(port, &b, "proc:tasks", Vars::new().with("pid", pid))
+        .await
+        .unwrap_err();
+    assert_eq!(err.code(), Code::NotFound);
+    let procs = send_with(port, &b, "proc:ls", Vars::new()).await.unwrap();
+    assert_eq!(procs["count"], 0);
+    let tasks = send_with(port, &b, "task:ls", Vars::new()).await.unwrap();
+    assert_eq!(tasks["count"], 0);
+    let tasks = send_with(port, &a, "task:ls", Vars::new()).await.unwrap();
+    assert!(tasks["count"].as_u64().unwrap() > 0);
+
+    // the packages are scoped too
+    send_with(
+        port,
+        &a,
+        "pack:publish",
+        Vars::new()
+            .with("id", "pack1")
+            .with("body", "act.set('a', 1);"),
+    )
+    .await
+    .unwrap();
+    let packs = send_with(port, &b, "pack:ls", Vars::new()).await.unwrap();
+    assert_eq!(packs["count"], 0);
+    let err = send_with(port, &b, "pack:get", Vars::new().with("id", "pack1"))
+        .await
+        .unwrap_err();
+    assert!(!err.message().contains("team-"));
+
+    // the system actions are only for the root namespace
+    let err = send_with(port, &a, "sys:export", Vars::new())
+        .await
+        .unwrap_err();
+    assert_eq!(err.code(), Code::PermissionDenied);
+}
+
+#[tokio::test]
+async fn grpc_namespace_token() {
+    let port = 10121;
+    let server = ServerOptions {
+        namespace: tenants(),
+        ..Default::default()
+    };
+    serve_with("grpc_namespace_token", port, server).await;
+
+    let err = send_with(port, &[], "model:ls", Vars::new())
+        .await
+        .unwrap_err();
+    assert_eq!(err.code(), Code::Unauthenticated);
+    // the metadata never selects the namespace without the token
+    let err = send_with(
+        port,
+        &[("x-acts-namespace", "team-a")],
+        "model:ls",
+        Vars::new(),
+    )
+    .await
+    .unwrap_err();
+    assert_eq!(err.code(), Code::Unauthenticated);
+    let err = send_with(
+        port,
+        &[("authorization", "Bearer bad")],
+        "model:ls",
+        Vars::new(),
+    )
+    .await
+    .unwrap_err();
+    assert_eq!(err.code(), Code::Unauthenticated);
+    let err = send_with(
+        port,
+        &[
+            ("authorization", "Bearer team-a"),
+            ("x-acts-namespace", "team-b"),
+        ],
+        "model:ls",
+        Vars::new(),
+    )
+    .await
+    .unwrap_err();
+    assert_eq!(err.code(), Code::PermissionDenied);
+
+    send_with(
+        port,
+        &[("authorization", "Bearer team-a")],
+        "model:deploy",
+        Vars::new().with("model", "id: m1\nsteps:\n  - id: step1\n"),
+    )
+    .await
+    .unwrap();
+    let model = send_with(
+        port,
+        &[("authorization", "Bearer admin")],
+        "model:get",
+        Vars::new().with("id", "team-a/m1"),
+    )
+    .await
+    .unwrap();
+    assert_eq!(model["id"], "team-a/m1");
+
+    // the root token can select any namespace by the metadata
+    let model = send_with(
+        port,
+        &[
+            ("authorization", "Bearer admin"),
+            ("x-acts-namespace", "team-a"),
+        ],
+        "model:get",
+        Vars::new().with("id", "m1"),
+    )
+    .await
+    .unwrap();
+    assert_eq!(model["id"], "m1");
+}
+
+#[tokio::test]
+async fn grpc_namespace_default() {
+    let port = 10142;
+    let mut options = tenants();
+    options.default = Some("public".to_string());
+    let server = ServerOptions {
+        namespace: options,
+        ..Default::default()
+    };
+    serve_with("grpc_namespace_default", port, server).await;
+
+    // the requests without the token go to the default namespace
+    send_with(
+        port,
+        &[],
+        "model:deploy",
+        Vars::new().with("model", "id: m1\nsteps:\n  - id: step1\n"),
+    )
+    .await
+    .unwrap();
+    let models = send_with(port, &ADMIN, "model:ls", Vars::new())
+        .await
+        .unwrap();
+    assert_eq!(models["rows"][0]["id"], "public/m1");
+    let err = send_with(
+        port,
+        &[("x-acts-namespace", "team-a")],
+        "model:ls",
+        Vars::new(),
+    )
+    .await
+    .unwrap_err();
+    assert_eq!(err.code(), Code::PermissionDenied);
+    let err = send_with(port, &[], "sys:export", Vars::new())
+        .await
+        .unwrap_err();
+    assert_eq!(err.code(), Code::PermissionDenied);
+
+    // the default namespace is never the root
+    let mut options = tenants();
+    options.default = Some("*".to_string());
+    assert!(options.check().is_err());
+}
+
+#[tokio::test]
+async fn grpc_namespace_messages() {
+    let port = 10122;
+    serve_with(
+        "grpc_namespace_messages",
+        port,
+        ServerOptions {
+            namespace: tenants(),
+            ..Default::default()
+        },
+    )
+    .await;
+
+    let mut streams = Vec::new();
+    for ns in ["team-a", "team-b"] {
+        let mut client = ActsServiceClient::connect(format!("http://127.0.0.1:{port}"))
+            .await
+            .unwrap();
+        let mut request = Request::new(MessageOptions {
+            client_id: "client1".to_string(),
+            r#type: "*".to_string(),
+            state: "*".to_string(),
+            tag: "*".to_string(),
+            key: "*".to_string(),
+        });
+        request
+            .metadata_mut()
+            .insert("authorization", format!("Bearer {ns}").parse().unwrap());
+        streams.push(client.on_message(request).await.unwrap().into_inner());
+    }
+
+    let a = [("authorization", "Bearer team-a")];
+    send_with(
+        port,
+        &a,
+        "model:deploy",
+        Vars::new().with("model", "id: m1\nsteps:\n  - id: step1\n"),
+    )
+    .await
+    .unwrap();
+    send_with(port, &a, "proc:start", Vars::new().with("id", "m1"))
+        .await
+        .unwrap();
+
+    let message = tokio::time::timeout(Duration::from_secs(2), streams[0].next())
+        .await
+        .unwrap()
+        .unwrap()
+        .unwrap();
+    let data: Value = serde_json::from_slice(message.data()).unwrap();
+    assert_eq!(data["model"]["id"], "m1");
+
+    // the subscriber of the other namespace receives nothing
+    let ret = tokio::time::timeout(Duration::from_millis(300), streams[1].next()).await;
+    assert!(ret.is_err());
+}
+
+#[tokio::test]
+async fn grpc_audit_ls() {
+    let port = 10123;
+    serve_with(
+        "grpc_audit_ls",
+        port,
+        ServerOptions {
+            namespace: tenants(),
+            ..Default::default()
+        },
+    )
+    .await;
+    let alice = [("x-acts-user", "alice"), ("authorization", "Bearer admin")];
+
+    send_with(
+        port,
+        &alice,
+        "model:deploy",
+        Vars::new().with(
+            "model",
+            "id: m1\nsteps:\n  - id: step1\n    acts:\n      - act: irq\n        key: act1\n",
+        ),
+    )
+    .await
+    .unwrap();
+    let pid = send_with(
+        port,
+        &alice,
+        "proc:start",
+        Vars::new().with("id", "m1").with("password", "123456"),
+    )
+    .await
+    .unwrap();
+    send_with(
+        port,
+        &alice,
+        "act:complete",
+        Vars::new().with("pid", "no-proc").with("tid", "no-task"),
+    )
+    .await
+    .unwrap_err();
+    // the reading actions are not audited
+    send_with(port, &alice, "model:ls", Vars::new())
+        .await
+        .unwrap();
+    send_with(
+        port,
+        &[("authorization", "Bearer team-a")],
+        "model:deploy",
+        Vars::new().with("model", "id: m2\nsteps:\n  - id: step1\n"),
+    )
+    .await
+    .unwrap();
+
+    let audits = send_with(port, &ADMIN, "audit:ls", Vars::new())
+        .await
+        .unwrap();
+    let mut actions = audits["rows"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|a| a["action"].as_str().unwrap())
+        .collect::<Vec<_>>();
+    actions.sort();
+    assert_eq!(
+        actions,
+        vec!["act:complete", "model:deploy", "model:deploy", "proc:start"]
+    );
+
+    let audits = send_with(
+        port,
+        &ADMIN,
+        "audit:ls",
+        Vars::new().with("query_by", vec![("action", "proc:start")]),
+    )
+    .await
+    .unwrap();
+    let audit = &audits["rows"][0];
+    assert_eq!(audit["user"], "alice");
+    assert_eq!(audit["outcome"], "ok");
+    assert_eq!(audit["targets"]["pid"], pid);
+    assert_eq!(audit["targets"]["id"], "m1");
+    assert_eq!(audit["params"]["password"], "***");
+    assert!(!audit["peer"].as_str().unwrap().is_empty());
+
+    let audits = send_with(
+        port,
+        &ADMIN,
+        "audit:ls",
+        Vars::new().with("query_by", vec![("action", "act:complete")]),
+    )
+    .await
+    .unwrap();
+    assert_ne!(audits["rows"][0]["outcome"], "ok");
+    assert_eq!(audits["rows"][0]["pid"], "no-proc");
+
+    // the namespace only lists its own audits
+    let audits = send_with(
+        port,
+        &[("authorization", "Bearer team-a")],
+        "audit:ls",
+        Vars::new(),
+    )
+    .await
+    .unwrap();
+    assert_eq!(audits["count"], 1);
+    assert_eq!(audits["rows"][0]["namespace"], "team-a");
+
+    // the actions which are not read-only are audited, such as the system actions
+    send_with(port, &ADMIN, "sys:upload", Vars::new().with("data", "{}\n"))
+        .await
+        .unwrap();
+    send_with(port, &ADMIN, "sys:export", Vars::new())
+        .await
+        .unwrap();
+    for action in ["sys:upload", "sys:export"] {
+        let audits = send_with(
+            port,
+            &ADMIN,
+            "audit:ls",
+            Vars::new().with("query_by", vec![("action", action)]),
+        )
+        .await
+        .unwrap();
+        assert_eq!(audits["count"], 1);
+    }
+}
+
+#[tokio::test]
+async fn grpc_idempotency_replay() {
+    let port = 10124;
+    serve_with("grpc_idempotency_replay", port, ServerOptions::default()).await;
+    let model = "id: m1\nsteps:\n  - id: step1\n    acts:\n      - act: irq\n        key: act1\n  - id: step2\n    acts:\n      - act: irq\n        key: act2\n";
+    send_with(port, &[], "model:deploy", Vars::new().with("model", model))
+        .await
+        .unwrap();
+
+    // the retried start returns the first pid and starts nothing
+    let start = Vars::new()
+        .with("id", "m1")
+        .with("idempotency_key", "start-1");
+    let pid = send_with(port, &[], "proc:start", start.clone())
+        .await
+        .unwrap();
+    let retried = send_with(port, &[], "proc:start", start).await.unwrap();
+    assert_eq!(pid, retried);
+    let retried = send_with(
+        port,
+        &[("x-idempotency-key", "start-1")],
+        "proc:start",
+        Vars::new().with("id", "m1"),
+    )
+    .await
+    .unwrap();
+    assert_eq!(pid, retried);
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    let procs = send_with(port, &[], "proc:ls", Vars::new()).await.unwrap();
+    assert_eq!(procs["count"], 1);
+
+    // the key is not saved as a proc var
+    let pid = pid.as_str().unwrap();
+    let vars = send_with(port, &[], "proc:vars:get", Vars::new().with("pid", pid))
+        .await
+        .unwrap();
+    assert!(vars.get("idempotency_key").is_none());
+
+    // the retried complete never completes the next act
+    let tasks = |proc: &Value, key: &str| {
+        proc["tasks"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .filter(|t| t["key"] == key)
+            .cloned()
+            .collect::<Vec<_>>()
+    };
+    let proc = send_with(port, &[], "proc:get", Vars::new().with("pid", pid))
+        .await
+        .unwrap();
+    let tid = tasks(&proc, "act1")[0]["id"].as_str().unwrap().to_string();
+    let complete = Vars::new()
+        .with("pid", pid)
+        .with("tid", &tid)
+        .with("idempotency_key", "complete-1");
+    for _ in 0..2 {
+        send_with(port, &[], "act:complete", complete.clone())
+            .await
+            .unwrap();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    let proc = send_with(port, &[], "proc:get", Vars::new().with("pid", pid))
+        .await
+        .unwrap();
+    let act2 = tasks(&proc, "act2");
+    assert_eq!(act2.len(), 1);
+    assert_ne!(act2[0]["state"], "completed");
+
+    // the key cannot be reused by another action
+    let err = send_with(
+        port,
+        &[],
+        "model:rm",
+        Vars::new()
+            .with("id", "m1")
+            .with("idempotency_key", "start-1"),
+    )
+    .await
+    .unwrap_err();
+    assert_eq!(err.code(), Code::FailedPrecondition);
+
+    // the key cannot be reused with the different options
+    let err = send_with(
+        port,
+        &[],
+        "proc:start",
+        Vars::new()
+            .with("id", "m1")
+            .with("a", 1)
+            .with("idempotency_key", "start-1"),
+    )
+    .await
+    .unwrap_err();
+    assert_eq!(err.code(), Code::FailedPrecondition);
+    assert!(err.message().contains("different options"));
+}
+
+#[tokio::test]
+async fn grpc_idempotency_window() {
+    let port = 10125;
+    let server = ServerOptions {
+        namespace: tenants(),
+        idempotency: idempotency::IdempotencyOptions { window: 1 },
+        ..Default::default()
+    };
+    serve_with("grpc_idempotency_window", port, server).await;
+    send_with(
+        port,
+        &ADMIN,
+        "model:deploy",
+        Vars::new().with(
+            "model",
+            "id: m1\nsteps:\n  - id: step1\n    acts:\n      - act: irq\n        key: act1\n",
+        ),
+    )
+    .await
+    .unwrap();
+
+    let start = Vars::new()
+        .with("id", "m1")
+        .with("idempotency_key", "start-1");
+    let pid = send_with(port, &ADMIN, "proc:start", start.clone())
+        .await
+        .unwrap();
+    // the namespaces have their own keys
+    let other = send_with(
+        port,
+        &[("authorization", "Bearer team-a")],
+        "proc:start",
+        start.clone(),
+    )
+    .await
+    .unwrap_err();
+    assert_eq!(other.code(), Code::Internal);
+
+    // the key is forgotten after the window
+    tokio::time::sleep(Duration::from_millis(1100)).await;
+    let next = send_with(port, &ADMIN, "proc:start", start).await.unwrap();
+    assert_ne!(pid, next);
+
+    // the expired responses are removed by the purge
+    let dir = std::env::temp_dir()
+        .join("acts-server-tests")
+        .join("grpc_idempotency_window");
+    let store = Store::new(&dir.to_string_lossy()).unwrap();
+    let now = crate::utils::time_millis();
+    assert_eq!(idempotency::purge(&store, now).unwrap(), 0);
+    assert_eq!(idempotency::purge(&store, now + 2000).unwrap(), 1);
+}
+
+#[tokio::test]
+async fn grpc_act_expected_revision() {
+    let port = 10126;
+    serve_with("grpc_act_expected_revision", port, ServerOptions::default()).await;
+    let model = "id: m1\nsteps:\n  - id: step1\n    acts:\n      - act: irq\n        key: act1\n  - id: step2\n    acts:\n      - act: irq\n        key: act2\n";
+    send_with(port, &[], "model:deploy", Vars::new().with("model", model))
+        .await
+        .unwrap();
+    let pid = send_with(port, &[], "proc:start", Vars::new().with("id", "m1"))
+        .await
+        .unwrap();
+    let pid = pid.as_str().unwrap();
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    let proc = send_with(port, &[], "proc:get", Vars::new().with("pid", pid))
+        .await
+        .unwrap();
+    let tid = proc["tasks"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|t| t["key"] == "act1")
+        .map(|t| t["id"].as_str().unwrap().to_string())
+        .unwrap();
+
+    let task = send_with(
+        port,
+        &[],
+        "task:get",
+        Vars::new().with("pid", pid).with("tid", &tid),
+    )
+    .await
+    .unwrap();
+    let revision = task["revision"].as_str().unwrap().to_string();
+    assert_eq!(task["state"], "interrupted");
+
+    // the stale update returns the current task
+    let err = send_with(
+        port,
+        &[],
+        "act:complete",
+        Vars::new()
+            .with("pid", pid)
+            .with("tid", &tid)
+            .with("expected_revision", "0000000000000000"),
+    )
+    .await
+    .unwrap_err();
+    assert_eq!(err.code(), Code::FailedPrecondition);
+    let stale: Value = serde_json::from_str(err.message()).unwrap();
+    assert_eq!(stale["ecode"], "E_STALE");
+    assert_eq!(stale["state"], "interrupted");
+    assert_eq!(stale["revision"], revision);
+
+    // only one of the racing operators completes the act
+    let mut handles = Vec::new();
+    for _ in 0..2 {
+        let options = Vars::new()
+            .with("pid", pid)
+            .with("tid", &tid)
+            .with("expected_revision", &revision)
+            .with("expected_state", "interrupted");
+        handles.push(tokio::spawn(async move {
+            send_with(port, &[], "act:complete", options).await
+        }));
+    }
+    let mut codes = Vec::new();
+    for handle in handles {
+        codes.push(
+            handle
+                .await
+                .unwrap()
+                .map_or_else(|err| err.code(), |_| Code::Ok),
+        );
+    }
+    codes.sort_by_key(|code| *code as i32);
+    assert_eq!(codes, vec![Code::Ok, Code::FailedPrecondition]);
+
+    let task = send_with(
+        port,
+        &[],
+        "task:get",
+        Vars::new().with("pid", pid).with("tid", &tid),
+    )
+    .await
+    .unwrap();
+    assert_eq!(task["state"], "completed");
+    assert_ne!(task["revision"], revision.as_str());
+}
+
+#[tokio::test]
+async fn revision_lock_by_proc() {
+    let lock = crate::revision::lock("p1").await;
+
+    // the action on another proc never waits
+    tokio::time::timeout(Duration::from_millis(50), crate::revision::lock("p2"))
+        .await
+        .unwrap();
+
+    // the action on the same proc waits without blocking the runtime
+    let waiting = tokio::spawn(crate::revision::lock("p1"));
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    assert!(!waiting.is_finished());
+
+    drop(lock);
+    tokio::time::timeout(Duration::from_millis(50), waiting)
+        .await
+        .unwrap()
+        .unwrap();
+}
+
+#[tokio::test]
+async fn grpc_schedule_one_shot() {
+    let port = 10127;
+    serve_with("grpc_schedule_one_shot", port, ServerOptions::default()).await;
+    let model = "id: m1\nsteps:\n  - id: step1\n    acts:\n      - act: irq\n        key: act1\n";
+    send_with(port, &[], "model:deploy", Vars::new().with("model", model))
+        .await
+        .unwrap();
+
+    let at = crate::utils::time_millis() + 300;
+    let schedule = send_with(
+        port,
+        &[],
+        "schedule:create",
+        Vars::new()
+            .with("mid", "m1")
+            .with("at", at)
+            .with("vars", Vars::new().with("a", 1)),
+    )
+    .await
+    .unwrap();
+    let id = schedule["id"].as_str().unwrap().to_string();
+    assert_eq!(schedule["state"], "active");
+    assert_eq!(schedule["next_time"], at);
+
+    let mut schedule = Value::Null;
+    for _ in 0..100 {
+        let schedules = send_with(port, &[], "schedule:ls", Vars::new())
+            .await
+            .unwrap();
+        schedule = schedules["rows"][0].clone();
+        if schedule["state"] == "completed" {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    assert_eq!(schedule["state"], "completed");
+    assert_eq!(schedule["runs"], 1);
+    let pid = schedule["last_pid"].as_str().unwrap();
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    let vars = send_with(port, &[], "proc:vars:get", Vars::new().with("pid", pid))
+        .await
+        .unwrap();
+    assert_eq!(vars["a"], 1);
+
+    // the start by the schedule is audited
+    let audits = send_with(
+        port,
+        &[],
+        "audit:ls",
+        Vars::new().with("query_by", vec![("user", "system")]),
+    )
+    .await
+    .unwrap();
+    assert_eq!(audits["count"], 1);
+    let audit = &audits["rows"][0];
+    assert_eq!(audit["action"], "proc:start");
+    assert_eq!(audit["peer"], format!("schedule:{id}"));
+    assert_eq!(audit["pid"], pid);
+    assert_eq!(audit["outcome"], "ok");
+
+    // the completed schedule cannot be resumed
+    let err = send_with(port, &[], "schedule:resume", Vars::new().with("id", &id))
+        .await
+        .unwrap_err();
+    assert!(err.message().contains("completed"));
+    send_with(port, &[], "schedule:rm", Vars::new().with("id", &id))
+        .await
+        .unwrap();
+    let schedules = send_with(port, &[], "schedule:ls", Vars::new())
+        .await
+        .unwrap();
+    assert_eq!(schedules["count"], 0);
+}
+
+#[tokio::test]
+async fn grpc_schedule_catch_up() {
+    let port = 10128;
+    let options = config("grpc_schedule_catch_up");
+
+    // the schedule is missed for hours before the server starts
+    let store = crate::store::Store::new(&options.data_dir).unwrap();
+    let hour = 3_600_000;
+    // the latest missed run is half an hour ago, so the skip one is never on time
+    let minute = (crate::utils::time_millis() / 60_000 + 30) % 60;
+    for (id, catch_up) in [("s1", "once"), ("s2", "skip"), ("s3", "once")] {
+        let schedule = crate::schedule::Schedule {
+            id: id.to_string(),
+            mid: "m1".to_string(),
+            cron: format!("{minute} * * * *"),
+            catch_up: catch_up.to_string(),
+            state: if id == "s3" { "paused" } else { "active" }.to_string(),
+            next_time: crate::utils::time_millis() - 3 * hour,
+            ..Default::default()
+        };
+        store
+            .collection::<crate::schedule::Schedule>()
+            .unwrap()
+            .create(&schedule)
+            .unwrap();
+    }
+    drop(store);
+
+    tokio::spawn(async move {
+        let addr = format!("127.0.0.1:{port}").parse().unwrap();
+        grpc::start(addr, &options).await.unwrap();
+    });
+    connect(port).await;
+
+    let mut schedules = Value::Null;
+    for _ in 0..30 {
+        schedules = send_with(
+            port,
+            &[],
+            "schedule:ls",
+            Vars::new().with("order_by", vec![("id", false)]),
+        )
+        .await
+        .unwrap();
+        if schedules["rows"][0]["runs"] == 1 {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    let rows = schedules["rows"].as_array().unwrap();
+    // the model is not deployed, so the run fails with the error
+    assert_eq!(rows[0]["runs"], 1);
+    assert!(!rows[0]["last_error"].as_str().unwrap().is_empty());
+    assert_eq!(rows[1]["runs"], 0);
+    assert_eq!(rows[2]["runs"], 0);
+    for row in &rows[..2] {
+        assert!(row["next_time"].as_i64().unwrap() > crate::utils::time_millis());
+    }
+}
+
+/// the request received by the http stub
+#[derive(Debug, Clone)]
+struct StubRequest {
+    headers: std::collections::HashMap<String, String>,
+    body: String,
+}
+
+/// a local http server which fails the first requests with 500 and records all of them
+async fn http_stub(port: u16, fails: usize) -> Arc<Mutex<Vec<StubRequest>>> {
+    http_stub_with(port, move |count| {
+        if count <= fails {
+            "500 Internal Server Error".to_string()
+        } else {
+            "200 OK".to_string()
+        }
+    })
+    .await
+}
+
+/// a local http server which responds the status and headers by the count of the requests
+async fn http_stub_with(
+    port: u16,
+    respond: impl Fn(usize) -> String + Send + 'static,
+) -> Arc<Mutex<Vec<StubRequest>>> {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    let requests = Arc::new(Mutex::new(Vec::new()));
+    let listener = tokio::net::TcpListener::bind(format!("127.0.0.1:{port}"))
+        .await
+        .unwrap();
+    let received = requests.clone();
+    tokio::spawn(async move {
+        loop {
+            let Ok((mut stream, _)) = listener.accept().await else {
+                break;
+            };
+            let mut data = Vec::new();
+            let mut buf = [0; 4096];
+            let (head, body) = loop {
+                let n = stream.read(&mut buf).await.unwrap_or_default();
+                if n == 0 {
+                    break (String::new(), String::new());
+                }
+                data.extend_from_slice(&buf[..n]);
+                let text = String::from_utf8_lossy(&data).to_string();
+                let Some((head, body)) = text.split_once("\r\n\r\n") else {
+                    continue;
+                };
+                let len = head
+                    .lines()
+                    .find_map(|line| {
+                        let (key, value) = line.split_once(':')?;
+                        key.eq_ignore_ascii_case("content-length")
+                            .then(|| value.trim().parse::<usize>().ok())?
+                    })
+                    .unwrap_or_default();
+                if body.len() >= len {
+                    break (head.to_string(), body.to_string());
+                }
+            };
+            let headers = head
+                .lines()
+                .skip(1)
+                .filter_map(|line| line.split_once(':'))
+                .map(|(key, value)| (key.trim().to_lowercase(), value.trim().to_string()))
+                .collect();
+            let count = {
+                let mut requests = received.lock().unwrap();
+                requests.push(StubRequest { headers, body });
+                requests.len()
+            };
+            let status = respond(count);
+            let resp =
+                format!("HTTP/1.1 {status}\r\ncontent-length: 0\r\nconnection: close\r\n\r\n");
+            let _ = stream.write_all(resp.as_bytes()).await;
+            let _ = stream.shutdown().await;
+        }
+    });
+    requests
+}
+
+#[tokio::test]
+async fn grpc_webhook_retry() {
+    let port = 10129;
+    let requests = http_stub(10130, 2).await;
+    let server = ServerOptions {
+        webhook: webhook::WebhookOptions {
+            retries: 3,
+            backoff: 50,
+            allow_hosts: vec!["127.0.0.1".to_string()],
+        },
+        ..Default::default()
+    };
+    serve_with("grpc_webhook_retry", port, server).await;
+    let model = "id: m1\nsteps:\n  - id: step1\n    acts:\n      - act: irq\n        key: act1\n";
+    send_with(port, &[], "model:deploy", Vars::new().with("model", model))
+        .await
+        .unwrap();
+    let hook = send_with(
+        port,
+        &[],
+        "webhook:create",
+        Vars::new()
+            .with("url", "http://127.0.0.1:10130/hook")
+            .with("secret", "s3cret")
+            .with("type", "irq")
+            .with("key", "act*"),
+    )
+    .await
+    .unwrap();
+    assert_eq!(hook["secret"], "***");
+    assert_eq!(hook["state"], "*");
+
+    // the internal addresses are denied unless their hosts are allowed
+    for url in [
+        "http://10.1.2.3/hook",
+        "http://169.254.169.254/latest",
+        "http://[::1]:10130/hook",
+        "http://localhost:10130/hook",
+        "ftp://127.0.0.1/hook",
+    ] {
+        let err = send_with(
+            port,
+            &[],
+            "webhook:create",
+            Vars::new().with("url", url).with("secret", "s3cret"),
+        )
+        .await
+        .unwrap_err();
+        assert!(err.message().contains("webhook"), "{url}: {err}");
+    }
+
+    send_with(port, &[], "proc:start", Vars::new().with("id", "m1"))
+        .await
+        .unwrap();
+    for _ in 0..50 {
+        if requests.lock().unwrap().len() >= 3 {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    // wait for the delivered one to be removed
+    tokio::time::sleep(Duration::from_millis(200)).await;
+
+    let requests = requests.lock().unwrap().clone();
+    assert_eq!(requests.len(), 3);
+    let delivery = &requests[0].headers[webhook::DELIVERY_HEADER];
+    for request in &requests {
+        // the retries are the same delivery
+        assert_eq!(&request.headers[webhook::DELIVERY_HEADER], delivery);
+        let timestamp = request.headers[webhook::TIMESTAMP_HEADER]
+            .parse::<i64>()
+            .unwrap();
+        assert_eq!(
+            request.headers[webhook::SIGNATURE_HEADER],
+            format!(
+                "sha256={}",
+                webhook::sign("s3cret", timestamp, &request.body)
+            )
+        );
+        let payload = serde_json::from_str::<Value>(&request.body).unwrap();
+        assert_eq!(payload["key"], "act1");
+        assert_eq!(payload["type"], "irq");
+    }
+
+    let dead = send_with(port, &[], "webhook:failed:ls", Vars::new())
+        .await
+        .unwrap();
+    assert_eq!(dead["count"], 0);
+    let hooks = send_with(port, &[], "webhook:ls", Vars::new())
+        .await
+        .unwrap();
+    assert_eq!(hooks["count"], 1);
+    assert_eq!(hooks["rows"][0]["secret"], "***");
+}
+
+#[tokio::test]
+async fn grpc_webhook_redirect() {
+    let port = 10149;
+    let requests = http_stub(10150, 0).await;
+    // the redirect to the other address is never followed
+    let redirects = http_stub_with(10151, |_| {
+        "302 Found\r\nlocation: http://127.0.0.1:10150/hook".to_string()
+    })
+    .await;
+    let server = ServerOptions {
+        webhook: webhook::WebhookOptions {
+            retries: 0,
+            allow_hosts: vec!["127.0.0.1".to_string()],
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    serve_with("grpc_webhook_redirect", port, server).await;
+    let model = "id: m1\nsteps:\n  - id: step1\n    acts:\n      - act: irq\n        key: act1\n";
+    send_with(port, &[], "model:deploy", Vars::new().with("model", model))
+        .await
+        .unwrap();
+    send_with(
+        port,
+        &[],
+        "webhook:create",
+        Vars::new()
+            .with("url", "http://127.0.0.1:10151/hook")
+            .with("secret", "s3cret")
+            .with("type", "irq"),
+    )
+    .await
+    .unwrap();
+    send_with(port, &[], "proc:start", Vars::new().with("id", "m1"))
+        .await
+        .unwrap();
+
+    let mut dead = Value::Null;
+    for _ in 0..50 {
+        dead = send_with(port, &[], "webhook:failed:ls", Vars::new())
+            .await
+            .unwrap();
+        if dead["count"] == 1 {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    assert_eq!(dead["count"], 1);
+    assert!(dead["rows"][0]["last_error"]
+        .as_str()
+        .unwrap()
+        .contains("302"));
+    assert_eq!(redirects.lock().unwrap().len(), 1);
+    assert!(requests.lock().unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn grpc_webhook_concurrent() {
+    let port = 10143;
+    let requests = http_stub(10144, 0).await;
+    // the webhook which never responds
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:10145")
+        .await
+        .unwrap();
+    tokio::spawn(async move {
+        let mut streams = Vec::new();
+        while let Ok((stream, _)) = listener.accept().await {
+            streams.push(stream);
+        }
+    });
+    let server = ServerOptions {
+        webhook: webhook::WebhookOptions {
+            allow_hosts: vec!["127.0.0.1".to_string()],
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    serve_with("grpc_webhook_concurrent", port, server).await;
+    let model = "id: m1\nsteps:\n  - id: step1\n    acts:\n      - act: irq\n        key: act1\n";
+    send_with(port, &[], "model:deploy", Vars::new().with("model", model))
+        .await
+        .unwrap();
+    for (id, url) in [
+        ("slow", "http://127.0.0.1:10145/hook"),
+        ("fast", "http://127.0.0.1:10144/hook"),
+    ] {
+        send_with(
+            port,
+            &[],
+            "webhook:create",
+            Vars::new()
+                .with("id", id)
+                .with("url", url)
+                .with("secret", "s3cret")
+                .with("type", "irq"),
+        )
+        .await
+        .unwrap();
+    }
+
+    // the slow webhook never blocks the other one
+    for _ in 0..2 {
+        send_with(port, &[], "proc:start", Vars::new().with("id", "m1"))
+            .await
+            .unwrap();
+    }
+    for _ in 0..30 {
+        if requests.lock().unwrap().len() >= 2 {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    assert_eq!(requests.lock().unwrap().len(), 2);
+}
+
+#[tokio::test]
+async fn grpc_webhook_failed() {
+    let port = 10131;
+    let requests = http_stub(10132, usize::MAX).await;
+    let server = ServerOptions {
+        namespace: tenants(),
+        webhook: webhook::WebhookOptions {
+            retries: 1,
+            backoff: 50,
+            allow_hosts: vec!["127.0.0.1".to_string()],
+        },
+        ..Default::default()
+    };
+    serve_with("grpc_webhook_failed", port, server).await;
+    let a = [("authorization", "Bearer team-a")];
+    let model = "id: m1\nsteps:\n  - id: step1\n    acts:\n      - act: irq\n        key: act1\n";
+    for ns in [&a[..], &ADMIN[..]] {
+        send_with(port, ns, "model:deploy", Vars::new().with("model", model))
+            .await
+            .unwrap();
+    }
+    let hook = send_with(
+        port,
+        &a,
+        "webhook:create",
+        Vars::new()
+            .with("id", "h1")
+            .with("url", "http://127.0.0.1:10132/hook")
+            .with("secret", "s3cret")
+            .with("type", "irq"),
+    )
+    .await
+    .unwrap();
+    assert_eq!(hook["id"], "h1");
+
+    // the proc in the other namespace is never sent to the webhook
+    send_with(port, &ADMIN, "proc:start", Vars::new().with("id", "m1"))
+        .await
+        .unwrap();
+    send_with(port, &a, "proc:start", Vars::new().with("id", "m1"))
+        .await
+        .unwrap();
+
+    let mut dead = Value::Null;
+    for _ in 0..50 {
+        dead = send_with(port, &a, "webhook:failed:ls", Vars::new())
+            .await
+            .unwrap();
+        if dead["count"] == 1 {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    assert_eq!(dead["count"], 1);
+    let row = &dead["rows"][0];
+    assert_eq!(row["hook"], "h1");
+    assert_eq!(row["status"], "failed");
+    assert_eq!(row["attempts"], 2);
+    assert_eq!(row["payload"]["mid"], "m1");
+    assert!(row["last_error"].as_str().unwrap().contains("500"));
+    assert_eq!(requests.lock().unwrap().len(), 2);
+
+    // the dead letters of the other namespaces are never listed
+    let b = [("authorization", "Bearer team-b")];
+    let dead = send_with(port, &b, "webhook:failed:ls", Vars::new())
+        .await
+        .unwrap();
+    assert_eq!(dead["count"], 0);
+    let dead = send_with(port, &ADMIN, "webhook:failed:ls", Vars::new())
+        .await
+        .unwrap();
+    assert_eq!(dead["rows"][0]["hook"], "team-a/h1");
+
+    // the same dead letters are in the dead-letter view of msg:ls
+    let view = send_with(port, &a, "msg:ls", Vars::new().with("dead_letter", true))
+        .await
+        .unwrap();
+    assert_eq!(view["count"], 1);
+    assert_eq!(view["rows"][0]["hook"], "h1");
+    assert_eq!(view["rows"][0]["status"], "failed");
+    let view = send_with(port, &b, "msg:ls", Vars::new().with("dead_letter", true))
+        .await
+        .unwrap();
+    assert_eq!(view["count"], 0);
+
+    // the dead letters are removed with the webhook
+    send_with(port, &a, "webhook:rm", Vars::new().with("id", "h1"))
+        .await
+        .unwrap();
+    let dead = send_with(port, &a, "webhook:failed:ls", Vars::new())
+        .await
+        .unwrap();
+    assert_eq!(dead["count"], 0);
+
+    // the removed webhook is never sent to, and the created one is sent to at once
+    send_with(port, &a, "proc:start", Vars::new().with("id", "m1"))
+        .await
+        .unwrap();
+    tokio::time::sleep(Duration::from_millis(300)).await;
+    assert_eq!(requests.lock().unwrap().len(), 2);
+    send_with(
+        port,
+        &a,
+        "webhook:create",
+        Vars::new()
+            .with("id", "h2")
+            .with("url", "http://127.0.0.1:10132/hook")
+            .with("secret", "s3cret")
+            .with("type", "irq"),
+    )
+    .await
+    .unwrap();
+    send_with(port, &a, "proc:start", Vars::new().with("id", "m1"))
+        .await
+        .unwrap();
+    for _ in 0..30 {
+        if requests.lock().unwrap().len() > 2 {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    assert!(requests.lock().unwrap().len() > 2);
+}
+
+#[tokio::test]
+async fn grpc_trigger() {
+    let port = 10133;
+    let server = ServerOptions {
+        namespace: tenants(),
+        trigger: crate::trigger::TriggerOptions { port: Some(10134) },
+        ..Default::default()
+    };
+    serve_with("grpc_trigger", port, server).await;
+    let a = [("authorization", "Bearer team-a")];
+    let model = "id: m1\nsteps:\n  - id: step1\n    acts:\n      - act: irq\n        key: act1\n";
+    send_with(port, &a, "model:deploy", Vars::new().with("model", model))
+        .await
+        .unwrap();
+    let trigger = send_with(
+        port,
+        &a,
+        "trigger:create",
+        Vars::new()
+            .with("id", "t1")
+            .with("path", "forms/order")
+            .with("secret", "s3cret")
+            .with("mid", "m1")
+            .with(
+                "mapping",
+                serde_json::json!({ "name": "/user/name", "total": "/order/total" }),
+            )
+            .with("vars", Vars::new().with("source", "form")),
+    )
+    .await
+    .unwrap();
+    assert_eq!(trigger["path"], "/team-a/forms/order");
+    assert_eq!(trigger["mid"], "m1");
+    assert_eq!(trigger["secret"], "***");
+
+    // the path is under the namespace, so the tenants never take the paths of each other
+    let b = [("authorization", "Bearer team-b")];
+    send_with(port, &b, "model:deploy", Vars::new().with("model", model))
+        .await
+        .unwrap();
+    let trigger = send_with(
+        port,
+        &b,
+        "trigger:create",
+        Vars::new()
+            .with("id", "t1")
+            .with("path", "/forms/order/")
+            .with("secret", "b-secret")
+            .with("mid", "m1"),
+    )
+    .await
+    .unwrap();
+    assert_eq!(trigger["path"], "/team-b/forms/order");
+    for path in ["../team-a/forms/order", "forms/./order", "forms//order"] {
+        let err = send_with(
+            port,
+            &b,
+            "trigger:create",
+            Vars::new()
+                .with("path", path)
+                .with("secret", "s")
+                .with("mid", "m1"),
+        )
+        .await
+        .unwrap_err();
+        assert!(err.message().contains("invalid trigger path"), "{path}");
+    }
+    let err = send_with(
+        port,
+        &ADMIN,
+        "trigger:create",
+        Vars::new()
+            .with("path", "/team-a/forms/order/")
+            .with("secret", "s")
+            .with("mid", "m1"),
+    )
+    .await
+    .unwrap_err();
+    assert!(err.message().contains("already used"));
+
+    let client = reqwest::Client::new();
+    let url = "http://127.0.0.1:10134/team-a/forms/order";
+    let resp = client
+        .post(url)
+        .bearer_auth("b-secret")
+        .body("{}")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 401);
+    let resp = client
+        .post("http://127.0.0.1:10134/forms/order")
+        .bearer_auth("s3cret")
+        .body("{}")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 404);
+    let body = r#"{ "user": { "name": "Tom" }, "order": { "total": 12.5 } }"#;
+    let resp = client
+        .post(url)
+        .header(crate::trigger::SECRET_HEADER, "wrong")
+        .body(body)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 401);
+    let resp = client
+        .post("http://127.0.0.1:10134/forms/unknown")
+        .bearer_auth("s3cret")
+        .body(body)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 404);
+    let resp = client
+        .post(url)
+        .bearer_auth("s3cret")
+        .body("not json")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 400);
+
+    // the large body is rejected by its declared length before it is sent, or while it is streamed
+    let post = |head: &'static str, body: Vec<u8>| async move {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+        let mut stream = tokio::net::TcpStream::connect("127.0.0.1:10134")
+            .await
+            .unwrap();
+        let head = format!("POST /team-a/forms/order HTTP/1.1\r\nhost: 127.0.0.1\r\nauthorization: Bearer s3cret\r\n{head}\r\n");
+        stream.write_all(head.as_bytes()).await.unwrap();
+        let _ = stream.write_all(&body).await;
+        let mut resp = vec![0; 64];
+        let n = tokio::time::timeout(Duration::from_secs(2), stream.read(&mut resp))
+            .await
+            .unwrap()
+            .unwrap();
+        String::from_utf8_lossy(&resp[..n]).to_string()
+    };
+    let resp = post("content-length: 2097152\r\n", Vec::new()).await;
+    assert!(resp.starts_with("HTTP/1.1 413"), "{resp}");
+    let mut chunked = Vec::new();
+    for _ in 0..2 {
+        chunked.extend_from_slice(b"100000\r\n");
+        chunked.extend(std::iter::repeat_n(b' ', 0x100000));
+        chunked.extend_from_slice(b"\r\n");
+    }
+    let resp = post("transfer-encoding: chunked\r\n", chunked).await;
+    assert!(resp.starts_with("HTTP/1.1 413"), "{resp}");
+
+    let resp = client
+        .post(url)
+        .header(crate::trigger::SECRET_HEADER, "s3cret")
+        .body(body)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    let ret = serde_json::from_str::<Value>(&resp.text().await.unwrap()).unwrap();
+    let pid = ret["pid"].as_str().unwrap();
+
+    // the start by the trigger is audited in the namespace of the model
+    let audits = send_with(
+        port,
+        &a,
+        "audit:ls",
+        Vars::new().with("query_by", vec![("user", "system")]),
+    )
+    .await
+    .unwrap();
+    assert_eq!(audits["count"], 1);
+    assert_eq!(audits["rows"][0]["peer"], "trigger:team-a/t1");
+    assert_eq!(audits["rows"][0]["pid"], pid);
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    let vars = send_with(port, &a, "proc:vars:get", Vars::new().with("pid", pid))
+        .await
+        .unwrap();
+    assert_eq!(vars["name"], "Tom");
+    assert_eq!(vars["total"], 12.5);
+    assert_eq!(vars["source"], "form");
+
+    let triggers = send_with(port, &a, "trigger:ls", Vars::new())
+        .await
+        .unwrap();
+    assert_eq!(triggers["count"], 1);
+    assert_eq!(triggers["rows"][0]["runs"], 1);
+    assert_eq!(triggers["rows"][0]["last_pid"], pid);
+    assert_eq!(triggers["rows"][0]["secret"], "***");
+
+    send_with(port, &a, "trigger:rm", Vars::new().with("id", "t1"))
+        .await
+        .unwrap();
+    let resp = client
+        .post(url)
+        .bearer_auth("s3cret")
+        .body(body)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 404);
+}
+
+/// subscribe the irq messages with the replay metadata
+async fn subscribe_with(
+    port: u16,
+    client_id: &str,
+    replay: Option<&str>,
+) -> tonic::Streaming<Message> {
+    let mut client = ActsServiceClient::connect(format!("http://127.0.0.1:{port}"))
+        .await
+        .unwrap();
+    let mut request = Request::new(MessageOptions {
+        client_id: client_id.to_string(),
+        r#type: "irq".to_string(),
+        state: "created".to_string(),
+        tag: "*".to_string(),
+        key: "*".to_string(),
+    });
+    if let Some(replay) = replay {
+        request
+            .metadata_mut()
+            .insert(crate::cursor::REPLAY_METADATA, replay.parse().unwrap());
+    }
+    client.on_message(request).await.unwrap().into_inner()
+}
+
+/// the pids of the next messages in the stream
+async fn next_pids(stream: &mut tonic::Streaming<Message>, count: usize) -> Vec<String> {
+    let mut pids = Vec::new();
+    for _ in 0..count {
+        let message = tokio::time::timeout(Duration::from_secs(2), stream.next())
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap();
+        let data: Value = serde_json::from_slice(message.data()).unwrap();
+        pids.push(data["pid"].as_str().unwrap().to_string());
+    }
+    pids
+}
+
+#[tokio::test]
+async fn grpc_cursor_replay() {
+    let port = 10135;
+    serve_with("grpc_cursor_replay", port, ServerOptions::default()).await;
+    let model = "id: m1\nsteps:\n  - id: step1\n    acts:\n      - act: irq\n        key: act1\n";
+    send_with(port, &[], "model:deploy", Vars::new().with("model", model))
+        .await
+        .unwrap();
+    let start = || async {
+        send_with(port, &[], "proc:start", Vars::new().with("id", "m1"))
+            .await
+            .unwrap()
+            .as_str()
+            .unwrap()
+            .to_string()
+    };
+
+    let mut stream = subscribe_with(port, "c1", None).await;
+    let p1 = start().await;
+    let message = tokio::time::timeout(Duration::from_secs(2), stream.next())
+        .await
+        .unwrap()
+        .unwrap()
+        .unwrap();
+    send_with(port, &[], "msg:ack", Vars::new().with("id", &message.seq))
+        .await
+        .unwrap();
+    drop(stream);
+
+    // the messages are emitted while the client is down
+    let p2 = start().await;
+    let p3 = start().await;
+    tokio::time::sleep(Duration::from_millis(200)).await;
+
+    // the messages after the acked one are replayed in order before the live ones
+    let mut stream = subscribe_with(port, "c1", Some("acked")).await;
+    let p4 = start().await;
+    assert_eq!(
+        next_pids(&mut stream, 3).await,
+        [p2.clone(), p3.clone(), p4.clone()]
+    );
+    drop(stream);
+
+    // replay all of the messages from the first seq
+    let mut stream = subscribe_with(port, "c2", Some("seq:1")).await;
+    assert_eq!(next_pids(&mut stream, 4).await, [p1, p2, p3, p4]);
+    drop(stream);
+
+    // nothing is replayed after the time
+    let time = format!("time:{}", crate::utils::time_millis() + 60_000);
+    let mut stream = subscribe_with(port, "c3", Some(&time)).await;
+    let p5 = start().await;
+    assert_eq!(next_pids(&mut stream, 1).await, [p5]);
+
+    let mut client = ActsServiceClient::connect(format!("http://127.0.0.1:{port}"))
+        .await
+        .unwrap();
+    let mut request = Request::new(MessageOptions {
+        client_id: "c4".to_string(),
+        r#type: "*".to_string(),
+        state: "*".to_string(),
+        tag: "*".to_string(),
+        key: "*".to_string(),
+    });
+    request
+        .metadata_mut()
+        .insert(crate::cursor::REPLAY_METADATA, "latest".parse().unwrap());
+    let err = client.on_message(request).await.unwrap_err();
+    assert_eq!(err.code(), Code::InvalidArgument);
+}
+
+#[test]
+fn cursor_ack_low_water() {
+    let store = Store::memory();
+    for (mid, seq) in [("m1", 1), ("m2", 2), ("m3", 3)] {
+        cursor::deliver(&store, "c1", mid, seq).unwrap();
+    }
+    let acked = || cursor::position(&store, "c1", &cursor::Replay::Acked).unwrap() - 1;
+
+    // the cursor stops before the first message in flight
+    assert!(cursor::ack(&store, "m2").unwrap());
+    assert_eq!(acked(), 0);
+    assert!(cursor::ack(&store, "m1").unwrap());
+    assert_eq!(acked(), 2);
+    assert!(cursor::ack(&store, "m3").unwrap());
+    assert_eq!(acked(), 3);
+    assert!(!cursor::ack(&store, "m3").unwrap());
+}
+
+#[test]
+fn cursor_journal_flush() {
+    let store = Arc::new(Store::memory());
+    let journal = cursor::Journal::new(&store).unwrap();
+    let message = acts::Message {
+        id: "m1".to_string(),
+        ..Default::default()
+    };
+    assert_eq!(journal.append(&message), 1);
+
+    // the appended entries are written by the flush
+    assert!(cursor::read(&store, 1, cursor::BATCH).unwrap().is_empty());
+    journal.flush().unwrap();
+    let entries = cursor::read(&store, 1, cursor::BATCH).unwrap();
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].mid, "m1");
+
+    let time = entries[0].create_time;
+    assert_eq!(
+        cursor::position(&store, "c1", &cursor::Replay::Time(time)).unwrap(),
+        1
+    );
+    assert_eq!(cursor::Journal::new(&store).unwrap().append(&message), 2);
+}
+
+#[tokio::test]
+async fn grpc_consumer_group() {
+    let port = 10136;
+    serve_with("grpc_consumer_group", port, ServerOptions::default()).await;
+    let model = "id: m1\nsteps:\n  - id: step1\n    acts:\n      - act: irq\n        key: act1\n";
+    send_with(port, &[], "model:deploy", Vars::new().with("model", model))
+        .await
+        .unwrap();
+    let join = |client_id: &'static str| async move {
+        let mut client = ActsServiceClient::connect(format!("http://127.0.0.1:{port}"))
+            .await
+            .unwrap();
+        let mut request = Request::new(MessageOptions {
+            client_id: client_id.to_string(),
+            r#type: "irq".to_string(),
+            state: "created".to_string(),
+            tag: "*".to_string(),
+            key: "*".to_string(),
+        });
+        request
+            .metadata_mut()
+            .insert(crate::group::GROUP_METADATA, "workers".parse().unwrap());
+        client.on_message(request).await.unwrap().into_inner()
+    };
+    let start = || async {
+        send_with(port, &[], "proc:start", Vars::new().with("id", "m1"))
+            .await
+            .unwrap()
+            .as_str()
+            .unwrap()
+            .to_string()
+    };
+    async fn next(stream: &mut tonic::Streaming<Message>) -> Option<(String, String)> {
+        let next = tokio::time::timeout(Duration::from_millis(500), stream.next()).await;
+        next.ok().flatten().map(|message| {
+            let message = message.unwrap();
+            let data: Value = serde_json::from_slice(message.data()).unwrap();
+            (message.seq, data["pid"].as_str().unwrap().to_string())
+        })
+    }
+
+    let mut w1 = join("w1").await;
+    let mut w2 = join("w2").await;
+    let mut all = subscribe_with(port, "observer", None).await;
+    let p1 = start().await;
+    let p2 = start().await;
+    assert_eq!(next_pids(&mut all, 2).await, [p1.clone(), p2.clone()]);
+
+    // each message goes to exactly one member
+    let (m1, pid1) = next(&mut w1).await.unwrap();
+    let (m2, pid2) = next(&mut w2).await.unwrap();
+    let mut pids = vec![pid1.clone(), pid2];
+    pids.sort();
+    let mut expected = vec![p1, p2];
+    expected.sort();
+    assert_eq!(pids, expected);
+    assert!(next(&mut w1).await.is_none());
+    assert!(next(&mut w2).await.is_none());
+    send_with(port, &[], "msg:ack", Vars::new().with("id", &m2))
+        .await
+        .unwrap();
+
+    // the unacked message of the disconnected member goes to the other one
+    drop(w1);
+    let (redelivered, pid) = next(&mut w2).await.unwrap();
+    assert_eq!(redelivered, m1);
+    assert_eq!(pid, pid1);
+    send_with(port, &[], "msg:ack", Vars::new().with("id", &m1))
+        .await
+        .unwrap();
+
+    // the new messages go to the live member only
+    let p3 = start().await;
+    assert_eq!(next(&mut w2).await.unwrap().1, p3);
+}
+
+#[tokio::test]
+async fn grpc_consumer_group_deadline() {
+    let port = 10147;
+    let options = ServerOptions {
+        group: crate::group::GroupOptions { ack_timeout: 1 },
+        ..Default::default()
+    };
+    serve_with("grpc_consumer_group_deadline", port, options).await;
+    let model = "id: m1\nsteps:\n  - id: step1\n    acts:\n      - act: irq\n        key: act1\n";
+    send_with(port, &[], "model:deploy", Vars::new().with("model", model))
+        .await
+        .unwrap();
+    let join = |client_id: &'static str, state: &'static str| async move {
+        let mut client = ActsServiceClient::connect(format!("http://127.0.0.1:{port}"))
+            .await
+            .unwrap();
+        let mut request = Request::new(MessageOptions {
+            client_id: client_id.to_string(),
+            r#type: "irq".to_string(),
+            state: state.to_string(),
+            tag: "*".to_string(),
+            key: "*".to_string(),
+        });
+        request
+            .metadata_mut()
+            .insert(crate::group::GROUP_METADATA, "workers".parse().unwrap());
+        client
+            .on_message(request)
+            .await
+            .map(|resp| resp.into_inner())
+    };
+    async fn next(stream: &mut tonic::Streaming<Message>) -> Option<String> {
+        let next = tokio::time::timeout(Duration::from_millis(2500), stream.next()).await;
+        next.ok().flatten().map(|message| message.unwrap().seq)
+    }
+
+    let mut w1 = join("w1", "created").await.unwrap();
+    let mut w2 = join("w2", "created").await.unwrap();
+
+    // the member with the other filters is rejected
+    let err = join("w3", "*").await.unwrap_err();
+    assert_eq!(err.code(), Code::FailedPrecondition);
+
+    // the message which is not acked before the deadline goes to the next member
+    send_with(port, &[], "proc:start", Vars::new().with("id", "m1"))
+        .await
+        .unwrap();
+    let first = next(&mut w1).await.unwrap();
+    assert_eq!(next(&mut w2).await.unwrap(), first);
+    send_with(port, &[], "msg:ack", Vars::new().with("id", &first))
+        .await
+        .unwrap();
+    drop(w1);
+    drop(w2);
+    tokio::time::sleep(Duration::from_millis(200)).await;
+
+    // the group is removed with the last member, so the new member sets the filters
+    let mut w3 = join("w3", "*").await.unwrap();
+    send_with(port, &[], "proc:start", Vars::new().with("id", "m1"))
+        .await
+        .unwrap();
+    assert!(next(&mut w3).await.is_some());
+}
+
+#[tokio::test]
+async fn grpc_dead_letter() {
+    let port = 10137;
+    let options = Config {
+        tick_interval_secs: 1,
+        max_message_retry_times: 2,
+        ..config("grpc_dead_letter")
+    };
+    tokio::spawn(async move {
+        let addr = format!("127.0.0.1:{port}").parse().unwrap();
+        grpc::start_with(addr, &options, &ServerOptions::default())
+            .await
+            .unwrap();
+    });
+    connect(port).await;
+    let model = "id: m1\nsteps:\n  - id: step1\n    acts:\n      - act: irq\n        key: act1\n";
+    send_with(port, &[], "model:deploy", Vars::new().with("model", model))
+        .await
+        .unwrap();
+    let dead_letters = || async {
+        send_with(port, &[], "msg:dlq:ls", Vars::new())
+            .await
+            .unwrap()
+    };
+    let wait_dead = |count: usize| async move {
+        for _ in 0..100 {
+            let page = dead_letters().await;
+            if page["rows"].as_array().unwrap().len() == count {
+                return page;
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+        panic!("the messages are not dead");
+    };
+
+    // the message which is never acked is dead after the retries
+    let mut stream = subscribe_with(port, "c1", None).await;
+    let pid = send_with(port, &[], "proc:start", Vars::new().with("id", "m1"))
+        .await
+        .unwrap()
+        .as_str()
+        .unwrap()
+        .to_string();
+    assert_eq!(next_pids(&mut stream, 1).await[0], pid);
+    let page = wait_dead(1).await;
+    let row = &page["rows"][0];
+    assert_eq!(row["pid"], pid);
+    assert_eq!(row["status"], "error");
+    assert_eq!(row["retry_times"], 2);
+    assert_eq!(row["reason"], "not acked in 1s");
+    assert_eq!(row["attempts"], 2);
+    let id = row["id"].as_str().unwrap().to_string();
+
+    // the retried message goes to the subscriber again, and is dead until it is acked
+    let retry = |user: &'static str| {
+        let id = id.clone();
+        async move {
+            send_with(
+                port,
+                &[("x-acts-user", user)],
+                "msg:dlq:retry",
+                Vars::new().with("id", &id),
+            )
+            .await
+            .unwrap()
+        }
+    };
+    assert_eq!(retry("ops").await, 1);
+    assert_eq!(next_pids(&mut stream, 1).await[0], pid);
+    assert_eq!(dead_letters().await["rows"].as_array().unwrap().len(), 1);
+
+    // the disconnected client fails to receive the retried message
+    drop(stream);
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    retry("ops").await;
+    let mut history = Value::Null;
+    for _ in 0..50 {
+        history = send_with(port, &[], "msg:dlq:history", Vars::new().with("id", &id))
+            .await
+            .unwrap();
+        if history.to_string().contains("disconnected") {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    let page = wait_dead(1).await;
+    assert_eq!(page["rows"][0]["reason"], "client 'c1' is disconnected");
+    let kinds = history
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|attempt| attempt["kind"].as_str().unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(&kinds[..3], ["timeout", "timeout", "retried"]);
+    assert!(kinds.contains(&"disconnected"));
+    assert!(history[2]["reason"]
+        .as_str()
+        .unwrap()
+        .starts_with("retried by ops@"));
+
+    // only the dead messages are retried
+    let err = send_with(
+        port,
+        &[],
+        "msg:dlq:retry",
+        Vars::new().with("id", "unknown"),
+    )
+    .await
+    .unwrap_err();
+    assert_eq!(err.code(), Code::Internal);
+
+    // purge by the query
+    let query_by = vec![("pid".to_string(), "other".to_string())];
+    let ret = send_with(
+        port,
+        &[],
+        "msg:dlq:purge",
+        Vars::new().with("query_by", &query_by),
+    )
+    .await
+    .unwrap();
+    assert_eq!(ret, 0);
+    let query_by = vec![("pid".to_string(), pid.clone())];
+    let ret = send_with(
+        port,
+        &[],
+        "msg:dlq:purge",
+        Vars::new().with("query_by", &query_by),
+    )
+    .await
+    .unwrap();
+    assert_eq!(ret, 1);
+    assert!(dead_letters().await["rows"].as_array().unwrap().is_empty());
+    let history = send_with(port, &[], "msg:dlq:history", Vars::new().with("id", &id))
+        .await
+        .unwrap();
+    assert!(history.as_array().unwrap().is_empty());
+}
+
+/// subscribe the irq messages with the metadata, such as the projection and encoding
+async fn subscribe_metadata(
+    port: u16,
+    client_id: &str,
+    metadata: &[(&'static str, &str)],
+) -> Result<tonic::Streaming<Message>, Status> {
+    let mut client = ActsServiceClient::connect(format!("http://127.0.0.1:{port}"))
+        .await
+        .unwrap();
+    let mut request = Request::new(MessageOptions {
+        client_id: client_id.to_string(),
+        r#type: "irq".to_string(),
+        state: "created".to_string(),
+        tag: "*".to_string(),
+        key: "*".to_string(),
+    });
+    for (key, value) in metadata {
+        request.metadata_mut().insert(*key, value.parse().unwrap());
+    }
+    Ok(client.on_message(request).await?.into_inner())
+}
+
+#[tokio::test]
+async fn grpc_message_transform() {
+    use crate::transform::{ENCODING_METADATA, FIELDS_METADATA};
+    use prost::Message as _;
+
+    let port = 10138;
+    serve_with("grpc_message_transform", port, ServerOptions::default()).await;
+    let model = "id: m1\nsteps:\n  - id: step1\n    acts:\n      - act: irq\n        key: act1\n";
+    send_with(port, &[], "model:deploy", Vars::new().with("model", model))
+        .await
+        .unwrap();
+    async fn next(stream: &mut tonic::Streaming<Message>) -> Message {
+        tokio::time::timeout(Duration::from_secs(2), stream.next())
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap()
+    }
+
+    let mut json = subscribe_metadata(
+        port,
+        "json",
+        &[(FIELDS_METADATA, "pid,state,$.inputs.step.node_id")],
+    )
+    .await
+    .unwrap();
+    let mut msgpack = subscribe_metadata(
+        port,
+        "msgpack",
+        &[(FIELDS_METADATA, "key"), (ENCODING_METADATA, "msgpack")],
+    )
+    .await
+    .unwrap();
+    let mut protobuf = subscribe_metadata(
+        port,
+        "protobuf",
+        &[
+            (FIELDS_METADATA, "pid,key"),
+            (ENCODING_METADATA, "protobuf"),
+        ],
+    )
+    .await
+    .unwrap();
+    let pid = send_with(port, &[], "proc:start", Vars::new().with("id", "m1"))
+        .await
+        .unwrap();
+
+    // only the selected fields are sent
+    let message = next(&mut json).await;
+    let data: Value = serde_json::from_slice(message.data()).unwrap();
+    assert_eq!(
+        data,
+        serde_json::json!({
+            "pid": pid,
+            "state": "created",
+            "inputs": { "step": { "node_id": "step1" } },
+        })
+    );
+
+    // {"key": "act1"} in MessagePack
+    let message = next(&mut msgpack).await;
+    assert_eq!(message.data(), b"\x81\xa3key\xa4act1");
+    assert!(!message.seq.is_empty());
+
+    let message = next(&mut protobuf).await;
+    let data = prost_types::Struct::decode(message.data()).unwrap();
+    assert_eq!(
+        data.fields["pid"].kind,
+        Some(prost_types::value::Kind::StringValue(
+            pid.as_str().unwrap().to_string()
+        ))
+    );
+    assert_eq!(data.fields.len(), 2);
+
+    // the invalid options are rejected
+    for metadata in [(ENCODING_METADATA, "xml"), (FIELDS_METADATA, "inputs[0]")] {
+        let err = subscribe_metadata(port, "invalid", &[metadata])
+            .await
+            .unwrap_err();
+        assert_eq!(err.code(), Code::InvalidArgument);
+    }
+}
+
+#[tokio::test]
+async fn grpc_compression() {
+    use tonic::codec::CompressionEncoding;
+
+    let port = 10139;
+    serve_with("grpc_compression", port, ServerOptions::default()).await;
+    let connect = |gzip: bool| async move {
+        let client = ActsServiceClient::connect(format!("http://127.0.0.1:{port}"))
+            .await
+            .unwrap();
+        if gzip {
+            client
+                .accept_compressed(CompressionEncoding::Gzip)
+                .send_compressed(CompressionEncoding::Gzip)
+        } else {
+            client
+        }
+    };
+    let request = |name: &str, data: Vars| {
+        Request::new(Message {
+            name: name.to_string(),
+            seq: acts_channel::create_seq(),
+            ack: None,
+            data: Some(data.to_bytes()),
+        })
+    };
+
+    // the compressed requests are accepted
+    let mut gzip = connect(true).await;
+    let model = "id: m1\nsteps:\n  - id: step1\n    acts:\n      - act: irq\n        key: act1\n";
+    gzip.send(request("model:deploy", Vars::new().with("model", model)))
+        .await
+        .unwrap();
+    let mut stream = gzip
+        .on_message(MessageOptions {
+            client_id: "gzip".to_string(),
+            r#type: "irq".to_string(),
+            state: "created".to_string(),
+            tag: "*".to_string(),
+            key: "*".to_string(),
+        })
+        .await
+        .unwrap()
+        .into_inner();
+    let big = (0..20000).map(|i| format!("{i},")).collect::<String>();
+    let resp = gzip
+        .send(request(
+            "proc:start",
+            Vars::new().with("id", "m1").with("big", &big),
+        ))
+        .await
+        .unwrap()
+        .into_inner();
+    let pid: Value = serde_json::from_slice(resp.data()).unwrap();
+
+    // the stream message is compressed too
+    let message = tokio::time::timeout(Duration::from_secs(2), stream.next())
+        .await
+        .unwrap()
+        .unwrap()
+        .unwrap();
+    let data: Value = serde_json::from_slice(message.data()).unwrap();
+    assert_eq!(data["pid"], pid);
+
+    // the response is compressed for the client which accepts gzip only
+    for (client, encoding) in [(gzip, Some("gzip")), (connect(false).await, None)] {
+        let mut client = client;
+        let resp = client
+            .send(request("proc:vars:get", Vars::new().with("pid", &pid)))
+            .await
+            .unwrap();
+        assert_eq!(
+            resp.metadata()
+                .get("grpc-encoding")
+                .map(|v| v.to_str().unwrap()),
+            encoding
+        );
+        let vars: Value = serde_json::from_slice(resp.into_inner().data()).unwrap();
+        assert_eq!(vars["big"], big);
+    }
+
+    assert!(crate::compression::encoding("gzip").unwrap().is_some());
+    assert!(crate::compression::encoding("zstd").unwrap().is_some());
+    assert!(crate::compression::encoding("none").unwrap().is_none());
+    assert!(crate::compression::encoding("br").is_err());
+}
+
+#[tokio::test]
+async fn compression_frames() {
+    use crate::compression::{Encoding, Frames, Transform};
+
+    let frame = |compressed: bool, data: &[u8]| {
+        let mut ret = vec![compressed as u8];
+        ret.extend_from_slice(&(data.len() as u32).to_be_bytes());
+        ret.extend_from_slice(data);
+        ret
+    };
+    let frames = |body: &[u8]| {
+        let mut ret = Vec::new();
+        let mut rest = body;
+        while !rest.is_empty() {
+            let len = u32::from_be_bytes(rest[1..5].try_into().unwrap()) as usize;
+            ret.push((rest[0] == 1, rest[5..5 + len].to_vec()));
+            rest = &rest[5 + len..];
+        }
+        ret
+    };
+    let small = vec![b'a'; 100];
+    let big = (0..2000).map(|i| format!("{i},")).collect::<String>();
+    let body = [frame(false, &small), frame(false, big.as_bytes())].concat();
+
+    for encoding in [Encoding::Gzip, Encoding::Zstd] {
+        // the message which is smaller than the threshold is not compressed in the same stream
+        let compressed = Frames::new(
+            hyper::Body::from(body.clone()),
+            Some(Transform::Compress(encoding, 1024)),
+        );
+        let compressed = hyper::body::to_bytes(compressed).await.unwrap();
+        let messages = frames(&compressed);
+        assert_eq!(messages[0], (false, small.clone()));
+        assert!(messages[1].0);
+        assert!(messages[1].1.len() < big.len());
+
+        // the frames are split across the chunks
+        let chunks = compressed
+            .chunks(7)
+            .map(|chunk| Ok::<_, std::io::Error>(chunk.to_vec()))
+            .collect::<Vec<_>>();
+        let decompressed = Frames::new(
+            hyper::Body::wrap_stream(tokio_stream::iter(chunks)),
+            Some(Transform::Decompress(encoding)),
+        );
+        let decompressed = hyper::body::to_bytes(decompressed).await.unwrap();
+        assert_eq!(decompressed.to_vec(), body);
+    }
+
+    let incomplete = Frames::new(
+        hyper::Body::from(body[..body.len() - 1].to_vec()),
+        Some(Transform::Decompress(Encoding::Zstd)),
+    );
+    assert!(hyper::body::to_bytes(incomplete).await.is_err());
+}
+
+#[tokio::test]
+async fn grpc_compression_zstd() {
+    use crate::compression::{CompressionOptions, Encoding};
+    use prost::Message as _;
+
+    let port = 10152;
+    let server = ServerOptions {
+        compression: CompressionOptions {
+            encoding: Some(Encoding::Zstd),
+            threshold: 1024,
+        },
+        ..Default::default()
+    };
+    serve_with("grpc_compression_zstd", port, server).await;
+    let model = "id: m1\nsteps:\n  - id: step1\n    acts:\n      - act: irq\n        key: act1\n";
+    send_with(port, &[], "model:deploy", Vars::new().with("model", model))
+        .await
+        .unwrap();
+    let big = (0..20000).map(|i| format!("{i},")).collect::<String>();
+    let ret = send_with(
+        port,
+        &[],
+        "proc:start",
+        Vars::new().with("id", "m1").with("big", &big),
+    )
+    .await
+    .unwrap();
+    let pid = ret.as_str().unwrap().to_string();
+
+    // send the zstd request as a client which is not built on tonic 0.8
+    let client = hyper::Client::builder()
+        .http2_only(true)
+        .build_http::<hyper::Body>();
+    let call = |accept: &'static str| {
+        let client = client.clone();
+        let message = Message {
+            name: "proc:vars:get".to_string(),
+            seq: acts_channel::create_seq(),
+            ack: None,
+            data: Some(Vars::new().with("pid", &pid).to_bytes()),
+        };
+        async move {
+            let data = zstd::encode_all(&message.encode_to_vec()[..], 0).unwrap();
+            let mut body = vec![1];
+            body.extend_from_slice(&(data.len() as u32).to_be_bytes());
+            body.extend_from_slice(&data);
+            let req = hyper::Request::post(format!(
+                "http://127.0.0.1:{port}/acts.grpc.ActsService/Send"
+            ))
+            .header("content-type", "application/grpc")
+            .header("te", "trailers")
+            .header("grpc-encoding", "zstd")
+            .header("grpc-accept-encoding", accept)
+            .body(hyper::Body::from(body))
+            .unwrap();
+            let resp = client.request(req).await.unwrap();
+            let encoding = resp.headers()["grpc-encoding"]
+                .to_str()
+                .unwrap()
+                .to_string();
+            let body = hyper::body::to_bytes(resp.into_body()).await.unwrap();
+            (encoding, body)
+        }
+    };
+
+    let (encoding, body) = call("zstd,gzip").await;
+    assert_eq!(encoding, "zstd");
+    assert_eq!(body[0], 1);
+    let message = Message::decode(&zstd::decode_all(&body[5..]).unwrap()[..]).unwrap();
+    let vars: Value = serde_json::from_slice(message.data()).unwrap();
+    assert_eq!(vars["big"], big);
+
+    // gzip is used for the clients which do not accept zstd
+    let (encoding, body) = call("gzip").await;
+    assert_eq!(encoding, "gzip");
+    assert_eq!(body[0], 1);
+}
@@ -0,0 +1,208 @@
+#[cfg(test)]
+mod tests;
+
+use acts::{ActError, Result};
+use prost::Message as _;
+use prost_types::{value::Kind, ListValue, Struct};
+use serde_json::{Map, Value};
+use std::str::FromStr;
+
+/// the metadata of on_message to send only the fields of the messages
+/// it is the comma separated dotted field paths of the object keys, such as 'id,pid,state,$.outputs.total'
+/// the '$.' and "['key']" forms of JSONPath are accepted, but the array index and the wildcard are not
+pub const FIELDS_METADATA: &str = "x-acts-fields";
+
+/// the metadata of on_message to choose the encoding of the message data
+/// it is one of 'json', 'msgpack' and 'protobuf'
+pub const ENCODING_METADATA: &str = "x-acts-encoding";
+
+/// the encoding of the message data which is sent to the subscriber
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum Encoding {
+    #[default]
+    Json,
+    MessagePack,
+    /// the google.protobuf.Struct message
+    Protobuf,
+}
+
+impl FromStr for Encoding {
+    type Err = ActError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "json" => Ok(Encoding::Json),
+            "msgpack" | "messagepack" => Ok(Encoding::MessagePack),
+            "protobuf" | "struct" => Ok(Encoding::Protobuf),
+            _ => Err(ActError::Action(format!(
+                "invalid encoding '{s}', it should be one of 'json', 'msgpack' and 'protobuf'"
+            ))),
+        }
+    }
+}
+
+/// the selected fields of the message, the nested ones keep their parents in the result
+#[derive(Debug, Clone, PartialEq)]
+pub struct Projection {
+    paths: Vec<Vec<String>>,
+}
+
+impl FromStr for Projection {
+    type Err = ActError;
+
+    /// parse the field names and the JSONPath selections of the object keys,
+    /// such as 'id', 'inputs.step', '$.outputs.total' and "$['a.b']"
+    fn from_str(s: &str) -> Result<Self> {
+        let mut paths = Vec::new();
+        let mut chars = s.chars().peekable();
+        loop {
+            paths.push(parse_path(s, &mut chars)?);
+            match chars.next() {
+                Some(',') => continue,
+                None => break,
+                Some(c) => {
+                    return Err(ActError::Action(format!(
+                        "invalid fields '{s}', unexpected '{c}'"
+                    )))
+                }
+            }
+        }
+        Ok(Self { paths })
+    }
+}
+
+impl Projection {
+    pub fn apply(&self, value: &Value) -> Value {
+        let mut ret = Map::new();
+        for path in &self.paths {
+            if let Some(selected) = path.iter().try_fold(value, |v, key| v.get(key)) {
+                insert(&mut ret, path, selected.clone());
+            }
+        }
+        Value::Object(ret)
+    }
+}
+
+/// the projection and encoding of one subscription
+#[derive(Debug, Default, Clone)]
+pub struct Transform {
+    pub projection: Option<Projection>,
+    pub encoding: Encoding,
+}
+
+impl Transform {
+    pub fn new(fields: Option<&str>, encoding: Option<&str>) -> Result<Self> {
+        Ok(Self {
+            projection: fields.map(str::parse).transpose()?,
+            encoding: encoding.map(str::parse).transpose()?.unwrap_or_default(),
+        })
+    }
+
+    /// the message data to send
+    pub fn encode(&self, value: &Value) -> Vec<u8> {
+        let projected;
+        let value = match &self.projection {
+            Some(projection) => {
+                projected = projection.apply(value);
+                &projected
+            }
+            None => value,
+        };
+        match self.encoding {
+            Encoding::Json => serde_json::to_vec(value).unwrap_or_default(),
+            Encoding::MessagePack => rmp_serde::to_vec(value).unwrap_or_default(),
+            Encoding::Protobuf => {
+                let fields = match value {
+                    Value::Object(map) => to_struct(map),
+                    _ => Struct::default(),
+                };
+                fields.encode_to_vec()
+            }
+        }
+    }
+}
+
+fn parse_path(s: &str, chars: &mut std::iter::Peekable<std::str::Chars>) -> Result<Vec<String>> {
+    let invalid = |reason: &str| ActError::Action(format!("invalid fields '{s}', {reason}"));
+    while chars.next_if(|c| c.is_whitespace()).is_some() {}
+    if chars.next_if_eq(&'$').is_some()
+        && chars.next_if_eq(&'.').is_none()
+        && chars.peek() != Some(&'[')
+    {
+        return Err(invalid("the JSONPath should start with '$.' or '$['"));
+    }
+
+    let mut path = Vec::new();
+    loop {
+        let key = if chars.next_if_eq(&'[').is_some() {
+            let Some(quote) = chars.next_if(|c| *c == '\'' || *c == '"') else {
+                return Err(invalid("only the quoted object keys are supported in '[]'"));
+            };
+            let key = chars
+                .by_ref()
+                .take_while(|c| *c != quote)
+                .collect::<String>();
+            if chars.next() != Some(']') {
+                return Err(invalid("the ']' is expected"));
+            }
+            key
+        } else {
+            let mut key = String::new();
+            while let Some(c) = chars.next_if(|c| !matches!(c, '.' | '[' | ',')) {
+                key.push(c);
+            }
+            let key = key.trim().to_string();
+            if key.is_empty() {
+                return Err(invalid("the field name is empty"));
+            }
+            if key == "*" {
+                return Err(invalid("the wildcard is not supported"));
+            }
+            key
+        };
+        path.push(key);
+        if chars.next_if_eq(&'.').is_none() && chars.peek() != Some(&'[') {
+            return Ok(path);
+        }
+    }
+}
+
+fn insert(map: &mut Map<String, Value>, path: &[String], value: Value) {
+    match path {
+        [key] => {
+            map.insert(key.clone(), value);
+        }
+        [key, rest @ ..] => {
+            let node = map
+                .entry(key.clone())
+                .or_insert_with(|| Value::Object(Map::new()));
+            if let Value::Object(node) = node {
+                insert(node, rest, value);
+            }
+        }
+        [] => {}
+    }
+}
+
+fn to_struct(map: &Map<String, Value>) -> Struct {
+    Struct {
+        fields: map
+            .iter()
+            .map(|(key, value)| (key.clone(), to_value(value)))
+            .collect(),
+    }
+}
+
+fn to_value(value: &Value) -> prost_types::Value {
+    let kind = match value {
+        Value::Null => Kind::NullValue(0),
+        Value::Bool(v) => Kind::BoolValue(*v),
+        Value::Number(v) => Kind::NumberValue(v.as_f64().unwrap_or_default()),
+        Value::String(v) => Kind::StringValue(v.clone()),
+        Value::Array(items) => Kind::ListValue(ListValue {
+            values: items.iter().map(to_value).collect(),
+        }),
+        Value::Object(map) => Kind::StructValue(to_struct(map)),
+    };
+    prost_types::Value { kind: Some(kind) }
+}
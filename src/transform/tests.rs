@@ -0,0 +1,126 @@
+use super::{Encoding, Projection, Transform};
+use prost::Message as _;
+use prost_types::{value::Kind, Struct};
+use serde_json::json;
+
+#[test]
+fn transform_projection_parse() {
+    let projection = "id, inputs.step,$.outputs.total,$['a.b']['c'],$.x['y']"
+        .parse::<Projection>()
+        .unwrap();
+    assert_eq!(
+        projection.paths,
+        [
+            vec!["id"],
+            vec!["inputs", "step"],
+            vec!["outputs", "total"],
+            vec!["a.b", "c"],
+            vec!["x", "y"],
+        ]
+    );
+    for fields in ["", "id,", "$", "$id", "a[0]", "a.*", "$['a'"] {
+        assert!(fields.parse::<Projection>().is_err(), "{fields}");
+    }
+}
+
+#[test]
+fn transform_projection_apply() {
+    let message = json!({
+        "id": "m1",
+        "pid": "p1",
+        "inputs": { "step": { "node_id": "step1" }, "big": [1, 2, 3] },
+        "outputs": { "total": 10 },
+    });
+    let projection = "id,inputs.step.node_id,outputs.total,outputs.missing,unknown"
+        .parse::<Projection>()
+        .unwrap();
+    assert_eq!(
+        projection.apply(&message),
+        json!({
+            "id": "m1",
+            "inputs": { "step": { "node_id": "step1" } },
+            "outputs": { "total": 10 },
+        })
+    );
+}
+
+#[test]
+fn transform_msgpack_encode() {
+    let msgpack = Transform::new(None, Some("msgpack")).unwrap();
+    assert_eq!(msgpack.encode(&json!(null)), [0xc0]);
+    assert_eq!(msgpack.encode(&json!([true, false])), [0x92, 0xc3, 0xc2]);
+    assert_eq!(msgpack.encode(&json!(5)), [0x05]);
+    assert_eq!(msgpack.encode(&json!(200)), [0xcc, 200]);
+    assert_eq!(msgpack.encode(&json!(1000)), [0xcd, 0x03, 0xe8]);
+    assert_eq!(msgpack.encode(&json!(70000)), [0xce, 0, 1, 0x11, 0x70]);
+    assert_eq!(
+        msgpack.encode(&json!(u64::MAX)),
+        [0xcf, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff]
+    );
+    assert_eq!(msgpack.encode(&json!(-1)), [0xff]);
+    assert_eq!(msgpack.encode(&json!(-33)), [0xd0, 0xdf]);
+    assert_eq!(msgpack.encode(&json!(-1000)), [0xd1, 0xfc, 0x18]);
+    assert_eq!(
+        msgpack.encode(&json!(1.5)),
+        [0xcb, 0x3f, 0xf8, 0, 0, 0, 0, 0, 0]
+    );
+    assert_eq!(msgpack.encode(&json!("abc")), [0xa3, b'a', b'b', b'c']);
+    let long = "a".repeat(40);
+    let encoded = msgpack.encode(&json!(long));
+    assert_eq!(encoded[..2], [0xd9, 40]);
+    assert_eq!(encoded.len(), 42);
+    assert_eq!(msgpack.encode(&json!({ "a": 1 })), [0x81, 0xa1, b'a', 0x01]);
+    let items = vec![0; 20];
+    assert_eq!(msgpack.encode(&json!(items))[..3], [0xdc, 0, 20]);
+}
+
+#[test]
+fn transform_msgpack_roundtrip() {
+    let msgpack = Transform::new(None, Some("msgpack")).unwrap();
+    let message = json!({
+        "id": "m1",
+        "retry_times": 0,
+        "start_time": 1700000000000i64,
+        "offset": -40000,
+        "ratio": 0.25,
+        "acked": false,
+        "inputs": { "items": ["a".repeat(300), null, [1, 2, 3]], "big": u64::MAX },
+        "outputs": {},
+    });
+    let decoded = rmp_serde::from_slice::<serde_json::Value>(&msgpack.encode(&message)).unwrap();
+    assert_eq!(decoded, message);
+}
+
+#[test]
+fn transform_encoding() {
+    assert_eq!("json".parse::<Encoding>().unwrap(), Encoding::Json);
+    assert_eq!(
+        "msgpack".parse::<Encoding>().unwrap(),
+        Encoding::MessagePack
+    );
+    assert_eq!("protobuf".parse::<Encoding>().unwrap(), Encoding::Protobuf);
+    assert!("xml".parse::<Encoding>().is_err());
+
+    let message = json!({ "id": "m1", "retry_times": 2, "outputs": { "ok": true } });
+    let transform = Transform::new(Some("id,retry_times,outputs"), Some("protobuf")).unwrap();
+    let data = Struct::decode(transform.encode(&message).as_slice()).unwrap();
+    assert_eq!(
+        data.fields["id"].kind,
+        Some(Kind::StringValue("m1".to_string()))
+    );
+    assert_eq!(
+        data.fields["retry_times"].kind,
+        Some(Kind::NumberValue(2.0))
+    );
+    let Some(Kind::StructValue(outputs)) = &data.fields["outputs"].kind else {
+        panic!("outputs should be a struct");
+    };
+    assert_eq!(outputs.fields["ok"].kind, Some(Kind::BoolValue(true)));
+
+    // the json without projection is the same as the message
+    let transform = Transform::new(None, None).unwrap();
+    assert_eq!(
+        serde_json::from_slice::<serde_json::Value>(&transform.encode(&message)).unwrap(),
+        message
+    );
+}
@@ -0,0 +1,175 @@
+mod node;
+
+use crate::{model, store::Store};
+use acts::{Executor, ExecutorQuery, ProcInfo, Result, Step, TaskInfo, Workflow};
+use std::collections::HashMap;
+
+pub use node::TaskTree;
+
+const PAGE_SIZE: usize = 500;
+
+impl From<&TaskInfo> for TaskTree {
+    fn from(t: &TaskInfo) -> Self {
+        Self {
+            id: t.id.clone(),
+            nid: t.nid.clone(),
+            name: t.name.clone(),
+            r#type: t.r#type.clone(),
+            state: t.state.clone(),
+            tag: t.tag.clone(),
+            key: t.key.clone(),
+            start_time: t.start_time,
+            end_time: t.end_time,
+            timestamp: t.timestamp,
+            data: serde_json::from_str(&t.data)
+                .unwrap_or(serde_json::Value::String(t.data.clone())),
+            children: Vec::new(),
+        }
+    }
+}
+
+/// load all tasks of the proc and build them into the step/branch/act hierarchy
+pub fn proc_tasks(executor: &Executor, store: &Store, pid: &str) -> Result<Vec<TaskTree>> {
+    let proc = executor.proc().get(pid)?;
+    let tasks = tasks(executor, &proc)?;
+
+    let mut levels = HashMap::new();
+    if let Ok(workflow) = model::proc_model(executor, store, &proc) {
+        model_levels(&workflow, &mut levels);
+    }
+
+    Ok(build(&tasks, &levels))
+}
+
+/// all tasks of the proc in time order
+pub fn tasks(executor: &Executor, proc: &ProcInfo) -> Result<Vec<TaskInfo>> {
+    let pid = proc.id.as_str();
+    let mut tasks = proc.tasks.clone();
+    if tasks.is_empty() {
+        // the proc is not in the engine cache, load the tasks from store
+        let mut offset = 0;
+        loop {
+            let query = ExecutorQuery::new()
+                .with_query("pid", pid)
+                .with_offset(offset)
+                .with_count(PAGE_SIZE);
+            let page = executor.task().list(&query)?;
+            let len = page.rows.len();
+            tasks.extend(page.rows);
+            offset += len;
+            if len < PAGE_SIZE {
+                break;
+            }
+        }
+        tasks.sort_by_key(|a| a.timestamp);
+    }
+
+    Ok(tasks)
+}
+
+/// build the tree by the task prev chain
+/// the parent of a task is the first prev task with a lower level, which is the same rule as the engine
+pub fn build(tasks: &[TaskInfo], levels: &HashMap<String, usize>) -> Vec<TaskTree> {
+    let index: HashMap<&str, usize> = tasks
+        .iter()
+        .enumerate()
+        .map(|(i, t)| (t.id.as_str(), i))
+        .collect();
+
+    let mut cache = HashMap::new();
+    let mut roots = Vec::new();
+    let mut children: HashMap<usize, Vec<usize>> = HashMap::new();
+    for (i, task) in tasks.iter().enumerate() {
+        let level = task_level(i, tasks, &index, levels, &mut cache);
+        let mut parent = None;
+        let mut prev = task.prev.as_deref().and_then(|id| index.get(id).copied());
+        while let Some(p) = prev {
+            if task_level(p, tasks, &index, levels, &mut cache) < level {
+                parent = Some(p);
+                break;
+            }
+            prev = tasks[p]
+                .prev
+                .as_deref()
+                .and_then(|id| index.get(id).copied());
+        }
+
+        match parent {
+            Some(p) => children.entry(p).or_default().push(i),
+            None => roots.push(i),
+        }
+    }
+
+    roots
+        .iter()
+        .map(|i| make_node(*i, tasks, &children))
+        .collect()
+}
+
+fn make_node(i: usize, tasks: &[TaskInfo], children: &HashMap<usize, Vec<usize>>) -> TaskTree {
+    let mut node: TaskTree = (&tasks[i]).into();
+    if let Some(items) = children.get(&i) {
+        node.children = items
+            .iter()
+            .map(|c| make_node(*c, tasks, children))
+            .collect();
+    }
+    node
+}
+
+fn task_level(
+    i: usize,
+    tasks: &[TaskInfo],
+    index: &HashMap<&str, usize>,
+    levels: &HashMap<String, usize>,
+    cache: &mut HashMap<usize, usize>,
+) -> usize {
+    if let Some(level) = cache.get(&i) {
+        return *level;
+    }
+
+    let task = &tasks[i];
+    let level = match levels.get(&task.nid) {
+        Some(level) => *level,
+        None => match task.prev.as_deref().and_then(|id| index.get(id).copied()) {
+            Some(p) => {
+                let prev_level = task_level(p, tasks, index, levels, cache);
+                if is_container(&tasks[p].r#type) {
+                    prev_level + 1
+                } else {
+                    prev_level
+                }
+            }
+            None => 0,
+        },
+    };
+    cache.insert(i, level);
+
+    level
+}
+
+fn is_container(r#type: &str) -> bool {
+    matches!(r#type, "workflow" | "step" | "branch")
+}
+
+fn model_levels(workflow: &Workflow, levels: &mut HashMap<String, usize>) {
+    levels.insert(workflow.id.clone(), 0);
+    step_levels(&workflow.steps, 1, levels);
+}
+
+fn step_levels(steps: &[Step], level: usize, levels: &mut HashMap<String, usize>) {
+    for step in steps {
+        if !step.id.is_empty() {
+            levels.insert(step.id.clone(), level);
+        }
+        for act in step.acts.iter().filter(|a| !a.id.is_empty()) {
+            levels.insert(act.id.clone(), level + 1);
+        }
+        for branch in &step.branches {
+            if !branch.id.is_empty() {
+                levels.insert(branch.id.clone(), level + 1);
+            }
+            step_levels(&branch.steps, level + 2, levels);
+        }
+    }
+}
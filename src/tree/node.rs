@@ -0,0 +1,19 @@
+//! the cli includes this file by path to share the type with the server
+use serde::{Deserialize, Serialize};
+
+/// task node in the proc tasks tree
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TaskTree {
+    pub id: String,
+    pub nid: String,
+    pub name: String,
+    pub r#type: String,
+    pub state: String,
+    pub tag: String,
+    pub key: String,
+    pub start_time: i64,
+    pub end_time: i64,
+    pub timestamp: i64,
+    pub data: serde_json::Value,
+    pub children: Vec<TaskTree>,
+}
@@ -0,0 +1,323 @@
+use crate::{
+    audit, model,
+    namespace::Namespace,
+    store::{DbItem, PageData, Store},
+    utils,
+};
+use acts::{ActError, Engine, ExecutorQuery, Result, Vars};
+use hyper::{
+    body::HttpBody,
+    service::{make_service_fn, service_fn},
+    Body, Method, Request, Response, Server, StatusCode,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::{collections::HashMap, convert::Infallible, net::SocketAddr, sync::Arc, time::Instant};
+
+/// the header of the trigger secret, the 'authorization: Bearer {secret}' header is also accepted
+pub const SECRET_HEADER: &str = "x-acts-secret";
+
+/// the max size of the request body
+const MAX_BODY: usize = 1024 * 1024;
+
+/// the http trigger options in acts.conf
+#[derive(Debug, Default, Clone)]
+pub struct TriggerOptions {
+    /// the port of the http endpoints, the endpoints are disabled without it
+    pub port: Option<u16>,
+}
+
+/// start the proc of the model when the path receives a http post with the secret
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Trigger {
+    pub id: String,
+    /// the url path of the endpoint, such as '/forms/order', it starts with the namespace of the trigger
+    pub path: String,
+    /// it is never listed
+    pub secret: String,
+    pub mid: String,
+    /// map the var name to the json pointer of the request body, such as 'name: /user/name'
+    /// all of the top level fields are the vars without the mapping
+    pub mapping: HashMap<String, String>,
+    /// the default vars which are overridden by the mapped ones
+    pub vars: Vars,
+    pub runs: u64,
+    pub last_pid: String,
+    pub last_time: i64,
+    pub create_time: i64,
+}
+
+impl DbItem for Trigger {
+    fn name() -> &'static str {
+        "trigger"
+    }
+
+    fn id(&self) -> &str {
+        &self.id
+    }
+}
+
+/// create the trigger on the path under the namespace, such as '/{namespace}/forms/order', so the
+/// tenants never take the paths of each other
+pub fn create(store: &Store, ns: &Namespace, trigger: &Trigger) -> Result<Trigger> {
+    if trigger.mid.is_empty() {
+        return Err(ActError::Action("trigger 'mid' is required".to_string()));
+    }
+    if trigger.secret.is_empty() {
+        return Err(ActError::Action("trigger 'secret' is required".to_string()));
+    }
+    let path = normalize(&trigger.path);
+    let valid = path.len() > 1
+        && path
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '/' | '-' | '_' | '.'))
+        && path
+            .split('/')
+            .skip(1)
+            .all(|segment| !matches!(segment, "" | "." | ".."));
+    if !valid {
+        return Err(ActError::Action(format!(
+            "invalid trigger path '{}', it should only contain letters, digits, '/', '-', '_' and '.'",
+            trigger.path
+        )));
+    }
+    let path = normalize(&ns.scope(path.trim_start_matches('/')));
+    for (name, pointer) in &trigger.mapping {
+        if !pointer.is_empty() && !pointer.starts_with('/') {
+            return Err(ActError::Action(format!(
+                "invalid mapping '{name}: {pointer}', it should be a json pointer such as '/user/name'"
+            )));
+        }
+    }
+
+    let trigger = Trigger {
+        id: if trigger.id.is_empty() {
+            utils::longid()
+        } else {
+            trigger.id.clone()
+        },
+        path,
+        runs: 0,
+        last_pid: String::new(),
+        last_time: 0,
+        create_time: utils::time_millis(),
+        ..trigger.clone()
+    };
+    let triggers = store.collection::<Trigger>()?;
+    if triggers.exists(&trigger.id)? {
+        return Err(ActError::Action(format!(
+            "trigger '{}' already exists",
+            trigger.id
+        )));
+    }
+    if !triggers.find_by("path", &trigger.path)?.is_empty() {
+        return Err(ActError::Action(format!(
+            "trigger path '{}' is already used",
+            trigger.path
+        )));
+    }
+    triggers.create(&trigger)?;
+    Ok(masked(trigger))
+}
+
+pub fn list(store: &Store, query: &ExecutorQuery) -> Result<PageData<Trigger>> {
+    let page = store.collection::<Trigger>()?.query(query)?;
+    Ok(PageData {
+        rows: page.rows.into_iter().map(masked).collect(),
+        ..page
+    })
+}
+
+pub fn rm(store: &Store, id: &str) -> Result<bool> {
+    store.collection::<Trigger>()?.delete(id)
+}
+
+/// map the request body to the proc vars
+pub fn vars(trigger: &Trigger, body: &Value) -> Result<Vars> {
+    let mut vars = trigger.vars.clone();
+    if trigger.mapping.is_empty() {
+        match body {
+            Value::Object(map) => {
+                for (name, value) in map {
+                    vars.set(name, value.clone());
+                }
+            }
+            Value::Null => {}
+            _ => {
+                return Err(ActError::Action(
+                    "the request body should be a json object".to_string(),
+                ))
+            }
+        }
+        return Ok(vars);
+    }
+    for (name, pointer) in &trigger.mapping {
+        if let Some(value) = body.pointer(pointer) {
+            vars.set(name, value.clone());
+        }
+    }
+    Ok(vars)
+}
+
+/// serve the trigger endpoints on the port
+pub fn serve(addr: SocketAddr, engine: Arc<Engine>, store: Arc<Store>) {
+    tokio::spawn(async move {
+        let make_service = make_service_fn(move |_| {
+            let engine = engine.clone();
+            let store = store.clone();
+            async move {
+                Ok::<_, Infallible>(service_fn(move |req| {
+                    handle(engine.clone(), store.clone(), req)
+                }))
+            }
+        });
+        tracing::info!("trigger: listen on {addr}");
+        if let Err(err) = Server::bind(&addr).serve(make_service).await {
+            tracing::error!("trigger: {err}");
+        }
+    });
+}
+
+async fn handle(
+    engine: Arc<Engine>,
+    store: Arc<Store>,
+    req: Request<Body>,
+) -> std::result::Result<Response<Body>, Infallible> {
+    if req.method() != Method::POST {
+        return Ok(reply(
+            StatusCode::METHOD_NOT_ALLOWED,
+            "only the post method is allowed",
+        ));
+    }
+    let path = normalize(req.uri().path());
+    let secret = req
+        .headers()
+        .get(SECRET_HEADER)
+        .or(req.headers().get("authorization"))
+        .and_then(|value| value.to_str().ok())
+        .map(|value| value.strip_prefix("Bearer ").unwrap_or(value).to_string())
+        .unwrap_or_default();
+    let body = match read_body(req.into_body()).await {
+        Ok(body) => body,
+        Err((status, message)) => return Ok(reply(status, &message)),
+    };
+
+    let ret = tokio::task::spawn_blocking(move || fire(&engine, &store, &path, &secret, &body))
+        .await
+        .unwrap_or_else(|err| Err((StatusCode::INTERNAL_SERVER_ERROR, err.to_string())));
+    Ok(match ret {
+        Ok(pid) => json_reply(StatusCode::OK, json!({ "pid": pid })),
+        Err((status, message)) => reply(status, &message),
+    })
+}
+
+/// read the request body up to the max size, the larger one is rejected by its declared length
+/// before reading, or once the read chunks are over the size
+async fn read_body(mut body: Body) -> std::result::Result<Vec<u8>, (StatusCode, String)> {
+    let too_large = || {
+        (
+            StatusCode::PAYLOAD_TOO_LARGE,
+            "the request body is too large".to_string(),
+        )
+    };
+    if body.size_hint().lower() > MAX_BODY as u64 {
+        return Err(too_large());
+    }
+    let mut data = Vec::new();
+    while let Some(chunk) = body.data().await {
+        let chunk = chunk.map_err(|err| (StatusCode::BAD_REQUEST, err.to_string()))?;
+        if data.len() + chunk.len() > MAX_BODY {
+            return Err(too_large());
+        }
+        data.extend_from_slice(&chunk);
+    }
+    Ok(data)
+}
+
+/// start the proc of the trigger on the path
+fn fire(
+    engine: &Engine,
+    store: &Store,
+    path: &str,
+    secret: &str,
+    body: &[u8],
+) -> std::result::Result<String, (StatusCode, String)> {
+    let internal = |err: ActError| (StatusCode::INTERNAL_SERVER_ERROR, err.to_string());
+    let triggers = store.collection::<Trigger>().map_err(internal)?;
+    let Some(mut trigger) = triggers
+        .find_by("path", path)
+        .map_err(internal)?
+        .into_iter()
+        .next()
+    else {
+        return Err((
+            StatusCode::NOT_FOUND,
+            format!("cannot find trigger '{path}'"),
+        ));
+    };
+    if !verify(&trigger.secret, secret) {
+        return Err((StatusCode::UNAUTHORIZED, "invalid secret".to_string()));
+    }
+
+    let body = if body.is_empty() {
+        Value::Null
+    } else {
+        serde_json::from_slice::<Value>(body)
+            .map_err(|err| (StatusCode::BAD_REQUEST, err.to_string()))?
+    };
+    let vars = vars(&trigger, &body).map_err(|err| (StatusCode::BAD_REQUEST, err.to_string()))?;
+    let begin = Instant::now();
+    let ret = model::start(&engine.executor(), store, &trigger.mid, None, &vars);
+    audit::log_start(
+        store,
+        &format!("trigger:{}", trigger.id),
+        &trigger.mid,
+        &vars,
+        ret.as_deref(),
+        begin.elapsed(),
+    );
+    let pid = ret.map_err(|err| (StatusCode::UNPROCESSABLE_ENTITY, err.to_string()))?;
+
+    trigger.runs += 1;
+    trigger.last_pid = pid.clone();
+    trigger.last_time = utils::time_millis();
+    if let Err(err) = triggers.update(&trigger) {
+        tracing::error!("trigger '{}': {err}", trigger.id);
+    }
+    tracing::info!("trigger: path={path} pid={pid}");
+    Ok(pid)
+}
+
+/// compare the secrets in constant time
+fn verify(expected: &str, actual: &str) -> bool {
+    expected.len() == actual.len()
+        && expected
+            .bytes()
+            .zip(actual.bytes())
+            .fold(0, |acc, (a, b)| acc | (a ^ b))
+            == 0
+}
+
+fn normalize(path: &str) -> String {
+    format!("/{}", path.trim_matches('/'))
+}
+
+fn reply(status: StatusCode, message: &str) -> Response<Body> {
+    json_reply(status, json!({ "error": message }))
+}
+
+fn json_reply(status: StatusCode, value: Value) -> Response<Body> {
+    let mut resp = Response::new(Body::from(value.to_string()));
+    *resp.status_mut() = status;
+    resp.headers_mut()
+        .insert("content-type", "application/json".parse().unwrap());
+    resp
+}
+
+fn masked(trigger: Trigger) -> Trigger {
+    Trigger {
+        secret: "***".to_string(),
+        ..trigger
+    }
+}
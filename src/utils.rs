@@ -1,4 +1,5 @@
 use acts_channel::{create_seq, Message};
+use globset::{Glob, GlobMatcher};
 use serde::Serialize;
 
 pub fn wrap_message<T: ?Sized + Serialize>(name: &str, value: &T) -> Message {
@@ -9,3 +10,45 @@ pub fn wrap_message<T: ?Sized + Serialize>(name: &str, value: &T) -> Message {
         data: Some(serde_json::to_vec(value).unwrap()),
     }
 }
+
+pub fn time_millis() -> i64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_millis() as i64
+}
+
+pub fn longid() -> String {
+    nanoid::nanoid!()
+}
+
+/// match the engine message with the type, state, tag and key globs like the message subscription
+pub struct MessageFilter {
+    r#type: GlobMatcher,
+    state: GlobMatcher,
+    tag: GlobMatcher,
+    key: GlobMatcher,
+}
+
+impl MessageFilter {
+    pub fn new(r#type: &str, state: &str, tag: &str, key: &str) -> acts::Result<Self> {
+        let glob = |pattern: &str| {
+            Glob::new(pattern)
+                .map(|glob| glob.compile_matcher())
+                .map_err(|err| acts::ActError::Action(err.to_string()))
+        };
+        Ok(Self {
+            r#type: glob(r#type)?,
+            state: glob(state)?,
+            tag: glob(tag)?,
+            key: glob(key)?,
+        })
+    }
+
+    pub fn is_match(&self, message: &acts::Message) -> bool {
+        self.r#type.is_match(&message.r#type)
+            && self.state.is_match(&message.state)
+            && (self.tag.is_match(&message.tag) || self.tag.is_match(&message.model.tag))
+            && self.key.is_match(&message.key)
+    }
+}
@@ -0,0 +1,614 @@
+use crate::{model, namespace::Namespace, package, store::Store};
+use acts::{Act, Branch, Executor, Step, Vars, Workflow};
+use rquickjs::{Context, Runtime};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+
+const ACTS: [&str; 19] = [
+    "set",
+    "expose",
+    "irq",
+    "msg",
+    "cmd",
+    "each",
+    "chain",
+    "block",
+    "if",
+    "call",
+    "pack",
+    "on_created",
+    "on_timeout",
+    "on_updated",
+    "on_before_update",
+    "on_step",
+    "on_completed",
+    "on_catch",
+    "",
+];
+
+/// a problem found in the model source
+/// the line and column are 1-based, and 0 means the position is unknown
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Diagnostic {
+    pub level: String,
+    pub message: String,
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Diagnostic {
+    fn error(message: &str, (line, column): (usize, usize)) -> Self {
+        Self {
+            level: "error".to_string(),
+            message: message.to_string(),
+            line,
+            column,
+        }
+    }
+
+    pub fn is_error(&self) -> bool {
+        self.level == "error"
+    }
+}
+
+/// a model in the deploying text, the bundle text can contain several models
+struct Document {
+    workflow: Workflow,
+    /// the line range of the model source in the text
+    start: usize,
+    end: usize,
+}
+
+/// run the static check for the model text and return all of the diagnostics
+/// the format is one of 'yaml' and 'json', the mid overrides the model id as the same as deploying
+pub fn validate(
+    executor: &Executor,
+    store: &Store,
+    ns: &Namespace,
+    text: &str,
+    format: &str,
+    mid: Option<&str>,
+) -> Vec<Diagnostic> {
+    check_all(executor, store, ns, text, format, mid).1
+}
+
+/// load the models from the text, fails with the error diagnostics if any of the models is invalid
+pub fn load(
+    executor: &Executor,
+    store: &Store,
+    ns: &Namespace,
+    text: &str,
+    format: &str,
+    mid: Option<&str>,
+) -> std::result::Result<Vec<Workflow>, Vec<Diagnostic>> {
+    let (workflows, diagnostics) = check_all(executor, store, ns, text, format, mid);
+    let errors: Vec<Diagnostic> = diagnostics.into_iter().filter(|d| d.is_error()).collect();
+    if !errors.is_empty() {
+        return Err(errors);
+    }
+    Ok(workflows)
+}
+
+fn check_all(
+    executor: &Executor,
+    store: &Store,
+    ns: &Namespace,
+    text: &str,
+    format: &str,
+    mid: Option<&str>,
+) -> (Vec<Workflow>, Vec<Diagnostic>) {
+    let mut docs = match parse(text, format) {
+        Ok(docs) => docs,
+        Err(err) => return (Vec::new(), vec![err]),
+    };
+
+    let mut diagnostics = Vec::new();
+    if docs.is_empty() {
+        diagnostics.push(Diagnostic::error("cannot find any model in text", (0, 0)));
+    }
+    if let Some(mid) = mid {
+        if docs.len() > 1 {
+            diagnostics.push(Diagnostic::error(
+                "cannot set the model id for a bundle with several models",
+                (0, 0),
+            ));
+        }
+        for doc in docs.iter_mut() {
+            doc.workflow.set_id(mid);
+        }
+    }
+
+    let lines: Vec<&str> = text.lines().collect();
+    let mut models = HashSet::new();
+    let mut found = HashMap::new();
+    for doc in &docs {
+        if format != "json" {
+            // the yaml documents have their own source lines
+            found.clear();
+        }
+        let mut checker = Checker::new(executor, store, ns, &lines, doc, &mut found);
+        checker.check(&doc.workflow);
+        if !doc.workflow.id.is_empty() && !models.insert(doc.workflow.id.clone()) {
+            let pos = checker.position;
+            checker.error(
+                &format!("duplicate model id '{}' in bundle", doc.workflow.id),
+                pos,
+            );
+        }
+
+        // the engine check covers the rules which are not in the checker
+        let has_error = checker.diagnostics.iter().any(|d| d.is_error());
+        if !has_error {
+            if let Err(err) = doc.workflow.valid() {
+                checker.error(&err.to_string(), (doc.start + 1, 1));
+            }
+        }
+        diagnostics.append(&mut checker.diagnostics);
+    }
+
+    (docs.into_iter().map(|d| d.workflow).collect(), diagnostics)
+}
+
+fn parse(text: &str, format: &str) -> std::result::Result<Vec<Document>, Diagnostic> {
+    match format {
+        "yaml" | "yml" => parse_yaml(text),
+        "json" => parse_json(text),
+        _ => Err(Diagnostic::error(
+            &format!("unsupported model format '{format}'"),
+            (0, 0),
+        )),
+    }
+}
+
+/// read the yaml documents in the text, the empty documents are skipped
+fn parse_yaml(text: &str) -> std::result::Result<Vec<Document>, Diagnostic> {
+    let mut workflows = Vec::new();
+    for de in serde_yaml::Deserializer::from_str(text) {
+        // the deserializer keeps returning the same error, so stop at the first one
+        let workflow = Option::<Workflow>::deserialize(de).map_err(|err| {
+            let pos = err
+                .location()
+                .map_or((0, 0), |loc| (loc.line(), loc.column()));
+            Diagnostic::error(&err.to_string(), pos)
+        })?;
+        workflows.extend(workflow);
+    }
+
+    let lines: Vec<&str> = text.lines().collect();
+    let mut ranges = source_ranges(&lines);
+    if ranges.len() != workflows.len() {
+        // the source lines are only used to find the positions, so use the whole text
+        ranges = vec![(0, lines.len()); workflows.len()];
+    }
+
+    Ok(workflows
+        .into_iter()
+        .zip(ranges)
+        .map(|(workflow, (start, end))| Document {
+            workflow,
+            start,
+            end,
+        })
+        .collect())
+}
+
+/// the line ranges of the non-empty yaml documents, which are split by the '---' and '...' markers
+fn source_ranges(lines: &[&str]) -> Vec<(usize, usize)> {
+    let mut ranges = Vec::new();
+    let mut start = 0;
+    for end in 0..=lines.len() {
+        let marker = match lines.get(end) {
+            Some(line) => ["---", "..."].into_iter().find_map(|m| {
+                let rest = line.strip_prefix(m)?;
+                (rest.is_empty() || rest.starts_with(char::is_whitespace)).then_some(rest)
+            }),
+            None => Some(""),
+        };
+        let Some(rest) = marker else {
+            continue;
+        };
+
+        let is_empty = lines[start..end].iter().all(|l| {
+            let l = l.trim();
+            l.is_empty() || l.starts_with('#')
+        });
+        if !is_empty {
+            ranges.push((start, end));
+        }
+
+        // the content can follow the marker in the same line
+        let rest = rest.trim();
+        start = if rest.is_empty() || rest.starts_with('#') {
+            end + 1
+        } else {
+            end
+        };
+    }
+
+    ranges
+}
+
+/// the json text can be a model object or an array of models
+fn parse_json(text: &str) -> std::result::Result<Vec<Document>, Diagnostic> {
+    let value = serde_json::from_str::<serde_json::Value>(text)
+        .map_err(|err| Diagnostic::error(&err.to_string(), (err.line(), err.column())))?;
+    let values = match value {
+        serde_json::Value::Array(values) => values,
+        serde_json::Value::Object(_) => vec![value],
+        _ => {
+            return Err(Diagnostic::error(
+                "the json model should be an object or an array",
+                (1, 1),
+            ))
+        }
+    };
+
+    let end = text.lines().count();
+    values
+        .into_iter()
+        .map(|value| {
+            let workflow = serde_json::from_value::<Workflow>(value)
+                .map_err(|err| Diagnostic::error(&err.to_string(), (0, 0)))?;
+            Ok(Document {
+                workflow,
+                start: 0,
+                end,
+            })
+        })
+        .collect()
+}
+
+struct Checker<'a> {
+    executor: &'a Executor,
+    store: &'a Store,
+    /// the pack and call keys are resolved in the namespace
+    ns: &'a Namespace,
+    lines: &'a [&'a str],
+    offset: usize,
+    found: &'a mut HashMap<(String, String), usize>,
+    ids: HashSet<String>,
+    steps: HashSet<String>,
+    js: Option<Context>,
+    diagnostics: Vec<Diagnostic>,
+    /// the position of the model id
+    position: (usize, usize),
+    found_at: (usize, usize),
+}
+
+impl<'a> Checker<'a> {
+    fn new(
+        executor: &'a Executor,
+        store: &'a Store,
+        ns: &'a Namespace,
+        lines: &'a [&'a str],
+        doc: &Document,
+        found: &'a mut HashMap<(String, String), usize>,
+    ) -> Self {
+        let mut steps = HashSet::new();
+        collect_steps(&doc.workflow.steps, &mut steps);
+        let js = Runtime::new().ok().and_then(|rt| Context::full(&rt).ok());
+
+        Self {
+            executor,
+            store,
+            ns,
+            lines: &lines[doc.start..doc.end],
+            offset: doc.start,
+            found,
+            ids: HashSet::new(),
+            steps,
+            js,
+            diagnostics: Vec::new(),
+            position: (doc.start + 1, 1),
+            found_at: (0, 0),
+        }
+    }
+
+    fn check(&mut self, workflow: &Workflow) {
+        if workflow.id.is_empty() {
+            self.error("missing id in model", (self.offset + 1, 1));
+        } else {
+            self.check_id("id", &workflow.id);
+            self.position = self.found_at;
+            if workflow.id.contains(model::PIN_SEPARATOR) {
+                self.error(
+                    &format!("the model id cannot contain '{}'", model::PIN_SEPARATOR),
+                    self.found_at,
+                );
+            }
+        }
+
+        self.check_vars(&workflow.inputs);
+        self.check_vars(&workflow.outputs);
+        self.check_vars(&workflow.env);
+        self.check_acts(&workflow.setup);
+        self.check_steps(&workflow.steps);
+    }
+
+    fn check_steps(&mut self, steps: &[Step]) {
+        for step in steps {
+            self.check_id("id", &step.id);
+            self.check_vars(&step.inputs);
+            self.check_vars(&step.outputs);
+            if let Some(expr) = &step.r#if {
+                self.check_expr("if", expr);
+            }
+            if let Some(script) = &step.run {
+                self.check_script(script);
+            }
+            if let Some(next) = &step.next {
+                self.check_target("next", next);
+            }
+
+            let branches: HashSet<&str> = step.branches.iter().map(|b| b.id.as_str()).collect();
+            for branch in &step.branches {
+                self.check_branch(branch, &branches);
+            }
+
+            self.check_acts(&step.setup);
+            self.check_acts(&step.acts);
+            for catch in &step.catches {
+                self.check_vars(&catch.inputs);
+                self.check_acts(&catch.then);
+            }
+            for timeout in &step.timeout {
+                self.check_acts(&timeout.then);
+            }
+        }
+    }
+
+    fn check_branch(&mut self, branch: &Branch, branches: &HashSet<&str>) {
+        self.check_id("id", &branch.id);
+        self.check_vars(&branch.inputs);
+        self.check_vars(&branch.outputs);
+        if let Some(expr) = &branch.r#if {
+            self.check_expr("if", expr);
+        }
+        if let Some(script) = &branch.run {
+            self.check_script(script);
+        }
+        if let Some(next) = &branch.next {
+            self.check_target("next", next);
+        }
+        for need in &branch.needs {
+            if !branches.contains(need.as_str()) {
+                let pos = self.locate_text(need);
+                self.error(&format!("cannot find the needed branch '{need}'"), pos);
+            }
+        }
+        self.check_steps(&branch.steps);
+    }
+
+    fn check_acts(&mut self, acts: &[Act]) {
+        for act in acts {
+            self.check_act(act);
+        }
+    }
+
+    fn check_act(&mut self, act: &Act) {
+        self.check_id("id", &act.id);
+        let pos = self.locate("act", &act.act);
+        if !ACTS.contains(&act.act.as_str()) {
+            self.warning(&format!("unknown act '{}'", act.act), pos);
+        }
+
+        match act.act.as_str() {
+            "pack"
+                if !package::exists(self.executor, self.store, &self.ns.scope(&act.key))
+                    .unwrap_or(false) =>
+            {
+                let pos = self.locate("key", &act.key);
+                let message = format!("cannot find package '{}'", act.key);
+                // the pinned version should be published before deploying
+                if act.key.contains('@') {
+                    self.error(&message, pos);
+                } else {
+                    self.warning(&message, pos);
+                }
+            }
+            "call"
+                if self
+                    .executor
+                    .model()
+                    .get(&self.ns.scope(&act.key), "text")
+                    .is_err() =>
+            {
+                let pos = self.locate("key", &act.key);
+                self.warning(&format!("cannot find model '{}'", act.key), pos);
+            }
+            "cmd" if act.key == "back" => match act.inputs.get::<String>("to") {
+                Some(to) => self.check_target("to", &to),
+                None => self.error("missing 'to' for the back command", pos),
+            },
+            _ => {}
+        }
+
+        if !act.on.is_empty() && act.act == "if" {
+            self.check_expr("on", &act.on);
+        }
+        if !act.r#in.is_empty() {
+            self.check_expr("in", &act.r#in);
+        }
+        self.check_vars(&act.inputs);
+        self.check_vars(&act.rets);
+        self.check_vars(&act.outputs);
+
+        self.check_acts(&act.setup);
+        self.check_acts(&act.then);
+        self.check_acts(&act.r#else);
+        if let Some(next) = &act.next {
+            self.check_act(next);
+        }
+        for catch in &act.catches {
+            self.check_vars(&catch.inputs);
+            self.check_acts(&catch.then);
+        }
+        for timeout in &act.timeout {
+            self.check_acts(&timeout.then);
+        }
+    }
+
+    fn check_id(&mut self, key: &str, id: &str) {
+        if id.is_empty() {
+            return;
+        }
+        let pos = self.locate(key, id);
+        self.found_at = pos;
+        if !self.ids.insert(id.to_string()) {
+            self.error(&format!("duplicate id '{id}'"), pos);
+        }
+    }
+
+    fn check_target(&mut self, key: &str, target: &str) {
+        let pos = self.locate(key, target);
+        if !self.steps.contains(target) {
+            self.error(&format!("cannot find the target step '{target}'"), pos);
+        }
+    }
+
+    /// the same rule as the engine, only the whole '${ }' string value is an expression
+    fn check_vars(&mut self, vars: &Vars) {
+        for (_, value) in vars.iter() {
+            if let Some(text) = value.as_str() {
+                let text = text.trim();
+                if let Some(expr) = text.strip_prefix("${").and_then(|t| t.strip_suffix('}')) {
+                    if let Err(err) = self.compile(&format!("return ({expr});")) {
+                        let pos = self.locate_text(text);
+                        self.error(&format!("invalid expression '{text}': {err}"), pos);
+                    }
+                }
+            }
+        }
+    }
+
+    fn check_expr(&mut self, key: &str, expr: &str) {
+        if let Err(err) = self.compile(&format!("return ({expr});")) {
+            let pos = self.locate(key, expr);
+            self.error(&format!("invalid expression '{expr}': {err}"), pos);
+        }
+    }
+
+    fn check_script(&mut self, script: &str) {
+        if let Err(err) = self.compile(script) {
+            let pos = self.locate_text(script.lines().next().unwrap_or_default());
+            self.error(&format!("invalid script: {err}"), pos);
+        }
+    }
+
+    /// compile the source as a function body without running it
+    fn compile(&self, source: &str) -> Result<(), String> {
+        let Some(js) = &self.js else {
+            return Ok(());
+        };
+        js.with(|ctx| {
+            ctx.globals()
+                .set("__source", source)
+                .map_err(|err| err.to_string())?;
+            match ctx.eval::<(), _>("new Function(__source);") {
+                Ok(_) => Ok(()),
+                Err(rquickjs::Error::Exception) => {
+                    let exception = ctx.catch();
+                    let message = exception
+                        .as_exception()
+                        .and_then(|e| e.message())
+                        .unwrap_or_else(|| "syntax error".to_string());
+                    Err(message)
+                }
+                Err(err) => Err(err.to_string()),
+            }
+        })
+    }
+
+    /// find the position of the nth 'key: value' in source, the nth is counted by the calls with the same key and value
+    fn locate(&mut self, key: &str, value: &str) -> (usize, usize) {
+        let nth = self
+            .found
+            .entry((key.to_string(), value.to_string()))
+            .or_insert(0);
+        let mut count = 0;
+        let lines = self.lines;
+        for (index, line) in lines.iter().enumerate() {
+            for (column, found) in key_values(line, key) {
+                if found == value {
+                    if count == *nth {
+                        *nth += 1;
+                        return (self.offset + index + 1, column);
+                    }
+                    count += 1;
+                }
+            }
+        }
+        (0, 0)
+    }
+
+    fn locate_text(&self, text: &str) -> (usize, usize) {
+        let text = text.trim();
+        if text.is_empty() {
+            return (0, 0);
+        }
+        for (index, line) in self.lines.iter().enumerate() {
+            if let Some(column) = line.find(text) {
+                return (self.offset + index + 1, column + 1);
+            }
+        }
+        (0, 0)
+    }
+
+    fn error(&mut self, message: &str, pos: (usize, usize)) {
+        self.push("error", message, pos);
+    }
+
+    fn warning(&mut self, message: &str, pos: (usize, usize)) {
+        self.push("warning", message, pos);
+    }
+
+    fn push(&mut self, level: &str, message: &str, (line, column): (usize, usize)) {
+        self.diagnostics.push(Diagnostic {
+            level: level.to_string(),
+            message: message.to_string(),
+            line,
+            column,
+        });
+    }
+}
+
+/// find the values of the key in the source line with the 1-based columns
+/// both of the yaml 'key: value' and the json '"key": value' are supported
+fn key_values<'a>(line: &'a str, key: &str) -> Vec<(usize, &'a str)> {
+    let mut ret = Vec::new();
+    let content = line.trim_start().trim_start_matches("- ").trim_start();
+    if let Some(rest) = content.strip_prefix(key).and_then(|r| r.strip_prefix(':')) {
+        let value = rest.trim().trim_matches(|c| c == '"' || c == '\'');
+        ret.push((line.len() - content.len() + 1, value));
+        return ret;
+    }
+
+    let quoted = format!("\"{key}\"");
+    for (start, _) in line.match_indices(&quoted) {
+        let Some(rest) = line[start + quoted.len()..].trim_start().strip_prefix(':') else {
+            continue;
+        };
+        let rest = rest.trim_start();
+        let value = match rest.strip_prefix('"') {
+            Some(text) => text.split('"').next().unwrap_or_default(),
+            None => rest
+                .split([',', '}', ']'])
+                .next()
+                .unwrap_or_default()
+                .trim(),
+        };
+        ret.push((start + 1, value));
+    }
+    ret
+}
+
+fn collect_steps(steps: &[Step], ids: &mut HashSet<String>) {
+    for step in steps {
+        if !step.id.is_empty() {
+            ids.insert(step.id.clone());
+        }
+        for branch in &step.branches {
+            collect_steps(&branch.steps, ids);
+        }
+    }
+}
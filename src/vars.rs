@@ -0,0 +1,113 @@
+use crate::{audit::Change, utils};
+use acts::{ActError, Executor, Result, TaskInfo, Vars};
+use serde_json::{Map, Value};
+use std::time::Duration;
+
+/// the task data key which stops the engine to emit the messages of the task
+const EMIT_DISABLED: &str = "emit_disabled";
+
+/// the interval and the times to read the proc tasks until the pushed act runs
+const APPLY_INTERVAL: Duration = Duration::from_millis(10);
+const APPLY_TIMES: usize = 300;
+
+/// get the proc scoped vars, which are the vars of the root task
+pub fn proc_vars(executor: &Executor, pid: &str) -> Result<Vars> {
+    let tasks = executor.proc().get(pid)?.tasks;
+    match tasks.iter().find(|t| t.r#type == "workflow") {
+        Some(root) => task_data(root),
+        // the proc is not in the engine cache
+        None => task_data(&executor.task().get(pid, "$")?),
+    }
+}
+
+/// get the task scoped vars
+pub fn task_vars(executor: &Executor, pid: &str, tid: &str) -> Result<Vars> {
+    let tasks = executor.proc().get(pid)?.tasks;
+    match tasks.iter().find(|t| t.id == tid) {
+        Some(task) => task_data(task),
+        None => task_data(&executor.task().get(pid, tid)?),
+    }
+}
+
+/// patch the proc vars with json merge-patch semantics (RFC 7386), the nested objects are merged
+/// the engine api has no write of the proc vars, so a silent act is pushed to the running step and removed with
+/// the patch as the action options, which the engine only applies to the vars which exist, so the top-level keys
+/// are never added or removed
+/// returns the vars which are read from the proc after the patch and the patched keys to audit
+pub async fn set_proc_vars(executor: &Executor, pid: &str, patch: &Vars) -> Result<(Vars, Change)> {
+    let tasks = executor.proc().get(pid)?.tasks;
+    let step = tasks
+        .iter()
+        .rev()
+        .find(|t| t.r#type == "step" && (t.state == "running" || t.state == "ready"))
+        .ok_or(ActError::Action(format!(
+            "cannot find a running step in proc '{pid}'"
+        )))?;
+
+    let vars = proc_vars(executor, pid)?;
+    let mut before = Vars::new();
+    let mut after = Vars::new();
+    for (key, value) in patch.iter() {
+        let prev = vars.get_value(key).ok_or(ActError::Action(format!(
+            "cannot add var '{key}' to proc '{pid}', only the existing vars can be patched"
+        )))?;
+        if value.is_null() {
+            return Err(ActError::Action(format!(
+                "cannot remove var '{key}' from proc '{pid}', only the existing vars can be patched"
+            )));
+        }
+        let mut next = prev.clone();
+        merge_patch(&mut next, value);
+        before.set(key, prev);
+        after.set(key, next);
+    }
+
+    let nid = format!("vars-{}", utils::longid());
+    let options = Vars::new()
+        .with("id", &nid)
+        .with("act", "set")
+        .with("with", Vars::new().with(EMIT_DISABLED, true));
+    executor.act().push(pid, &step.id, &options)?;
+
+    // the engine runs the pushed act in its scheduler
+    for _ in 0..APPLY_TIMES {
+        let tasks = executor.proc().get(pid)?.tasks;
+        if let Some(task) = tasks.iter().find(|t| t.nid == nid && t.state == "running") {
+            executor.act().remove(pid, &task.id, &after)?;
+            let change = Change {
+                before: before.into(),
+                after: after.into(),
+            };
+            return Ok((proc_vars(executor, pid)?, change));
+        }
+        tokio::time::sleep(APPLY_INTERVAL).await;
+    }
+    Err(ActError::Runtime(format!(
+        "the vars of proc '{pid}' are not applied in time"
+    )))
+}
+
+/// apply the json merge patch (RFC 7386) to the target
+pub fn merge_patch(target: &mut Value, patch: &Value) {
+    match patch {
+        Value::Object(patch) => {
+            if !target.is_object() {
+                *target = Value::Object(Map::new());
+            }
+            let map = target.as_object_mut().unwrap();
+            for (key, value) in patch {
+                if value.is_null() {
+                    map.remove(key);
+                } else {
+                    merge_patch(map.entry(key.clone()).or_insert(Value::Null), value);
+                }
+            }
+        }
+        _ => *target = patch.clone(),
+    }
+}
+
+fn task_data(task: &TaskInfo) -> Result<Vars> {
+    let value: Value = serde_json::from_str(&task.data)?;
+    Ok(value.into())
+}
@@ -0,0 +1,543 @@
+use crate::{
+    model,
+    namespace::{self, Namespace},
+    store::{DbItem, PageData, Store},
+    utils::{self, MessageFilter},
+};
+use acts::{ActError, Engine, ExecutorQuery, Result};
+use globset::Glob;
+use ring::hmac;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::{
+    collections::{BTreeMap, HashSet},
+    net::{IpAddr, SocketAddr},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use tokio::sync::{mpsc, Notify};
+
+/// the headers of the webhook request
+/// the signature is 'sha256={hex}' of the hmac of '{timestamp}.{body}' with the webhook secret
+pub const SIGNATURE_HEADER: &str = "x-acts-signature";
+pub const TIMESTAMP_HEADER: &str = "x-acts-timestamp";
+pub const DELIVERY_HEADER: &str = "x-acts-delivery";
+pub const EVENT_HEADER: &str = "x-acts-event";
+
+pub const STATUS_PENDING: &str = "pending";
+pub const STATUS_FAILED: &str = "failed";
+
+/// the interval to check the retried deliveries
+const TICK: Duration = Duration::from_secs(1);
+
+/// the timeout of one delivery request
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// the max retry delay in millis
+const MAX_BACKOFF: u64 = 3_600_000;
+
+/// the webhook options in acts.conf
+#[derive(Debug, Clone)]
+pub struct WebhookOptions {
+    /// the max retries after the first failed delivery
+    pub retries: u32,
+    /// the millis before the first retry, it doubles for each retry
+    pub backoff: u64,
+    /// the hosts which can be private or loopback addresses, the others are denied
+    pub allow_hosts: Vec<String>,
+}
+
+impl Default for WebhookOptions {
+    fn default() -> Self {
+        Self {
+            retries: 8,
+            backoff: 1000,
+            allow_hosts: Vec::new(),
+        }
+    }
+}
+
+/// the url to receive the engine messages which match the globs, like the message subscription
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Webhook {
+    pub id: String,
+    pub url: String,
+    /// the hmac key of the signature, it is never listed
+    pub secret: String,
+    pub r#type: String,
+    pub state: String,
+    pub tag: String,
+    pub key: String,
+    /// the messages of the models in other namespaces are never sent
+    pub namespace: String,
+    pub create_time: i64,
+}
+
+impl DbItem for Webhook {
+    fn name() -> &'static str {
+        "webhook"
+    }
+
+    fn id(&self) -> &str {
+        &self.id
+    }
+}
+
+/// the message to send to the webhook, it is removed after delivered
+/// and kept as the failed one when all of the retries failed, the dead letters are the engine messages only
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Delivery {
+    pub id: String,
+    pub hook: String,
+    pub url: String,
+    /// the message id and name
+    pub mid: String,
+    pub event: String,
+    pub payload: Value,
+    /// one of 'pending' and 'failed'
+    pub status: String,
+    pub attempts: u32,
+    pub last_error: String,
+    pub next_time: i64,
+    pub create_time: i64,
+    pub update_time: i64,
+}
+
+impl DbItem for Delivery {
+    fn name() -> &'static str {
+        "webhook_delivery"
+    }
+
+    fn id(&self) -> &str {
+        &self.id
+    }
+}
+
+pub fn create(
+    store: &Store,
+    hooks: &Hooks,
+    hook: &Webhook,
+    options: &WebhookOptions,
+) -> Result<Webhook> {
+    let url = reqwest::Url::parse(&hook.url)
+        .ok()
+        .filter(|url| matches!(url.scheme(), "http" | "https"))
+        .ok_or(ActError::Action(format!(
+            "invalid webhook url '{}', it should be a http or https url",
+            hook.url
+        )))?;
+    // the domain is resolved and checked again when sending
+    let host = url.host_str().unwrap_or_default();
+    if let Ok(ip) = host.trim_matches(['[', ']']).parse::<IpAddr>() {
+        check_addr(host, ip, options)?;
+    } else if host == "localhost" || host.ends_with(".localhost") {
+        check_addr(host, IpAddr::from([127, 0, 0, 1]), options)?;
+    }
+    if hook.secret.is_empty() {
+        return Err(ActError::Action("webhook 'secret' is required".to_string()));
+    }
+    let pattern = |value: &str| -> Result<String> {
+        let value = if value.is_empty() { "*" } else { value };
+        Glob::new(value).map_err(|err| ActError::Action(err.to_string()))?;
+        Ok(value.to_string())
+    };
+
+    let hook = Webhook {
+        id: if hook.id.is_empty() {
+            utils::longid()
+        } else {
+            hook.id.clone()
+        },
+        r#type: pattern(&hook.r#type)?,
+        state: pattern(&hook.state)?,
+        tag: pattern(&hook.tag)?,
+        key: pattern(&hook.key)?,
+        create_time: utils::time_millis(),
+        ..hook.clone()
+    };
+    let collection = store.collection::<Webhook>()?;
+    if collection.exists(&hook.id)? {
+        return Err(ActError::Action(format!(
+            "webhook '{}' already exists",
+            hook.id
+        )));
+    }
+    collection.create(&hook)?;
+    hooks.clear();
+    Ok(masked(hook))
+}
+
+pub fn list(store: &Store, query: &ExecutorQuery) -> Result<PageData<Webhook>> {
+    let page = store.collection::<Webhook>()?.query(query)?;
+    Ok(PageData {
+        rows: page.rows.into_iter().map(masked).collect(),
+        ..page
+    })
+}
+
+/// remove the webhook and its pending and failed deliveries
+pub fn rm(store: &Store, hooks: &Hooks, id: &str) -> Result<bool> {
+    store.collection::<Delivery>()?.delete_by("hook", id)?;
+    let ret = store.collection::<Webhook>()?.delete(id)?;
+    hooks.clear();
+    Ok(ret)
+}
+
+/// the deliveries which are failed after all of the retries
+pub fn failed(store: &Store, query: &ExecutorQuery) -> Result<PageData<Delivery>> {
+    let mut query_by = query.query_by.clone();
+    query_by.retain(|(key, _)| key != "status");
+    query_by.push(("status".to_string(), STATUS_FAILED.to_string()));
+    let order_by = if query.order_by.is_empty() {
+        vec![("update_time".to_string(), true)]
+    } else {
+        query.order_by.clone()
+    };
+    store.collection::<Delivery>()?.query(&ExecutorQuery {
+        offset: query.offset,
+        count: query.count,
+        query_by,
+        order_by,
+    })
+}
+
+/// the failed deliveries of the webhooks which are owned by the namespace
+pub fn failed_in(
+    store: &Store,
+    ns: &Namespace,
+    query: &ExecutorQuery,
+) -> Result<PageData<Delivery>> {
+    if ns.is_root() {
+        return failed(store, query);
+    }
+    namespace::list(
+        query,
+        |q| failed(store, q).map(|page| (page.rows, page.count)),
+        |delivery| ns.owns(&delivery.hook),
+    )
+}
+
+/// the hex hmac-sha256 of '{timestamp}.{body}'
+pub fn sign(secret: &str, timestamp: i64, body: &str) -> String {
+    let key = hmac::Key::new(hmac::HMAC_SHA256, secret.as_bytes());
+    let tag = hmac::sign(&key, format!("{timestamp}.{body}").as_bytes());
+    tag.as_ref().iter().map(|b| format!("{b:02x}")).collect()
+}
+
+/// the delay before the next attempt, it doubles for each failed attempt
+pub fn backoff(options: &WebhookOptions, attempts: u32) -> u64 {
+    let exp = attempts.saturating_sub(1).min(20);
+    options.backoff.saturating_mul(1 << exp).min(MAX_BACKOFF)
+}
+
+/// send the engine messages to the matched webhooks in background
+/// the deliveries of each webhook are sent in order, and the webhooks are sent concurrently
+pub fn run(engine: &Arc<Engine>, store: Arc<Store>, hooks: Arc<Hooks>, options: WebhookOptions) {
+    let notify = Arc::new(Notify::new());
+    let (tx, mut rx) = mpsc::unbounded_channel::<acts::Message>();
+    engine.channel().on_message(move |e| {
+        let _ = tx.send(model::unpin_message(e.inner()));
+    });
+
+    // the deliveries are saved out of the engine callback
+    {
+        let store = store.clone();
+        let notify = notify.clone();
+        tokio::spawn(async move {
+            while let Some(message) = rx.recv().await {
+                let mut messages = vec![message];
+                while let Ok(message) = rx.try_recv() {
+                    messages.push(message);
+                }
+                let store = store.clone();
+                let hooks = hooks.clone();
+                let ret =
+                    tokio::task::spawn_blocking(move || enqueue(&store, &hooks, &messages)).await;
+                match ret {
+                    Ok(Ok(0)) => {}
+                    Ok(Ok(_)) => notify.notify_one(),
+                    Ok(Err(err)) => tracing::error!("webhook: {err}"),
+                    Err(err) => tracing::error!("webhook: {err}"),
+                }
+            }
+        });
+    }
+
+    tokio::spawn(async move {
+        // the webhooks which are being sent, their deliveries are picked up after the sending ends
+        let sending = Arc::new(Mutex::new(HashSet::<String>::new()));
+        loop {
+            tokio::select! {
+                _ = notify.notified() => {}
+                _ = tokio::time::sleep(TICK) => {}
+            }
+            let store2 = store.clone();
+            let ret = tokio::task::spawn_blocking(move || due(&store2, utils::time_millis())).await;
+            let due = match ret {
+                Ok(Ok(due)) => due,
+                Ok(Err(err)) => {
+                    tracing::error!("webhook: {err}");
+                    continue;
+                }
+                Err(err) => {
+                    tracing::error!("webhook: {err}");
+                    continue;
+                }
+            };
+            for (hook, deliveries) in due {
+                if !sending.lock().unwrap().insert(hook.id.clone()) {
+                    continue;
+                }
+                let store = store.clone();
+                let options = options.clone();
+                let sending = sending.clone();
+                tokio::spawn(async move {
+                    for delivery in deliveries {
+                        let ret = send(&hook, &delivery, &options).await;
+                        let store = store.clone();
+                        let options = options.clone();
+                        let ret = tokio::task::spawn_blocking(move || {
+                            complete(&store, delivery, ret, &options, utils::time_millis())
+                        })
+                        .await;
+                        match ret {
+                            Ok(Err(err)) => tracing::error!("webhook: {err}"),
+                            Err(err) => tracing::error!("webhook: {err}"),
+                            _ => {}
+                        }
+                    }
+                    sending.lock().unwrap().remove(&hook.id);
+                });
+            }
+        }
+    });
+}
+
+type FilteredHook = (Webhook, MessageFilter);
+
+/// the webhooks with their compiled filters, they are loaded from the store once and cleared when
+/// a webhook is created or removed
+#[derive(Default)]
+pub struct Hooks(Mutex<Option<Arc<Vec<FilteredHook>>>>);
+
+impl Hooks {
+    /// the cached webhooks, the lock is held while loading, so a clear after the loading is never lost
+    fn load(&self, store: &Store) -> Result<Arc<Vec<FilteredHook>>> {
+        let mut hooks = self.0.lock().unwrap();
+        if let Some(hooks) = &*hooks {
+            return Ok(hooks.clone());
+        }
+        let mut loaded = Vec::new();
+        for hook in store.collection::<Webhook>()?.all()? {
+            let Ok(filter) = MessageFilter::new(&hook.r#type, &hook.state, &hook.tag, &hook.key)
+            else {
+                continue;
+            };
+            loaded.push((hook, filter));
+        }
+        let loaded = Arc::new(loaded);
+        *hooks = Some(loaded.clone());
+        Ok(loaded)
+    }
+
+    pub fn clear(&self) {
+        self.0.lock().unwrap().take();
+    }
+}
+
+/// create the deliveries of the webhooks which match the messages
+fn enqueue(store: &Store, hooks: &Hooks, messages: &[acts::Message]) -> Result<usize> {
+    let hooks = hooks.load(store)?;
+    let deliveries = store.collection::<Delivery>()?;
+    let now = utils::time_millis();
+    let mut count = 0;
+    for message in messages {
+        let mut payload = None;
+        for (hook, filter) in hooks.iter() {
+            if !filter.is_match(message) {
+                continue;
+            }
+            let ns = Namespace::new(&hook.namespace).unwrap_or_default();
+            if !ns.owns(&message.mid) {
+                continue;
+            }
+            let mut payload = match &payload {
+                Some(payload) => payload,
+                None => payload.insert(serde_json::to_value(message)?),
+            }
+            .clone();
+            ns.unscope_value(&mut payload);
+            deliveries.create(&Delivery {
+                id: utils::longid(),
+                hook: hook.id.clone(),
+                url: hook.url.clone(),
+                mid: message.id.clone(),
+                event: message.name.clone(),
+                payload,
+                status: STATUS_PENDING.to_string(),
+                next_time: now,
+                create_time: now,
+                update_time: now,
+                ..Default::default()
+            })?;
+            count += 1;
+        }
+    }
+    Ok(count)
+}
+
+/// the pending deliveries to send now by their webhooks
+fn due(store: &Store, now: i64) -> Result<Vec<(Webhook, Vec<Delivery>)>> {
+    let hooks = store.collection::<Webhook>()?;
+    let mut ret: BTreeMap<String, (Webhook, Vec<Delivery>)> = BTreeMap::new();
+    for delivery in store
+        .collection::<Delivery>()?
+        .find_by("status", STATUS_PENDING)?
+    {
+        if delivery.next_time > now {
+            continue;
+        }
+        if let Some((_, deliveries)) = ret.get_mut(&delivery.hook) {
+            deliveries.push(delivery);
+        } else if let Ok(hook) = hooks.find(&delivery.hook) {
+            ret.insert(hook.id.clone(), (hook, vec![delivery]));
+        }
+    }
+    Ok(ret.into_values().collect())
+}
+
+/// send the delivery to the checked addresses of the webhook host
+/// the client connects to the addresses which are checked instead of resolving the host again, and never
+/// follows the redirects, so the request cannot be turned to an internal address after the check
+async fn send(
+    hook: &Webhook,
+    delivery: &Delivery,
+    options: &WebhookOptions,
+) -> std::result::Result<(), String> {
+    let (host, addrs) = check_url(&hook.url, options).await?;
+    let client = reqwest::Client::builder()
+        .timeout(TIMEOUT)
+        .redirect(reqwest::redirect::Policy::none())
+        .resolve_to_addrs(&host, &addrs)
+        .build()
+        .map_err(|err| err.to_string())?;
+    let body = serde_json::to_string(&delivery.payload).map_err(|err| err.to_string())?;
+    let timestamp = utils::time_millis();
+    let resp = client
+        .post(&hook.url)
+        .header("content-type", "application/json")
+        .header(
+            SIGNATURE_HEADER,
+            format!("sha256={}", sign(&hook.secret, timestamp, &body)),
+        )
+        .header(TIMESTAMP_HEADER, timestamp.to_string())
+        .header(DELIVERY_HEADER, &delivery.id)
+        .header(EVENT_HEADER, &delivery.event)
+        .body(body)
+        .send()
+        .await
+        .map_err(|err| err.to_string())?;
+    if !resp.status().is_success() {
+        return Err(format!("status {}", resp.status()));
+    }
+    Ok(())
+}
+
+/// remove the delivered one, or retry it later until it is failed
+fn complete(
+    store: &Store,
+    mut delivery: Delivery,
+    ret: std::result::Result<(), String>,
+    options: &WebhookOptions,
+    now: i64,
+) -> Result<()> {
+    let deliveries = store.collection::<Delivery>()?;
+    delivery.attempts += 1;
+    delivery.update_time = now;
+    match ret {
+        Ok(()) => {
+            deliveries.delete(&delivery.id)?;
+        }
+        Err(err) => {
+            tracing::warn!(
+                "webhook: delivery={} url={} attempts={} error={err}",
+                delivery.id,
+                delivery.url,
+                delivery.attempts
+            );
+            delivery.last_error = err;
+            if delivery.attempts > options.retries {
+                delivery.status = STATUS_FAILED.to_string();
+            } else {
+                delivery.next_time = now + backoff(options, delivery.attempts) as i64;
+            }
+            // the webhook may be removed while sending
+            if deliveries.exists(&delivery.id)? {
+                deliveries.update(&delivery)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// resolve the host of the url and deny the private and loopback addresses which are not allowed
+/// returns the host with its checked addresses
+async fn check_url(
+    url: &str,
+    options: &WebhookOptions,
+) -> std::result::Result<(String, Vec<SocketAddr>), String> {
+    let url = reqwest::Url::parse(url).map_err(|err| err.to_string())?;
+    let host = url.host_str().unwrap_or_default();
+    let port = url.port_or_known_default().unwrap_or_default();
+    let addrs: Vec<SocketAddr> = tokio::net::lookup_host((host.trim_matches(['[', ']']), port))
+        .await
+        .map_err(|err| err.to_string())?
+        .collect();
+    for addr in &addrs {
+        check_addr(host, addr.ip(), options).map_err(|err| err.to_string())?;
+    }
+    Ok((host.to_string(), addrs))
+}
+
+/// the private, loopback and link-local addresses are only for the allowed hosts
+pub fn check_addr(host: &str, ip: IpAddr, options: &WebhookOptions) -> Result<()> {
+    if options.allow_hosts.iter().any(|allowed| allowed == host) || !is_internal(ip) {
+        return Ok(());
+    }
+    Err(ActError::Action(format!(
+        "webhook host '{host}' is the internal address {ip}, add it to the webhook allow_hosts to send to it"
+    )))
+}
+
+fn is_internal(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [a, b, ..] = ip.octets();
+            ip.is_private()
+                || ip.is_loopback()
+                || ip.is_link_local()
+                || ip.is_unspecified()
+                || ip.is_broadcast()
+                // the shared address space 100.64.0.0/10
+                || (a == 100 && (64..128).contains(&b))
+        }
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(ip) => is_internal(IpAddr::V4(ip)),
+            None => {
+                ip.is_loopback()
+                    || ip.is_unspecified()
+                    || ip.is_unique_local()
+                    || ip.is_unicast_link_local()
+            }
+        },
+    }
+}
+
+fn masked(hook: Webhook) -> Webhook {
+    Webhook {
+        secret: "***".to_string(),
+        ..hook
+    }
+}